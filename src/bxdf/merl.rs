@@ -8,7 +8,7 @@ use std::f32;
 
 use crate::{
     bxdf::{self, BxDF, BxDFType},
-    film::Colorf,
+    film::{Colorf, Wavelengths},
     linalg::{self, Vector},
 };
 
@@ -28,16 +28,26 @@ pub struct Merl<'a> {
     n_theta_d: usize,
     /// Number of phi_d measurements in `brdf`
     n_phi_d: usize,
+    /// Wavelengths to upsample the measured RGB values to, in spectral mode
+    wavelengths: Option<Wavelengths>,
 }
 
 impl<'a> Merl<'a> {
-    /// Create a MERL BRDF to use data loaded from a MERL BRDF data file
-    pub fn new_bxdf(brdf: &'a [f32], n_theta_h: usize, n_theta_d: usize, n_phi_d: usize) -> BxDFs {
+    /// Create a MERL BRDF to use data loaded from a MERL BRDF data file, returning
+    /// its values at the `wavelengths` if rendering in spectral mode
+    pub fn new_bxdf(
+        brdf: &'a [f32],
+        n_theta_h: usize,
+        n_theta_d: usize,
+        n_phi_d: usize,
+        wavelengths: Option<Wavelengths>,
+    ) -> BxDFs {
         BxDFs::Merl(Self {
             brdf,
             n_theta_h,
             n_theta_d,
             n_phi_d,
+            wavelengths,
         })
     }
 
@@ -101,6 +111,10 @@ impl<'a> BxDF for Merl<'a> {
         let phi_d_idx = Merl::map_index(phi_d, f32::consts::PI, self.n_phi_d);
         let i = phi_d_idx + self.n_phi_d * (theta_d_idx + theta_h_idx * self.n_theta_d);
         assert!(i < self.brdf.len());
-        Colorf::new(self.brdf[3 * i], self.brdf[3 * i + 1], self.brdf[3 * i + 2])
+        let c = Colorf::new(self.brdf[3 * i], self.brdf[3 * i + 1], self.brdf[3 * i + 2]);
+        match self.wavelengths {
+            Some(w) => w.upsample(&c),
+            None => c,
+        }
     }
 }
@@ -0,0 +1,311 @@
+//! The master splits the blocks of each frame into a job for each worker and hands
+//! them out to the workers as they finish their previous ones, adding the results
+//! into the image of the frame. The jobs of workers which disconnect or fail to
+//! connect are given to the remaining workers.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    io::{self, BufReader, BufWriter},
+    net::TcpStream,
+    sync::{Condvar, Mutex},
+};
+
+use scoped_threadpool::Pool;
+
+use crate::{
+    exec::{
+        distrib::{Frame, Instructions},
+        Config,
+    },
+    film::{CropWindow, Image},
+    sampler::BlockQueue,
+};
+
+/// The master distributing the frames set in the config across the workers
+pub struct Master {
+    /// Addresses of the workers, e.g. `localhost:63234`
+    workers: Vec<String>,
+    config: Config,
+    /// Dimensions of the image
+    dim: (usize, usize),
+    /// The window of the image to render
+    window: CropWindow,
+}
+
+/// The jobs waiting to be given to a worker
+struct JobQueue {
+    pending: VecDeque<Instructions>,
+    /// Number of jobs being rendered by workers, which may be put back in the
+    /// queue if the worker fails
+    in_flight: usize,
+}
+
+/// A frame being assembled from the results of the workers
+struct FrameImage {
+    image: Image,
+    /// Number of jobs for the frame not yet finished
+    remaining: usize,
+}
+
+/// State shared between the threads talking to the workers
+struct Shared<F> {
+    jobs: Mutex<JobQueue>,
+    job_changed: Condvar,
+    frames: Mutex<HashMap<usize, FrameImage>>,
+    frame_done: Mutex<F>,
+}
+
+impl Master {
+    /// Create a master which renders the scene in the config on the `workers`.
+    /// `dim` is the dimensions of the image and `crop` the scene's crop window, if any
+    pub fn new(
+        workers: Vec<String>,
+        config: Config,
+        dim: (usize, usize),
+        crop: Option<CropWindow>,
+    ) -> Master {
+        Master {
+            workers,
+            config,
+            dim,
+            window: crop.unwrap_or_else(|| CropWindow::full(dim)),
+        }
+    }
+    /// Render the frames on the workers, calling `frame_done` with the image of each
+    /// frame as it's finished. Frames may finish out of order. Returns an error if
+    /// all the workers failed before the frames were finished
+    pub fn render<F>(&self, frame_done: F) -> io::Result<()>
+    where
+        F: FnMut(usize, &Image) + Send,
+    {
+        if self.workers.is_empty() {
+            return Err(io::Error::other("No workers to render with"));
+        }
+        let block_dim = self.config.sampler.block_dim;
+        let num_blocks = BlockQueue::new(&self.window, block_dim, (0, 0)).len();
+        let ranges = partition(num_blocks, self.workers.len());
+        let info = &self.config.frame_info;
+        let mut pending = VecDeque::new();
+        let mut frames = HashMap::new();
+        for frame in info.start..info.end + 1 {
+            for &(block_start, block_count) in &ranges {
+                pending.push_back(Instructions {
+                    scene_file: self.config.scene_file.clone(),
+                    spp: self.config.spp,
+                    frame,
+                    block_start,
+                    block_count,
+                });
+            }
+            let image = FrameImage {
+                image: Image::new(self.dim),
+                remaining: ranges.len(),
+            };
+            frames.insert(frame, image);
+        }
+        let shared = Shared {
+            jobs: Mutex::new(JobQueue {
+                pending,
+                in_flight: 0,
+            }),
+            job_changed: Condvar::new(),
+            frames: Mutex::new(frames),
+            frame_done: Mutex::new(frame_done),
+        };
+
+        let mut pool = Pool::new(self.workers.len() as u32);
+        pool.scoped(|scope| {
+            for w in &self.workers {
+                let s = &shared;
+                scope.execute(move || self.run_worker(w, s));
+            }
+        });
+        let unfinished = shared.jobs.into_inner().unwrap().pending.len();
+        if unfinished > 0 {
+            return Err(io::Error::other(format!(
+                "All workers failed with {} jobs left to render",
+                unfinished
+            )));
+        }
+        Ok(())
+    }
+    /// Give jobs to the worker at `addr` until there are none left or it fails
+    fn run_worker<F>(&self, addr: &str, shared: &Shared<F>)
+    where
+        F: FnMut(usize, &Image) + Send,
+    {
+        let stream = match TcpStream::connect(addr) {
+            Ok(s) => s,
+            Err(e) => {
+                println!("Warning: failed to connect to worker {}: {}", addr, e);
+                return;
+            }
+        };
+        let mut reader = match stream.try_clone() {
+            Ok(s) => BufReader::new(s),
+            Err(e) => {
+                println!("Warning: failed to connect to worker {}: {}", addr, e);
+                return;
+            }
+        };
+        let mut writer = BufWriter::new(stream);
+        while let Some(job) = next_job(shared) {
+            let result = job
+                .write(&mut writer)
+                .and_then(|_| Frame::read(&mut reader))
+                .and_then(|f| {
+                    if f.frame == job.frame && f.fits(self.dim) {
+                        Ok(f)
+                    } else {
+                        Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "the results don't match the job sent",
+                        ))
+                    }
+                });
+            match result {
+                Ok(frame) => {
+                    add_result(shared, &frame);
+                    job_finished(shared, None);
+                }
+                Err(e) => {
+                    println!(
+                        "Warning: lost worker {}: {}, reassigning frame {} blocks {} to {}",
+                        addr,
+                        e,
+                        job.frame,
+                        job.block_start,
+                        job.block_start + job.block_count - 1
+                    );
+                    job_finished(shared, Some(job));
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Take the next job from the queue, waiting for jobs being rendered by other workers
+/// in case they fail. Returns None once all jobs are finished
+fn next_job<F>(shared: &Shared<F>) -> Option<Instructions> {
+    let mut jobs = shared.jobs.lock().unwrap();
+    loop {
+        if let Some(job) = jobs.pending.pop_front() {
+            jobs.in_flight += 1;
+            return Some(job);
+        }
+        if jobs.in_flight == 0 {
+            return None;
+        }
+        jobs = shared.job_changed.wait(jobs).unwrap();
+    }
+}
+
+/// Record that a job being rendered is finished, or put it back in the queue if
+/// the worker failed to render it
+fn job_finished<F>(shared: &Shared<F>, failed: Option<Instructions>) {
+    let mut jobs = shared.jobs.lock().unwrap();
+    jobs.in_flight -= 1;
+    if let Some(job) = failed {
+        jobs.pending.push_back(job);
+    }
+    shared.job_changed.notify_all();
+}
+
+/// Add the results of a job into the image of its frame, passing the image on if
+/// the frame is finished
+fn add_result<F>(shared: &Shared<F>, frame: &Frame)
+where
+    F: FnMut(usize, &Image),
+{
+    let finished = {
+        let mut frames = shared.frames.lock().unwrap();
+        let f = frames.get_mut(&frame.frame).unwrap();
+        f.image
+            .add_blocks(frame.block_size, &frame.blocks, &frame.pixels);
+        f.remaining -= 1;
+        if f.remaining == 0 {
+            frames.remove(&frame.frame)
+        } else {
+            None
+        }
+    };
+    if let Some(f) = finished {
+        let mut frame_done = shared.frame_done.lock().unwrap();
+        (*frame_done)(frame.frame, &f.image);
+    }
+}
+
+/// Split `num_blocks` blocks into a contiguous `(start, count)` range for each of up
+/// to `workers` workers
+fn partition(num_blocks: usize, workers: usize) -> Vec<(usize, usize)> {
+    let per_worker = num_blocks.div_ceil(workers).max(1);
+    (0..num_blocks)
+        .step_by(per_worker)
+        .map(|start| (start, per_worker.min(num_blocks - start)))
+        .collect()
+}
+
+#[test]
+fn test_partition() {
+    assert_eq!(partition(8, 2), [(0, 4), (4, 4)]);
+    assert_eq!(partition(10, 3), [(0, 4), (4, 4), (8, 2)]);
+    assert_eq!(partition(2, 4), [(0, 1), (1, 1)]);
+}
+
+#[test]
+fn test_distributed_render() {
+    use crate::{exec::distrib::Worker, film::FrameInfo, scene};
+    use std::{net::TcpListener, thread};
+
+    let scene_file =
+        std::env::temp_dir().join(format!("aperture_distrib_{}.json", std::process::id()));
+    // Move the light out of the plane of the floor so the image isn't black
+    let mut desc = scene::test_scene();
+    desc["objects"][1]["transform"][0]["translation"] = serde_json::json!([0, 10, -10]);
+    std::fs::write(&scene_file, desc.to_string()).unwrap();
+    let worker = Worker::bind("127.0.0.1:0", 1).unwrap();
+    let worker_addr = worker.local_addr().unwrap().to_string();
+    // A worker which dies as soon as it receives its first job
+    let dead = TcpListener::bind("127.0.0.1:0").unwrap();
+    let dead_addr = dead.local_addr().unwrap().to_string();
+    let worker_thread = thread::spawn(move || worker.serve_one().unwrap());
+    let dead_thread = thread::spawn(move || {
+        let (stream, _) = dead.accept().unwrap();
+        let _ = Instructions::read(&mut BufReader::new(stream));
+    });
+
+    let config = Config::new(
+        Default::default(),
+        scene_file.to_string_lossy().into_owned(),
+        1,
+        1,
+        FrameInfo::new(1, 0.0, 0, 0),
+        (0, 0),
+    );
+    let workers = vec![
+        dead_addr,
+        worker_addr,
+        // Nothing is listening on this port as the listener is dropped right away
+        TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .to_string(),
+    ];
+    let master = Master::new(workers, config, (16, 16), None);
+    let mut frames = Vec::new();
+    master
+        .render(|frame, img| {
+            frames.push(frame);
+            assert_eq!(img.dimensions(), (16, 16));
+            let pixels = img.get_srgb8(&Default::default());
+            assert!(pixels.iter().any(|&p| p > 0));
+        })
+        .unwrap();
+    assert_eq!(frames, [0]);
+    drop(master);
+    worker_thread.join().unwrap();
+    dead_thread.join().unwrap();
+    let _ = std::fs::remove_file(scene_file);
+}
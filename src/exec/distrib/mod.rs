@@ -0,0 +1,166 @@
+//! The distrib module provides distributed rendering, where a master process splits
+//! the blocks of each frame across worker processes over TCP and merges the results
+//! they send back.
+//!
+//! Workers listen for a master to connect and then render the jobs it sends until it
+//! disconnects. Each job is sent as `Instructions` naming the scene file, which must be
+//! at the same path on the worker and is loaded as a pbrt scene if it has the `.pbrt`
+//! extension, the frame and the range of blocks to render. The
+//! worker replies with the weighted sums of the samples written to its render target
+//! as a `Frame`, which the master adds into the image of the frame. As the sums of the
+//! workers are added together the blocks the filter spreads samples into outside the
+//! range are sent back too. If a worker disconnects before sending its results the job
+//! is given to another worker.
+//!
+//! Messages are sent as little endian binary, with strings and lists sent as their
+//! length followed by their elements.
+
+use std::io::{self, Read, Write};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+pub use self::{master::Master, worker::Worker};
+
+pub mod master;
+pub mod worker;
+
+/// Port workers listen on if another isn't specified
+pub const DEFAULT_PORT: u16 = 63234;
+
+/// Instructions sent by the master telling a worker what to render
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instructions {
+    /// Path to the scene file to render
+    pub scene_file: String,
+    pub spp: usize,
+    pub frame: usize,
+    /// The range of blocks to render, in the same form as `Config::select_blocks`
+    pub block_start: usize,
+    pub block_count: usize,
+}
+
+impl Instructions {
+    /// Send the instructions over the stream
+    pub fn write<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        w.write_u64::<LittleEndian>(self.scene_file.len() as u64)?;
+        w.write_all(self.scene_file.as_bytes())?;
+        for x in &[self.spp, self.frame, self.block_start, self.block_count] {
+            w.write_u64::<LittleEndian>(*x as u64)?;
+        }
+        w.flush()
+    }
+    /// Receive instructions from the stream. Returns an error of kind `UnexpectedEof`
+    /// if the stream was closed instead of sending them
+    pub fn read<R: Read>(r: &mut R) -> io::Result<Instructions> {
+        let len = r.read_u64::<LittleEndian>()?;
+        let mut scene_file = String::new();
+        r.take(len).read_to_string(&mut scene_file)?;
+        if scene_file.len() as u64 != len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(Instructions {
+            scene_file,
+            spp: r.read_u64::<LittleEndian>()? as usize,
+            frame: r.read_u64::<LittleEndian>()? as usize,
+            block_start: r.read_u64::<LittleEndian>()? as usize,
+            block_count: r.read_u64::<LittleEndian>()? as usize,
+        })
+    }
+}
+
+/// The results of a job sent back by a worker, in the form returned by
+/// `RenderTarget::get_rendered_blocks`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub frame: usize,
+    pub block_size: (usize, usize),
+    /// Pixel position of each block
+    pub blocks: Vec<(usize, usize)>,
+    /// The RGBA weighted sums of the pixels, `block_size.0 * block_size.1 * 4` for
+    /// each block
+    pub pixels: Vec<f32>,
+}
+
+impl Frame {
+    /// Send the frame over the stream
+    pub fn write<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        w.write_u64::<LittleEndian>(self.frame as u64)?;
+        w.write_u64::<LittleEndian>(self.block_size.0 as u64)?;
+        w.write_u64::<LittleEndian>(self.block_size.1 as u64)?;
+        w.write_u64::<LittleEndian>(self.blocks.len() as u64)?;
+        for b in &self.blocks {
+            w.write_u64::<LittleEndian>(b.0 as u64)?;
+            w.write_u64::<LittleEndian>(b.1 as u64)?;
+        }
+        for px in &self.pixels {
+            w.write_f32::<LittleEndian>(*px)?;
+        }
+        w.flush()
+    }
+    /// Receive a frame from the stream
+    pub fn read<R: Read>(r: &mut R) -> io::Result<Frame> {
+        let frame = r.read_u64::<LittleEndian>()? as usize;
+        let block_size = (
+            r.read_u64::<LittleEndian>()? as usize,
+            r.read_u64::<LittleEndian>()? as usize,
+        );
+        let num_blocks = r.read_u64::<LittleEndian>()? as usize;
+        let mut blocks = Vec::new();
+        for _ in 0..num_blocks {
+            blocks.push((
+                r.read_u64::<LittleEndian>()? as usize,
+                r.read_u64::<LittleEndian>()? as usize,
+            ));
+        }
+        let num_pixels = num_blocks
+            .checked_mul(block_size.0)
+            .and_then(|n| n.checked_mul(block_size.1))
+            .and_then(|n| n.checked_mul(4))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Frame is too large"))?;
+        let mut pixels = Vec::new();
+        for _ in 0..num_pixels {
+            pixels.push(r.read_f32::<LittleEndian>()?);
+        }
+        Ok(Frame {
+            frame,
+            block_size,
+            blocks,
+            pixels,
+        })
+    }
+    /// Check that the blocks of the frame start inside an image with dimensions `dim`,
+    /// blocks at the edges of the image may extend past it
+    pub fn fits(&self, dim: (usize, usize)) -> bool {
+        self.blocks.iter().all(|b| b.0 < dim.0 && b.1 < dim.1)
+    }
+}
+
+#[test]
+fn test_messages() {
+    let instr = Instructions {
+        scene_file: "scenes/cornell.json".to_owned(),
+        spp: 16,
+        frame: 3,
+        block_start: 8,
+        block_count: 4,
+    };
+    let mut buf = Vec::new();
+    instr.write(&mut buf).unwrap();
+    assert_eq!(Instructions::read(&mut &buf[..]).unwrap(), instr);
+    let err = Instructions::read(&mut &buf[..buf.len() - 1]).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+    let frame = Frame {
+        frame: 3,
+        block_size: (1, 2),
+        blocks: vec![(0, 0), (3, 2)],
+        pixels: (0..16).map(|x| x as f32 * 0.5).collect(),
+    };
+    let mut buf = Vec::new();
+    frame.write(&mut buf).unwrap();
+    assert_eq!(Frame::read(&mut &buf[..]).unwrap(), frame);
+    assert!(frame.fits((4, 4)));
+    // Blocks at the edges can extend past the image
+    assert!(frame.fits((4, 3)));
+    assert!(!frame.fits((4, 2)));
+}
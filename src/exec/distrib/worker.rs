@@ -0,0 +1,122 @@
+//! The worker renders the jobs sent to it by a master, sending back the results of
+//! each job once it's rendered. Workers serve one master at a time.
+
+use std::{
+    io::{self, BufReader, BufWriter},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    path::PathBuf,
+};
+
+use crate::{
+    exec::{
+        distrib::{Frame, Instructions},
+        Config, Exec, MultiThreaded, NoObserver,
+    },
+    film::{FrameInfo, RenderTarget},
+    scene::Scene,
+};
+
+/// A worker process listening for a master to connect to it
+pub struct Worker {
+    listener: TcpListener,
+    num_threads: u32,
+}
+
+/// The scene the worker last loaded, which is kept for the next job if it's for the
+/// same scene
+struct LoadedScene {
+    file: String,
+    scene: Scene,
+    rt: RenderTarget,
+    frame_info: FrameInfo,
+}
+
+impl Worker {
+    /// Create a worker listening on `addr` which renders using `num_threads` threads
+    pub fn bind<A: ToSocketAddrs>(addr: A, num_threads: u32) -> io::Result<Worker> {
+        Ok(Worker {
+            listener: TcpListener::bind(addr)?,
+            num_threads,
+        })
+    }
+    /// Get the address the worker is listening on
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+    /// Serve masters as they connect to the worker, forever
+    pub fn serve(&self) -> ! {
+        loop {
+            if let Err(e) = self.serve_one() {
+                println!("Worker: lost connection to the master: {}", e);
+            }
+        }
+    }
+    /// Wait for a master to connect and render the jobs it sends until it disconnects
+    pub fn serve_one(&self) -> io::Result<()> {
+        let (stream, addr) = self.listener.accept()?;
+        println!("Worker: master {} connected", addr);
+        self.serve_master(stream)?;
+        println!("Worker: master {} disconnected", addr);
+        Ok(())
+    }
+    /// Render the jobs sent by the master over `stream`
+    fn serve_master(&self, stream: TcpStream) -> io::Result<()> {
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut writer = BufWriter::new(stream);
+        let mut exec = MultiThreaded::new(self.num_threads);
+        let mut loaded: Option<LoadedScene> = None;
+        loop {
+            let instr = match Instructions::read(&mut reader) {
+                Ok(i) => i,
+                Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+                Err(e) => return Err(e),
+            };
+            if loaded.as_ref().is_none_or(|l| l.file != instr.scene_file) {
+                let scene = if instr.scene_file.ends_with(".pbrt") {
+                    Scene::load_pbrt(&instr.scene_file)
+                } else {
+                    Scene::load_file(&instr.scene_file)
+                };
+                let (scene, rt, _, frame_info) =
+                    scene.map_err(|e| io::Error::other(format!("Failed to load scene: {}", e)))?;
+                loaded = Some(LoadedScene {
+                    file: instr.scene_file.clone(),
+                    scene,
+                    rt,
+                    frame_info,
+                });
+            }
+            let l = loaded.as_mut().unwrap();
+            if instr.frame >= l.frame_info.frames {
+                return Err(io::Error::other(format!(
+                    "Frame {} is outside the scene's {} frames",
+                    instr.frame, l.frame_info.frames
+                )));
+            }
+            let mut config = Config::new(
+                PathBuf::new(),
+                instr.scene_file.clone(),
+                instr.spp,
+                self.num_threads,
+                l.frame_info,
+                (instr.block_start, instr.block_count),
+            );
+            config.current_frame = instr.frame;
+            config.sampler = l.scene.sampler;
+            exec.render(&mut l.scene, &mut l.rt, &config, &NoObserver);
+            let (block_size, blocks, pixels) = l.rt.get_rendered_blocks();
+            l.rt.clear();
+            Frame {
+                frame: instr.frame,
+                block_size,
+                blocks,
+                pixels,
+            }
+            .write(&mut writer)?;
+            println!(
+                "Worker: frame {}: rendered {} blocks starting at {}",
+                instr.frame, instr.block_count, instr.block_start
+            );
+        }
+    }
+}
@@ -1,16 +1,38 @@
 //! The exec module provides an abstraction of the execution backends
 //! used to actually render the image
 
-use std::path::PathBuf;
+use std::{path::PathBuf, time::Duration};
 
 use crate::{
     film::{FrameInfo, RenderTarget},
+    sampler::SamplerConfig,
     scene::Scene,
 };
 
-pub use self::multithreaded::MultiThreaded;
+pub use self::{
+    multithreaded::MultiThreaded,
+    observer::{NoObserver, Observer, PrintProgress, Progress},
+    progressive::{Budget, Pass},
+};
 
+pub mod distrib;
 pub mod multithreaded;
+pub mod observer;
+pub mod progressive;
+
+/// Dimensions of the blocks of pixels the image is split into for rendering, unless
+/// the scene sets its own in its sampler
+pub const BLOCK_DIM: (u32, u32) = (8, 8);
+
+/// Where and how often to save checkpoints of the render, which it can be resumed
+/// from by loading the checkpoint into the render target
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    pub file: PathBuf,
+    /// Time between checkpoints saved while rendering, checkpoints are also saved
+    /// after each pass over the frame
+    pub interval: Duration,
+}
 
 /// Config passed to set up the execution environment with information
 /// on what it should be rendering and where to put the results
@@ -26,6 +48,13 @@ pub struct Config {
     /// Which blocks the executor should render, stored
     /// as (start, count) of the block indices
     pub select_blocks: (usize, usize),
+    /// Render each frame progressively in passes of `spp` samples per pixel
+    /// until the budget is reached, instead of in a single pass
+    pub progressive: Option<Budget>,
+    /// Save checkpoints of the render target while rendering
+    pub checkpoint: Option<Checkpoint>,
+    /// The sampler to render with, usually the scene's `Scene::sampler`
+    pub sampler: SamplerConfig,
 }
 
 impl Config {
@@ -45,6 +74,9 @@ impl Config {
             frame_info,
             current_frame: frame_info.start,
             select_blocks,
+            progressive: None,
+            checkpoint: None,
+            sampler: SamplerConfig::default(),
         }
     }
 }
@@ -52,11 +84,16 @@ impl Config {
 /// Trait implemented by different execution environments that provides
 /// a method to call and render the scene, given the rendering arguments
 pub trait Exec {
-    /// Render the scene using this rendering backend, will render out
-    /// all frames of the image and save them out as instructed by
-    /// the command line arguments
-    /// TODO: In order to have a cleaner seperation we should pass more parameters
-    /// to render. E.g. the scene. Or maybe a callback to a function that gets the
-    /// frame's render target and can save it out?
-    fn render(&mut self, scene: &mut Scene, rt: &mut RenderTarget, config: &Config);
+    /// Render the current frame of the scene set in `config` using this rendering
+    /// backend, writing the results to the render target. The `observer` is notified
+    /// as the frame is started and finished and as each block of the image is rendered.
+    /// Passes and blocks already written to the render target, e.g. when resuming from
+    /// a checkpoint, are not rendered again
+    fn render(
+        &mut self,
+        scene: &mut Scene,
+        rt: &mut RenderTarget,
+        config: &Config,
+        observer: &dyn Observer,
+    );
 }
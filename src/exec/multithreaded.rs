@@ -2,17 +2,28 @@
 //! the image.
 
 use crate::{
-    exec::{Config, Exec},
-    film::{Colorf, ImageSample, RenderTarget},
-    geometry::{Emitter, Instance},
+    bxdf::BxDFType,
+    exec::{progressive, Budget, Config, Exec, Observer, Pass, Progress},
+    film::{aov, Aov, AovSample, Colorf, ImageSample, LightSplit, RenderTarget, Wavelengths},
+    geometry::{Emitter, Instance, Intersection},
     integrator::Integrator,
-    sampler::{self, BlockQueue, Sampler, Samplers},
+    linalg::{self, Ray},
+    material::Material,
+    sampler::{BlockQueue, Region, Sample, Sampler, Samplers},
     scene::Scene,
 };
-use light_arena;
-use rand::StdRng;
+use light_arena::{self, Allocator};
+use rand::{Rng, SeedableRng, StdRng};
 use scoped_threadpool::Pool;
-use std::{iter, time::SystemTime};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::SystemTime,
+};
 
 /// The `MultiThreaded` execution uses a configurable number of threads in
 /// a threadpool to render each frame
@@ -27,11 +38,21 @@ impl MultiThreaded {
             pool: Pool::new(num_threads),
         }
     }
-    /// Launch a rendering job in parallel across the threads and wait for it to finish
-    fn render_parallel(&mut self, scene: &Scene, rt: &RenderTarget, config: &Config) {
-        let dim = rt.dimensions();
-        let block_queue =
-            BlockQueue::new((dim.0 as u32, dim.1 as u32), (8, 8), config.select_blocks);
+    /// Launch a rendering job in parallel across the threads and wait for it to finish,
+    /// skipping any blocks of the pass already written to the render target
+    fn render_parallel(
+        &mut self,
+        scene: &Scene,
+        rt: &RenderTarget,
+        config: &Config,
+        pass: usize,
+        observer: &dyn Observer,
+        checkpoints: &Checkpointer,
+    ) {
+        let mut block_queue =
+            BlockQueue::new(&rt.window(), config.sampler.block_dim, config.select_blocks);
+        let blocks_total = block_queue.len();
+        block_queue.skip_blocks(&rt.blocks_written());
         let light_list: Vec<_> = scene
             .bvh
             .iter()
@@ -40,85 +61,354 @@ impl MultiThreaded {
                 _ => None,
             })
             .collect();
-        assert!(!light_list.is_empty(), "At least one light is required");
+        let progress = FrameProgress {
+            frame: config.current_frame,
+            pass,
+            blocks_total,
+            blocks_done: AtomicUsize::new(blocks_total - block_queue.len()),
+            start: SystemTime::now(),
+        };
+        let aovs = rt.accumulated_aovs();
+        // Statistics layers are computed from the image samples and don't need AOV samples
+        let aov_context = if aovs.iter().all(Aov::is_stat) {
+            None
+        } else {
+            Some(AovContext {
+                light: aovs.iter().any(Aov::is_light),
+                material_ids: scene
+                    .materials
+                    .iter()
+                    .map(|(name, m)| (Arc::as_ptr(m) as usize, aov::id_of(name)))
+                    .collect(),
+            })
+        };
+        // Seeded renders write the blocks in order so the image doesn't depend on
+        // which thread finishes first
+        let ordered_writes = config.sampler.seed.map(|_| OrderedWrites::new());
         let n = self.pool.thread_count();
         self.pool.scoped(|scope| {
             for _ in 0..n {
                 let b = &block_queue;
                 let r = &rt;
                 let l = &light_list;
+                let p = &progress;
+                let a = aov_context.as_ref();
+                let s = config.sampler.create(config.spp);
+                let seed = config.sampler.seed;
+                let o = ordered_writes.as_ref();
                 scope.execute(move || {
-                    thread_work(config.spp, b, scene, r, l);
+                    thread_work(s, seed, b, scene, r, l, observer, p, checkpoints, a, o);
                 });
             }
         });
+        rt.finish_pass();
+        checkpoints.save(rt);
+    }
+    /// Render passes over the frame, accumulating them in the render target, until
+    /// the budget is reached. Rendering continues on from the passes already finished
+    /// in the render target
+    fn render_progressive(
+        &mut self,
+        scene: &Scene,
+        rt: &RenderTarget,
+        config: &Config,
+        budget: &Budget,
+        observer: &dyn Observer,
+        checkpoints: &Checkpointer,
+    ) {
+        let start = SystemTime::now();
+        let mut previous: Option<Vec<f32>> = None;
+        for pass in rt.passes() + 1.. {
+            if pass > 1 && budget.passes.is_some_and(|p| pass > p) {
+                break;
+            }
+            let pass_start = SystemTime::now();
+            self.render_parallel(scene, rt, config, pass, observer, checkpoints);
+            let image = rt.get_renderf32();
+            let noise = previous
+                .as_ref()
+                .map(|p| progressive::estimate_noise(p, &image, pass));
+            let pass = Pass {
+                frame: config.current_frame,
+                pass,
+                elapsed: start.elapsed().unwrap_or_default(),
+                pass_time: pass_start.elapsed().unwrap_or_default(),
+                noise,
+            };
+            observer.pass_finished(&pass, rt);
+            if budget.done(&pass) {
+                break;
+            }
+            previous = Some(image);
+        }
     }
 }
 
 impl Exec for MultiThreaded {
-    fn render(&mut self, scene: &mut Scene, rt: &mut RenderTarget, config: &Config) {
-        println!(
-            "Rendering using {} threads\n--------------------",
-            self.pool.thread_count()
-        );
+    fn render(
+        &mut self,
+        scene: &mut Scene,
+        rt: &mut RenderTarget,
+        config: &Config,
+        observer: &dyn Observer,
+    ) {
         let time_step = config.frame_info.time / config.frame_info.frames as f32;
         let frame_start_time = config.current_frame as f32 * time_step;
         let frame_end_time = (config.current_frame as f32 + 1.0) * time_step;
         scene.update_frame(config.current_frame, frame_start_time, frame_end_time);
 
-        println!(
-            "Frame {}: rendering for {} to {}",
-            config.current_frame, frame_start_time, frame_end_time
+        observer.frame_started(
+            config.current_frame,
+            (frame_start_time, frame_end_time),
+            self.pool.thread_count(),
         );
         let scene_start = SystemTime::now();
-        self.render_parallel(scene, rt, config);
+        let checkpoints = Checkpointer::new(config);
+        match config.progressive {
+            Some(ref budget) => {
+                self.render_progressive(scene, rt, config, budget, observer, &checkpoints)
+            }
+            None if rt.passes() == 0 => {
+                self.render_parallel(scene, rt, config, 1, observer, &checkpoints)
+            }
+            None => {}
+        }
         let time = scene_start.elapsed().expect("Failed to get render time?");
-        println!(
-            "Frame {}: rendering took {:4}s",
-            config.current_frame,
-            time.as_secs() as f64 + time.subsec_nanos() as f64 * 1e-9
-        );
+        observer.frame_finished(config.current_frame, time);
+    }
+}
+
+/// Tracks the blocks of the pass rendered by the threads to report the progress
+struct FrameProgress {
+    frame: usize,
+    pass: usize,
+    blocks_total: usize,
+    blocks_done: AtomicUsize,
+    start: SystemTime,
+}
+
+impl FrameProgress {
+    /// Record that a block has been rendered, returning the progress through the pass
+    fn block_done(&self) -> Progress {
+        Progress {
+            frame: self.frame,
+            pass: self.pass,
+            blocks_done: self.blocks_done.fetch_add(1, Ordering::AcqRel) + 1,
+            blocks_total: self.blocks_total,
+            elapsed: self.start.elapsed().unwrap_or_default(),
+        }
+    }
+}
+
+/// Saves checkpoints of the render target to the file set in the config, if any,
+/// after each pass and once the interval has passed after finishing a block
+struct Checkpointer<'a> {
+    config: &'a Config,
+    /// When the last checkpoint was saved
+    last: Mutex<SystemTime>,
+}
+
+impl<'a> Checkpointer<'a> {
+    fn new(config: &'a Config) -> Self {
+        Checkpointer {
+            config,
+            last: Mutex::new(SystemTime::now()),
+        }
+    }
+    /// Save a checkpoint if the interval has passed since the last one. If another
+    /// thread is already saving one this thread carries on rendering
+    fn block_done(&self, rt: &RenderTarget) {
+        let interval = match self.config.checkpoint {
+            Some(ref c) => c.interval,
+            None => return,
+        };
+        if let Ok(mut last) = self.last.try_lock() {
+            if last.elapsed().unwrap_or_default() >= interval {
+                self.save(rt);
+                *last = SystemTime::now();
+            }
+        }
+    }
+    /// Save a checkpoint, failing to save one is reported but doesn't stop the render
+    fn save(&self, rt: &RenderTarget) {
+        if let Some(ref c) = self.config.checkpoint {
+            if let Err(e) = rt.save_checkpoint(&c.file, self.config.current_frame) {
+                println!(
+                    "Warning: failed to save checkpoint '{}': {}",
+                    c.file.display(),
+                    e
+                );
+            }
+        }
+    }
+}
+
+/// The samples of a block rendered by a thread
+struct FinishedBlock {
+    region: Region,
+    samples: Vec<ImageSample>,
+    aov_samples: Vec<AovSample>,
+}
+
+/// Writes the blocks rendered by the threads to the render target in the order of
+/// the block queue. The sums accumulated in the pixels then don't depend on which
+/// thread finishes its block first, as floating point addition isn't associative
+struct OrderedWrites {
+    /// Index of the next block to write and the finished blocks waiting on it
+    pending: Mutex<(usize, BTreeMap<usize, FinishedBlock>)>,
+}
+
+impl OrderedWrites {
+    fn new() -> Self {
+        OrderedWrites {
+            pending: Mutex::new((0, BTreeMap::new())),
+        }
+    }
+    /// Add the block at `index` in the queue, writing it along with any blocks
+    /// after it which were waiting on it
+    fn write(&self, index: usize, block: FinishedBlock, target: &RenderTarget) {
+        let mut pending = self.pending.lock().unwrap();
+        let (ref mut next, ref mut blocks) = *pending;
+        blocks.insert(index, block);
+        while let Some(b) = blocks.remove(next) {
+            target.write_block(&b.samples, &b.aov_samples, &b.region);
+            *next += 1;
+        }
+    }
+}
+
+/// Get the seed of the random numbers used for the samples of `pixel` in the pass
+/// of a frame, for renders seeded with `seed`
+fn pixel_seed(seed: u64, frame: usize, pass: usize, pixel: (u32, u32)) -> [usize; 4] {
+    let pixel = (u64::from(pixel.0) << 32) | u64::from(pixel.1);
+    let mut state = seed;
+    for v in &[frame as u64, pass as u64, pixel] {
+        state = splitmix64(state ^ v);
+    }
+    let mut words = [0; 4];
+    for w in &mut words {
+        state = splitmix64(state);
+        *w = state as usize;
+    }
+    words
+}
+
+/// The SplitMix64 hash, which spreads small changes in `x` over all of the bits
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// What the threads need to compute the AOV samples when AOVs are being rendered
+struct AovContext {
+    /// If the light split by path is needed from the integrator
+    light: bool,
+    /// Ids of the scene's materials by their address
+    material_ids: HashMap<usize, f32>,
+}
+
+/// Compute the AOV sample for the camera ray through `pos` hitting `hit`
+fn aov_sample(
+    pos: &(f32, f32),
+    ray: &Ray,
+    hit: &Intersection,
+    light: LightSplit,
+    context: &AovContext,
+    rng: &mut StdRng,
+    alloc: &Allocator,
+) -> AovSample {
+    // Estimate the albedo from a sample of the BSDF
+    let bsdf = hit.material.bsdf(hit, alloc);
+    let sample = Sample::new(&(rng.next_f32(), rng.next_f32()), rng.next_f32());
+    let (f, w_i, pdf, _) = bsdf.sample(&-ray.d, BxDFType::all(), &sample);
+    let mut albedo = if pdf > 0.0 {
+        f * f32::abs(linalg::dot(&w_i, &bsdf.n)) / pdf
+    } else {
+        Colorf::black()
+    };
+    if let Some(w) = ray.wavelengths {
+        albedo = w.to_rgb(&albedo);
+    }
+    let material = hit.material as *const _ as usize;
+    AovSample {
+        x: pos.0,
+        y: pos.1,
+        albedo,
+        normal: hit.dg.n.normalized(),
+        position: hit.dg.p,
+        depth: (hit.dg.p - ray.o).length(),
+        uv: (hit.dg.u, hit.dg.v),
+        instance_id: aov::id_of(hit.instance.tag()),
+        material_id: context.material_ids.get(&material).cloned().unwrap_or(0.0),
+        light,
     }
 }
 
 fn thread_work(
-    spp: usize,
+    mut sampler: Samplers,
+    seed: Option<u64>,
     queue: &BlockQueue,
     scene: &Scene,
     target: &RenderTarget,
     light_list: &[&Emitter],
+    observer: &dyn Observer,
+    progress: &FrameProgress,
+    checkpoints: &Checkpointer,
+    aovs: Option<&AovContext>,
+    ordered_writes: Option<&OrderedWrites>,
 ) {
-    let mut sampler: Samplers = sampler::LowDiscrepancy::new(queue.block_dim(), spp).into();
     let mut sample_pos = Vec::with_capacity(sampler.max_spp());
-    let mut time_samples: Vec<_> = iter::repeat(0.0).take(sampler.max_spp()).collect();
+    let mut time_samples = vec![0.0; sampler.max_spp()];
+    let mut wavelength_samples = time_samples.clone();
     let block_dim = queue.block_dim();
     let mut block_samples =
         Vec::with_capacity(sampler.max_spp() * (block_dim.0 * block_dim.1) as usize);
-    let mut rng = match StdRng::new() {
-        Ok(r) => r,
-        Err(e) => {
-            println!("Failed to get StdRng, {}", e);
-            return;
-        }
+    let mut aov_samples = Vec::new();
+    let mut rng = match seed {
+        Some(s) => StdRng::from_seed(&[s as usize][..]),
+        None => match StdRng::new() {
+            Ok(r) => r,
+            Err(e) => {
+                println!("Failed to get StdRng, {}", e);
+                return;
+            }
+        },
     };
     let mut arena = light_arena::MemoryArena::new(8);
     let camera = scene.active_camera();
     // Grab a block from the queue and start working on it, submitting samples
     // to the render target thread after each pixel
-    for b in queue.iter() {
-        sampler.select_block(b);
+    for (i, b) in queue.iter_indexed() {
+        sampler.select_block(b, queue.window());
         let mut pixel_samples = 0;
         while sampler.has_samples() {
+            // Seeded renders start a new stream of random numbers for each pixel, which
+            // continues through any extra samples the sampler takes for the pixel
+            if let Some(s) = seed {
+                if pixel_samples == block_samples.len() {
+                    let pixel = sampler.get_region().current;
+                    rng.reseed(&pixel_seed(s, progress.frame, progress.pass, pixel)[..]);
+                }
+            }
             // Get samples for a pixel and render them
             sampler.get_samples(&mut sample_pos, &mut rng);
             sampler.get_samples_1d(&mut time_samples[..], &mut rng);
-            for (s, t) in sample_pos.iter().zip(time_samples.iter()) {
+            if target.spectral() {
+                sampler.get_samples_1d(&mut wavelength_samples[..], &mut rng);
+            }
+            for (i, (s, t)) in sample_pos.iter().zip(time_samples.iter()).enumerate() {
                 let alloc = arena.allocator();
                 let mut ray = camera.generate_ray(s, *t);
+                if target.spectral() {
+                    let space = target.working_space();
+                    ray.wavelengths = Some(Wavelengths::sample(wavelength_samples[i], space));
+                }
                 if let Some(hit) = scene.intersect(&mut ray) {
-                    let c = scene
-                        .integrator
-                        .illumination(
+                    let integrator = &scene.integrator;
+                    let (c, light) = if aovs.is_some_and(|a| a.light) {
+                        integrator.illumination_split(
                             scene,
                             light_list,
                             &ray,
@@ -127,9 +417,30 @@ fn thread_work(
                             &mut rng,
                             &alloc,
                         )
-                        .clamp();
-                    block_samples.push(ImageSample::new(s.0, s.1, c));
+                    } else {
+                        let c = integrator.illumination(
+                            scene,
+                            light_list,
+                            &ray,
+                            &hit,
+                            &mut sampler,
+                            &mut rng,
+                            &alloc,
+                        );
+                        (c, LightSplit::black())
+                    };
+                    let (c, light) = match ray.wavelengths {
+                        Some(w) => (w.to_rgb(&c), light.to_rgb(&w)),
+                        None => (c, light),
+                    };
+                    if let Some(a) = aovs {
+                        aov_samples.push(aov_sample(s, &ray, &hit, light, a, &mut rng, &alloc));
+                    }
+                    block_samples.push(ImageSample::new(s.0, s.1, target.clamp_sample(&c)));
                 } else {
+                    if aovs.is_some() {
+                        aov_samples.push(AovSample::background(s.0, s.1));
+                    }
                     block_samples.push(ImageSample::new(s.0, s.1, Colorf::black()));
                 }
             }
@@ -139,7 +450,363 @@ fn thread_work(
                 pixel_samples = block_samples.len();
             }
         }
-        target.write(&block_samples, sampler.get_region());
+        match ordered_writes {
+            Some(o) => {
+                let block = FinishedBlock {
+                    region: *sampler.get_region(),
+                    samples: block_samples.clone(),
+                    aov_samples: aov_samples.clone(),
+                };
+                o.write(i, block, target);
+            }
+            None => {
+                target.write_block(&block_samples, &aov_samples, sampler.get_region());
+            }
+        }
+        aov_samples.clear();
+        observer.block_finished(sampler.get_region(), &block_samples, &progress.block_done());
+        checkpoints.block_done(target);
         block_samples.clear();
     }
 }
+
+#[cfg(test)]
+use crate::{
+    exec::{Checkpoint, PrintProgress, BLOCK_DIM},
+    film::{AnimatedColor, Camera, ColorKeyframe, FrameInfo},
+    geometry::Sphere,
+    integrator,
+    linalg::{AnimatedTransform, Transform, Vector},
+    material::Matte,
+    sampler::{self, SamplerConfig, SamplerType},
+    scene::SceneBuilder,
+    texture::{ConstantColor, ConstantScalar},
+};
+#[cfg(test)]
+use std::time::Duration;
+
+/// Records the calls made to it, along with printing them
+#[cfg(test)]
+#[derive(Default)]
+struct RecordObserver {
+    print: PrintProgress,
+    events: Mutex<Vec<String>>,
+    blocks: Mutex<Vec<Progress>>,
+}
+
+#[cfg(test)]
+impl Observer for RecordObserver {
+    fn frame_started(&self, frame: usize, time: (f32, f32), threads: u32) {
+        self.print.frame_started(frame, time, threads);
+        let event = format!("start {} {:?} {}", frame, time, threads);
+        self.events.lock().unwrap().push(event);
+    }
+    fn block_finished(
+        &self,
+        region: &sampler::Region,
+        samples: &[ImageSample],
+        progress: &Progress,
+    ) {
+        self.print.block_finished(region, samples, progress);
+        assert_eq!(samples.len(), (region.dim.0 * region.dim.1) as usize);
+        self.blocks.lock().unwrap().push(*progress);
+    }
+    fn pass_finished(&self, pass: &Pass, rt: &RenderTarget) {
+        self.print.pass_finished(pass, rt);
+        let event = format!("pass {} {}", pass.pass, pass.noise.is_some());
+        self.events.lock().unwrap().push(event);
+    }
+    fn frame_finished(&self, frame: usize, time: Duration) {
+        self.print.frame_finished(frame, time);
+        self.events
+            .lock()
+            .unwrap()
+            .push(format!("finish {}", frame));
+    }
+}
+
+/// Build a small scene of a sphere and light to render frame 1 of
+#[cfg(test)]
+fn test_scene(progressive: Option<Budget>) -> (Scene, RenderTarget, Config) {
+    test_scene_dim((32, 16), progressive)
+}
+
+/// Build the test scene with an image of dimensions `dim`
+#[cfg(test)]
+fn test_scene_dim(
+    dim: (usize, usize),
+    progressive: Option<Budget>,
+) -> (Scene, RenderTarget, Config) {
+    let mut builder = SceneBuilder::new(dim, 1);
+    builder.frames(FrameInfo::new(2, 1.0, 0, 1));
+    let camera_world = Transform::translate(&Vector::new(0.0, 0.0, -10.0));
+    builder.camera(Camera::new(
+        AnimatedTransform::unanimated(&camera_world),
+        30.0,
+        builder.dimensions(),
+        0.5,
+        0,
+    ));
+    builder.integrator(integrator::Whitted::new_integrator(1));
+    let white = builder.material(
+        "white",
+        Matte::new_material(
+            Arc::new(ConstantColor::new_texture(Colorf::broadcast(1.0))),
+            Arc::new(ConstantScalar::new_texture(1.0)),
+        ),
+    );
+    builder.receiver(
+        Arc::new(Sphere::new(1.0).into()),
+        white,
+        AnimatedTransform::unanimated(&Transform::identity()),
+        "ball".to_owned(),
+    );
+    builder.point_light(
+        AnimatedTransform::unanimated(&Transform::translate(&Vector::new(0.0, 5.0, -5.0))),
+        AnimatedColor::with_keyframes(vec![ColorKeyframe::new(&Colorf::broadcast(50.0), 0.0)]),
+        "light".to_owned(),
+    );
+    let (scene, rt, spp, frame_info) = builder.build().unwrap();
+    let mut config = Config::new(
+        std::path::PathBuf::new(),
+        String::new(),
+        spp,
+        2,
+        frame_info,
+        (0, 0),
+    );
+    config.current_frame = 1;
+    config.progressive = progressive;
+    (scene, rt, config)
+}
+
+/// Render the test scene, returning the events recorded
+#[cfg(test)]
+fn render_test_scene(progressive: Option<Budget>) -> RecordObserver {
+    let (mut scene, mut rt, config) = test_scene(progressive);
+    let observer = RecordObserver::default();
+    MultiThreaded::new(2).render(&mut scene, &mut rt, &config, &observer);
+    observer
+}
+
+#[test]
+fn test_observer() {
+    let observer = render_test_scene(None);
+    assert_eq!(
+        *observer.events.lock().unwrap(),
+        ["start 1 (0.5, 1.0) 2", "finish 1"]
+    );
+    let blocks = observer.blocks.lock().unwrap();
+    let mut done: Vec<_> = blocks.iter().map(|p| p.blocks_done).collect();
+    done.sort_unstable();
+    assert_eq!(done, [1, 2, 3, 4, 5, 6, 7, 8]);
+    assert!(blocks
+        .iter()
+        .all(|p| p.frame == 1 && p.pass == 1 && p.blocks_total == 8));
+}
+
+#[test]
+fn test_progressive() {
+    let budget = Budget {
+        passes: Some(3),
+        ..Budget::default()
+    };
+    let observer = render_test_scene(Some(budget));
+    assert_eq!(
+        *observer.events.lock().unwrap(),
+        [
+            "start 1 (0.5, 1.0) 2",
+            "pass 1 false",
+            "pass 2 true",
+            "pass 3 true",
+            "finish 1"
+        ]
+    );
+    let blocks = observer.blocks.lock().unwrap();
+    assert_eq!(blocks.len(), 24);
+    for pass in 1..4 {
+        assert_eq!(blocks.iter().filter(|p| p.pass == pass).count(), 8);
+    }
+
+    // A noise threshold that can't be reached stops at the time budget instead
+    let budget = Budget {
+        time: Some(Duration::from_nanos(1)),
+        noise: Some(0.0),
+        ..Budget::default()
+    };
+    let observer = render_test_scene(Some(budget));
+    assert_eq!(
+        *observer.events.lock().unwrap(),
+        ["start 1 (0.5, 1.0) 2", "pass 1 false", "finish 1"]
+    );
+}
+
+#[test]
+fn test_resume_checkpoint() {
+    let file = std::env::temp_dir().join(format!("aperture_resume_{}.chk", std::process::id()));
+    let passes = |p| {
+        Some(Budget {
+            passes: Some(p),
+            ..Budget::default()
+        })
+    };
+    let (mut scene, mut rt, mut config) = test_scene(passes(2));
+    config.checkpoint = Some(Checkpoint {
+        file: file.clone(),
+        interval: Duration::from_secs(3600),
+    });
+    MultiThreaded::new(2).render(&mut scene, &mut rt, &config, &RecordObserver::default());
+
+    // Resuming renders the remaining pass, or nothing if all of them were rendered
+    let (mut scene, mut resumed, mut config) = test_scene(passes(3));
+    assert_eq!(resumed.load_checkpoint(&file).unwrap(), 1);
+    assert_eq!(resumed.get_renderf32(), rt.get_renderf32());
+    let observer = RecordObserver::default();
+    MultiThreaded::new(2).render(&mut scene, &mut resumed, &config, &observer);
+    assert_eq!(
+        *observer.events.lock().unwrap(),
+        ["start 1 (0.5, 1.0) 2", "pass 3 false", "finish 1"]
+    );
+    assert_eq!(resumed.passes(), 3);
+    let observer = RecordObserver::default();
+    MultiThreaded::new(2).render(&mut scene, &mut resumed, &config, &observer);
+    assert_eq!(
+        *observer.events.lock().unwrap(),
+        ["start 1 (0.5, 1.0) 2", "finish 1"]
+    );
+
+    // Blocks already written in a pass are skipped
+    config.progressive = None;
+    let (_, mut rt, _) = test_scene(None);
+    let samples: Vec<_> = (0..64)
+        .map(|i| ImageSample::new(8.5 + (i % 8) as f32, 0.5 + (i / 8) as f32, Colorf::black()))
+        .collect();
+    rt.write(&samples, &sampler::Region::new((8, 0), BLOCK_DIM));
+    let observer = RecordObserver::default();
+    MultiThreaded::new(2).render(&mut scene, &mut rt, &config, &observer);
+    let blocks = observer.blocks.lock().unwrap();
+    let mut done: Vec<_> = blocks.iter().map(|p| p.blocks_done).collect();
+    done.sort_unstable();
+    assert_eq!(done, [2, 3, 4, 5, 6, 7, 8]);
+    assert_eq!(rt.passes(), 1);
+    let _ = std::fs::remove_file(file);
+}
+
+#[test]
+fn test_aovs() {
+    let (mut scene, mut rt, config) = test_scene(None);
+    rt.set_aovs(&[
+        Aov::Depth,
+        Aov::Normal,
+        Aov::InstanceId,
+        Aov::MaterialId,
+        Aov::Direct,
+    ]);
+    MultiThreaded::new(2).render(&mut scene, &mut rt, &config, &crate::exec::NoObserver);
+    // The sphere is 10 units in front of the camera with a radius of 1, covering the
+    // center of the image
+    let (w, h) = rt.dimensions();
+    let center = (h / 2) * w + w / 2;
+    let depth = rt.get_aov(Aov::Depth).unwrap();
+    assert!((depth[center] - 9.0).abs() < 0.2);
+    assert_eq!(depth[0], 0.0);
+    let normal = rt.get_aov(Aov::Normal).unwrap();
+    assert!(normal[center * 3 + 2] < -0.9);
+    assert_eq!(
+        rt.get_aov(Aov::InstanceId).unwrap()[center],
+        aov::id_of("ball")
+    );
+    assert_eq!(
+        rt.get_aov(Aov::MaterialId).unwrap()[center],
+        aov::id_of("white")
+    );
+    assert_eq!(rt.get_aov(Aov::InstanceId).unwrap()[0], 0.0);
+    // Whitted doesn't split the light, so all of it is direct
+    let direct = rt.get_aov(Aov::Direct).unwrap();
+    assert!(direct[center * 3] > 0.0);
+    assert_eq!(direct[0], 0.0);
+}
+
+#[test]
+fn test_sampler_config() {
+    // The uniform sampler takes one sample per pixel, in the 4 blocks of the image
+    let (mut scene, mut rt, mut config) = test_scene(None);
+    config.sampler = SamplerConfig::new(SamplerType::Uniform, (16, 8));
+    let observer = RecordObserver::default();
+    MultiThreaded::new(2).render(&mut scene, &mut rt, &config, &observer);
+    let blocks = observer.blocks.lock().unwrap();
+    assert_eq!(blocks.len(), 4);
+    assert!(blocks.iter().all(|p| p.blocks_total == 4));
+    assert!(rt.get_render_linear().iter().any(|&c| c > 0.0));
+
+    let (mut scene, mut rt, mut config) = test_scene(None);
+    let adaptive = SamplerType::Adaptive {
+        min_spp: 2,
+        max_spp: 8,
+    };
+    config.sampler = SamplerConfig::new(adaptive, (8, 16));
+    MultiThreaded::new(2).render(&mut scene, &mut rt, &config, &crate::exec::NoObserver);
+    assert_eq!(rt.passes(), 1);
+    assert!(rt.get_render_linear().iter().any(|&c| c > 0.0));
+}
+
+#[test]
+fn test_seeded_render() {
+    let render = |seed: u64, threads: u32| {
+        let budget = Budget {
+            passes: Some(2),
+            ..Budget::default()
+        };
+        let (mut scene, mut rt, mut config) = test_scene(Some(budget));
+        config.spp = 4;
+        config.sampler.seed = Some(seed);
+        MultiThreaded::new(threads).render(&mut scene, &mut rt, &config, &crate::exec::NoObserver);
+        rt.get_render_linear()
+    };
+    // The image is the same bit-for-bit whatever the number of threads
+    let image = render(1, 1);
+    assert!(image.iter().any(|&c| c > 0.0));
+    assert_eq!(render(1, 4), image);
+    assert_ne!(render(2, 4), image);
+}
+
+#[test]
+fn test_partial_blocks() {
+    // The 8x8 blocks are cut short along the right and bottom edges of the image
+    let (mut scene, mut rt, config) = test_scene_dim((30, 13), None);
+    let observer = RecordObserver::default();
+    MultiThreaded::new(2).render(&mut scene, &mut rt, &config, &observer);
+    let blocks = observer.blocks.lock().unwrap();
+    assert_eq!(blocks.len(), 8);
+    assert!(blocks.iter().all(|p| p.blocks_total == 8));
+    let image = rt.get_renderf32();
+    assert_eq!(image.len(), 30 * 13 * 4);
+    assert!(image.chunks(4).all(|px| px[3] > 0.0));
+}
+
+#[test]
+fn test_spectral_render() {
+    let render = |spectral: bool| {
+        let (mut scene, mut rt, mut config) = test_scene(None);
+        rt.set_spectral(spectral);
+        config.spp = 16;
+        config.sampler.seed = Some(1);
+        MultiThreaded::new(2).render(&mut scene, &mut rt, &config, &crate::exec::NoObserver);
+        // Sum each channel of the image
+        rt.get_render_linear()
+            .chunks(3)
+            .fold([0.0; 3], |s, px| [s[0] + px[0], s[1] + px[1], s[2] + px[2]])
+    };
+    // The white ball under the white light is white whether it's rendered with
+    // wavelengths or RGB
+    let rgb = render(false);
+    let spectral = render(true);
+    for c in 0..3 {
+        assert!(
+            f32::abs(spectral[c] - rgb[c]) < 0.05 * rgb[c],
+            "{:?} != {:?}",
+            spectral,
+            rgb
+        );
+    }
+}
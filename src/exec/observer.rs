@@ -0,0 +1,156 @@
+//! Provides the `Observer` trait which is notified of the progress of a render, e.g. to
+//! show a progress bar or display the blocks of the image as they're finished, and the
+//! `PrintProgress` observer which prints the progress to stdout.
+
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use crate::{
+    exec::Pass,
+    film::{ImageSample, RenderTarget},
+    sampler::Region,
+};
+
+/// The progress through rendering the blocks of a pass over the frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Progress {
+    /// The frame being rendered
+    pub frame: usize,
+    /// The pass being rendered, starting from 1. Frames which aren't rendered
+    /// progressively are rendered in a single pass
+    pub pass: usize,
+    /// Number of blocks of the pass which have been rendered
+    pub blocks_done: usize,
+    /// Number of blocks being rendered in each pass
+    pub blocks_total: usize,
+    /// Time spent rendering the pass so far
+    pub elapsed: Duration,
+}
+
+impl Progress {
+    /// Get the percentage of the blocks of the pass which have been rendered
+    pub fn percent(&self) -> f32 {
+        if self.blocks_total == 0 {
+            100.0
+        } else {
+            100.0 * self.blocks_done as f32 / self.blocks_total as f32
+        }
+    }
+    /// Estimate the time remaining to finish the pass, assuming the remaining blocks
+    /// take as long as the ones done so far. Returns None if no blocks are done yet
+    pub fn eta(&self) -> Option<Duration> {
+        if self.blocks_done == 0 {
+            return None;
+        }
+        let remaining = (self.blocks_total - self.blocks_done) as f64;
+        Some(Duration::from_secs_f64(
+            self.elapsed.as_secs_f64() * remaining / self.blocks_done as f64,
+        ))
+    }
+}
+
+/// Notified by the execution backend as it renders the scene. Blocks are reported from
+/// the threads rendering them, so observers must be safe to share between threads.
+/// All the methods do nothing by default so observers only implement the ones they need.
+pub trait Observer: Sync {
+    /// Called when rendering of `frame` starts, `time` is the time span of the scene
+    /// the frame covers and `threads` the number of threads rendering it
+    fn frame_started(&self, _frame: usize, _time: (f32, f32), _threads: u32) {}
+    /// Called when a block of the image has been rendered and its `samples` written
+    /// to the render target, along with the progress through the frame
+    fn block_finished(&self, _region: &Region, _samples: &[ImageSample], _progress: &Progress) {}
+    /// Called after each pass of a progressive render with the render target, which
+    /// holds the image rendered so far
+    fn pass_finished(&self, _pass: &Pass, _rt: &RenderTarget) {}
+    /// Called when `frame` has been rendered, taking `time` to render
+    fn frame_finished(&self, _frame: usize, _time: Duration) {}
+}
+
+/// Observer which ignores the progress of the render
+pub struct NoObserver;
+
+impl Observer for NoObserver {}
+
+/// Prints the progress of the render to stdout, reporting every 10% of each frame
+/// along with the estimated time remaining. For progressive renders the blocks are
+/// only reported for the first pass, followed by a line for each pass.
+pub struct PrintProgress {
+    /// The last multiple of 10% of the frame done which was printed
+    printed: AtomicUsize,
+}
+
+impl PrintProgress {
+    pub fn new() -> PrintProgress {
+        PrintProgress {
+            printed: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl Default for PrintProgress {
+    fn default() -> PrintProgress {
+        PrintProgress::new()
+    }
+}
+
+impl Observer for PrintProgress {
+    fn frame_started(&self, frame: usize, time: (f32, f32), threads: u32) {
+        self.printed.store(0, Ordering::Release);
+        println!("Rendering using {} threads\n--------------------", threads);
+        println!("Frame {}: rendering for {} to {}", frame, time.0, time.1);
+    }
+    fn block_finished(&self, _: &Region, _: &[ImageSample], progress: &Progress) {
+        if progress.pass != 1 {
+            return;
+        }
+        let step = progress.percent() as usize / 10;
+        // Only the thread which moves the progress on to the next step prints it
+        if step > self.printed.fetch_max(step, Ordering::AcqRel) && step < 10 {
+            let eta = progress.eta().unwrap_or_default();
+            println!(
+                "Frame {}: {}% done, about {:.1}s remaining",
+                progress.frame,
+                step * 10,
+                eta.as_secs_f64()
+            );
+        }
+    }
+    fn pass_finished(&self, pass: &Pass, _: &RenderTarget) {
+        let noise = match pass.noise {
+            Some(n) => format!(", estimated error {:.4}", n),
+            None => String::new(),
+        };
+        println!(
+            "Frame {}: pass {} done after {:.1}s{}",
+            pass.frame,
+            pass.pass,
+            pass.elapsed.as_secs_f64(),
+            noise
+        );
+    }
+    fn frame_finished(&self, frame: usize, time: Duration) {
+        println!("Frame {}: rendering took {:4}s", frame, time.as_secs_f64());
+    }
+}
+
+#[test]
+fn test_progress() {
+    let mut progress = Progress {
+        frame: 0,
+        pass: 1,
+        blocks_done: 0,
+        blocks_total: 8,
+        elapsed: Duration::from_secs(1),
+    };
+    assert_eq!(progress.percent(), 0.0);
+    assert_eq!(progress.eta(), None);
+    progress.blocks_done = 2;
+    progress.elapsed = Duration::from_secs(3);
+    assert_eq!(progress.percent(), 25.0);
+    assert_eq!(progress.eta(), Some(Duration::from_secs(9)));
+    progress.blocks_done = 8;
+    assert_eq!(progress.percent(), 100.0);
+    assert_eq!(progress.eta(), Some(Duration::from_secs(0)));
+}
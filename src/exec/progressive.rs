@@ -0,0 +1,141 @@
+//! Provides the limits for progressive rendering, where each frame is rendered in passes
+//! which are accumulated in the render target until a pass count, time budget or noise
+//! threshold is reached. Each pass takes the samples per pixel set in the `Config`, so
+//! the image after any pass is a complete, if noisy, render of the frame.
+//!
+//! The noise of the image is estimated from how much the last pass changed it. If the
+//! image after `n` passes is `I_n`, the change `I_n - I_n-1` has a variance of
+//! `1 / (n - 1)` times the variance of `I_n`, so the relative RMS error of the image is
+//! estimated as `sqrt((n - 1) * mean((L_n - L_n-1)^2)) / mean(L_n)` using the luminance
+//! `L` of the pixels.
+
+use std::time::Duration;
+
+use crate::film::Colorf;
+
+/// Limits on the passes of a progressive render, rendering stops once any of the
+/// limits set is reached. At least one pass is always rendered, and only one is
+/// rendered if no limits are set.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Budget {
+    /// Maximum number of passes to render
+    pub passes: Option<usize>,
+    /// Wall clock time the passes must finish within, no pass is started which is
+    /// expected to run past it based on the time taken by the last one
+    pub time: Option<Duration>,
+    /// Stop once the estimated relative RMS error of the image is below this
+    pub noise: Option<f32>,
+}
+
+impl Budget {
+    /// Check if rendering should stop after the pass
+    pub fn done(&self, pass: &Pass) -> bool {
+        if self.passes.is_none() && self.time.is_none() && self.noise.is_none() {
+            return true;
+        }
+        self.passes.is_some_and(|p| pass.pass >= p)
+            || self.time.is_some_and(|t| pass.elapsed + pass.pass_time > t)
+            || self
+                .noise
+                .is_some_and(|n| pass.noise.is_some_and(|e| e <= n))
+    }
+}
+
+/// The state of the render after a pass of a progressive render
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pass {
+    /// The frame being rendered
+    pub frame: usize,
+    /// Number of passes rendered so far, starting from 1
+    pub pass: usize,
+    /// Time spent rendering the frame so far
+    pub elapsed: Duration,
+    /// Time taken to render the last pass
+    pub pass_time: Duration,
+    /// The estimated relative RMS error of the image, which can only be
+    /// estimated after the second pass
+    pub noise: Option<f32>,
+}
+
+/// Estimate the relative RMS error of the image `current` after `passes` passes from
+/// the `previous` image before the last pass. The images are the weighted sums of
+/// RGBA samples returned by `RenderTarget::get_renderf32`.
+pub fn estimate_noise(previous: &[f32], current: &[f32], passes: usize) -> f32 {
+    let luminance = |px: &[f32]| {
+        if px[3] > 0.0 {
+            Colorf::new(px[0], px[1], px[2]).luminance() / px[3]
+        } else {
+            0.0
+        }
+    };
+    let mut sum = 0.0;
+    let mut sum_sqr_diff = 0.0;
+    for (p, c) in previous.chunks(4).zip(current.chunks(4)) {
+        let l = luminance(c);
+        let diff = l - luminance(p);
+        sum += l as f64;
+        sum_sqr_diff += (diff * diff) as f64;
+    }
+    let n = (current.len() / 4) as f64;
+    if n == 0.0 || sum <= 0.0 {
+        return 0.0;
+    }
+    let mse = (passes.max(2) - 1) as f64 * sum_sqr_diff / n;
+    (mse.sqrt() / (sum / n)) as f32
+}
+
+#[test]
+fn test_budget() {
+    let pass = Pass {
+        frame: 0,
+        pass: 3,
+        elapsed: Duration::from_secs(10),
+        pass_time: Duration::from_secs(4),
+        noise: None,
+    };
+    assert!(Budget::default().done(&pass));
+    let passes = |p| Budget {
+        passes: Some(p),
+        ..Budget::default()
+    };
+    assert!(passes(3).done(&pass));
+    assert!(!passes(4).done(&pass));
+    let time = |t| Budget {
+        time: Some(Duration::from_secs(t)),
+        ..Budget::default()
+    };
+    assert!(time(13).done(&pass));
+    assert!(!time(14).done(&pass));
+    let noise = Budget {
+        noise: Some(0.05),
+        ..Budget::default()
+    };
+    assert!(!noise.done(&pass));
+    assert!(!noise.done(&Pass {
+        noise: Some(0.1),
+        ..pass
+    }));
+    assert!(noise.done(&Pass {
+        noise: Some(0.05),
+        ..pass
+    }));
+}
+
+#[test]
+fn test_estimate_noise() {
+    // Gray pixels with weight 2, and the same colors with the changes of the last pass
+    let pixel = |l: f32| [2.0 * l, 2.0 * l, 2.0 * l, 2.0];
+    let previous: Vec<f32> = [0.5, 1.0, 1.5, 1.0]
+        .iter()
+        .flat_map(|&l| pixel(l))
+        .collect();
+    let current: Vec<f32> = [0.4, 1.1, 1.5, 1.0]
+        .iter()
+        .flat_map(|&l| pixel(l))
+        .collect();
+    // mean luminance is 1 and the mean squared change is 0.02 / 4
+    let expected = f32::sqrt(4.0 * 0.02 / 4.0);
+    assert!((estimate_noise(&previous, &current, 5) - expected).abs() < 1e-5);
+    assert_eq!(estimate_noise(&previous, &previous, 5), 0.0);
+    assert_eq!(estimate_noise(&[0.0; 8], &[0.0; 8], 2), 0.0);
+}
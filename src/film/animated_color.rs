@@ -50,6 +50,10 @@ impl AnimatedColor {
         keyframes.sort();
         Self { keyframes }
     }
+    /// Get the color keyframes, in time order
+    pub fn keyframes(&self) -> &[ColorKeyframe] {
+        &self.keyframes
+    }
     /// Compute the color at the desired time
     pub fn color(&self, time: f32) -> Colorf {
         if self.keyframes.is_empty() {
@@ -0,0 +1,241 @@
+//! Provides the arbitrary output variables (AOVs), extra layers of the image which
+//! the render target can accumulate alongside the beauty image for compositing.
+//! The layers are taken from the first surface hit by each camera ray:
+//!
+//! - `albedo`: The reflectance of the surface, estimated from a sample of its BSDF
+//! - `normal`: The world space shading normal
+//! - `position`: The world space position of the hit
+//! - `depth`: The distance along the camera ray to the hit
+//! - `uv`: The surface's texture coordinates
+//! - `instance_id`: An id of the instance's name (`Instance::tag`)
+//! - `material_id`: An id of the name of the instance's material
+//!
+//! Or are the light reaching the camera split by the path it took, for integrators
+//! which track it:
+//!
+//! - `direct`: Light reflected once off the first surface, along with lights seen
+//!   directly, while `indirect` holds the light of all longer paths. These sum to the
+//!   beauty image
+//! - `diffuse`: Light reflected diffusely off the first surface, while `specular`
+//!   holds the light reflected off it by glossy or specular BxDFs. These sum to the
+//!   beauty image without the lights seen directly
+//!
+//! Or are statistics of the samples of the beauty image, see `film::variance`:
+//!
+//! - `variance`: The variance of the mean of each pixel's samples
+//!
+//! Rays which don't hit anything count as 0 in each layer. The ids are hashes of the
+//! names, which are the same between renders, and are 0 where nothing was hit. Ids
+//! are taken from the sample nearest the pixel's center while the other layers are
+//! averaged over the pixel.
+//!
+//! AOVs are saved in checkpoints, but can't be rendered by distributed workers.
+//!
+//! OpenEXR and PFM images store the layers as they are, while Radiance RGBE and 8-bit
+//! images can't store negative values, so normals are saved to them remapped to
+//! `n * 0.5 + 0.5`. Other layers with values these formats can't store, like
+//! positions or depths beyond 1 in 8-bit images, are clamped with a warning.
+//!
+//! # Scene Usage Example
+//! The layers to render are listed in the film:
+//!
+//! ```json
+//! "film": {
+//!     ...
+//!     "aovs": ["albedo", "normal", "depth", "instance_id", "direct", "indirect"]
+//! }
+//! ```
+
+use crate::{
+    film::{Colorf, Wavelengths},
+    linalg::{Normal, Point},
+};
+
+/// The layers which can be rendered alongside the image
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Aov {
+    Albedo,
+    Normal,
+    Position,
+    Depth,
+    Uv,
+    InstanceId,
+    MaterialId,
+    Direct,
+    Indirect,
+    Diffuse,
+    Specular,
+    Variance,
+}
+
+impl Aov {
+    /// All the AOVs which can be rendered
+    pub const ALL: [Aov; 12] = [
+        Aov::Albedo,
+        Aov::Normal,
+        Aov::Position,
+        Aov::Depth,
+        Aov::Uv,
+        Aov::InstanceId,
+        Aov::MaterialId,
+        Aov::Direct,
+        Aov::Indirect,
+        Aov::Diffuse,
+        Aov::Specular,
+        Aov::Variance,
+    ];
+    /// Get the name of the AOV used in scene files and image file names
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Aov::Albedo => "albedo",
+            Aov::Normal => "normal",
+            Aov::Position => "position",
+            Aov::Depth => "depth",
+            Aov::Uv => "uv",
+            Aov::InstanceId => "instance_id",
+            Aov::MaterialId => "material_id",
+            Aov::Direct => "direct",
+            Aov::Indirect => "indirect",
+            Aov::Diffuse => "diffuse",
+            Aov::Specular => "specular",
+            Aov::Variance => "variance",
+        }
+    }
+    /// Find the AOV with the name
+    pub fn from_name(name: &str) -> Option<Aov> {
+        Aov::ALL.iter().find(|a| a.name() == name).cloned()
+    }
+    /// Get the names of the channels of the layer
+    pub fn channels(&self) -> &'static [&'static str] {
+        match *self {
+            Aov::Normal | Aov::Position => &["X", "Y", "Z"],
+            Aov::Depth => &["Z"],
+            Aov::Uv => &["U", "V"],
+            Aov::InstanceId | Aov::MaterialId => &["id"],
+            _ => &["R", "G", "B"],
+        }
+    }
+    /// Check if the layer holds ids, which can't be averaged
+    pub fn is_id(&self) -> bool {
+        matches!(*self, Aov::InstanceId | Aov::MaterialId)
+    }
+    /// Check if the layer holds light split by path, which is computed by the integrator
+    pub fn is_light(&self) -> bool {
+        matches!(
+            *self,
+            Aov::Direct | Aov::Indirect | Aov::Diffuse | Aov::Specular
+        )
+    }
+    /// Check if the layer holds data which can be negative
+    pub fn is_signed(&self) -> bool {
+        matches!(*self, Aov::Normal | Aov::Position)
+    }
+    /// Check if the layer holds statistics of the image samples, which are computed
+    /// from the samples written to the image rather than from AOV samples
+    pub fn is_stat(&self) -> bool {
+        matches!(*self, Aov::Variance)
+    }
+    /// Get the layer's value for the sample, the unused channels are 0 as are all
+    /// the channels of statistics layers
+    pub fn value(&self, s: &AovSample) -> [f32; 3] {
+        let color = |c: &Colorf| [c.r, c.g, c.b];
+        match *self {
+            Aov::Albedo => color(&s.albedo),
+            Aov::Normal => [s.normal.x, s.normal.y, s.normal.z],
+            Aov::Position => [s.position.x, s.position.y, s.position.z],
+            Aov::Depth => [s.depth, 0.0, 0.0],
+            Aov::Uv => [s.uv.0, s.uv.1, 0.0],
+            Aov::InstanceId => [s.instance_id, 0.0, 0.0],
+            Aov::MaterialId => [s.material_id, 0.0, 0.0],
+            Aov::Direct => color(&s.light.direct),
+            Aov::Indirect => color(&s.light.indirect),
+            Aov::Diffuse => color(&s.light.diffuse),
+            Aov::Specular => color(&s.light.specular),
+            Aov::Variance => [0.0; 3],
+        }
+    }
+}
+
+/// The light reaching the camera split by the path it took
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LightSplit {
+    pub direct: Colorf,
+    pub indirect: Colorf,
+    pub diffuse: Colorf,
+    pub specular: Colorf,
+}
+
+impl LightSplit {
+    pub fn black() -> LightSplit {
+        LightSplit {
+            direct: Colorf::black(),
+            indirect: Colorf::black(),
+            diffuse: Colorf::black(),
+            specular: Colorf::black(),
+        }
+    }
+    /// Convert the light split, carried at the wavelengths in spectral mode, to RGB
+    #[must_use]
+    pub fn to_rgb(&self, w: &Wavelengths) -> LightSplit {
+        LightSplit {
+            direct: w.to_rgb(&self.direct),
+            indirect: w.to_rgb(&self.indirect),
+            diffuse: w.to_rgb(&self.diffuse),
+            specular: w.to_rgb(&self.specular),
+        }
+    }
+}
+
+/// The values of the AOVs for a camera ray through the image at `(x, y)`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AovSample {
+    pub x: f32,
+    pub y: f32,
+    pub albedo: Colorf,
+    pub normal: Normal,
+    pub position: Point,
+    pub depth: f32,
+    pub uv: (f32, f32),
+    pub instance_id: f32,
+    pub material_id: f32,
+    pub light: LightSplit,
+}
+
+impl AovSample {
+    /// The sample for a ray which didn't hit anything
+    pub fn background(x: f32, y: f32) -> AovSample {
+        AovSample {
+            x,
+            y,
+            albedo: Colorf::black(),
+            normal: Normal::broadcast(0.0),
+            position: Point::broadcast(0.0),
+            depth: 0.0,
+            uv: (0.0, 0.0),
+            instance_id: 0.0,
+            material_id: 0.0,
+            light: LightSplit::black(),
+        }
+    }
+}
+
+/// Get the id written to the id layers for the name, a 24 bit FNV-1a hash so
+/// it's stored exactly as a float. Only names which hash to 0 share the id of
+/// the background
+pub fn id_of(name: &str) -> f32 {
+    let hash = name.bytes().fold(0x811c_9dc5u32, |h, b| {
+        (h ^ u32::from(b)).wrapping_mul(0x0100_0193)
+    });
+    (hash & 0xff_ffff) as f32
+}
+
+#[test]
+fn test_aov_names() {
+    for aov in &Aov::ALL {
+        assert_eq!(Aov::from_name(aov.name()), Some(*aov));
+    }
+    assert_eq!(Aov::from_name("beauty"), None);
+    assert_eq!(id_of("floor"), id_of("floor"));
+    assert_ne!(id_of("floor"), id_of("light"));
+    assert_eq!(id_of("floor").fract(), 0.0);
+}
@@ -23,9 +23,11 @@
 use crate::linalg::{self, AnimatedTransform, Matrix4, Point, Ray, Transform, Vector};
 use bspline::BSpline;
 
+/// The field of view of the camera, in degrees
 #[derive(Clone, Debug)]
-enum CameraFov {
+pub enum CameraFov {
     Unanimated(f32),
+    /// The field of view animated along a B-spline over time
     Animated(BSpline<f32>),
 }
 
@@ -200,6 +202,18 @@ impl Camera {
             self.shutter_open, self.shutter_close
         );
     }
+    /// Get the transformation from camera to world space
+    pub fn transform(&self) -> &AnimatedTransform {
+        &self.cam_world
+    }
+    /// Get the field of view of the camera
+    pub fn fov(&self) -> &CameraFov {
+        &self.fov
+    }
+    /// Get the percentage of the frame the shutter is open for
+    pub fn shutter_size(&self) -> f32 {
+        self.shutter_size
+    }
     /// Get the time that the shutter opens and closes at
     pub fn shutter_time(&self) -> (f32, f32) {
         (self.shutter_open, self.shutter_close)
@@ -0,0 +1,297 @@
+//! Provides the RGB color spaces scenes are rendered and saved in. The scene is
+//! rendered in its working space, which is the space the RGB colors of materials and
+//! lights in the scene are given in, and saved in its output space. The color spaces
+//! supported are:
+//!
+//! - `srgb`: The primaries and D65 white point of Rec.709 with the sRGB transfer
+//!   curve, which is what 8-bit images are usually encoded in
+//! - `linear_srgb`: The same primaries as `srgb` without the transfer curve
+//! - `acescg`: The wide gamut AP1 primaries of ACES with its D60 white point, which
+//!   is linear. Colors are adapted to and from D65 with the Bradford transform
+//! - `rec2020`: The ultra wide gamut primaries and D65 white point of Rec.2020, with
+//!   its transfer curve
+//! - `display_p3`: The primaries of DCI-P3 with the D65 white point and the sRGB
+//!   transfer curve, as used by Apple's displays
+//!
+//! Rendering is always done in linear RGB, so only the primaries of the working space
+//! matter. The output space's transfer curve is applied to 8-bit images after the
+//! display transform, while HDR images are saved linear with its primaries. Light
+//! and albedo AOVs are converted to the output space like the image.
+//!
+//! Image textures are decoded from the color space they're tagged with and converted
+//! to the working space when they're sampled. Colors of untagged textures are decoded
+//! as `srgb`, while scalars read from them, e.g. for roughness or bump maps, are used
+//! as they are. Tagging a texture `srgb` decodes its scalars too, and textures holding
+//! data rather than colors should be tagged `raw` so their colors are used as they are.
+//!
+//! In spectral mode colors are converted from the working space to linear sRGB before
+//! they're upsampled to spectra, so parts of wide gamut colors outside the sRGB gamut
+//! are lost, see `film::spectrum`.
+//!
+//! # Scene Usage Example
+//! The color spaces are set in the film, both are optional and default to
+//! `linear_srgb` and `srgb`, which renders as scenes did before color spaces
+//! were added.
+//!
+//! ```json
+//! "film": {
+//!     ...
+//!     "colorspace": {
+//!         "working": "acescg",
+//!         "output": "display_p3"
+//!     }
+//! }
+//! ```
+//!
+//! Textures are tagged with the color space of their image file.
+//!
+//! ```json
+//! "textures": [
+//!     {
+//!         "name": "bricks",
+//!         "type": "image",
+//!         "file": "bricks.png",
+//!         "colorspace": "srgb"
+//!     },
+//!     {
+//!         "name": "bricks_roughness",
+//!         "type": "image",
+//!         "file": "bricks_roughness.png",
+//!         "colorspace": "raw"
+//!     },
+//!     ...
+//! ]
+//! ```
+
+use std::f32;
+
+use crate::film::Colorf;
+
+/// Converts linear Rec.709 RGB to XYZ with the D65 white point
+const SRGB_TO_XYZ: [[f32; 3]; 3] = [
+    [0.412_390_8, 0.357_584_3, 0.180_480_8],
+    [0.212_639, 0.715_168_7, 0.072_192_3],
+    [0.019_330_8, 0.119_194_8, 0.950_532_2],
+];
+const XYZ_TO_SRGB: [[f32; 3]; 3] = [
+    [3.240_97, -1.537_383_2, -0.498_610_8],
+    [-0.969_243_6, 1.875_967_5, 0.041_555_1],
+    [0.055_630_1, -0.203_977, 1.056_971_5],
+];
+/// Converts ACEScg to XYZ, adapted from its D60 white point to D65
+const ACESCG_TO_XYZ: [[f32; 3]; 3] = [
+    [0.652_237_5, 0.128_236_1, 0.169_982_2],
+    [0.267_672_2, 0.674_34, 0.057_987_8],
+    [-0.005_381_8, 0.001_369_1, 1.093_070_5],
+];
+const XYZ_TO_ACESCG: [[f32; 3]; 3] = [
+    [1.660_585_3, -0.315_295_6, -0.241_509_3],
+    [-0.659_926_1, 1.608_391_5, 0.017_298_6],
+    [0.009_002_6, -0.003_566_9, 0.913_643_3],
+];
+const REC2020_TO_XYZ: [[f32; 3]; 3] = [
+    [0.636_958, 0.144_616_9, 0.168_881],
+    [0.262_700_2, 0.677_998_1, 0.059_301_7],
+    [0.0, 0.028_072_7, 1.060_985_1],
+];
+const XYZ_TO_REC2020: [[f32; 3]; 3] = [
+    [1.716_651_2, -0.355_670_8, -0.253_366_3],
+    [-0.666_684_4, 1.616_481_2, 0.015_768_5],
+    [0.017_639_9, -0.042_770_6, 0.942_103_1],
+];
+const DISPLAY_P3_TO_XYZ: [[f32; 3]; 3] = [
+    [0.486_570_9, 0.265_667_7, 0.198_217_3],
+    [0.228_974_6, 0.691_738_5, 0.079_286_9],
+    [0.0, 0.045_113_4, 1.043_944_4],
+];
+const XYZ_TO_DISPLAY_P3: [[f32; 3]; 3] = [
+    [2.493_497, -0.931_383_6, -0.402_710_8],
+    [-0.829_489, 1.762_664_1, 0.023_624_7],
+    [0.035_845_8, -0.076_172_4, 0.956_884_5],
+];
+
+/// The RGB color spaces colors can be rendered, loaded and saved in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSpace {
+    Srgb,
+    LinearSrgb,
+    Acescg,
+    Rec2020,
+    DisplayP3,
+}
+
+impl ColorSpace {
+    /// All the color spaces, in the order they're listed in errors
+    pub const ALL: [ColorSpace; 5] = [
+        ColorSpace::Srgb,
+        ColorSpace::LinearSrgb,
+        ColorSpace::Acescg,
+        ColorSpace::Rec2020,
+        ColorSpace::DisplayP3,
+    ];
+
+    /// Get the name of the color space used in scene files
+    pub fn name(&self) -> &'static str {
+        match *self {
+            ColorSpace::Srgb => "srgb",
+            ColorSpace::LinearSrgb => "linear_srgb",
+            ColorSpace::Acescg => "acescg",
+            ColorSpace::Rec2020 => "rec2020",
+            ColorSpace::DisplayP3 => "display_p3",
+        }
+    }
+    /// Find the color space with the name used in scene files
+    pub fn from_name(name: &str) -> Option<ColorSpace> {
+        ColorSpace::ALL.iter().find(|c| c.name() == name).cloned()
+    }
+    /// Convert the linear color from this color space to `to`, keeping its alpha
+    pub fn convert(&self, c: &Colorf, to: ColorSpace) -> Colorf {
+        let (to_xyz, _) = self.matrices();
+        let (other_to_xyz, from_xyz) = to.matrices();
+        // sRGB and linear sRGB share their primaries
+        if to_xyz == other_to_xyz {
+            return *c;
+        }
+        let mut out = mul(from_xyz, &mul(to_xyz, c));
+        out.a = c.a;
+        out
+    }
+    /// Apply the transfer curve of the color space to the linear color, giving the
+    /// values to store in an 8-bit image
+    pub fn encode(&self, c: &Colorf) -> Colorf {
+        let mut out = *c;
+        for i in 0..3 {
+            out[i] = match *self {
+                ColorSpace::Srgb | ColorSpace::DisplayP3 => srgb_encode(c[i]),
+                ColorSpace::Rec2020 => rec2020_encode(c[i]),
+                ColorSpace::LinearSrgb | ColorSpace::Acescg => c[i],
+            };
+        }
+        out
+    }
+    /// Undo the transfer curve of the color space, giving the linear color
+    /// of the values stored in an image
+    pub fn decode(&self, c: &Colorf) -> Colorf {
+        let mut out = *c;
+        for i in 0..3 {
+            out[i] = self.decode_f32(c[i]);
+        }
+        out
+    }
+    /// Undo the transfer curve of the color space for a single value
+    pub fn decode_f32(&self, x: f32) -> f32 {
+        match *self {
+            ColorSpace::Srgb | ColorSpace::DisplayP3 => srgb_decode(x),
+            ColorSpace::Rec2020 => rec2020_decode(x),
+            ColorSpace::LinearSrgb | ColorSpace::Acescg => x,
+        }
+    }
+    /// Get the matrices converting the color space to and from XYZ
+    fn matrices(&self) -> (&'static [[f32; 3]; 3], &'static [[f32; 3]; 3]) {
+        match *self {
+            ColorSpace::Srgb | ColorSpace::LinearSrgb => (&SRGB_TO_XYZ, &XYZ_TO_SRGB),
+            ColorSpace::Acescg => (&ACESCG_TO_XYZ, &XYZ_TO_ACESCG),
+            ColorSpace::Rec2020 => (&REC2020_TO_XYZ, &XYZ_TO_REC2020),
+            ColorSpace::DisplayP3 => (&DISPLAY_P3_TO_XYZ, &XYZ_TO_DISPLAY_P3),
+        }
+    }
+}
+
+fn mul(m: &[[f32; 3]; 3], c: &Colorf) -> Colorf {
+    let row = |r: &[f32; 3]| r[0] * c.r + r[1] * c.g + r[2] * c.b;
+    Colorf::new(row(&m[0]), row(&m[1]), row(&m[2]))
+}
+
+fn srgb_encode(x: f32) -> f32 {
+    if x <= 0.003_130_8 {
+        12.92 * x
+    } else {
+        1.055 * f32::powf(x, 1.0 / 2.4) - 0.055
+    }
+}
+
+fn srgb_decode(x: f32) -> f32 {
+    if x <= 0.040_45 {
+        x / 12.92
+    } else {
+        f32::powf((x + 0.055) / 1.055, 2.4)
+    }
+}
+
+/// The Rec.2020 transfer curve, with its constants for 12-bit video
+fn rec2020_encode(x: f32) -> f32 {
+    if x < 0.018_054 {
+        4.5 * x
+    } else {
+        1.099_297 * f32::powf(x, 0.45) - 0.099_297
+    }
+}
+
+fn rec2020_decode(x: f32) -> f32 {
+    if x < 0.081_243 {
+        x / 4.5
+    } else {
+        f32::powf((x + 0.099_297) / 1.099_297, 1.0 / 0.45)
+    }
+}
+
+#[cfg(test)]
+fn assert_color_near(a: &Colorf, b: &Colorf, tolerance: f32) {
+    for i in 0..3 {
+        assert!((a[i] - b[i]).abs() < tolerance, "{:?} != {:?}", a, b);
+    }
+}
+
+#[test]
+fn test_names() {
+    for c in &ColorSpace::ALL {
+        assert_eq!(ColorSpace::from_name(c.name()), Some(*c));
+    }
+    assert_eq!(ColorSpace::from_name("raw"), None);
+}
+
+#[test]
+fn test_convert() {
+    let white = Colorf::broadcast(1.0);
+    let c = Colorf::with_alpha(0.8, 0.3, 0.1, 0.5);
+    for from in &ColorSpace::ALL {
+        // All the spaces share the same white
+        assert_color_near(&from.convert(&white, ColorSpace::LinearSrgb), &white, 1e-4);
+        for to in &ColorSpace::ALL {
+            let converted = from.convert(&c, *to);
+            assert_eq!(converted.a, c.a);
+            assert_color_near(&to.convert(&converted, *from), &c, 1e-4);
+        }
+    }
+    assert_eq!(ColorSpace::Srgb.convert(&c, ColorSpace::LinearSrgb), c);
+    // Saturated sRGB colors are less saturated in wider gamuts
+    let red = Colorf::new(1.0, 0.0, 0.0);
+    assert_color_near(
+        &ColorSpace::LinearSrgb.convert(&red, ColorSpace::Acescg),
+        &Colorf::new(0.6131, 0.0702, 0.0206),
+        1e-3,
+    );
+    assert_color_near(
+        &ColorSpace::LinearSrgb.convert(&red, ColorSpace::Rec2020),
+        &Colorf::new(0.6274, 0.0691, 0.0164),
+        1e-3,
+    );
+    assert_color_near(
+        &ColorSpace::LinearSrgb.convert(&red, ColorSpace::DisplayP3),
+        &Colorf::new(0.8225, 0.0332, 0.0171),
+        1e-3,
+    );
+}
+
+#[test]
+fn test_transfer() {
+    let c = Colorf::new(0.0, 0.002, 0.5);
+    for space in &ColorSpace::ALL {
+        assert_color_near(&space.decode(&space.encode(&c)), &c, 1e-5);
+    }
+    assert_color_near(&ColorSpace::Srgb.encode(&c), &c.to_srgb(), 1e-6);
+    assert_eq!(ColorSpace::Acescg.encode(&c), c);
+    assert!((ColorSpace::Srgb.decode_f32(0.5) - 0.214).abs() < 1e-3);
+    assert!((ColorSpace::Rec2020.decode_f32(0.5) - 0.260).abs() < 1e-3);
+    assert!((ColorSpace::Rec2020.encode(&Colorf::broadcast(1.0)).r - 1.0).abs() < 1e-5);
+}
@@ -0,0 +1,119 @@
+//! Provides the crop window, which restricts rendering to a rectangle of the image
+//! so part of it can be re-rendered. Only the blocks of the image overlapping the
+//! window are rendered and samples are only written to the pixels inside it.
+//!
+//! # Scene Usage Example
+//! The window is set in the film, either as fractions of the image like pbrt's
+//! `cropwindow`, or in pixels with `pixel_bounds`. Both are given as
+//! `[x_min, x_max, y_min, y_max]`, where the max is excluded from the window.
+//!
+//! ```json
+//! "film": {
+//!     ...
+//!     "crop_window": [0.25, 0.75, 0.0, 0.5]
+//! }
+//! ```
+//!
+//! ```json
+//! "film": {
+//!     ...
+//!     "pixel_bounds": [160, 480, 0, 240]
+//! }
+//! ```
+//!
+//! The window can also be set on the command line with `--crop` or `--crop-pixels`,
+//! while `--crop-output` picks if the full image, just the window or the window
+//! composited over the existing image in the output file is saved.
+
+/// A rectangle of pixels of the image, from `start` up to but not including `end`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CropWindow {
+    pub start: (u32, u32),
+    pub end: (u32, u32),
+}
+
+impl CropWindow {
+    pub fn new(start: (u32, u32), end: (u32, u32)) -> CropWindow {
+        CropWindow { start, end }
+    }
+    /// Get the window covering the whole image of dimensions `dim`
+    pub fn full(dim: (usize, usize)) -> CropWindow {
+        CropWindow::new((0, 0), (dim.0 as u32, dim.1 as u32))
+    }
+    /// Get the window of the image covering the fractions `x` and `y` of its width
+    /// and height. Pixels are rounded the same way as pbrt's crop window
+    pub fn from_normalized(dim: (usize, usize), x: (f32, f32), y: (f32, f32)) -> CropWindow {
+        let px = |f: f32, size: usize| (f * size as f32).ceil().max(0.0) as u32;
+        CropWindow::new(
+            (px(x.0, dim.0), px(y.0, dim.1)),
+            (px(x.1, dim.0), px(y.1, dim.1)),
+        )
+    }
+    /// Get the dimensions of the window in pixels
+    pub fn dimensions(&self) -> (usize, usize) {
+        (
+            self.end.0.saturating_sub(self.start.0) as usize,
+            self.end.1.saturating_sub(self.start.1) as usize,
+        )
+    }
+    /// Check the window covers some pixels and is inside the image of dimensions `dim`
+    pub fn fits(&self, dim: (usize, usize)) -> bool {
+        self.start.0 < self.end.0
+            && self.start.1 < self.end.1
+            && self.end.0 as usize <= dim.0
+            && self.end.1 as usize <= dim.1
+    }
+    /// Check if the pixel `(x, y)` is in the window
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        x >= self.start.0 as usize
+            && x < self.end.0 as usize
+            && y >= self.start.1 as usize
+            && y < self.end.1 as usize
+    }
+    /// Cut the window out of the `data` of an image `width` pixels wide with
+    /// `channels` values per pixel
+    pub fn crop<T: Copy>(&self, data: &[T], width: usize, channels: usize) -> Vec<T> {
+        let (w, h) = self.dimensions();
+        let mut cropped = Vec::with_capacity(w * h * channels);
+        for y in self.start.1 as usize..self.end.1 as usize {
+            let row = (y * width + self.start.0 as usize) * channels;
+            cropped.extend_from_slice(&data[row..row + w * channels]);
+        }
+        cropped
+    }
+    /// Replace the pixels of `data` outside the window with those of `base`, both
+    /// images `width` pixels wide with `channels` values per pixel
+    pub fn composite<T: Copy>(&self, data: &mut [T], base: &[T], width: usize, channels: usize) {
+        for (i, (px, b)) in data
+            .chunks_mut(channels)
+            .zip(base.chunks(channels))
+            .enumerate()
+        {
+            if !self.contains(i % width, i / width) {
+                px.copy_from_slice(b);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_crop_window() {
+    let window = CropWindow::from_normalized((10, 4), (0.25, 0.75), (0.0, 0.5));
+    assert_eq!(window, CropWindow::new((3, 0), (8, 2)));
+    assert_eq!(window.dimensions(), (5, 2));
+    assert!(window.fits((10, 4)));
+    assert!(!window.fits((7, 4)));
+    assert!(!CropWindow::new((3, 2), (3, 4)).fits((10, 4)));
+    assert_eq!(CropWindow::full((10, 4)).dimensions(), (10, 4));
+    assert!(window.contains(3, 1) && !window.contains(8, 1) && !window.contains(3, 2));
+
+    let image: Vec<_> = (0..40).collect();
+    assert_eq!(
+        window.crop(&image, 10, 1),
+        [3, 4, 5, 6, 7, 13, 14, 15, 16, 17]
+    );
+    let mut data = vec![-1; 40];
+    window.composite(&mut data, &image, 10, 1);
+    assert_eq!(data[..4], [0, 1, 2, -1]);
+    assert_eq!(data[20..], image[20..]);
+}
@@ -0,0 +1,247 @@
+//! Provides a joint cross-bilateral denoiser which is run on the image after it's
+//! accumulated, making low sample count previews easier to judge. Each pixel is
+//! replaced with a weighted average of the pixels around it, where pixels only
+//! contribute if they see a similar surface, judged by feature buffers gathered at
+//! the first hit, and have a similar color given how noisy the two pixels are.
+//!
+//! The weight of pixel `q` in the average for pixel `p` is the product of a Gaussian
+//! falloff with the distance between them and a Gaussian of the difference of each
+//! feature available:
+//!
+//! - `albedo`: The difference in albedo, scaled by `albedo_sigma`. The color is
+//!   divided by the albedo before filtering and multiplied by it after, so textures
+//!   aren't blurred
+//! - `normal`: The difference in shading normal, scaled by `normal_sigma`
+//! - `depth`: The difference in depth relative to the farther of the two, scaled
+//!   by `depth_sigma`
+//! - `variance`: The difference in color beyond that expected from the noise,
+//!   relative to the variance of the mean of the two pixels and scaled by
+//!   `color_sigma` standard deviations, so noise is averaged out while edges the
+//!   features miss, like shadows, are kept
+//!
+//! When denoising renders the render target accumulates the `albedo`, `normal`,
+//! `depth` and `variance` AOVs it needs, and the denoised image is saved in place
+//! of the noisy one. Renders distributed across workers can't be denoised.
+//!
+//! Previously saved HDR images can also be denoised with `aperture --denoise`, which
+//! reads the features from the AOV images saved next to the image, e.g.
+//! `frame_albedo.pfm`, skipping any which are missing. Normals read from Radiance
+//! RGBE images are mapped back from the `n * 0.5 + 0.5` they're saved as.
+//!
+//! # Scene Usage Example
+//! The denoiser is enabled in the film, all of its properties are optional and
+//! default to the values below.
+//!
+//! ```json
+//! "film": {
+//!     ...
+//!     "denoise": {
+//!         "radius": 5,
+//!         "color_sigma": 1.0,
+//!         "albedo_sigma": 0.1,
+//!         "normal_sigma": 0.1,
+//!         "depth_sigma": 0.1
+//!     }
+//! }
+//! ```
+
+use std::f32;
+
+use crate::film::Aov;
+
+/// The feature buffers guiding the denoiser, each with a value per pixel of the
+/// image. Albedo, normal and variance have 3 channels per pixel and depth has 1, as
+/// returned by `RenderTarget::get_aov`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Features<'a> {
+    pub albedo: Option<&'a [f32]>,
+    pub normal: Option<&'a [f32]>,
+    pub depth: Option<&'a [f32]>,
+    pub variance: Option<&'a [f32]>,
+}
+
+/// The joint cross-bilateral denoiser
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Denoiser {
+    /// Radius of the filter in pixels
+    pub radius: u32,
+    /// How much colors can differ, in standard deviations of the noise of the pixels
+    pub color_sigma: f32,
+    pub albedo_sigma: f32,
+    pub normal_sigma: f32,
+    /// How much depths can differ, relative to the depth of the farther pixel
+    pub depth_sigma: f32,
+}
+
+impl Denoiser {
+    /// The AOV layers the denoiser uses as features
+    pub const FEATURES: [Aov; 4] = [Aov::Albedo, Aov::Normal, Aov::Depth, Aov::Variance];
+
+    pub fn new(
+        radius: u32,
+        color_sigma: f32,
+        albedo_sigma: f32,
+        normal_sigma: f32,
+        depth_sigma: f32,
+    ) -> Denoiser {
+        Denoiser {
+            radius,
+            color_sigma,
+            albedo_sigma,
+            normal_sigma,
+            depth_sigma,
+        }
+    }
+    /// Denoise the linear RGB image of dimensions `dim`, guided by the features
+    /// available. Returns the denoised RGB image
+    pub fn denoise(&self, dim: (usize, usize), rgb: &[f32], features: &Features) -> Vec<f32> {
+        let (width, height) = dim;
+        // Remove the albedo from the color and its variance, dark albedos are kept
+        // so the color isn't scaled up by dividing by a small value
+        let albedo_at = |i: usize, c: usize| match features.albedo {
+            Some(a) if a[i * 3 + c] > 0.01 => a[i * 3 + c],
+            _ => 1.0,
+        };
+        let mut color = Vec::with_capacity(rgb.len());
+        let mut variance = Vec::with_capacity(width * height);
+        for i in 0..width * height {
+            let mut v = 0.0;
+            for c in 0..3 {
+                let a = albedo_at(i, c);
+                color.push(rgb[i * 3 + c] / a);
+                v += features
+                    .variance
+                    .map_or(0.0, |var| var[i * 3 + c] / (a * a));
+            }
+            variance.push(v);
+        }
+
+        let r = self.radius as i64;
+        let spatial_sigma = f32::max(0.5, self.radius as f32 / 2.0);
+        let sqr_dist =
+            |a: &[f32], b: &[f32]| -> f32 { a.iter().zip(b).map(|(x, y)| (x - y) * (x - y)).sum() };
+        let gaussian = |d2: f32, sigma: f32| f32::exp(-d2 / (2.0 * sigma * sigma));
+        let mut out = Vec::with_capacity(rgb.len());
+        for y in 0..height as i64 {
+            for x in 0..width as i64 {
+                let p = y as usize * width + x as usize;
+                let mut sum = [0.0; 3];
+                let mut weight_sum = 0.0;
+                for qy in i64::max(0, y - r)..i64::min(height as i64, y + r + 1) {
+                    for qx in i64::max(0, x - r)..i64::min(width as i64, x + r + 1) {
+                        let q = qy as usize * width + qx as usize;
+                        let mut w =
+                            gaussian(((qx - x).pow(2) + (qy - y).pow(2)) as f32, spatial_sigma);
+                        if let Some(a) = features.albedo {
+                            let d2 = sqr_dist(&a[p * 3..p * 3 + 3], &a[q * 3..q * 3 + 3]);
+                            w *= gaussian(d2, self.albedo_sigma);
+                        }
+                        if let Some(n) = features.normal {
+                            let d2 = sqr_dist(&n[p * 3..p * 3 + 3], &n[q * 3..q * 3 + 3]);
+                            w *= gaussian(d2, self.normal_sigma);
+                        }
+                        if let Some(d) = features.depth {
+                            let scale = f32::max(f32::max(d[p], d[q]), 1e-4);
+                            w *= gaussian(((d[p] - d[q]) / scale).powi(2), self.depth_sigma);
+                        }
+                        if features.variance.is_some() {
+                            let d2 = sqr_dist(&color[p * 3..p * 3 + 3], &color[q * 3..q * 3 + 3]);
+                            // The difference expected from the noise alone is ignored
+                            let v = variance[p] + variance[q];
+                            let d = f32::max(0.0, d2 - v) / (self.color_sigma.powi(2) * v + 1e-6);
+                            w *= f32::exp(-d);
+                        }
+                        for c in 0..3 {
+                            sum[c] += w * color[q * 3 + c];
+                        }
+                        weight_sum += w;
+                    }
+                }
+                for (c, s) in sum.iter().enumerate() {
+                    // The pixel itself always has a weight of 1
+                    out.push(s / weight_sum * albedo_at(p, c));
+                }
+            }
+        }
+        out
+    }
+}
+
+impl Default for Denoiser {
+    fn default() -> Denoiser {
+        Denoiser::new(5, 1.0, 0.1, 0.1, 0.1)
+    }
+}
+
+#[cfg(test)]
+fn mean_sqr_error(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| (x - y).powi(2)).sum::<f32>() / a.len() as f32
+}
+
+#[test]
+fn test_denoise_flat() {
+    let dim = (6, 4);
+    let flat = vec![0.5; 6 * 4 * 3];
+    let denoiser = Denoiser::default();
+    let out = denoiser.denoise(dim, &flat, &Features::default());
+    assert!(mean_sqr_error(&out, &flat) < 1e-10);
+    // Noise around a flat color is averaged out
+    let noisy: Vec<f32> = (0..flat.len())
+        .map(|i| if (i / 3) % 2 == 0 { 0.3 } else { 0.7 })
+        .collect();
+    let variance = vec![0.04; flat.len()];
+    let features = Features {
+        variance: Some(&variance),
+        ..Features::default()
+    };
+    let out = denoiser.denoise(dim, &noisy, &features);
+    assert!(mean_sqr_error(&out, &flat) < 0.25 * mean_sqr_error(&noisy, &flat));
+    // A radius of 0 leaves the image unchanged
+    let identity = Denoiser::new(0, 1.0, 0.1, 0.1, 0.1).denoise(dim, &noisy, &features);
+    assert!(mean_sqr_error(&identity, &noisy) < 1e-10);
+}
+
+#[test]
+fn test_denoise_edges() {
+    // The left and right halves are different surfaces, which the features separate
+    let dim = (8, 4);
+    let left = |i: usize| (i % 8) < 4;
+    let rgb: Vec<f32> = (0..32)
+        .flat_map(|i| if left(i) { [0.2; 3] } else { [0.8; 3] })
+        .collect();
+    let mut denoiser = Denoiser::default();
+    let blurred = denoiser.denoise(dim, &rgb, &Features::default());
+    assert!(mean_sqr_error(&blurred, &rgb) > 1e-3);
+    for feature in 0..3 {
+        let normal: Vec<f32> = (0..32)
+            .flat_map(|i| {
+                if left(i) {
+                    [0.0, 1.0, 0.0]
+                } else {
+                    [1.0, 0.0, 0.0]
+                }
+            })
+            .collect();
+        let depth: Vec<f32> = (0..32).map(|i| if left(i) { 1.0 } else { 2.0 }).collect();
+        let albedo: Vec<f32> = (0..32)
+            .flat_map(|i| if left(i) { [0.4; 3] } else { [0.8; 3] })
+            .collect();
+        let features = Features {
+            albedo: Some(&albedo).filter(|_| feature == 0).map(|a| &a[..]),
+            normal: Some(&normal).filter(|_| feature == 1).map(|n| &n[..]),
+            depth: Some(&depth).filter(|_| feature == 2).map(|d| &d[..]),
+            variance: None,
+        };
+        let out = denoiser.denoise(dim, &rgb, &features);
+        assert!(mean_sqr_error(&out, &rgb) < 1e-6, "feature {}", feature);
+    }
+    // An edge in the color alone is kept if the pixels aren't noisy
+    let variance = vec![1e-4; 32 * 3];
+    denoiser.color_sigma = 2.0;
+    let features = Features {
+        variance: Some(&variance),
+        ..Features::default()
+    };
+    let out = denoiser.denoise(dim, &rgb, &features);
+    assert!(mean_sqr_error(&out, &rgb) < 1e-6);
+}
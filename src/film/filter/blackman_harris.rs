@@ -0,0 +1,86 @@
+//! Provides a Blackman-Harris reconstruction filter, using the 4 term Blackman-Harris
+//! window centered on the filter and falling to almost zero at its extent.
+//! See [On the Use of Windows for Harmonic Analysis with the Discrete Fourier Transform](https://web.mit.edu/xiphmont/Public/windows.pdf).
+
+use crate::film::filter::{Filter, Filters};
+use std::f32;
+
+/// Coefficients of the 4 term Blackman-Harris window
+const COEFFICIENTS: [f32; 4] = [0.35875, 0.48829, 0.14128, 0.01168];
+
+/// A Blackman-Harris reconstruction filter.
+/// Recommended parameters to try: w = 2.0, h = 2.0
+#[derive(Copy, Clone, Debug)]
+pub struct BlackmanHarris {
+    w: f32,
+    h: f32,
+    inv_w: f32,
+    inv_h: f32,
+}
+
+impl BlackmanHarris {
+    pub fn new_filter(w: f32, h: f32) -> Filters {
+        Filters::BlackmanHarris(Self {
+            w,
+            h,
+            inv_w: 1.0 / w,
+            inv_h: 1.0 / h,
+        })
+    }
+
+    /// Compute the window at `x`, given relative to the filter's extent so the
+    /// window covers [-1, 1]
+    fn weight_1d(x: f32) -> f32 {
+        if f32::abs(x) > 1.0 {
+            return 0.0;
+        }
+        let [a0, a1, a2, a3] = COEFFICIENTS;
+        let t = f32::consts::PI * x;
+        a0 + a1 * f32::cos(t) + a2 * f32::cos(2.0 * t) + a3 * f32::cos(3.0 * t)
+    }
+}
+
+impl Filter for BlackmanHarris {
+    fn weight(&self, x: f32, y: f32) -> f32 {
+        Self::weight_1d(x * self.inv_w) * Self::weight_1d(y * self.inv_h)
+    }
+
+    fn width(&self) -> f32 {
+        self.w
+    }
+
+    fn inv_width(&self) -> f32 {
+        self.inv_w
+    }
+
+    fn height(&self) -> f32 {
+        self.h
+    }
+
+    fn inv_height(&self) -> f32 {
+        self.inv_h
+    }
+}
+
+#[test]
+fn test_blackman_harris() {
+    use std::f32::consts::PI;
+
+    let f = BlackmanHarris::new_filter(2.0, 1.0);
+    assert_eq!((f.width(), f.height()), (2.0, 1.0));
+    assert!((f.weight(0.0, 0.0) - 1.0).abs() < 1e-6);
+    // The window falls to almost zero at the edge of the filter and is zero beyond
+    assert!(f.weight(2.0, 0.0).abs() < 1e-4);
+    assert!(f.weight(0.0, -1.0).abs() < 1e-4);
+    assert_eq!(f.weight(2.1, 0.0), 0.0);
+    assert_eq!(f.weight(0.0, 1.1), 0.0);
+    // The classic window of N + 1 samples, sample n at x = 2 * n / N - 1 on the filter
+    let n = 8.0;
+    for i in 0..9 {
+        let t = 2.0 * PI * i as f32 / n;
+        let expected = 0.35875 - 0.48829 * f32::cos(t) + 0.14128 * f32::cos(2.0 * t)
+            - 0.01168 * f32::cos(3.0 * t);
+        let x = 2.0 * (2.0 * i as f32 / n - 1.0);
+        assert!((f.weight(x, 0.0) - expected).abs() < 1e-5, "x = {}", x);
+    }
+}
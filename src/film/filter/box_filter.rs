@@ -0,0 +1,62 @@
+//! Provides a box reconstruction filter.
+
+use crate::film::filter::{Filter, Filters};
+
+/// A box reconstruction filter, which weights all samples within its extent equally.
+/// Recommended parameters to try: w = 0.5, h = 0.5
+#[derive(Copy, Clone, Debug)]
+pub struct BoxFilter {
+    w: f32,
+    h: f32,
+    inv_w: f32,
+    inv_h: f32,
+}
+
+impl BoxFilter {
+    pub fn new_filter(w: f32, h: f32) -> Filters {
+        Filters::BoxFilter(Self {
+            w,
+            h,
+            inv_w: 1.0 / w,
+            inv_h: 1.0 / h,
+        })
+    }
+}
+
+impl Filter for BoxFilter {
+    fn weight(&self, x: f32, y: f32) -> f32 {
+        if f32::abs(x) <= self.w && f32::abs(y) <= self.h {
+            1.0
+        } else {
+            0.0
+        }
+    }
+
+    fn width(&self) -> f32 {
+        self.w
+    }
+
+    fn inv_width(&self) -> f32 {
+        self.inv_w
+    }
+
+    fn height(&self) -> f32 {
+        self.h
+    }
+
+    fn inv_height(&self) -> f32 {
+        self.inv_h
+    }
+}
+
+#[test]
+fn test_box() {
+    let f = BoxFilter::new_filter(0.5, 1.5);
+    assert_eq!((f.width(), f.height()), (0.5, 1.5));
+    assert_eq!(f.inv_height(), 1.0 / 1.5);
+    assert_eq!(f.weight(0.0, 0.0), 1.0);
+    assert_eq!(f.weight(-0.5, 1.5), 1.0);
+    assert_eq!(f.weight(0.25, -1.2), 1.0);
+    assert_eq!(f.weight(0.51, 0.0), 0.0);
+    assert_eq!(f.weight(0.0, -1.6), 0.0);
+}
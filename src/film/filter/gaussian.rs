@@ -28,6 +28,10 @@ impl Gaussian {
             exp_y: f32::exp(-alpha * h * h),
         })
     }
+    /// Get the falloff of the Gaussian
+    pub fn alpha(&self) -> f32 {
+        self.alpha
+    }
 
     fn weight_1d(&self, x: f32, e: f32) -> f32 {
         f32::max(0.0, f32::exp(-self.alpha * x * x) - e)
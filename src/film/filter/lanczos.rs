@@ -0,0 +1,98 @@
+//! Provides a Lanczos windowed sinc reconstruction filter, the same as pbrt's
+//! `LanczosSincFilter`. The sinc function is windowed by a Lanczos window whose
+//! lobes are `tau` times wider than the sinc's, so `tau` sets the number of lobes of
+//! the sinc kept within the window.
+
+use crate::film::filter::{Filter, Filters};
+use std::f32;
+
+/// A Lanczos windowed sinc reconstruction filter.
+/// Recommended parameters to try: w = 4.0, h = 4.0, tau = 3.0
+#[derive(Copy, Clone, Debug)]
+pub struct Lanczos {
+    w: f32,
+    h: f32,
+    inv_w: f32,
+    inv_h: f32,
+    tau: f32,
+}
+
+impl Lanczos {
+    pub fn new_filter(w: f32, h: f32, tau: f32) -> Filters {
+        Filters::Lanczos(Self {
+            w,
+            h,
+            inv_w: 1.0 / w,
+            inv_h: 1.0 / h,
+            tau,
+        })
+    }
+    /// Get the number of lobes of the sinc within the Lanczos window
+    pub fn tau(&self) -> f32 {
+        self.tau
+    }
+
+    /// Compute the windowed sinc at `x`, which is 0 beyond the `radius` of the filter
+    fn weight_1d(&self, x: f32, radius: f32) -> f32 {
+        let x = f32::abs(x);
+        if x > radius {
+            0.0
+        } else {
+            sinc(x) * sinc(x / self.tau)
+        }
+    }
+}
+
+/// The normalized sinc function, `sin(pi * x) / (pi * x)`
+fn sinc(x: f32) -> f32 {
+    if x.abs() < 1e-5 {
+        1.0
+    } else {
+        let px = f32::consts::PI * x;
+        f32::sin(px) / px
+    }
+}
+
+impl Filter for Lanczos {
+    fn weight(&self, x: f32, y: f32) -> f32 {
+        self.weight_1d(x, self.w) * self.weight_1d(y, self.h)
+    }
+
+    fn width(&self) -> f32 {
+        self.w
+    }
+
+    fn inv_width(&self) -> f32 {
+        self.inv_w
+    }
+
+    fn height(&self) -> f32 {
+        self.h
+    }
+
+    fn inv_height(&self) -> f32 {
+        self.inv_h
+    }
+}
+
+#[test]
+fn test_lanczos() {
+    use std::f32::consts::PI;
+
+    let f = Lanczos::new_filter(4.0, 3.0, 3.0);
+    assert_eq!((f.width(), f.height()), (4.0, 3.0));
+    assert_eq!(f.weight(0.0, 0.0), 1.0);
+    // The sinc is zero at the integers, giving the filter its negative lobes between
+    assert!(f.weight(1.0, 0.0).abs() < 1e-6);
+    assert!(f.weight(2.0, 0.0).abs() < 1e-6);
+    assert!(f.weight(1.5, 0.0) < 0.0);
+    for &x in &[0.25, 0.5, 1.5, 2.7, 3.9] {
+        let sinc = |x: f32| f32::sin(PI * x) / (PI * x);
+        let expected = sinc(x) * sinc(x / 3.0);
+        assert!((f.weight(x, 0.0) - expected).abs() < 1e-6, "x = {}", x);
+        assert!((f.weight(-x, 0.0) - expected).abs() < 1e-6, "x = {}", -x);
+    }
+    assert!((f.weight(0.0, 2.7) - f.weight(2.7, 0.0)).abs() < 1e-6);
+    assert_eq!(f.weight(4.1, 0.0), 0.0);
+    assert_eq!(f.weight(0.0, 3.1), 0.0);
+}
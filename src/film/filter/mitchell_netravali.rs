@@ -43,6 +43,14 @@ impl MitchellNetravali {
             c: linalg::clamp(c, 0.0, 1.0),
         })
     }
+    /// Get the B parameter of the filter
+    pub fn b(&self) -> f32 {
+        self.b
+    }
+    /// Get the C parameter of the filter
+    pub fn c(&self) -> f32 {
+        self.c
+    }
 
     /// Compute a 1d weight for the filter. Note that the Mitchell-Netravali
     /// filter is defined on [-2, 2] so x should be in this range
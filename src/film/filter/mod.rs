@@ -3,10 +3,17 @@
 //! height refer to how many pixels the filter covers, where a single
 //! pixel is 0.5x0.5
 
-pub use self::{gaussian::Gaussian, mitchell_netravali::MitchellNetravali};
+pub use self::{
+    blackman_harris::BlackmanHarris, box_filter::BoxFilter, gaussian::Gaussian, lanczos::Lanczos,
+    mitchell_netravali::MitchellNetravali, triangle::Triangle,
+};
 
+pub mod blackman_harris;
+pub mod box_filter;
 pub mod gaussian;
+pub mod lanczos;
 pub mod mitchell_netravali;
+pub mod triangle;
 
 /// Trait implemented by all reconstructon filters. Provides methods for getting
 /// the width/height and computing the weight at some point relative to the filter
@@ -30,4 +37,8 @@ pub trait Filter {
 pub enum Filters {
     MitchellNetravali,
     Gaussian,
+    BoxFilter,
+    Triangle,
+    Lanczos,
+    BlackmanHarris,
 }
@@ -0,0 +1,59 @@
+//! Provides a triangle (tent) reconstruction filter.
+
+use crate::film::filter::{Filter, Filters};
+
+/// A triangle reconstruction filter, whose weight falls off linearly from the center
+/// to zero at its extent.
+/// Recommended parameters to try: w = 2.0, h = 2.0
+#[derive(Copy, Clone, Debug)]
+pub struct Triangle {
+    w: f32,
+    h: f32,
+    inv_w: f32,
+    inv_h: f32,
+}
+
+impl Triangle {
+    pub fn new_filter(w: f32, h: f32) -> Filters {
+        Filters::Triangle(Self {
+            w,
+            h,
+            inv_w: 1.0 / w,
+            inv_h: 1.0 / h,
+        })
+    }
+}
+
+impl Filter for Triangle {
+    fn weight(&self, x: f32, y: f32) -> f32 {
+        f32::max(0.0, self.w - f32::abs(x)) * f32::max(0.0, self.h - f32::abs(y))
+    }
+
+    fn width(&self) -> f32 {
+        self.w
+    }
+
+    fn inv_width(&self) -> f32 {
+        self.inv_w
+    }
+
+    fn height(&self) -> f32 {
+        self.h
+    }
+
+    fn inv_height(&self) -> f32 {
+        self.inv_h
+    }
+}
+
+#[test]
+fn test_triangle() {
+    let f = Triangle::new_filter(2.0, 1.0);
+    assert_eq!((f.width(), f.height()), (2.0, 1.0));
+    assert_eq!(f.weight(0.0, 0.0), 2.0);
+    assert_eq!(f.weight(1.0, 0.0), 1.0);
+    assert_eq!(f.weight(-0.5, 0.5), 1.5 * 0.5);
+    assert_eq!(f.weight(0.5, -0.5), f.weight(-0.5, 0.5));
+    assert_eq!(f.weight(2.0, 0.0), 0.0);
+    assert_eq!(f.weight(0.0, 1.5), 0.0);
+}
@@ -0,0 +1,567 @@
+//! Provides writers for high dynamic range image formats, which store the linear
+//! RGB values of the render without clamping them so they can be graded and
+//! tonemapped later. The format is picked from the extension of the file:
+//!
+//! - `.pfm`: Portable Float Map, 32-bit float RGB
+//! - `.hdr`: Radiance RGBE, 8-bit RGB sharing an 8-bit exponent
+//! - `.exr`: Uncompressed scanline OpenEXR, with half or 32-bit float RGB channels
+//!
+//! The images passed are RGB triples in scanline order from the top left of the image,
+//! as returned by `RenderTarget::get_render_linear`. OpenEXR images can also hold
+//! further layers, such as the AOVs returned by `RenderTarget::get_aov`, which are
+//! written with `save_exr_layers`.
+//!
+//! PFM and Radiance RGBE images can also be read back, e.g. to composite the crop
+//! window of a render over the image saved by a previous render.
+
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use byteorder::{BigEndian, LittleEndian, ReadBytesExt, WriteBytesExt};
+
+/// The HDR image formats which can be written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HdrFormat {
+    Pfm,
+    Rgbe,
+    Exr(ExrPixel),
+}
+
+/// The type of the channels of an OpenEXR image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExrPixel {
+    Half,
+    Float,
+}
+
+impl HdrFormat {
+    /// Get the format to save the file in from its extension, or None if it's not
+    /// a HDR format. OpenEXR images are saved with half channels
+    pub fn from_path(file: &Path) -> Option<HdrFormat> {
+        let ext = file.extension()?.to_str()?.to_lowercase();
+        match &ext[..] {
+            "pfm" => Some(HdrFormat::Pfm),
+            "hdr" => Some(HdrFormat::Rgbe),
+            "exr" => Some(HdrFormat::Exr(ExrPixel::Half)),
+            _ => None,
+        }
+    }
+    /// Check if the format can store negative values
+    pub fn is_signed(&self) -> bool {
+        !matches!(*self, HdrFormat::Rgbe)
+    }
+    /// Save the `dim.0` by `dim.1` RGB image to `file` in this format
+    pub fn save(&self, file: &Path, dim: (usize, usize), rgb: &[f32]) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(file)?);
+        match *self {
+            HdrFormat::Pfm => write_pfm(&mut w, dim, rgb)?,
+            HdrFormat::Rgbe => write_rgbe(&mut w, dim, rgb)?,
+            HdrFormat::Exr(pixel) => write_exr(&mut w, dim, rgb, pixel)?,
+        }
+        w.flush()
+    }
+    /// Load the RGB image in `file` in this format, returning its dimensions and
+    /// pixels. Reading OpenEXR images isn't supported
+    pub fn load(&self, file: &Path) -> io::Result<((usize, usize), Vec<f32>)> {
+        let mut r = BufReader::new(File::open(file)?);
+        match *self {
+            HdrFormat::Pfm => read_pfm(&mut r),
+            HdrFormat::Rgbe => read_rgbe(r),
+            HdrFormat::Exr(_) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "reading OpenEXR images isn't supported",
+            )),
+        }
+    }
+}
+
+/// A layer of a multi-layer OpenEXR image
+#[derive(Debug, Clone, Copy)]
+pub struct ExrLayer<'a> {
+    /// Name of the layer, which prefixes its channel names as `name.channel`. The
+    /// channels of a layer with an empty name are written unprefixed
+    pub name: &'a str,
+    pub channels: &'a [&'a str],
+    pub pixel: ExrPixel,
+    /// The values of the channels of each pixel, in scanline order
+    pub data: &'a [f32],
+}
+
+/// Save the `dim.0` by `dim.1` image made of the layers to `file` as an OpenEXR image
+pub fn save_exr_layers(file: &Path, dim: (usize, usize), layers: &[ExrLayer]) -> io::Result<()> {
+    let mut w = BufWriter::new(File::create(file)?);
+    write_exr_layers(&mut w, dim, layers)?;
+    w.flush()
+}
+
+/// Write the image as a little endian PFM, which stores the rows from the bottom up
+pub fn write_pfm<W: Write>(w: &mut W, dim: (usize, usize), rgb: &[f32]) -> io::Result<()> {
+    write!(w, "PF\n{} {}\n-1.0\n", dim.0, dim.1)?;
+    for row in rgb.chunks(dim.0 * 3).rev() {
+        for c in row {
+            w.write_f32::<LittleEndian>(*c)?;
+        }
+    }
+    Ok(())
+}
+
+/// Read a color PFM image, in either byte order
+pub fn read_pfm<R: BufRead>(r: &mut R) -> io::Result<((usize, usize), Vec<f32>)> {
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_owned());
+    let mut header = Vec::new();
+    // The header is the type, dimensions and scale, each ended by whitespace
+    while header.len() < 4 {
+        let mut line = String::new();
+        if r.read_line(&mut line)? == 0 {
+            return Err(invalid("Truncated PFM header"));
+        }
+        header.extend(line.split_whitespace().map(str::to_owned));
+    }
+    if header[0] != "PF" || header.len() != 4 {
+        return Err(invalid("Not a color PFM image"));
+    }
+    let parse = |s: &str| {
+        s.parse::<usize>()
+            .map_err(|_| invalid("Invalid PFM dimensions"))
+    };
+    let dim = (parse(&header[1])?, parse(&header[2])?);
+    let scale: f32 = header[3]
+        .parse()
+        .map_err(|_| invalid("Invalid PFM scale"))?;
+    let mut rows = vec![0.0; dim.0 * dim.1 * 3];
+    if scale < 0.0 {
+        r.read_f32_into::<LittleEndian>(&mut rows)?;
+    } else {
+        r.read_f32_into::<BigEndian>(&mut rows)?;
+    }
+    // Rows are stored from the bottom up
+    let rgb = rows.chunks(dim.0 * 3).rev().flatten().cloned().collect();
+    Ok((dim, rgb))
+}
+
+/// Read a Radiance RGBE image
+pub fn read_rgbe<R: BufRead>(r: R) -> io::Result<((usize, usize), Vec<f32>)> {
+    let invalid = |e: image::ImageError| io::Error::new(io::ErrorKind::InvalidData, e.to_string());
+    let decoder = image::hdr::HDRDecoder::new(r).map_err(invalid)?;
+    let meta = decoder.metadata();
+    let dim = (meta.width as usize, meta.height as usize);
+    let pixels = decoder.read_image_hdr().map_err(invalid)?;
+    Ok((dim, pixels.iter().flat_map(|p| p.data).collect()))
+}
+
+/// Write the image as a Radiance RGBE image. Negative values, which RGBE can't store,
+/// are written as 0. Scanlines are run length encoded per component as Radiance
+/// does, which is only possible for widths of 8 to 32767, so narrower or wider
+/// images are written uncompressed
+pub fn write_rgbe<W: Write>(w: &mut W, dim: (usize, usize), rgb: &[f32]) -> io::Result<()> {
+    write!(
+        w,
+        "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+        dim.1, dim.0
+    )?;
+    let pixels: Vec<_> = rgb
+        .chunks(3)
+        .map(|px| to_rgbe(px[0], px[1], px[2]))
+        .collect();
+    if !(8..=0x7fff).contains(&dim.0) {
+        for px in &pixels {
+            w.write_all(px)?;
+        }
+        return Ok(());
+    }
+    let mut component = Vec::with_capacity(dim.0);
+    for line in pixels.chunks(dim.0) {
+        w.write_all(&[2, 2, (dim.0 >> 8) as u8, (dim.0 & 0xff) as u8])?;
+        for i in 0..4 {
+            component.clear();
+            component.extend(line.iter().map(|px| px[i]));
+            write_rle(w, &component)?;
+        }
+    }
+    Ok(())
+}
+
+/// Write a component of a scanline with Radiance's run length encoding, where runs of
+/// a value are written as 128 plus their length followed by the value and other
+/// values are written as their count followed by the values
+fn write_rle<W: Write>(w: &mut W, data: &[u8]) -> io::Result<()> {
+    // Shorter runs aren't worth breaking up the values around them for
+    const MIN_RUN: usize = 4;
+    let run = |at: usize, max: usize| {
+        data[at..]
+            .iter()
+            .take(max)
+            .take_while(|x| **x == data[at])
+            .count()
+    };
+    let mut i = 0;
+    while i < data.len() {
+        let len = run(i, 127);
+        if len >= MIN_RUN {
+            w.write_all(&[128 + len as u8, data[i]])?;
+            i += len;
+            continue;
+        }
+        let start = i;
+        while i < data.len() && i - start < 128 && run(i, MIN_RUN) < MIN_RUN {
+            i += 1;
+        }
+        w.write_all(&[(i - start) as u8])?;
+        w.write_all(&data[start..i])?;
+    }
+    Ok(())
+}
+
+/// Convert the color to RGBE, where the RGB mantissas share the exponent of the
+/// largest component
+fn to_rgbe(r: f32, g: f32, b: f32) -> [u8; 4] {
+    let (r, g, b) = (r.max(0.0), g.max(0.0), b.max(0.0));
+    let v = r.max(g).max(b);
+    if !v.is_finite() || v < 1e-32 {
+        return [0; 4];
+    }
+    // Find e such that v = m * 2^e with m in [0.5, 1)
+    let mut e = v.log2().floor() as i32 + 1;
+    if v / 2f32.powi(e) >= 1.0 {
+        e += 1;
+    }
+    let scale = 256.0 / 2f32.powi(e);
+    [
+        (r * scale) as u8,
+        (g * scale) as u8,
+        (b * scale) as u8,
+        (e + 128) as u8,
+    ]
+}
+
+/// Write the image as an uncompressed single part scanline OpenEXR image with
+/// R, G and B channels of type `pixel`
+pub fn write_exr<W: Write>(
+    w: &mut W,
+    dim: (usize, usize),
+    rgb: &[f32],
+    pixel: ExrPixel,
+) -> io::Result<()> {
+    let layer = ExrLayer {
+        name: "",
+        channels: &["R", "G", "B"],
+        pixel,
+        data: rgb,
+    };
+    write_exr_layers(w, dim, &[layer])
+}
+
+/// Write the layers as an uncompressed single part scanline OpenEXR image
+pub fn write_exr_layers<W: Write>(
+    w: &mut W,
+    dim: (usize, usize),
+    layers: &[ExrLayer],
+) -> io::Result<()> {
+    // Each channel as its full name, layer and index within the layer's pixels.
+    // Channels must be listed and stored in alphabetical order
+    let mut channels = Vec::new();
+    for l in layers {
+        for (i, c) in l.channels.iter().enumerate() {
+            let name = if l.name.is_empty() {
+                c.to_string()
+            } else {
+                format!("{}.{}", l.name, c)
+            };
+            channels.push((name, l, i));
+        }
+    }
+    channels.sort_by(|a, b| a.0.cmp(&b.0));
+    let pixel_size = |p: ExrPixel| match p {
+        ExrPixel::Half => 2,
+        ExrPixel::Float => 4,
+    };
+
+    let mut header = Vec::new();
+    // Magic number and version 2 with no flags set, for a single part scanline image
+    header.write_u32::<LittleEndian>(20_000_630)?;
+    header.write_u32::<LittleEndian>(2)?;
+    let mut chlist = Vec::new();
+    for &(ref name, l, _) in &channels {
+        chlist.extend_from_slice(name.as_bytes());
+        chlist.push(0);
+        chlist.write_i32::<LittleEndian>(match l.pixel {
+            ExrPixel::Half => 1,
+            ExrPixel::Float => 2,
+        })?;
+        // pLinear and the reserved bytes, then the x and y sampling
+        chlist.extend_from_slice(&[0; 4]);
+        chlist.write_i32::<LittleEndian>(1)?;
+        chlist.write_i32::<LittleEndian>(1)?;
+    }
+    chlist.push(0);
+    write_exr_attribute(&mut header, "channels", "chlist", &chlist)?;
+    write_exr_attribute(&mut header, "compression", "compression", &[0])?;
+    let mut window = Vec::new();
+    for x in &[0, 0, dim.0 as i32 - 1, dim.1 as i32 - 1] {
+        window.write_i32::<LittleEndian>(*x)?;
+    }
+    write_exr_attribute(&mut header, "dataWindow", "box2i", &window)?;
+    write_exr_attribute(&mut header, "displayWindow", "box2i", &window)?;
+    write_exr_attribute(&mut header, "lineOrder", "lineOrder", &[0])?;
+    write_exr_attribute(
+        &mut header,
+        "pixelAspectRatio",
+        "float",
+        &1f32.to_le_bytes(),
+    )?;
+    write_exr_attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8])?;
+    write_exr_attribute(
+        &mut header,
+        "screenWindowWidth",
+        "float",
+        &1f32.to_le_bytes(),
+    )?;
+    header.push(0);
+    w.write_all(&header)?;
+
+    // Each scanline is stored in its own chunk, found through the offset table
+    let line_size: usize = channels.iter().map(|c| dim.0 * pixel_size(c.1.pixel)).sum();
+    let chunk_size = 8 + line_size;
+    let first_chunk = header.len() + 8 * dim.1;
+    for y in 0..dim.1 {
+        w.write_u64::<LittleEndian>((first_chunk + y * chunk_size) as u64)?;
+    }
+    for y in 0..dim.1 {
+        w.write_i32::<LittleEndian>(y as i32)?;
+        w.write_i32::<LittleEndian>(line_size as i32)?;
+        for &(_, l, c) in &channels {
+            let n = l.channels.len();
+            let row = &l.data[y * dim.0 * n..(y + 1) * dim.0 * n];
+            for px in row.chunks(n) {
+                match l.pixel {
+                    ExrPixel::Half => w.write_u16::<LittleEndian>(to_half(px[c]))?,
+                    ExrPixel::Float => w.write_f32::<LittleEndian>(px[c])?,
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_exr_attribute(
+    header: &mut Vec<u8>,
+    name: &str,
+    kind: &str,
+    value: &[u8],
+) -> io::Result<()> {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(kind.as_bytes());
+    header.push(0);
+    header.write_i32::<LittleEndian>(value.len() as i32)?;
+    header.extend_from_slice(value);
+    Ok(())
+}
+
+/// Convert the float to the bits of the nearest half float, rounding ties to even.
+/// Values too large for a half become infinity
+fn to_half(f: f32) -> u16 {
+    let bits = f.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exp = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+    // Infinity and NaN, keeping NaNs as NaN
+    if exp == 0xff {
+        return sign | 0x7c00 | if mantissa != 0 { 0x200 } else { 0 };
+    }
+    let e = exp - 127 + 15;
+    if e >= 0x1f {
+        return sign | 0x7c00;
+    }
+    // Shift the mantissa down to the half's precision, which for subnormal halfs
+    // includes the implicit leading bit
+    let (mantissa, shift, base) = if e <= 0 {
+        if e < -10 {
+            return sign;
+        }
+        (mantissa | 0x80_0000, (14 - e) as u32, 0)
+    } else {
+        (mantissa, 13, (e as u32) << 10)
+    };
+    let half = mantissa >> shift;
+    let rem = mantissa & ((1 << shift) - 1);
+    let halfway = 1 << (shift - 1);
+    // Rounding up can carry into the exponent, which gives the right result
+    let round = u32::from(rem > halfway || (rem == halfway && half & 1 == 1));
+    sign | (base + half + round) as u16
+}
+
+#[test]
+fn test_to_half() {
+    assert_eq!(to_half(0.0), 0);
+    assert_eq!(to_half(-0.0), 0x8000);
+    assert_eq!(to_half(1.0), 0x3c00);
+    assert_eq!(to_half(0.5), 0x3800);
+    assert_eq!(to_half(-2.0), 0xc000);
+    assert_eq!(to_half(0.1), 0x2e66);
+    assert_eq!(to_half(65504.0), 0x7bff);
+    assert_eq!(to_half(1e6), 0x7c00);
+    assert_eq!(to_half(f32::INFINITY), 0x7c00);
+    assert!(to_half(f32::NAN) & 0x7fff > 0x7c00);
+    // Smallest subnormal and normal halfs
+    assert_eq!(to_half(2f32.powi(-24)), 0x0001);
+    assert_eq!(to_half(2f32.powi(-14)), 0x0400);
+    assert_eq!(to_half(2f32.powi(-26)), 0);
+    // 1 + 2^-11 is halfway between 1 and the next half, so rounds to even
+    assert_eq!(to_half(1.0 + 2f32.powi(-11)), 0x3c00);
+    assert_eq!(to_half(1.0 + 3.0 * 2f32.powi(-11)), 0x3c02);
+}
+
+#[test]
+fn test_to_rgbe() {
+    assert_eq!(to_rgbe(1.0, 0.5, 0.25), [128, 64, 32, 129]);
+    assert_eq!(to_rgbe(0.0, 0.0, 0.0), [0; 4]);
+    assert_eq!(to_rgbe(3.0, -1.0, 0.0), [192, 0, 0, 130]);
+    assert_eq!(to_rgbe(0.75, 0.0, 0.0), [192, 0, 0, 128]);
+}
+
+#[test]
+fn test_write_hdr() {
+    use std::io::Read;
+    // A 2x2 image with a different color in each pixel
+    let rgb = [
+        1.0, 0.0, 0.0, 0.0, 2.0, 0.0, //
+        0.0, 0.0, 3.0, 0.5, 0.5, 0.5,
+    ];
+    let mut pfm = Vec::new();
+    write_pfm(&mut pfm, (2, 2), &rgb).unwrap();
+    let header = b"PF\n2 2\n-1.0\n";
+    assert_eq!(&pfm[..header.len()], header);
+    let floats: Vec<f32> = pfm[header.len()..]
+        .chunks(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect();
+    assert_eq!(floats[..6], rgb[6..]);
+    assert_eq!(floats[6..], rgb[..6]);
+
+    // Images narrower than 8 pixels are written uncompressed
+    let mut hdr = Vec::new();
+    write_rgbe(&mut hdr, (2, 2), &rgb).unwrap();
+    let header = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 2 +X 2\n";
+    assert_eq!(&hdr[..header.len()], header);
+    assert_eq!(hdr.len(), header.len() + 16);
+    assert_eq!(hdr[header.len()..header.len() + 4], [128, 0, 0, 129]);
+
+    for &(pixel, size) in &[(ExrPixel::Half, 2), (ExrPixel::Float, 4)] {
+        let mut exr = Vec::new();
+        write_exr(&mut exr, (2, 2), &rgb, pixel).unwrap();
+        assert_eq!(exr[..4], [0x76, 0x2f, 0x31, 0x01]);
+        let line_size = 2 * 3 * size;
+        let header_size = exr.len() - 2 * 8 - 2 * (8 + line_size);
+        let read_u64 = |at: usize| {
+            let mut b = [0; 8];
+            (&exr[at..at + 8]).read_exact(&mut b).unwrap();
+            u64::from_le_bytes(b) as usize
+        };
+        assert_eq!(read_u64(header_size), header_size + 16);
+        assert_eq!(read_u64(header_size + 8), header_size + 16 + 8 + line_size);
+        // The second scanline holds B, G then R for its two pixels
+        let line = &exr[exr.len() - line_size..];
+        let values: Vec<f32> = match pixel {
+            ExrPixel::Half => line
+                .chunks(2)
+                .map(|b| match u16::from_le_bytes([b[0], b[1]]) {
+                    0x4200 => 3.0,
+                    0x3800 => 0.5,
+                    _ => 0.0,
+                })
+                .collect(),
+            ExrPixel::Float => line
+                .chunks(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect(),
+        };
+        assert_eq!(values, [3.0, 0.5, 0.0, 0.5, 0.0, 0.5]);
+    }
+    assert_eq!(
+        HdrFormat::from_path(Path::new("out/frame.EXR")),
+        Some(HdrFormat::Exr(ExrPixel::Half))
+    );
+    assert_eq!(HdrFormat::from_path(Path::new("frame.png")), None);
+}
+
+#[test]
+fn test_write_exr_layers() {
+    let rgb = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0];
+    let depth = [7.0, 8.0];
+    let layers = [
+        ExrLayer {
+            name: "",
+            channels: &["R", "G", "B"],
+            pixel: ExrPixel::Float,
+            data: &rgb,
+        },
+        ExrLayer {
+            name: "depth",
+            channels: &["Z"],
+            pixel: ExrPixel::Half,
+            data: &depth,
+        },
+    ];
+    let mut exr = Vec::new();
+    write_exr_layers(&mut exr, (2, 1), &layers).unwrap();
+    // The channels are sorted with the beauty channels before the layer's
+    let find = |s: &[u8]| exr.windows(s.len()).position(|w| w == s).unwrap();
+    assert!(find(b"B\0") < find(b"R\0"));
+    assert!(find(b"R\0") < find(b"depth.Z\0"));
+    // The single scanline holds B, G, R as floats then Z as halfs
+    let line_size = 3 * 2 * 4 + 2 * 2;
+    let line = &exr[exr.len() - line_size..];
+    let floats: Vec<f32> = line[..24]
+        .chunks(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect();
+    assert_eq!(floats, [3.0, 6.0, 2.0, 5.0, 1.0, 4.0]);
+    assert_eq!(line[24..], [0x00, 0x47, 0x00, 0x48]);
+    let header_size = exr.len() - 8 - 8 - line_size;
+    assert_eq!(
+        exr[header_size..header_size + 8],
+        ((header_size + 8) as u64).to_le_bytes()
+    );
+}
+
+#[test]
+fn test_read_hdr() {
+    let rgb = [
+        1.0, 0.0, 0.0, 0.0, 2.0, 0.0, //
+        0.0, 0.0, 3.0, 0.5, 0.5, 0.5,
+    ];
+    let mut pfm = Vec::new();
+    write_pfm(&mut pfm, (2, 2), &rgb).unwrap();
+    assert_eq!(read_pfm(&mut &pfm[..]).unwrap(), ((2, 2), rgb.to_vec()));
+    assert!(read_pfm(&mut &pfm[..pfm.len() - 1]).is_err());
+    assert!(read_pfm(&mut &b"Pf\n2 2\n-1.0\n"[..]).is_err());
+
+    let mut hdr = Vec::new();
+    write_rgbe(&mut hdr, (2, 2), &rgb).unwrap();
+    assert_eq!(read_rgbe(&hdr[..]).unwrap(), ((2, 2), rgb.to_vec()));
+
+    // The first pixel is stored as (2, 2, 128, 127), which only differs from the start
+    // of a run length encoded scanline in the high bit of blue, followed by a run of
+    // white and a mix of values
+    let mut rgb = vec![2.0 / 512.0, 2.0 / 512.0, 0.25];
+    for x in 1..300 {
+        let c = if x < 200 { 1.0 } else { x as f32 / 300.0 };
+        rgb.extend_from_slice(&[c, c * 0.5, 0.25]);
+    }
+    rgb.extend_from_within(..);
+    let mut hdr = Vec::new();
+    write_rgbe(&mut hdr, (300, 2), &rgb).unwrap();
+    assert!(hdr.len() < 300 * 2 * 4);
+    let header = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 2 +X 300\n";
+    assert_eq!(hdr[header.len()..header.len() + 4], [2, 2, 1, 44]);
+    let (dim, loaded) = read_rgbe(&hdr[..]).unwrap();
+    assert_eq!(dim, (300, 2));
+    assert_eq!(loaded[..3], rgb[..3]);
+    for (a, b) in loaded.iter().zip(&rgb) {
+        assert!((a - b).abs() <= 1.0 / 256.0, "{} != {}", a, b);
+    }
+}
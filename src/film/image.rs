@@ -3,7 +3,7 @@
 
 use std::iter;
 
-use crate::film::Colorf;
+use crate::film::{Colorf, Tonemap};
 
 #[derive(Debug)]
 pub struct Image {
@@ -37,7 +37,8 @@ impl Image {
     /// Add the blocks of RGBAf32 pixels to the image. It's assumed that the block information
     /// passed is equivalent to that returned by RenderTarget::get_blocks. `block_size` specifies
     /// the size of the blocks being passed, `blocks` contains the start points of each block and
-    /// `pixels` contains `block_size.0 * block_size.1 * 4` floats for each block.
+    /// `pixels` contains `block_size.0 * block_size.1 * 4` floats for each block. The pixels
+    /// of blocks at the edges which fall outside the image are skipped.
     pub fn add_blocks(
         &mut self,
         block_size: (usize, usize),
@@ -47,8 +48,8 @@ impl Image {
         let block_stride = block_size.0 * block_size.1 * 4;
         for (i, b) in blocks.iter().enumerate() {
             let block_px = &pixels[block_stride * i..block_stride * (i + 1)];
-            for by in 0..block_size.1 {
-                for bx in 0..block_size.0 {
+            for by in 0..block_size.1.min(self.dim.1 - b.1) {
+                for bx in 0..block_size.0.min(self.dim.0 - b.0) {
                     let c = &mut self.pixels[(by + b.1) * self.dim.0 + bx + b.0];
                     let px = by * block_size.0 * 4 + bx * 4;
                     for i in 0..4 {
@@ -58,8 +59,8 @@ impl Image {
             }
         }
     }
-    /// Convert the Image to sRGB8 format and return it
-    pub fn get_srgb8(&self) -> Vec<u8> {
+    /// Convert the Image to sRGB8 format with the display transform and return it
+    pub fn get_srgb8(&self, tonemap: &Tonemap) -> Vec<u8> {
         let mut render: Vec<u8> = iter::repeat(0u8)
             .take(self.dim.0 * self.dim.1 * 3)
             .collect();
@@ -67,7 +68,7 @@ impl Image {
             for x in 0..self.dim.0 {
                 let c = &self.pixels[y * self.dim.0 + x];
                 if c.a > 0.0 {
-                    let cn = (*c / c.a).clamp().to_srgb();
+                    let cn = tonemap.apply(&(*c / c.a)).to_srgb();
                     let px = y * self.dim.0 * 3 + x * 3;
                     for i in 0..3 {
                         render[px + i] = (cn[i] * 255.0) as u8;
@@ -77,6 +78,15 @@ impl Image {
         }
         render
     }
+    /// Get the linear RGB image without clamping it, for saving to HDR image formats
+    pub fn get_linear(&self) -> Vec<f32> {
+        let mut render = Vec::with_capacity(self.dim.0 * self.dim.1 * 3);
+        for c in &self.pixels {
+            let cn = if c.a > 0.0 { *c / c.a } else { Colorf::black() };
+            render.extend_from_slice(&[cn.r, cn.g, cn.b]);
+        }
+        render
+    }
     pub fn dimensions(&self) -> (usize, usize) {
         self.dim
     }
@@ -3,18 +3,33 @@
 
 pub use self::{
     animated_color::{AnimatedColor, ColorKeyframe},
+    aov::{Aov, AovSample, LightSplit},
     camera::Camera,
     color::Colorf,
+    colorspace::ColorSpace,
+    crop::CropWindow,
+    denoise::Denoiser,
     image::Image,
     render_target::{ImageSample, RenderTarget},
+    spectrum::{Blackbody, Wavelengths},
+    tonemap::Tonemap,
+    variance::PixelStats,
 };
 
 pub mod animated_color;
+pub mod aov;
 pub mod camera;
 pub mod color;
+pub mod colorspace;
+pub mod crop;
+pub mod denoise;
 pub mod filter;
+pub mod hdr;
 pub mod image;
 pub mod render_target;
+pub mod spectrum;
+pub mod tonemap;
+pub mod variance;
 
 /// Struct to store various parameters for the frame timing
 #[derive(Debug, Copy, Clone)]
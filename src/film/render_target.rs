@@ -1,19 +1,61 @@
 //! Defines the render target for tray, where our image will be written too
 //! during rendering
+//!
+//! The samples are accumulated without clamping, so bright lights and highlights
+//! are filtered correctly and can be saved to HDR images. Rare, very bright samples
+//! ("fireflies") can be tamed by setting a maximum sample luminance, samples
+//! brighter than it are scaled down to it before they're written, which trades
+//! some energy for less noise.
+//!
+//! # Checkpoints
+//! The weighted sums of the samples written to the render target can be saved to a
+//! checkpoint file along with its AOV layers, the blocks written in the current pass
+//! and the number of passes finished, so a long render can be resumed from it by
+//! loading it into a render target with the same size, block size, crop window and
+//! layers. The file is a little endian binary file containing:
+//!
+//! - The magic bytes `APCK` followed by the format version as a `u32`
+//! - The frame, width, height, block width, block height, passes finished and number
+//!   of blocks written as `u64`s
+//! - The start and end of the crop window as `u32`s, which is the whole image if it
+//!   isn't cropped
+//! - The `(x, y)` index of each block written as `u32`s
+//! - The RGBA weighted sums of each pixel as `f32`s, in the order they're stored
+//! - The number of AOV layers as a `u64`, then for each layer the length of its name
+//!   as a `u32` followed by the name. Layers which aren't statistics are followed by
+//!   the RGBA sums of each pixel as `f32`s
+//! - If any statistics layer is rendered, the sample count of each pixel as a `u32`
+//!   followed by the RGBA mean and sum of squared differences as `f32`s
 
 use crate::{
     film::{
+        aov::{Aov, AovSample},
+        denoise::{Denoiser, Features},
         filter::{Filter, Filters},
-        Colorf,
+        variance::{self, PixelStats},
+        ColorSpace, Colorf, CropWindow, Tonemap,
     },
     sampler::Region,
 };
-use std::{cmp, f32, iter, sync::Mutex, vec::Vec};
+use std::{
+    cmp, f32,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex, RwLock,
+    },
+    vec::Vec,
+};
 
 const FILTER_TABLE_SIZE: usize = 16;
+const CHECKPOINT_MAGIC: &[u8; 4] = b"APCK";
+const CHECKPOINT_VERSION: u32 = 2;
 
 /// A struct containing results of an image sample where a ray was fired through
 /// continuous pixel coordinates [x, y] and color `color` was computed
+#[derive(Clone, Copy)]
 pub struct ImageSample {
     pub x: f32,
     pub y: f32,
@@ -35,30 +77,55 @@ pub struct RenderTarget {
     filter: Box<Filters>,
     filter_table: Vec<f32>,
     filter_pixel_width: (i32, i32),
+    /// Indices of the blocks written since the last pass was finished
+    blocks_written: Mutex<Vec<(u32, u32)>>,
+    /// Number of passes over the image which have been finished
+    passes: AtomicUsize,
+    /// Held for reading while writing samples and for writing while saving a
+    /// checkpoint, so checkpoints only contain whole blocks
+    checkpoint_gate: RwLock<()>,
+    /// The AOV layers accumulated alongside the image. Each pixel holds the sum of the
+    /// sample values and the number of samples, or for id layers the id and squared
+    /// distance to the pixel's center of the sample it was taken from
+    aovs: Vec<(Aov, Mutex<Vec<[f32; 4]>>)>,
+    /// Number of the layers in `aovs` which were requested, the rest are only
+    /// accumulated as features for the denoiser
+    requested_aovs: usize,
+    /// The statistics of the samples in each pixel, if a statistics layer is
+    /// being accumulated. Statistics layers don't use their buffer in `aovs`
+    stats: Option<Mutex<Vec<PixelStats>>>,
+    /// The luminance samples are clamped to, if set
+    max_sample_luminance: Option<f32>,
+    /// The display transform used when converting the image to 8-bit
+    tonemap: Tonemap,
+    /// The window of the image to render, if only part of it is rendered
+    crop: Option<CropWindow>,
+    /// The denoiser run on the image when it's read, if it's denoised
+    denoiser: Option<Denoiser>,
+    /// If the image is rendered in spectral mode, see `film::spectrum`
+    spectral: bool,
+    /// The color space the image is rendered in, see `film::colorspace`
+    working_space: ColorSpace,
+    /// The color space the image is saved in
+    output_space: ColorSpace,
 }
 
 impl RenderTarget {
-    /// Create a render target with `width * height` pixels
+    /// Create a render target with `width * height` pixels, whose pixels are locked in
+    /// blocks of `lock_size`. If the image isn't evenly divided into the blocks the
+    /// blocks at its right and bottom edges are padded with pixels which are never written
     pub fn new(
         image_dim: (usize, usize),
         lock_size: (usize, usize),
         filter: Box<Filters>,
     ) -> RenderTarget {
-        if image_dim.0 % lock_size.0 != 0 || image_dim.1 % lock_size.1 != 0 {
-            panic!(
-                "Image with dimension {:?} not evenly divided by blocks of {:?}",
-                image_dim, lock_size
-            );
-        }
         let width = image_dim.0;
         let height = image_dim.1;
         let filter_pixel_width = (
             f32::floor(filter.width() / 0.5) as i32,
             f32::floor(filter.height() / 0.5) as i32,
         );
-        let mut filter_table: Vec<f32> = iter::repeat(0.0)
-            .take(FILTER_TABLE_SIZE * FILTER_TABLE_SIZE)
-            .collect();
+        let mut filter_table = vec![0.0; FILTER_TABLE_SIZE * FILTER_TABLE_SIZE];
         for y in 0..FILTER_TABLE_SIZE {
             let fy = (y as f32 + 0.5) * filter.height() / FILTER_TABLE_SIZE as f32;
             for x in 0..FILTER_TABLE_SIZE {
@@ -67,15 +134,14 @@ impl RenderTarget {
             }
         }
 
-        let x_blocks = width / lock_size.0;
-        let y_blocks = height / lock_size.1;
+        let x_blocks = width.div_ceil(lock_size.0);
+        let y_blocks = height.div_ceil(lock_size.1);
         let mut pixels_locked = Vec::with_capacity(x_blocks * y_blocks);
         for _ in 0..x_blocks * y_blocks {
-            pixels_locked.push(Mutex::new(
-                iter::repeat(Colorf::broadcast(0.0))
-                    .take(lock_size.0 * lock_size.1)
-                    .collect(),
-            ));
+            pixels_locked.push(Mutex::new(vec![
+                Colorf::broadcast(0.0);
+                lock_size.0 * lock_size.1
+            ]));
         }
 
         RenderTarget {
@@ -86,27 +152,195 @@ impl RenderTarget {
             filter,
             filter_table,
             filter_pixel_width,
+            blocks_written: Mutex::new(Vec::new()),
+            passes: AtomicUsize::new(0),
+            checkpoint_gate: RwLock::new(()),
+            aovs: Vec::new(),
+            requested_aovs: 0,
+            stats: None,
+            max_sample_luminance: None,
+            tonemap: Tonemap::default(),
+            crop: None,
+            denoiser: None,
+            spectral: false,
+            working_space: ColorSpace::LinearSrgb,
+            output_space: ColorSpace::Srgb,
+        }
+    }
+    /// Set the AOV layers to accumulate along with the image, see `film::aov`
+    pub fn set_aovs(&mut self, aovs: &[Aov]) {
+        let mut layers = aovs.to_vec();
+        if self.denoiser.is_some() {
+            for f in &Denoiser::FEATURES {
+                if !layers.contains(f) {
+                    layers.push(*f);
+                }
+            }
+        }
+        let empty = RenderTarget::empty_aov_pixel;
+        let size = |a: &Aov| {
+            if a.is_stat() {
+                0
+            } else {
+                self.width * self.height
+            }
+        };
+        self.aovs = layers
+            .iter()
+            .map(|a| (*a, Mutex::new(vec![empty(a); size(a)])))
+            .collect();
+        self.requested_aovs = aovs.len();
+        self.stats = if layers.iter().any(Aov::is_stat) {
+            Some(Mutex::new(vec![
+                PixelStats::new();
+                self.width * self.height
+            ]))
+        } else {
+            None
+        };
+    }
+    /// Set the maximum luminance of the samples written, or None to write them
+    /// unclamped
+    pub fn set_max_sample_luminance(&mut self, max: Option<f32>) {
+        self.max_sample_luminance = max;
+    }
+    pub fn max_sample_luminance(&self) -> Option<f32> {
+        self.max_sample_luminance
+    }
+    /// Set if the image is rendered in spectral mode, where samples carry wavelengths
+    /// which are converted to RGB before they're written, see `film::spectrum`
+    pub fn set_spectral(&mut self, spectral: bool) {
+        self.spectral = spectral;
+    }
+    pub fn spectral(&self) -> bool {
+        self.spectral
+    }
+    /// Set the color spaces the image is rendered and saved in, see `film::colorspace`
+    pub fn set_color_spaces(&mut self, working: ColorSpace, output: ColorSpace) {
+        self.working_space = working;
+        self.output_space = output;
+    }
+    pub fn working_space(&self) -> ColorSpace {
+        self.working_space
+    }
+    pub fn output_space(&self) -> ColorSpace {
+        self.output_space
+    }
+    /// Clamp the color of a sample to the maximum sample luminance, keeping its hue
+    pub fn clamp_sample(&self, c: &Colorf) -> Colorf {
+        match self.max_sample_luminance {
+            Some(max) if c.luminance() > max => {
+                let mut clamped = *c * (max / c.luminance());
+                clamped.a = c.a;
+                clamped
+            }
+            _ => *c,
+        }
+    }
+    /// Set the display transform used when converting the image to 8-bit, see
+    /// `film::tonemap`
+    pub fn set_tonemap(&mut self, tonemap: Tonemap) {
+        self.tonemap = tonemap;
+    }
+    pub fn tonemap(&self) -> &Tonemap {
+        &self.tonemap
+    }
+    /// Set the window of the image to render, see `film::crop`, or None to render all
+    /// of it. Samples are only written to the pixels in the window
+    pub fn set_crop(&mut self, crop: Option<CropWindow>) {
+        self.crop = crop;
+    }
+    pub fn crop(&self) -> Option<CropWindow> {
+        self.crop
+    }
+    /// Get the window of the image being rendered, which is the whole image if
+    /// it's not cropped
+    pub fn window(&self) -> CropWindow {
+        self.crop
+            .unwrap_or_else(|| CropWindow::full(self.dimensions()))
+    }
+    /// Set the denoiser run on the image when it's read, see `film::denoise`, or None
+    /// to not denoise it. The AOV layers the denoiser needs are accumulated along with
+    /// those set by `set_aovs`
+    pub fn set_denoiser(&mut self, denoiser: Option<Denoiser>) {
+        self.denoiser = denoiser;
+        let aovs = self.aovs();
+        self.set_aovs(&aovs);
+    }
+    pub fn denoiser(&self) -> Option<&Denoiser> {
+        self.denoiser.as_ref()
+    }
+    /// Get the AOV layers requested by `set_aovs`
+    pub fn aovs(&self) -> Vec<Aov> {
+        self.aovs[..self.requested_aovs]
+            .iter()
+            .map(|a| a.0)
+            .collect()
+    }
+    /// Get all the AOV layers being accumulated, including those only accumulated
+    /// for the denoiser
+    pub fn accumulated_aovs(&self) -> Vec<Aov> {
+        self.aovs.iter().map(|a| a.0).collect()
+    }
+    fn empty_aov_pixel(aov: &Aov) -> [f32; 4] {
+        if aov.is_id() {
+            [0.0, f32::INFINITY, 0.0, 0.0]
+        } else {
+            [0.0; 4]
         }
     }
 
-    /// Write all the image samples to the render target
+    /// Write all the image samples to the render target, the region is recorded as
+    /// a block written in the current pass
     pub fn write(&self, samples: &[ImageSample], region: &Region) {
-        // Determine which blocks we touch with our set of samples
+        let _gate = self.checkpoint_gate.read().unwrap();
+        self.write_samples(samples, region);
+    }
+    /// Write the image and AOV samples of a block to the render target together, so a
+    /// checkpoint can't be saved with the block's image samples but not its AOVs
+    pub fn write_block(&self, samples: &[ImageSample], aov_samples: &[AovSample], region: &Region) {
+        let _gate = self.checkpoint_gate.read().unwrap();
+        self.write_samples(samples, region);
+        self.accumulate_aovs(aov_samples);
+    }
+    fn write_samples(&self, samples: &[ImageSample], region: &Region) {
+        self.blocks_written
+            .lock()
+            .unwrap()
+            .push(region.block_index());
+        // Determine which blocks we touch with our set of samples, only writing to the
+        // pixels in the window being rendered
+        let window = self.window();
         let x_range = (
-            cmp::max(region.start.0 as i32 - self.filter_pixel_width.0, 0),
+            cmp::max(
+                region.start.0 as i32 - self.filter_pixel_width.0,
+                window.start.0 as i32,
+            ),
             cmp::min(
                 region.end.0 as i32 + self.filter_pixel_width.0,
-                self.width as i32 - 1,
+                window.end.0 as i32 - 1,
             ),
         );
         let y_range = (
-            cmp::max(region.start.1 as i32 - self.filter_pixel_width.1, 0),
+            cmp::max(
+                region.start.1 as i32 - self.filter_pixel_width.1,
+                window.start.1 as i32,
+            ),
             cmp::min(
                 region.end.1 as i32 + self.filter_pixel_width.1,
-                self.height as i32 - 1,
+                window.end.1 as i32 - 1,
             ),
         );
 
+        if let Some(ref stats) = self.stats {
+            let mut stats = stats.lock().unwrap();
+            for s in samples {
+                let (x, y) = (s.x.floor(), s.y.floor());
+                if x >= 0.0 && y >= 0.0 && window.contains(x as usize, y as usize) {
+                    stats[y as usize * self.width + x as usize].add(&s.color);
+                }
+            }
+        }
         if x_range.1 - x_range.0 < 0 || y_range.1 - y_range.0 < 0 {
             return;
         }
@@ -114,11 +348,10 @@ impl RenderTarget {
         let block_y_range = (y_range.0 / self.lock_size.1, y_range.1 / self.lock_size.1);
         // Temporary storage for filtered samples so we can compute the filtered results for
         // the block we're writing too without having to get the lock
-        let mut filtered_samples: Vec<_> = iter::repeat(Colorf::broadcast(0.0))
-            .take((self.lock_size.0 * self.lock_size.1) as usize)
-            .collect();
+        let mut filtered_samples =
+            vec![Colorf::broadcast(0.0); (self.lock_size.0 * self.lock_size.1) as usize];
 
-        let blocks_per_row = self.width as i32 / self.lock_size.0;
+        let blocks_per_row = self.lock_blocks().0 as i32;
         for y in block_y_range.0..block_y_range.1 + 1 {
             for x in block_x_range.0..block_x_range.1 + 1 {
                 let block_x_start = x * self.lock_size.0;
@@ -203,44 +436,327 @@ impl RenderTarget {
         }
     }
 
-    /// Clear the render target to black
+    /// Write the AOV samples to the pixels they're in
+    pub fn write_aovs(&self, samples: &[AovSample]) {
+        let _gate = self.checkpoint_gate.read().unwrap();
+        self.accumulate_aovs(samples);
+    }
+    fn accumulate_aovs(&self, samples: &[AovSample]) {
+        for (aov, pixels) in self.aovs.iter().filter(|a| !a.0.is_stat()) {
+            let mut pixels = pixels.lock().unwrap();
+            for s in samples {
+                let (x, y) = (s.x.floor(), s.y.floor());
+                if x < 0.0 || y < 0.0 || x >= self.width as f32 || y >= self.height as f32 {
+                    continue;
+                }
+                let px = &mut pixels[y as usize * self.width + x as usize];
+                let v = aov.value(s);
+                if aov.is_id() {
+                    let d = (s.x - x - 0.5).powi(2) + (s.y - y - 0.5).powi(2);
+                    if d < px[1] {
+                        *px = [v[0], d, 0.0, 0.0];
+                    }
+                } else {
+                    for i in 0..3 {
+                        px[i] += v[i];
+                    }
+                    px[3] += 1.0;
+                }
+            }
+        }
+    }
+    /// Get the image of the AOV layer, with a value for each channel of the layer
+    /// per pixel. Returns None if the layer isn't being accumulated
+    pub fn get_aov(&self, aov: Aov) -> Option<Vec<f32>> {
+        if aov == Aov::Variance {
+            let stats = self.stats.as_ref()?.lock().unwrap();
+            let mut render = Vec::with_capacity(stats.len() * 3);
+            for s in stats.iter() {
+                let v = s.variance_of_mean();
+                render.extend_from_slice(&[v.r, v.g, v.b]);
+            }
+            return Some(render);
+        }
+        let pixels = self.aovs.iter().find(|a| a.0 == aov)?.1.lock().unwrap();
+        let channels = aov.channels().len();
+        let mut render = Vec::with_capacity(pixels.len() * channels);
+        for px in pixels.iter() {
+            for i in 0..channels {
+                render.push(if aov.is_id() {
+                    px[0]
+                } else if px[3] > 0.0 {
+                    px[i] / px[3]
+                } else {
+                    0.0
+                });
+            }
+        }
+        Some(render)
+    }
+    /// Get the statistics of the samples in each pixel, or None if they aren't
+    /// being accumulated, see `film::variance`
+    pub fn pixel_stats(&self) -> Option<Vec<PixelStats>> {
+        Some(self.stats.as_ref()?.lock().unwrap().clone())
+    }
+    /// Estimate the relative RMS error of the pixels in the window being rendered
+    /// from the statistics of their samples, or None if they aren't being accumulated
+    pub fn estimate_error(&self) -> Option<f32> {
+        let stats = self.pixel_stats()?;
+        let window = self.window();
+        let in_window: Vec<_> = stats
+            .into_iter()
+            .enumerate()
+            .filter(|(i, _)| window.contains(i % self.width, i / self.width))
+            .map(|(_, s)| s)
+            .collect();
+        Some(variance::estimate_error(&in_window))
+    }
+
+    /// Clear the render target to black, resetting the passes finished
     pub fn clear(&mut self) {
-        let x_blocks = self.width / self.lock_size.0 as usize;
-        let y_blocks = self.height / self.lock_size.1 as usize;
-        for by in 0..y_blocks {
-            for bx in 0..x_blocks {
-                let block_idx = (by * x_blocks + bx) as usize;
-                let mut pixels = self.pixels_locked[block_idx].lock().unwrap();
-                for p in pixels.iter_mut() {
-                    *p = Colorf::broadcast(0.0);
+        for (aov, pixels) in &self.aovs {
+            let empty = RenderTarget::empty_aov_pixel(aov);
+            for px in pixels.lock().unwrap().iter_mut() {
+                *px = empty;
+            }
+        }
+        if let Some(ref stats) = self.stats {
+            for s in stats.lock().unwrap().iter_mut() {
+                *s = PixelStats::new();
+            }
+        }
+        self.blocks_written.lock().unwrap().clear();
+        self.passes.store(0, Ordering::Release);
+        for block in &self.pixels_locked {
+            for p in block.lock().unwrap().iter_mut() {
+                *p = Colorf::broadcast(0.0);
+            }
+        }
+    }
+
+    /// Record that a pass over the image has been finished, starting a new pass
+    pub fn finish_pass(&self) {
+        let _gate = self.checkpoint_gate.write().unwrap();
+        self.blocks_written.lock().unwrap().clear();
+        self.passes.fetch_add(1, Ordering::AcqRel);
+    }
+    /// Get the number of passes over the image which have been finished
+    pub fn passes(&self) -> usize {
+        self.passes.load(Ordering::Acquire)
+    }
+    /// Get the indices of the blocks written in the current pass
+    pub fn blocks_written(&self) -> Vec<(u32, u32)> {
+        self.blocks_written.lock().unwrap().clone()
+    }
+    /// Save a checkpoint of the render of `frame` to `file`. The checkpoint is written
+    /// to a temporary file first which then replaces `file`, so an interrupted save
+    /// doesn't lose the previous checkpoint
+    pub fn save_checkpoint(&self, file: &Path, frame: usize) -> io::Result<()> {
+        let mut tmp_name = file.as_os_str().to_owned();
+        tmp_name.push(".tmp");
+        let tmp = Path::new(&tmp_name);
+        {
+            let _gate = self.checkpoint_gate.write().unwrap();
+            let mut out = BufWriter::new(File::create(tmp)?);
+            out.write_all(CHECKPOINT_MAGIC)?;
+            out.write_all(&CHECKPOINT_VERSION.to_le_bytes())?;
+            let blocks = self.blocks_written.lock().unwrap();
+            let header = [
+                frame,
+                self.width,
+                self.height,
+                self.lock_size.0 as usize,
+                self.lock_size.1 as usize,
+                self.passes(),
+                blocks.len(),
+            ];
+            for x in &header {
+                out.write_all(&(*x as u64).to_le_bytes())?;
+            }
+            let window = self.window();
+            for x in &[window.start.0, window.start.1, window.end.0, window.end.1] {
+                out.write_all(&x.to_le_bytes())?;
+            }
+            for b in blocks.iter() {
+                out.write_all(&b.0.to_le_bytes())?;
+                out.write_all(&b.1.to_le_bytes())?;
+            }
+            for block in &self.pixels_locked {
+                for px in block.lock().unwrap().iter() {
+                    write_f32s(&mut out, &[px.r, px.g, px.b, px.a])?;
                 }
             }
+            out.write_all(&(self.aovs.len() as u64).to_le_bytes())?;
+            for (aov, pixels) in &self.aovs {
+                let name = aov.name().as_bytes();
+                out.write_all(&(name.len() as u32).to_le_bytes())?;
+                out.write_all(name)?;
+                for px in pixels.lock().unwrap().iter() {
+                    write_f32s(&mut out, px)?;
+                }
+            }
+            if let Some(ref stats) = self.stats {
+                for s in stats.lock().unwrap().iter() {
+                    out.write_all(&s.count.to_le_bytes())?;
+                    let (mean, m2) = (s.mean, s.sum_sq_diff());
+                    write_f32s(&mut out, &[mean.r, mean.g, mean.b, mean.a])?;
+                    write_f32s(&mut out, &[m2.r, m2.g, m2.b, m2.a])?;
+                }
+            }
+            out.flush()?;
         }
+        fs::rename(tmp, file)
+    }
+    /// Load the checkpoint saved in `file`, replacing the image and AOV layers in the
+    /// render target. Returns the frame the checkpoint was saved for, or an error if
+    /// the checkpoint can't be read or is for a render target with a different size,
+    /// block size, crop window or layers
+    pub fn load_checkpoint(&mut self, file: &Path) -> io::Result<usize> {
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_owned());
+        let mut input = BufReader::new(File::open(file)?);
+        let mut magic = [0u8; 4];
+        input.read_exact(&mut magic)?;
+        if &magic != CHECKPOINT_MAGIC {
+            return Err(invalid("Not a render checkpoint file"));
+        }
+        if read_u32(&mut input)? != CHECKPOINT_VERSION {
+            return Err(invalid("Unsupported render checkpoint version"));
+        }
+        let mut header = [0usize; 7];
+        for x in &mut header {
+            *x = read_u64(&mut input)? as usize;
+        }
+        let [frame, width, height, block_width, block_height, passes, num_blocks] = header;
+        if (width, height) != (self.width, self.height) {
+            return Err(invalid(&format!(
+                "The checkpoint is for a {}x{} image, not {}x{}",
+                width, height, self.width, self.height
+            )));
+        }
+        let lock_size = (self.lock_size.0 as usize, self.lock_size.1 as usize);
+        if (block_width, block_height) != lock_size {
+            return Err(invalid(&format!(
+                "The checkpoint is for {}x{} blocks, not {}x{}",
+                block_width, block_height, lock_size.0, lock_size.1
+            )));
+        }
+        let mut bounds = [0u32; 4];
+        for x in &mut bounds {
+            *x = read_u32(&mut input)?;
+        }
+        let window = CropWindow::new((bounds[0], bounds[1]), (bounds[2], bounds[3]));
+        if window != self.window() {
+            return Err(invalid("The checkpoint is for a different crop window"));
+        }
+        let mut blocks = Vec::new();
+        for _ in 0..num_blocks {
+            blocks.push((read_u32(&mut input)?, read_u32(&mut input)?));
+        }
+        // Read everything before replacing the render so a bad checkpoint leaves it as is
+        let num_pixels: usize = self
+            .pixels_locked
+            .iter()
+            .map(|b| b.lock().unwrap().len())
+            .sum();
+        let pixels = read_f32s(&mut input, num_pixels * 4)?;
+        let mut names = Vec::new();
+        let mut aovs = Vec::new();
+        for _ in 0..read_u64(&mut input)? {
+            let mut name = vec![0u8; read_u32(&mut input)? as usize];
+            input.read_exact(&mut name)?;
+            let aov = String::from_utf8(name)
+                .ok()
+                .and_then(|n| Aov::from_name(&n))
+                .ok_or_else(|| invalid("The checkpoint has an unknown AOV layer"))?;
+            let size = if aov.is_stat() {
+                0
+            } else {
+                self.width * self.height
+            };
+            names.push(aov);
+            aovs.push(read_f32s(&mut input, size * 4)?);
+        }
+        if names != self.accumulated_aovs() {
+            return Err(invalid(&format!(
+                "The checkpoint has the AOV layers [{}], not [{}]",
+                aov_names(&names),
+                aov_names(&self.accumulated_aovs())
+            )));
+        }
+        let stats = match self.stats {
+            Some(_) => {
+                let mut stats = Vec::with_capacity(self.width * self.height);
+                for _ in 0..self.width * self.height {
+                    let count = read_u32(&mut input)?;
+                    let v = read_f32s(&mut input, 8)?;
+                    let mean = Colorf::with_alpha(v[0], v[1], v[2], v[3]);
+                    let m2 = Colorf::with_alpha(v[4], v[5], v[6], v[7]);
+                    stats.push(PixelStats::from_parts(count, mean, m2));
+                }
+                Some(stats)
+            }
+            None => None,
+        };
+
+        let mut pixels = pixels.chunks(4);
+        for block in &self.pixels_locked {
+            for px in block.lock().unwrap().iter_mut() {
+                let v = pixels.next().unwrap();
+                *px = Colorf::with_alpha(v[0], v[1], v[2], v[3]);
+            }
+        }
+        for ((_, layer), values) in self.aovs.iter().zip(aovs) {
+            for (px, v) in layer.lock().unwrap().iter_mut().zip(values.chunks(4)) {
+                px.copy_from_slice(v);
+            }
+        }
+        if let (Some(target), Some(stats)) = (&self.stats, stats) {
+            *target.lock().unwrap() = stats;
+        }
+        *self.blocks_written.lock().unwrap() = blocks;
+        self.passes.store(passes, Ordering::Release);
+        Ok(frame)
     }
 
     /// Get the dimensions of the render target
     pub fn dimensions(&self) -> (usize, usize) {
         (self.width, self.height)
     }
+    /// Get the number of blocks of locked pixels along x and y
+    fn lock_blocks(&self) -> (usize, usize) {
+        (
+            self.width.div_ceil(self.lock_size.0 as usize),
+            self.height.div_ceil(self.lock_size.1 as usize),
+        )
+    }
+    /// Get the reconstruction filter used when writing samples
+    pub fn filter(&self) -> &Filters {
+        &self.filter
+    }
 
-    /// Convert the floating point color buffer to 24bpp sRGB for output to an image
+    /// Convert the floating point color buffer to 24bpp RGB in the output color space
+    /// for output to an image
     pub fn get_render(&self) -> Vec<u8> {
-        let mut render: Vec<u8> = iter::repeat(0u8)
-            .take(self.width * self.height * 3)
-            .collect();
-        let x_blocks = self.width / self.lock_size.0 as usize;
-        let y_blocks = self.height / self.lock_size.1 as usize;
+        let mut render = vec![0u8; self.width * self.height * 3];
+        let (x_blocks, y_blocks) = self.lock_blocks();
         for by in 0..y_blocks {
             for bx in 0..x_blocks {
                 let block_x_start = bx * self.lock_size.0 as usize;
                 let block_y_start = by * self.lock_size.1 as usize;
-                let block_idx = (by * x_blocks + bx) as usize;
+                let block_idx = by * x_blocks + bx;
                 let pixels = self.pixels_locked[block_idx].lock().unwrap();
                 for y in 0..self.lock_size.1 as usize {
+                    if y + block_y_start >= self.height {
+                        break;
+                    }
                     for x in 0..self.lock_size.0 as usize {
+                        if x + block_x_start >= self.width {
+                            break;
+                        }
                         let c = &pixels[y * self.lock_size.0 as usize + x];
                         if c.a > 0.0 {
-                            let cn = (*c / c.a).clamp().to_srgb();
+                            let c = self.working_space.convert(&(*c / c.a), self.output_space);
+                            let cn = self.output_space.encode(&self.tonemap.apply(&c));
                             let px = (y + block_y_start) * self.width * 3 + (x + block_x_start) * 3;
                             for i in 0..3 {
                                 render[px + i] = (cn[i] * 255.0) as u8;
@@ -253,23 +769,24 @@ impl RenderTarget {
         render
     }
 
-    /// Get the blocks that have had pixels written too them. Returns the size of each block,
+    /// Get the blocks that have had pixels written too them, including blocks only partly
+    /// covered by the filter of samples written nearby. Returns the size of each block,
     /// a list of block positions in pixels and then pixels for the blocks (in a single f32 vec).
     /// The block's pixels are stored in the same order their position appears in the block
-    /// positions vec and contain `dim.0 * dim.1 * 4` f32's per block.
+    /// positions vec and contain `dim.0 * dim.1 * 4` f32's per block. Blocks at the right and
+    /// bottom edges of the image are padded with black pixels outside the image.
     pub fn get_rendered_blocks(&self) -> ((usize, usize), Vec<(usize, usize)>, Vec<f32>) {
         let block_size = (self.lock_size.0 as usize, self.lock_size.1 as usize);
         let mut blocks = Vec::new();
         let mut render = Vec::new();
-        let x_blocks = self.width / block_size.0;
-        let y_blocks = self.height / block_size.1;
+        let (x_blocks, y_blocks) = self.lock_blocks();
         for by in 0..y_blocks {
             for bx in 0..x_blocks {
                 let block_x_start = bx * block_size.0;
                 let block_y_start = by * block_size.1;
                 let block_idx = by * x_blocks + bx;
                 let pixels = self.pixels_locked[block_idx].lock().unwrap();
-                if pixels.iter().all(|px| px.a != 0.0) {
+                if pixels.iter().any(|px| px.a != 0.0) {
                     blocks.push((block_x_start, block_y_start));
                     for y in 0..block_size.1 {
                         for x in 0..block_size.0 {
@@ -285,21 +802,52 @@ impl RenderTarget {
         (block_size, blocks, render)
     }
 
+    /// Get the linear RGB image without clamping it, for saving to HDR image formats.
+    /// Returns 3 floats per pixel, with pixels which haven't been written to set to black
+    pub fn get_render_linear(&self) -> Vec<f32> {
+        let mut render = Vec::with_capacity(self.width * self.height * 3);
+        for px in self.get_renderf32().chunks(4) {
+            for i in 0..3 {
+                render.push(if px[3] > 0.0 { px[i] / px[3] } else { 0.0 });
+            }
+        }
+        render
+    }
+    /// Get the linear RGB image denoised by the denoiser, or None if the image isn't
+    /// denoised
+    pub fn get_render_denoised(&self) -> Option<Vec<f32>> {
+        let denoiser = self.denoiser.as_ref()?;
+        let albedo = self.get_aov(Aov::Albedo);
+        let normal = self.get_aov(Aov::Normal);
+        let depth = self.get_aov(Aov::Depth);
+        let variance = self.get_aov(Aov::Variance);
+        let features = Features {
+            albedo: albedo.as_deref(),
+            normal: normal.as_deref(),
+            depth: depth.as_deref(),
+            variance: variance.as_deref(),
+        };
+        let rgb = self.get_render_linear();
+        Some(denoiser.denoise(self.dimensions(), &rgb, &features))
+    }
     /// Get the raw floating point framebuffer
     pub fn get_renderf32(&self) -> Vec<f32> {
-        let mut render: Vec<f32> = iter::repeat(0.0)
-            .take(self.width * self.height * 4)
-            .collect();
-        let x_blocks = self.width / self.lock_size.0 as usize;
-        let y_blocks = self.height / self.lock_size.1 as usize;
+        let mut render = vec![0.0; self.width * self.height * 4];
+        let (x_blocks, y_blocks) = self.lock_blocks();
         for by in 0..y_blocks {
             for bx in 0..x_blocks {
                 let block_x_start = bx * self.lock_size.0 as usize;
                 let block_y_start = by * self.lock_size.1 as usize;
-                let block_idx = (by * x_blocks + bx) as usize;
+                let block_idx = by * x_blocks + bx;
                 let pixels = self.pixels_locked[block_idx].lock().unwrap();
                 for y in 0..self.lock_size.1 as usize {
+                    if y + block_y_start >= self.height {
+                        break;
+                    }
                     for x in 0..self.lock_size.0 as usize {
+                        if x + block_x_start >= self.width {
+                            break;
+                        }
                         let c = &pixels[y * self.lock_size.0 as usize + x];
                         let px = (y + block_y_start) * self.width * 4 + (x + block_x_start) * 4;
                         for i in 0..4 {
@@ -312,3 +860,295 @@ impl RenderTarget {
         render
     }
 }
+
+fn read_u32<R: Read>(input: &mut R) -> io::Result<u32> {
+    let mut buf = [0u8; 4];
+    input.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64<R: Read>(input: &mut R) -> io::Result<u64> {
+    let mut buf = [0u8; 8];
+    input.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn read_f32s<R: Read>(input: &mut R, n: usize) -> io::Result<Vec<f32>> {
+    let mut values = Vec::with_capacity(n);
+    for _ in 0..n {
+        values.push(f32::from_bits(read_u32(input)?));
+    }
+    Ok(values)
+}
+
+fn write_f32s<W: Write>(out: &mut W, values: &[f32]) -> io::Result<()> {
+    for x in values {
+        out.write_all(&x.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+fn aov_names(aovs: &[Aov]) -> String {
+    aovs.iter().map(Aov::name).collect::<Vec<_>>().join(", ")
+}
+
+#[test]
+fn test_checkpoint() {
+    use crate::film::filter::Gaussian;
+    let target =
+        |dim| RenderTarget::new(dim, (2, 2), Box::new(Gaussian::new_filter(1.0, 1.0, 2.0)));
+    let mut rt = target((8, 4));
+    let samples: Vec<_> = (0..16)
+        .map(|i| {
+            let (x, y) = (i % 4, i / 4);
+            let c = Colorf::new(x as f32, y as f32, 0.25);
+            ImageSample::new(4.0 + x as f32 + 0.5, y as f32 + 0.5, c)
+        })
+        .collect();
+    rt.write(&samples, &Region::new((4, 0), (4, 4)));
+    rt.finish_pass();
+    rt.write(&samples, &Region::new((4, 0), (4, 4)));
+
+    let file = std::env::temp_dir().join(format!("aperture_checkpoint_{}.chk", std::process::id()));
+    rt.save_checkpoint(&file, 7).unwrap();
+    let mut loaded = target((8, 4));
+    assert_eq!(loaded.load_checkpoint(&file).unwrap(), 7);
+    assert_eq!(loaded.get_renderf32(), rt.get_renderf32());
+    assert_eq!(loaded.passes(), 1);
+    assert_eq!(loaded.blocks_written(), [(1, 0)]);
+
+    let mut other = target((4, 4));
+    let err = other.load_checkpoint(&file).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    rt.clear();
+    assert_eq!(rt.passes(), 0);
+    assert!(rt.blocks_written().is_empty());
+    let _ = fs::remove_file(file);
+}
+
+#[test]
+fn test_checkpoint_aovs() {
+    use crate::film::{aov::AovSample, filter::Gaussian};
+    let target = |block, aovs: &[Aov]| {
+        let filter = Box::new(Gaussian::new_filter(1.0, 1.0, 2.0));
+        let mut rt = RenderTarget::new((4, 4), block, filter);
+        rt.set_denoiser(Some(Denoiser::default()));
+        rt.set_aovs(aovs);
+        rt.set_crop(Some(CropWindow::new((0, 0), (4, 2))));
+        rt
+    };
+    let rt = target((2, 2), &[Aov::Variance, Aov::InstanceId]);
+    let mut samples = Vec::new();
+    let mut aov_samples = Vec::new();
+    for i in 0..16 {
+        let (x, y) = ((i % 4) as f32 + 0.25, (i / 8) as f32 + 0.5);
+        samples.push(ImageSample::new(x, y, Colorf::new(i as f32, 1.0, 0.5)));
+        aov_samples.push(AovSample {
+            depth: i as f32,
+            instance_id: 3.0,
+            ..AovSample::background(x, y)
+        });
+    }
+    rt.write(&samples, &Region::new((0, 0), (4, 2)));
+    rt.write_aovs(&aov_samples);
+
+    let file = std::env::temp_dir().join(format!(
+        "aperture_checkpoint_aovs_{}.chk",
+        std::process::id()
+    ));
+    rt.save_checkpoint(&file, 0).unwrap();
+    let mut loaded = target((2, 2), &[Aov::Variance, Aov::InstanceId]);
+    assert_eq!(loaded.load_checkpoint(&file).unwrap(), 0);
+    for aov in rt.accumulated_aovs() {
+        assert_eq!(loaded.get_aov(aov), rt.get_aov(aov));
+    }
+    assert_eq!(loaded.pixel_stats(), rt.pixel_stats());
+    assert_eq!(loaded.get_renderf32(), rt.get_renderf32());
+    // The render is denoised with the features read from the checkpoint
+    let denoised = loaded.get_render_denoised().unwrap();
+    assert_eq!(Some(denoised), rt.get_render_denoised());
+
+    let mut other_aovs = target((2, 2), &[Aov::InstanceId]);
+    let err = other_aovs.load_checkpoint(&file).unwrap_err();
+    assert!(err.to_string().contains("AOV layers"));
+    let mut other_blocks = target((4, 2), &[Aov::Variance, Aov::InstanceId]);
+    assert!(other_blocks.load_checkpoint(&file).is_err());
+    let mut other_crop = target((2, 2), &[Aov::Variance, Aov::InstanceId]);
+    other_crop.set_crop(None);
+    assert!(other_crop.load_checkpoint(&file).is_err());
+    let _ = fs::remove_file(file);
+}
+
+#[test]
+fn test_checkpoint_during_writes() {
+    use crate::film::{aov::AovSample, filter::BoxFilter};
+    // Each block adds the sample k with color and depth k to the only pixel, so the
+    // depth averages to the same value as the color if the checkpoint has the AOVs
+    // of each block whose color it has
+    let target = || {
+        let mut rt = RenderTarget::new((1, 1), (1, 1), Box::new(BoxFilter::new_filter(0.5, 0.5)));
+        rt.set_aovs(&[Aov::Depth]);
+        rt
+    };
+    let rt = target();
+    let file = std::env::temp_dir().join(format!(
+        "aperture_checkpoint_writes_{}.chk",
+        std::process::id()
+    ));
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            for k in 1..500 {
+                let sample = ImageSample::new(0.5, 0.5, Colorf::broadcast(k as f32));
+                let aov = AovSample {
+                    depth: k as f32,
+                    ..AovSample::background(0.5, 0.5)
+                };
+                rt.write_block(&[sample], &[aov], &Region::new((0, 0), (1, 1)));
+            }
+        });
+        for _ in 0..50 {
+            rt.save_checkpoint(&file, 0).unwrap();
+            let mut loaded = target();
+            loaded.load_checkpoint(&file).unwrap();
+            let color = loaded.get_render_linear()[0];
+            let depth = loaded.get_aov(Aov::Depth).unwrap()[0];
+            assert_eq!(color, depth);
+        }
+    });
+    let _ = fs::remove_file(file);
+}
+
+#[test]
+fn test_aovs() {
+    use crate::film::{aov::AovSample, filter::Gaussian};
+    let mut rt = RenderTarget::new(
+        (2, 2),
+        (2, 2),
+        Box::new(Gaussian::new_filter(1.0, 1.0, 2.0)),
+    );
+    rt.set_aovs(&[Aov::Depth, Aov::InstanceId, Aov::Uv]);
+    assert_eq!(rt.aovs(), [Aov::Depth, Aov::InstanceId, Aov::Uv]);
+    let sample = |x, y, depth, id| AovSample {
+        depth,
+        instance_id: id,
+        uv: (depth, 1.0),
+        ..AovSample::background(x, y)
+    };
+    rt.write_aovs(&[
+        sample(0.1, 0.1, 1.0, 5.0),
+        sample(0.4, 0.6, 3.0, 7.0),
+        sample(0.9, 0.9, 5.0, 9.0),
+        sample(1.5, 1.5, 2.0, 1.0),
+    ]);
+    // Depth is averaged while the id is of the sample nearest the pixel's center
+    assert_eq!(rt.get_aov(Aov::Depth).unwrap(), [3.0, 0.0, 0.0, 2.0]);
+    assert_eq!(rt.get_aov(Aov::InstanceId).unwrap(), [7.0, 0.0, 0.0, 1.0]);
+    let uv = rt.get_aov(Aov::Uv).unwrap();
+    assert_eq!(uv.len(), 8);
+    assert_eq!(uv[..2], [3.0, 1.0]);
+    assert_eq!(rt.get_aov(Aov::Normal), None);
+    rt.clear();
+    assert_eq!(rt.get_aov(Aov::Depth).unwrap(), [0.0; 4]);
+}
+
+#[test]
+fn test_variance() {
+    use crate::film::filter::Gaussian;
+    let mut rt = RenderTarget::new(
+        (2, 2),
+        (2, 2),
+        Box::new(Gaussian::new_filter(1.0, 1.0, 2.0)),
+    );
+    assert_eq!(rt.estimate_error(), None);
+    rt.set_aovs(&[Aov::Variance]);
+    assert_eq!(rt.aovs(), [Aov::Variance]);
+    let samples = [
+        ImageSample::new(0.2, 0.2, Colorf::broadcast(1.0)),
+        ImageSample::new(0.7, 0.4, Colorf::broadcast(3.0)),
+        ImageSample::new(1.5, 0.5, Colorf::broadcast(2.0)),
+        ImageSample::new(1.5, 1.5, Colorf::broadcast(2.0)),
+        ImageSample::new(1.2, 1.8, Colorf::broadcast(2.0)),
+    ];
+    rt.write(&samples, &Region::new((0, 0), (2, 2)));
+    let stats = rt.pixel_stats().unwrap();
+    assert_eq!(
+        stats.iter().map(|s| s.count).collect::<Vec<_>>(),
+        [2, 1, 0, 2]
+    );
+    assert_eq!(stats[0].mean.r, 2.0);
+    // The samples in the first pixel have a variance of 2, so their mean's is 1
+    let variance = rt.get_aov(Aov::Variance).unwrap();
+    assert_eq!(variance.len(), 12);
+    assert_eq!(variance[..3], [1.0; 3]);
+    assert!(variance[3..].iter().all(|&v| v == 0.0));
+    assert!(rt.estimate_error().unwrap() > 0.0);
+    // Only the pixels in the crop window are counted
+    rt.set_crop(Some(CropWindow::new((1, 1), (2, 2))));
+    assert_eq!(rt.estimate_error(), Some(0.0));
+    rt.clear();
+    assert!(rt.pixel_stats().unwrap().iter().all(|s| s.count == 0));
+}
+
+#[test]
+fn test_max_sample_luminance() {
+    use crate::film::filter::Gaussian;
+    let mut rt = RenderTarget::new(
+        (2, 2),
+        (2, 2),
+        Box::new(Gaussian::new_filter(1.0, 1.0, 2.0)),
+    );
+    let bright = Colorf::new(40.0, 20.0, 10.0);
+    // Samples are written unclamped by default
+    assert_eq!(rt.clamp_sample(&bright), bright);
+    let samples: Vec<_> = (0..4)
+        .map(|i| ImageSample::new((i % 2) as f32 + 0.5, (i / 2) as f32 + 0.5, bright))
+        .collect();
+    rt.write(&samples, &Region::new((0, 0), (2, 2)));
+    let render = rt.get_render_linear();
+    assert!((render[0] - 40.0).abs() < 1e-3);
+
+    rt.set_max_sample_luminance(Some(2.0));
+    let clamped = rt.clamp_sample(&bright);
+    assert!((clamped.luminance() - 2.0).abs() < 1e-5);
+    assert!((clamped.r / clamped.g - 2.0).abs() < 1e-5);
+    assert_eq!(clamped.a, bright.a);
+    let dim = Colorf::new(0.5, 0.5, 0.5);
+    assert_eq!(rt.clamp_sample(&dim), dim);
+}
+
+#[test]
+fn test_partial_blocks() {
+    use crate::film::{filter::Gaussian, Image};
+    let mut rt = RenderTarget::new(
+        (5, 3),
+        (2, 2),
+        Box::new(Gaussian::new_filter(1.0, 1.0, 2.0)),
+    );
+    let samples: Vec<_> = (0..15)
+        .map(|i| {
+            let c = Colorf::new(i as f32, 1.0, 0.5);
+            ImageSample::new((i % 5) as f32 + 0.5, (i / 5) as f32 + 0.5, c)
+        })
+        .collect();
+    let mut region = Region::new((0, 0), (4, 4));
+    region.select_region((1, 0), &CropWindow::full((5, 3)));
+    assert_eq!(
+        (region.start, region.end, region.dim),
+        ((4, 0), (5, 3), (1, 3))
+    );
+    assert_eq!(region.block_index(), (1, 0));
+    rt.write(&samples, &Region::new((0, 0), (5, 3)));
+    let image = rt.get_renderf32();
+    assert_eq!(image.len(), 5 * 3 * 4);
+    assert!(image.chunks(4).all(|px| px[3] > 0.0));
+    assert_eq!(rt.get_render().len(), 5 * 3 * 3);
+
+    // The padded edge blocks are clipped when added to an image
+    let (block_size, blocks, pixels) = rt.get_rendered_blocks();
+    assert_eq!(blocks.len(), 6);
+    let mut img = Image::new((5, 3));
+    img.add_blocks(block_size, &blocks, &pixels);
+    assert_eq!(img.get_linear(), rt.get_render_linear());
+    rt.clear();
+    assert!(rt.get_renderf32().iter().all(|&x| x == 0.0));
+}
@@ -0,0 +1,427 @@
+//! Provides the types for rendering in spectral mode, where each sample carries
+//! four wavelengths of light instead of RGB. The first, or hero, wavelength is
+//! picked uniformly over the visible range and the others are spaced evenly from
+//! it, wrapping around the range, so the four cover the spectrum well
+//! (see [Wilkie et al., Hero Wavelength Spectral Sampling](https://doi.org/10.1111/cgf.12419)).
+//! The values at the four wavelengths are stored in the `r`, `g`, `b` and `a`
+//! channels of a `Colorf`, so integrators work unchanged.
+//!
+//! RGB colors of materials, textures and lights are upsampled to spectra with
+//! [Smits' method](https://doi.org/10.1080/10867651.1999.10487511), while blackbody
+//! emitters are evaluated with Planck's law. Each sample is converted to XYZ with
+//! the [analytic fit of the CIE 1931 color matching functions](http://jcgt.org/published/0002/02/01/)
+//! by Wyman et al., and from there to the working color space before it's written
+//! to the film. Colors in wider working spaces than sRGB are converted to linear sRGB
+//! before they're upsampled, see `film::colorspace`.
+//!
+//! The white point of spectral mode is the equal energy illuminant, so a white RGB
+//! color is upsampled to a flat spectrum. It's adapted to the D65 white point of
+//! sRGB when converting to RGB, which means blackbody emitters look white at about
+//! 5500K and bluer above it.
+//!
+//! # Scene Usage Example
+//! Spectral mode is enabled in the film, while emitters can be given a temperature
+//! in Kelvin and a strength instead of their color. The strength is the luminance
+//! of the light, as for the strength of an RGB color.
+//!
+//! ```json
+//! "film": {
+//!     ...
+//!     "spectral": true
+//! }
+//! ```
+//!
+//! ```json
+//! "objects": [
+//!     {
+//!         "name": "candle",
+//!         "type": "emitter",
+//!         "emitter": "point",
+//!         "emission": { "temperature": 1900, "strength": 50 },
+//!         ...
+//!     },
+//!     ...
+//! ]
+//! ```
+//!
+//! Blackbody emitters are also used when rendering RGB, with the color of their
+//! spectrum.
+
+use std::f32;
+
+use crate::film::{ColorSpace, Colorf};
+
+/// The shortest wavelength sampled, in nanometers
+pub const MIN_WAVELENGTH: f32 = 360.0;
+/// The longest wavelength sampled, in nanometers
+pub const MAX_WAVELENGTH: f32 = 830.0;
+
+/// Integral of the fit of the CIE `y` color matching function over the wavelengths
+/// sampled, which normalizes a flat spectrum of 1 to a luminance of 1
+const CIE_Y_INTEGRAL: f32 = 106.922;
+
+/// Converts XYZ to linear sRGB, adapting the equal energy white point to D65 with
+/// the Bradford transform
+const XYZ_TO_RGB: [[f32; 3]; 3] = [
+    [3.146_251, -1.666_124, -0.480_127_1],
+    [-0.995_535, 1.955_763_4, 0.039_771_5],
+    [0.063_597_8, -0.214_596_5, 1.150_998_7],
+];
+
+/// Smits' spectra for the white, cyan, magenta, yellow, red, green and blue colors,
+/// sampled in 10 bins evenly spaced from 380nm to 720nm
+const SMITS_WHITE: [f32; 10] = [
+    1.0000, 1.0000, 0.9999, 0.9993, 0.9992, 0.9998, 1.0000, 1.0000, 1.0000, 1.0000,
+];
+const SMITS_CYAN: [f32; 10] = [
+    0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0.0000, 0.0000, 0.0000,
+];
+const SMITS_MAGENTA: [f32; 10] = [
+    1.0000, 1.0000, 0.9685, 0.2229, 0.0000, 0.0458, 0.8369, 1.0000, 1.0000, 0.9959,
+];
+const SMITS_YELLOW: [f32; 10] = [
+    0.0001, 0.0000, 0.1088, 0.6651, 1.0000, 1.0000, 0.9996, 0.9586, 0.9685, 0.9840,
+];
+const SMITS_RED: [f32; 10] = [
+    0.1012, 0.0515, 0.0000, 0.0000, 0.0000, 0.0000, 0.8325, 1.0149, 1.0149, 1.0149,
+];
+const SMITS_GREEN: [f32; 10] = [
+    0.0000, 0.0000, 0.0273, 0.7937, 1.0000, 0.9418, 0.1719, 0.0000, 0.0000, 0.0025,
+];
+const SMITS_BLUE: [f32; 10] = [
+    1.0000, 1.0000, 0.8916, 0.3323, 0.0000, 0.0000, 0.0003, 0.0369, 0.0483, 0.0496,
+];
+
+/// The four wavelengths, in nanometers, carried by a sample in spectral mode
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Wavelengths {
+    pub lambda: [f32; 4],
+    /// The working color space RGB colors are upsampled from and converted back to
+    pub space: ColorSpace,
+}
+
+impl Wavelengths {
+    /// Sample the wavelengths, picking the hero wavelength with the sample `u` in [0, 1),
+    /// for rendering in the working color space `space`
+    pub fn sample(u: f32, space: ColorSpace) -> Wavelengths {
+        let range = MAX_WAVELENGTH - MIN_WAVELENGTH;
+        let mut lambda = [0.0; 4];
+        for (i, l) in lambda.iter_mut().enumerate() {
+            let offset = (u + i as f32 / 4.0).fract() * range;
+            *l = MIN_WAVELENGTH + offset;
+        }
+        Wavelengths { lambda, space }
+    }
+    /// Get the PDF of sampling each of the wavelengths
+    pub fn pdf() -> f32 {
+        1.0 / (MAX_WAVELENGTH - MIN_WAVELENGTH)
+    }
+    /// Get the hero wavelength, which the others were placed relative to
+    pub fn hero(&self) -> f32 {
+        self.lambda[0]
+    }
+    /// Get the weights to multiply a path's throughput by when something it hits
+    /// depends on the wavelength so much that only the hero wavelength can follow
+    /// the path, e.g. the direction light is refracted by dispersive glass. The
+    /// hero wavelength is uniformly distributed so it's an estimate of the whole
+    /// spectrum by itself
+    pub fn hero_only() -> Colorf {
+        Colorf::with_alpha(4.0, 0.0, 0.0, 0.0)
+    }
+    /// Upsample the RGB color to a spectrum and return its values at the wavelengths
+    pub fn upsample(&self, rgb: &Colorf) -> Colorf {
+        let rgb = self.space.convert(rgb, ColorSpace::LinearSrgb);
+        let mut c = Colorf::black();
+        for (i, l) in self.lambda.iter().enumerate() {
+            c[i] = upsample(&rgb, *l);
+        }
+        c
+    }
+    /// Convert the values of a spectrum at the wavelengths to an estimate of its
+    /// XYZ color, returned as the `r`, `g` and `b` channels of the color
+    pub fn to_xyz(&self, c: &Colorf) -> Colorf {
+        let mut xyz = Colorf::black();
+        for (i, l) in self.lambda.iter().enumerate() {
+            let (x, y, z) = cie_xyz(*l);
+            xyz.r += c[i] * x;
+            xyz.g += c[i] * y;
+            xyz.b += c[i] * z;
+        }
+        let scale = 1.0 / (4.0 * Wavelengths::pdf() * CIE_Y_INTEGRAL);
+        Colorf::new(xyz.r * scale, xyz.g * scale, xyz.b * scale)
+    }
+    /// Convert the values of a spectrum at the wavelengths to an estimate of its
+    /// linear RGB color in the working space
+    pub fn to_rgb(&self, c: &Colorf) -> Colorf {
+        ColorSpace::LinearSrgb.convert(&xyz_to_rgb(&self.to_xyz(c)), self.space)
+    }
+}
+
+/// A blackbody emitter's spectrum, normalized so its luminance is `strength`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Blackbody {
+    /// Temperature of the blackbody in Kelvin
+    pub temperature: f32,
+    pub strength: f32,
+    /// Scales Planck's law to the strength
+    scale: f32,
+}
+
+impl Blackbody {
+    pub fn new(temperature: f32, strength: f32) -> Blackbody {
+        let bb = Blackbody {
+            temperature,
+            strength,
+            scale: 1.0,
+        };
+        let luminance = bb.xyz().g;
+        Blackbody {
+            scale: strength / luminance,
+            ..bb
+        }
+    }
+    /// Get the emitted radiance at the wavelength `lambda` in nanometers
+    pub fn radiance(&self, lambda: f32) -> f32 {
+        self.scale * planck(lambda, self.temperature)
+    }
+    /// Get the emitted radiance at each of the wavelengths
+    pub fn sample(&self, w: &Wavelengths) -> Colorf {
+        let mut c = Colorf::black();
+        for (i, l) in w.lambda.iter().enumerate() {
+            c[i] = self.radiance(*l);
+        }
+        c
+    }
+    /// Get the linear RGB color of the emitted light in the color space `space`,
+    /// colors outside the sRGB gamut are clamped to it
+    pub fn to_rgb(&self, space: ColorSpace) -> Colorf {
+        let rgb = xyz_to_rgb(&self.xyz());
+        let clamped = Colorf::new(
+            f32::max(rgb.r, 0.0),
+            f32::max(rgb.g, 0.0),
+            f32::max(rgb.b, 0.0),
+        );
+        ColorSpace::LinearSrgb.convert(&clamped, space)
+    }
+    /// Integrate the spectrum against the color matching functions, returning
+    /// XYZ in the `r`, `g` and `b` channels
+    fn xyz(&self) -> Colorf {
+        integrate_xyz(|l| self.radiance(l))
+    }
+}
+
+/// Convert a spectrum sampled at the `(wavelength, value)` pairs, in order of
+/// wavelength, to linear RGB. The spectrum is linearly interpolated between the
+/// samples and clamped to the first and last beyond them
+pub fn sampled_to_rgb(samples: &[(f32, f32)]) -> Colorf {
+    if samples.is_empty() {
+        return Colorf::black();
+    }
+    let value = |l: f32| match samples.iter().position(|s| s.0 >= l) {
+        Some(0) => samples[0].1,
+        Some(i) => {
+            let (a, b) = (samples[i - 1], samples[i]);
+            let t = (l - a.0) / (b.0 - a.0);
+            a.1 * (1.0 - t) + b.1 * t
+        }
+        None => samples[samples.len() - 1].1,
+    };
+    xyz_to_rgb(&integrate_xyz(value))
+}
+
+/// Get the value of the fit of the CIE 1931 color matching functions at the
+/// wavelength `lambda` in nanometers
+pub fn cie_xyz(lambda: f32) -> (f32, f32, f32) {
+    // Piecewise Gaussian with a different width on either side of the mean
+    let g = |mu: f32, sigma_lo: f32, sigma_hi: f32| {
+        let sigma = if lambda < mu { sigma_lo } else { sigma_hi };
+        f32::exp(-0.5 * ((lambda - mu) / sigma).powi(2))
+    };
+    let x =
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2);
+    let y = 0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1);
+    let z = 1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8);
+    (x, y, z)
+}
+
+/// Convert the XYZ color, stored in the `r`, `g` and `b` channels, to linear sRGB
+pub fn xyz_to_rgb(xyz: &Colorf) -> Colorf {
+    let m = &XYZ_TO_RGB;
+    let row = |r: &[f32; 3]| r[0] * xyz.r + r[1] * xyz.g + r[2] * xyz.b;
+    Colorf::new(row(&m[0]), row(&m[1]), row(&m[2]))
+}
+
+/// Get the value at the wavelength `lambda` of the spectrum upsampled from the
+/// RGB color by Smits' method. The spectrum is the sum of white for the smallest
+/// channel, the complementary color of the largest for the middle one and the
+/// color of the largest for what's left. Negative channels are treated as 0
+fn upsample(rgb: &Colorf, lambda: f32) -> f32 {
+    let bin = ((lambda - 380.0) / 34.0).clamp(0.0, 9.0) as usize;
+    let (r, g, b) = (
+        f32::max(rgb.r, 0.0),
+        f32::max(rgb.g, 0.0),
+        f32::max(rgb.b, 0.0),
+    );
+    if r <= g && r <= b {
+        let s = r * SMITS_WHITE[bin];
+        if g <= b {
+            s + (g - r) * SMITS_CYAN[bin] + (b - g) * SMITS_BLUE[bin]
+        } else {
+            s + (b - r) * SMITS_CYAN[bin] + (g - b) * SMITS_GREEN[bin]
+        }
+    } else if g <= r && g <= b {
+        let s = g * SMITS_WHITE[bin];
+        if r <= b {
+            s + (r - g) * SMITS_MAGENTA[bin] + (b - r) * SMITS_BLUE[bin]
+        } else {
+            s + (b - g) * SMITS_MAGENTA[bin] + (r - b) * SMITS_RED[bin]
+        }
+    } else {
+        let s = b * SMITS_WHITE[bin];
+        if r <= g {
+            s + (r - b) * SMITS_YELLOW[bin] + (g - r) * SMITS_GREEN[bin]
+        } else {
+            s + (g - b) * SMITS_YELLOW[bin] + (r - g) * SMITS_RED[bin]
+        }
+    }
+}
+
+/// Planck's law for the radiance of a blackbody at `temperature` Kelvin, at the
+/// wavelength `lambda` in nanometers
+fn planck(lambda: f32, temperature: f32) -> f32 {
+    const C: f64 = 299_792_458.0;
+    const H: f64 = 6.626_070_15e-34;
+    const KB: f64 = 1.380_649e-23;
+    // Computed in doubles as the fifth power of the wavelength in meters underflows
+    let l = lambda as f64 * 1e-9;
+    let t = temperature as f64;
+    (2.0 * H * C * C / (l.powi(5) * (f64::exp(H * C / (l * KB * t)) - 1.0))) as f32
+}
+
+/// Integrate the spectrum against the color matching functions in 1nm steps,
+/// returning XYZ in the `r`, `g` and `b` channels
+fn integrate_xyz<F: Fn(f32) -> f32>(spectrum: F) -> Colorf {
+    let steps = (MAX_WAVELENGTH - MIN_WAVELENGTH) as usize;
+    let mut xyz = Colorf::black();
+    for i in 0..steps {
+        let l = MIN_WAVELENGTH + i as f32 + 0.5;
+        let s = spectrum(l);
+        let (x, y, z) = cie_xyz(l);
+        xyz.r += s * x;
+        xyz.g += s * y;
+        xyz.b += s * z;
+    }
+    Colorf::new(
+        xyz.r / CIE_Y_INTEGRAL,
+        xyz.g / CIE_Y_INTEGRAL,
+        xyz.b / CIE_Y_INTEGRAL,
+    )
+}
+
+/// Average the RGB estimates of the spectrum for hero wavelengths spread evenly
+/// over the range
+#[cfg(test)]
+fn average_rgb<F: Fn(&Wavelengths) -> Colorf>(space: ColorSpace, spectrum: F) -> Colorf {
+    let n = 256;
+    let mut sum = Colorf::black();
+    for i in 0..n {
+        let w = Wavelengths::sample((i as f32 + 0.5) / n as f32, space);
+        sum = sum + w.to_rgb(&spectrum(&w));
+    }
+    sum / n as f32
+}
+
+#[cfg(test)]
+fn assert_color_near(a: &Colorf, b: &Colorf, tolerance: f32) {
+    for i in 0..3 {
+        assert!(
+            f32::abs(a[i] - b[i]) < tolerance,
+            "{:?} isn't near {:?}",
+            a,
+            b
+        );
+    }
+}
+
+#[test]
+fn test_wavelengths() {
+    let w = Wavelengths::sample(0.9, ColorSpace::LinearSrgb);
+    assert_eq!(w.hero(), w.lambda[0]);
+    let range = MAX_WAVELENGTH - MIN_WAVELENGTH;
+    for l in &w.lambda {
+        assert!(*l >= MIN_WAVELENGTH && *l < MAX_WAVELENGTH);
+    }
+    // The wavelengths are evenly spaced around the range
+    let mut sorted = w.lambda;
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    for i in 1..4 {
+        assert!(f32::abs(sorted[i] - sorted[i - 1] - range / 4.0) < 1e-3);
+    }
+    // A flat spectrum of 1 is a luminance of 1, and white
+    for space in &ColorSpace::ALL {
+        let white = average_rgb(*space, |_| Colorf::broadcast(1.0));
+        assert_color_near(&white, &Colorf::broadcast(1.0), 0.01);
+    }
+}
+
+#[test]
+fn test_upsample() {
+    let colors = [
+        Colorf::new(1.0, 1.0, 1.0),
+        Colorf::new(0.5, 0.5, 0.5),
+        Colorf::new(0.8, 0.5, 0.2),
+        Colorf::new(0.2, 0.3, 0.8),
+        Colorf::new(0.3, 0.7, 0.4),
+        Colorf::new(4.0, 3.0, 2.0),
+    ];
+    for c in &colors {
+        let rgb = average_rgb(ColorSpace::LinearSrgb, |w| w.upsample(c));
+        assert_color_near(&rgb, c, 0.05 * f32::max(1.0, c.r));
+        // Colors in other working spaces come back in the same space
+        let acescg = ColorSpace::LinearSrgb.convert(c, ColorSpace::Acescg);
+        let rgb = average_rgb(ColorSpace::Acescg, |w| w.upsample(&acescg));
+        assert_color_near(&rgb, &acescg, 0.05 * f32::max(1.0, c.r));
+    }
+    // Saturated colors are outside what the spectra can reproduce exactly
+    for c in &[Colorf::new(1.0, 0.0, 0.0), Colorf::new(0.1, 0.9, 0.4)] {
+        let rgb = average_rgb(ColorSpace::LinearSrgb, |w| w.upsample(c));
+        assert_color_near(&rgb, c, 0.15);
+    }
+    // Gray spectra are flat and negative channels are ignored
+    let w = Wavelengths::sample(0.3, ColorSpace::LinearSrgb);
+    let gray = w.upsample(&Colorf::new(0.5, 0.5, 0.5));
+    assert_color_near(&gray, &Colorf::broadcast(0.5), 1e-3);
+    assert_eq!(w.upsample(&Colorf::new(-1.0, 0.0, 0.0)), Colorf::black());
+}
+
+#[test]
+fn test_blackbody() {
+    let candle = Blackbody::new(1900.0, 2.0);
+    let sky = Blackbody::new(10000.0, 2.0);
+    for bb in &[candle, sky] {
+        let xyz = bb.xyz();
+        assert!(f32::abs(xyz.g - 2.0) < 1e-3);
+        // Averaging the spectrum sampled at the wavelengths matches its color
+        let rgb = average_rgb(ColorSpace::LinearSrgb, |w| bb.sample(w));
+        assert_color_near(&rgb, &bb.to_rgb(ColorSpace::LinearSrgb), 0.02);
+        let rgb = average_rgb(ColorSpace::Rec2020, |w| bb.sample(w));
+        assert_color_near(&rgb, &bb.to_rgb(ColorSpace::Rec2020), 0.02);
+    }
+    let c = candle.to_rgb(ColorSpace::LinearSrgb);
+    assert!(c.r > c.g && c.g > c.b);
+    let s = sky.to_rgb(ColorSpace::LinearSrgb);
+    assert!(s.b > s.g && s.g > s.r);
+    // Near the equal energy white point the blackbody is close to white
+    let white = Blackbody::new(5455.0, 1.0).to_rgb(ColorSpace::LinearSrgb);
+    assert_color_near(&white, &Colorf::broadcast(1.0), 0.1);
+}
+
+#[test]
+fn test_sampled_to_rgb() {
+    let flat = sampled_to_rgb(&[(400.0, 0.5), (700.0, 0.5)]);
+    assert_color_near(&flat, &Colorf::broadcast(0.5), 0.01);
+    // A spectrum rising towards the long wavelengths is red
+    let red = sampled_to_rgb(&[(400.0, 0.0), (600.0, 0.1), (700.0, 1.0)]);
+    assert!(red.r > red.g && red.r > red.b);
+    assert_eq!(sampled_to_rgb(&[]), Colorf::black());
+}
@@ -0,0 +1,154 @@
+//! Provides the display transform applied to the linear image when it's saved as an
+//! 8-bit image. The color of each pixel is exposed, white balanced and then mapped
+//! into the displayable [0, 1] range by one of the tonemapping operators:
+//!
+//! - `clamp`: Clamps the color to [0, 1], anything brighter is clipped to white
+//! - `reinhard`: The Reinhard operator `x / (1 + x)`, applied per channel
+//! - `hable`: John Hable's filmic curve from Uncharted 2, with its white point at 11.2
+//! - `aces`: Krzysztof Narkowicz's fit of the ACES filmic curve
+//!
+//! HDR images are saved without the transform so they can be graded later.
+//!
+//! # Scene Usage Example
+//! The transform is set in the film, all of its properties are optional. The exposure
+//! is in stops, where each stop doubles the brightness, and the white balance is the
+//! color of the light which should appear white.
+//!
+//! ```json
+//! "film": {
+//!     ...
+//!     "tonemap": {
+//!         "operator": "aces",
+//!         "exposure": 1.5,
+//!         "white_balance": [1.0, 0.9, 0.75]
+//!     }
+//! }
+//! ```
+
+use crate::{film::Colorf, linalg};
+
+/// The tonemapping operators which can be used to map colors into [0, 1]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Clamp,
+    Reinhard,
+    Hable,
+    Aces,
+}
+
+impl Operator {
+    /// Get the name of the operator used in scene files
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Operator::Clamp => "clamp",
+            Operator::Reinhard => "reinhard",
+            Operator::Hable => "hable",
+            Operator::Aces => "aces",
+        }
+    }
+    /// Map the value of a color channel into [0, 1]
+    pub fn map(&self, x: f32) -> f32 {
+        let x = x.max(0.0);
+        let y = match *self {
+            Operator::Clamp => x,
+            Operator::Reinhard => x / (1.0 + x),
+            // Hable's curve is used with an exposure bias of 2 and scaled so its
+            // white point of 11.2 maps to 1
+            Operator::Hable => hable(2.0 * x) / hable(11.2),
+            // Narkowicz's fit expects the input pre-exposed by 0.6
+            Operator::Aces => {
+                let x = 0.6 * x;
+                (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)
+            }
+        };
+        linalg::clamp(y, 0.0, 1.0)
+    }
+}
+
+fn hable(x: f32) -> f32 {
+    let (a, b, c, d, e, f) = (0.15, 0.50, 0.10, 0.20, 0.02, 0.30);
+    (x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f) - e / f
+}
+
+/// The display transform applied to the image
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tonemap {
+    /// Exposure adjustment in stops
+    pub exposure: f32,
+    /// Color of the light which should appear white
+    pub white_balance: Colorf,
+    pub operator: Operator,
+}
+
+impl Tonemap {
+    pub fn new(operator: Operator, exposure: f32, white_balance: Colorf) -> Tonemap {
+        Tonemap {
+            exposure,
+            white_balance,
+            operator,
+        }
+    }
+    /// Apply the transform to the linear color, giving the linear color to display
+    /// with each channel in [0, 1]
+    pub fn apply(&self, c: &Colorf) -> Colorf {
+        let scale = 2f32.powf(self.exposure);
+        // Normalize the white balance so it doesn't change the brightness
+        let wb = self.white_balance / self.white_balance.luminance();
+        let mut out = Colorf::black();
+        for i in 0..3 {
+            out[i] = self.operator.map(c[i] * scale / wb[i]);
+        }
+        out.a = 1.0;
+        out
+    }
+}
+
+impl Default for Tonemap {
+    /// The transform which just clamps the color, as images were saved before
+    /// tonemapping was added
+    fn default() -> Tonemap {
+        Tonemap::new(Operator::Clamp, 0.0, Colorf::broadcast(1.0))
+    }
+}
+
+#[test]
+fn test_operators() {
+    for op in &[
+        Operator::Clamp,
+        Operator::Reinhard,
+        Operator::Hable,
+        Operator::Aces,
+    ] {
+        assert!(op.map(0.0) < 1e-6, "{}", op.name());
+        assert_eq!(op.map(-1.0), op.map(0.0), "{}", op.name());
+        assert!(op.map(1e6) <= 1.0, "{}", op.name());
+        // The curves increase monotonically
+        let mut last = 0.0;
+        for i in 1..100 {
+            let y = op.map(i as f32 * 0.1);
+            assert!(y >= last, "{} decreases at {}", op.name(), i);
+            last = y;
+        }
+    }
+    assert_eq!(Operator::Clamp.map(0.5), 0.5);
+    assert_eq!(Operator::Reinhard.map(1.0), 0.5);
+    assert!((Operator::Hable.map(5.6) - 1.0).abs() < 1e-5);
+    assert!((Operator::Aces.map(1.0) - 0.673).abs() < 1e-3);
+}
+
+#[test]
+fn test_tonemap() {
+    let c = Colorf::new(0.25, 0.5, 0.125);
+    assert_eq!(
+        Tonemap::default().apply(&Colorf::new(2.0, 0.5, -1.0)),
+        Colorf::new(1.0, 0.5, 0.0)
+    );
+    // One stop of exposure doubles the color
+    let exposed = Tonemap::new(Operator::Clamp, 1.0, Colorf::broadcast(1.0)).apply(&c);
+    assert_eq!(exposed, Colorf::new(0.5, 1.0, 0.25));
+    // The color of the white balance becomes gray
+    let wb = Colorf::new(1.0, 0.8, 0.6);
+    let balanced = Tonemap::new(Operator::Clamp, 0.0, wb).apply(&(wb * 0.5));
+    assert!((balanced.r - balanced.g).abs() < 1e-6 && (balanced.g - balanced.b).abs() < 1e-6);
+    assert!((balanced.r - 0.5 * wb.luminance()).abs() < 1e-6);
+}
@@ -0,0 +1,154 @@
+//! Provides the per-pixel statistics of the image samples, which are accumulated by
+//! the render target when the `variance` AOV is rendered. The running mean and
+//! variance of the samples in each pixel are computed with Welford's algorithm, which
+//! stays accurate as the number of samples grows.
+//!
+//! The `variance` layer holds the variance of the mean of each pixel's samples, the
+//! expected squared error of the pixel given the samples taken so far, so noisy
+//! parts of the image stand out. It's summarized as the relative RMS error of the
+//! image, `sqrt(mean(Var(L) / n)) / mean(L)` using the luminance `L` of the samples,
+//! the same measure as the noise estimated by progressive renders.
+//!
+//! The statistics use the samples which land in each pixel, without the filter. They're
+//! saved in checkpoints but like the other AOVs can't be rendered by distributed workers.
+//!
+//! # Scene Usage Example
+//! The statistics are enabled by listing the `variance` AOV in the film:
+//!
+//! ```json
+//! "film": {
+//!     ...
+//!     "aovs": ["variance"]
+//! }
+//! ```
+
+use crate::film::Colorf;
+
+/// The running statistics of the samples in a pixel. The alpha channel of the mean
+/// and squared differences tracks the luminance of the samples
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PixelStats {
+    /// Number of samples taken in the pixel
+    pub count: u32,
+    /// Mean of the samples, with the mean luminance in alpha
+    pub mean: Colorf,
+    /// Sum of the squared differences of the samples from the mean
+    m2: Colorf,
+}
+
+impl PixelStats {
+    /// Get the statistics of a pixel without any samples
+    pub fn new() -> PixelStats {
+        PixelStats {
+            count: 0,
+            mean: Colorf::broadcast(0.0),
+            m2: Colorf::broadcast(0.0),
+        }
+    }
+    /// Get the statistics from their sample count, mean and sum of squared
+    /// differences, as saved in checkpoints
+    pub fn from_parts(count: u32, mean: Colorf, m2: Colorf) -> PixelStats {
+        PixelStats { count, mean, m2 }
+    }
+    /// Get the sum of the squared differences of the samples from the mean
+    pub fn sum_sq_diff(&self) -> Colorf {
+        self.m2
+    }
+    /// Add the color of a sample to the statistics
+    pub fn add(&mut self, c: &Colorf) {
+        let mut x = *c;
+        x.a = c.luminance();
+        self.count += 1;
+        let delta = x - self.mean;
+        self.mean = self.mean + delta / self.count as f32;
+        let delta2 = x - self.mean;
+        for i in 0..4 {
+            self.m2[i] += delta[i] * delta2[i];
+        }
+    }
+    /// Get the sample variance of the samples, which is 0 until there are at least 2
+    pub fn variance(&self) -> Colorf {
+        if self.count < 2 {
+            Colorf::broadcast(0.0)
+        } else {
+            self.m2 / (self.count - 1) as f32
+        }
+    }
+    /// Get the variance of the mean of the samples, the expected squared error of
+    /// the pixel
+    pub fn variance_of_mean(&self) -> Colorf {
+        if self.count < 2 {
+            Colorf::broadcast(0.0)
+        } else {
+            self.variance() / self.count as f32
+        }
+    }
+}
+
+impl Default for PixelStats {
+    fn default() -> PixelStats {
+        PixelStats::new()
+    }
+}
+
+/// Estimate the relative RMS error of the image from the statistics of its pixels,
+/// ignoring pixels without samples
+pub fn estimate_error(stats: &[PixelStats]) -> f32 {
+    let mut sum = 0.0;
+    let mut sum_var = 0.0;
+    let mut n = 0.0;
+    for s in stats.iter().filter(|s| s.count > 0) {
+        sum += s.mean.a as f64;
+        sum_var += s.variance_of_mean().a as f64;
+        n += 1.0;
+    }
+    if n == 0.0 || sum <= 0.0 {
+        return 0.0;
+    }
+    ((sum_var / n).sqrt() / (sum / n)) as f32
+}
+
+#[test]
+fn test_pixel_stats() {
+    let samples = [
+        Colorf::new(1.0, 0.0, 2.0),
+        Colorf::new(3.0, 0.5, 2.0),
+        Colorf::new(2.0, 1.0, 2.0),
+        Colorf::new(6.0, 0.5, 2.0),
+    ];
+    let mut stats = PixelStats::new();
+    assert_eq!(stats.variance(), Colorf::broadcast(0.0));
+    stats.add(&samples[0]);
+    assert_eq!(stats.variance(), Colorf::broadcast(0.0));
+    for s in &samples[1..] {
+        stats.add(s);
+    }
+    assert_eq!(stats.count, 4);
+    // Compare against the two pass mean and unbiased variance
+    for i in 0..3 {
+        let mean = samples.iter().map(|s| s[i]).sum::<f32>() / 4.0;
+        let var = samples.iter().map(|s| (s[i] - mean).powi(2)).sum::<f32>() / 3.0;
+        assert!((stats.mean[i] - mean).abs() < 1e-6);
+        assert!((stats.variance()[i] - var).abs() < 1e-5);
+        assert!((stats.variance_of_mean()[i] - var / 4.0).abs() < 1e-5);
+    }
+    assert_eq!(stats.variance().b, 0.0);
+    let lum_mean = samples.iter().map(|s| s.luminance()).sum::<f32>() / 4.0;
+    assert!((stats.mean.a - lum_mean).abs() < 1e-6);
+}
+
+#[test]
+fn test_estimate_error() {
+    let mut flat = PixelStats::new();
+    let mut noisy = PixelStats::new();
+    for i in 0..8 {
+        flat.add(&Colorf::broadcast(1.0));
+        noisy.add(&Colorf::broadcast((i % 2) as f32 * 2.0));
+    }
+    assert_eq!(estimate_error(&[flat, flat]), 0.0);
+    // The noisy pixel has a mean of 1 and variance of 8 / 7
+    let expected = (8.0f32 / 7.0 / 8.0 / 2.0).sqrt();
+    let error = estimate_error(&[flat, noisy, PixelStats::new()]);
+    assert!((error - expected).abs() < 1e-5, "{} != {}", error, expected);
+    assert_eq!(estimate_error(&[]), 0.0);
+}
@@ -160,6 +160,10 @@ impl<T: Boundable> BVH<T> {
     pub fn iter(&self) -> Iter<T> {
         self.geometry.iter()
     }
+    /// Get the geometry stored in the BVH, in the order it was passed to the constructor
+    pub fn geometry(&self) -> &[T] {
+        &self.geometry[..]
+    }
     /// Construct the BVH tree using SAH splitting heuristic to determine split locations
     /// returns the root node of the subtree constructed over the slice of geom info passed
     /// and will increment `total_nodes` by the number of nodes in this subtree
@@ -40,6 +40,14 @@ impl Disk {
             inner_radius,
         }
     }
+    /// Get the outer radius of the disk
+    pub fn radius(&self) -> f32 {
+        self.radius
+    }
+    /// Get the radius of the hole in the center of the disk
+    pub fn inner_radius(&self) -> f32 {
+        self.inner_radius
+    }
 }
 
 impl Geometry for Disk {
@@ -56,9 +56,17 @@
 //!     ...
 //! ]
 //! ```
+//!
+//! ## Blackbody Emission
+//! Instead of a color the emission can be the temperature of a blackbody in Kelvin,
+//! along with the strength of the light, see `film::spectrum`.
+//!
+//! ```json
+//! "emission": { "temperature": 3000, "strength": 100 }
+//! ```
 
 use crate::{
-    film::{AnimatedColor, Colorf},
+    film::{AnimatedColor, Blackbody, ColorKeyframe, ColorSpace, Colorf, Wavelengths},
     geometry::{BBox, Boundable, DifferentialGeometry, Geometry, Sampleable, SampleableGeometry},
     light::{Light, OcclusionTester},
     linalg::{self, AnimatedTransform, Normal, Point, Ray, Vector},
@@ -81,6 +89,9 @@ pub struct Emitter {
     emitter: EmitterType,
     /// The light intensity emitted
     pub emission: AnimatedColor,
+    /// The blackbody spectrum emitted, if the light was given a temperature. The
+    /// emission is then its RGB color
+    blackbody: Option<Blackbody>,
     /// The transform to world space
     transform: AnimatedTransform,
     /// Tag to identify the instance
@@ -108,6 +119,7 @@ impl Emitter {
         Emitter {
             emitter: EmitterType::Area(geom, material),
             emission,
+            blackbody: None,
             transform,
             tag,
         }
@@ -118,6 +130,7 @@ impl Emitter {
         Emitter {
             emitter: EmitterType::Point,
             emission,
+            blackbody: None,
             transform,
             tag,
         }
@@ -146,14 +159,55 @@ impl Emitter {
         }
     }
     /// Return the radiance emitted by the light in the direction `w`
-    /// from point `p` on the light's surface with normal `n`
-    pub fn radiance(&self, w: &Vector, _: &Point, n: &Normal, time: f32) -> Colorf {
+    /// from point `p` on the light's surface with normal `n`. In spectral mode
+    /// the radiance is returned at the `wavelengths`
+    pub fn radiance(
+        &self,
+        w: &Vector,
+        _: &Point,
+        n: &Normal,
+        time: f32,
+        wavelengths: Option<Wavelengths>,
+    ) -> Colorf {
         if linalg::dot(w, n) > 0.0 {
-            self.emission.color(time)
+            self.emitted(time, wavelengths)
         } else {
             Colorf::black()
         }
     }
+    /// Get the light emitted at `time`, at the `wavelengths` in spectral mode
+    fn emitted(&self, time: f32, wavelengths: Option<Wavelengths>) -> Colorf {
+        match (wavelengths, self.blackbody) {
+            (Some(w), Some(b)) => b.sample(&w),
+            (Some(w), None) => w.upsample(&self.emission.color(time)),
+            (None, _) => self.emission.color(time),
+        }
+    }
+    /// Get the blackbody spectrum emitted, if the light was given a temperature
+    pub fn blackbody(&self) -> Option<&Blackbody> {
+        self.blackbody.as_ref()
+    }
+    /// Make the light emit the blackbody spectrum, replacing its emission with the
+    /// color of the spectrum in the working color space `space`
+    pub fn set_blackbody(&mut self, blackbody: Blackbody, space: ColorSpace) {
+        let color = ColorKeyframe::new(&blackbody.to_rgb(space), 0.0);
+        self.emission = AnimatedColor::with_keyframes(vec![color]);
+        self.blackbody = Some(blackbody);
+    }
+    /// Get the geometry emitting light, if this is an area light
+    pub fn geometry(&self) -> Option<&Arc<SampleableGeometry>> {
+        match self.emitter {
+            EmitterType::Point => None,
+            EmitterType::Area(ref geom, _) => Some(geom),
+        }
+    }
+    /// Get the material of the geometry emitting light, if this is an area light
+    pub fn material(&self) -> Option<&Arc<Materials>> {
+        match self.emitter {
+            EmitterType::Point => None,
+            EmitterType::Area(_, ref mat) => Some(mat),
+        }
+    }
     /// Get the transform to place the emitter into world space
     pub fn get_transform(&self) -> &AnimatedTransform {
         &self.transform
@@ -185,6 +239,7 @@ impl Light for Emitter {
         p: &Point,
         samples: &(f32, f32),
         time: f32,
+        wavelengths: Option<Wavelengths>,
     ) -> (Colorf, Vector, f32, OcclusionTester) {
         match self.emitter {
             EmitterType::Point => {
@@ -192,7 +247,7 @@ impl Light for Emitter {
                 let pos = transform * Point::broadcast(0.0);
                 let w_i = (pos - *p).normalized();
                 (
-                    self.emission.color(time) / pos.distance_sqr(p),
+                    self.emitted(time, wavelengths) / pos.distance_sqr(p),
                     w_i,
                     1.0,
                     OcclusionTester::test_points(p, &pos, time),
@@ -204,7 +259,7 @@ impl Light for Emitter {
                 let (p_sampled, normal) = g.sample(&p_l, samples);
                 let w_il = (p_sampled - p_l).normalized();
                 let pdf = g.pdf(&p_l, &w_il);
-                let radiance = self.radiance(&-w_il, &p_sampled, &normal, time);
+                let radiance = self.radiance(&-w_il, &p_sampled, &normal, time, wavelengths);
                 let p_w = transform * p_sampled;
                 (
                     radiance,
@@ -143,6 +143,11 @@ impl Instance {
             Instance::Receiver(ref mut r) => r.set_transform(transform),
         }
     }
+
+    /// Check if this instance is a light
+    pub fn is_emitter(&self) -> bool {
+        matches!(*self, Instance::Emitter(_))
+    }
 }
 
 impl Boundable for Instance {
@@ -3,6 +3,7 @@
 //! that was intersected
 
 use crate::{
+    film::{Colorf, Wavelengths},
     geometry::{DifferentialGeometry, Instance},
     material::Materials,
 };
@@ -18,6 +19,8 @@ pub struct Intersection<'a, 'b> {
     pub instance: &'b Instance,
     /// The material of the instance that was hit
     pub material: &'b Materials,
+    /// The wavelengths carried by the ray which hit the instance, in spectral mode
+    pub wavelengths: Option<Wavelengths>,
 }
 
 impl<'a, 'b> Intersection<'a, 'b> {
@@ -33,6 +36,15 @@ impl<'a, 'b> Intersection<'a, 'b> {
             dg,
             instance,
             material,
+            wavelengths: None,
+        }
+    }
+    /// Convert the RGB color of the material at the hit to its values at the
+    /// wavelengths being rendered, if rendering in spectral mode
+    pub fn upsample(&self, rgb: &Colorf) -> Colorf {
+        match self.wavelengths {
+            Some(w) => w.upsample(rgb),
+            None => *rgb,
         }
     }
 }
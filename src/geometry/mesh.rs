@@ -19,15 +19,29 @@
 extern crate tobj;
 
 use crate::{
-    geometry::{BBox, Boundable, BoundableGeometry, DifferentialGeometry, Geometry, BVH},
+    geometry::{
+        BBox, Boundable, BoundableGeometry, DifferentialGeometry, Geometry, Sampleable, BVH,
+    },
     linalg::{self, Normal, Point, Ray, Vector},
+    mc,
+};
+use std::{
+    collections::HashMap,
+    f32,
+    path::{Path, PathBuf},
+    sync::Arc,
 };
-use std::{collections::HashMap, path::Path, sync::Arc};
 
 /// A mesh composed of triangles, specified by directly passing the position,
 /// normal and index buffers for the triangles making up the mesh
 pub struct Mesh {
     pub bvh: BVH<Triangle>,
+    /// CDF of the triangle areas, used to pick triangles when sampling the mesh
+    area_cdf: Vec<f32>,
+    /// Total surface area of the triangles in the mesh
+    surface_area: f32,
+    /// The OBJ file and name of the model the mesh was loaded from, if it was
+    pub obj: Option<(PathBuf, String)>,
 }
 
 impl Mesh {
@@ -53,64 +67,68 @@ impl Mesh {
                 )
             })
             .collect();
+        let bvh = BVH::unanimated(16, triangles);
+        let mut surface_area = 0.0;
+        let mut area_cdf: Vec<f32> = bvh
+            .geometry()
+            .iter()
+            .map(|t| {
+                surface_area += t.surface_area();
+                surface_area
+            })
+            .collect();
+        for a in &mut area_cdf {
+            *a /= surface_area;
+        }
         Mesh {
-            bvh: BVH::unanimated(16, triangles),
+            bvh,
+            area_cdf,
+            surface_area,
+            obj: None,
         }
     }
     /// Load all the meshes defined in an OBJ file and return them in a hashmap that maps the
-    /// model's name in the file to its loaded mesh. TODO: Don't build the BVH until we actually
-    /// use the mesh in the scene, will reduce scene load time.
+    /// model's name in the file to its loaded mesh. Returns an error if the file can't be
+    /// read or parsed. TODO: Don't build the BVH until we actually use the mesh in the scene,
+    /// will reduce scene load time.
     /// TODO: Currently materials are ignored
-    pub fn load_obj(file_name: &Path) -> HashMap<String, Arc<BoundableGeometry>> {
-        match tobj::load_obj(file_name) {
-            Ok((models, _)) => {
-                let mut meshes = HashMap::new();
-                for m in models {
-                    println!("Loading model {}", m.name);
-                    let mesh = m.mesh;
-                    if mesh.normals.is_empty() || mesh.texcoords.is_empty() {
-                        print!(
-                            "Mesh::load_obj error! Normals and texture coordinates are required!"
-                        );
-                        println!("Skipping {}", m.name);
-                        continue;
-                    }
-                    println!("{} has {} triangles", m.name, mesh.indices.len() / 3);
-                    let positions = Arc::new(
-                        mesh.positions
-                            .chunks(3)
-                            .map(|i| Point::new(i[0], i[1], i[2]))
-                            .collect(),
-                    );
-                    let normals = Arc::new(
-                        mesh.normals
-                            .chunks(3)
-                            .map(|i| Normal::new(i[0], i[1], i[2]))
-                            .collect(),
-                    );
-                    let texcoords = Arc::new(
-                        mesh.texcoords
-                            .chunks(2)
-                            .map(|i| Point::new(i[0], i[1], 0.0))
-                            .collect(),
-                    );
-                    meshes.insert(
-                        m.name,
-                        Arc::new(BoundableGeometry::Mesh(Mesh::new(
-                            positions,
-                            normals,
-                            texcoords,
-                            mesh.indices,
-                        ))),
-                    );
-                }
-                meshes
-            }
-            Err(e) => {
-                println!("Failed to load {:?} due to {:?}", file_name, e);
-                HashMap::new()
+    pub fn load_obj(
+        file_name: &Path,
+    ) -> Result<HashMap<String, Arc<BoundableGeometry>>, tobj::LoadError> {
+        let (models, _) = tobj::load_obj(file_name)?;
+        let mut meshes = HashMap::new();
+        for m in models {
+            println!("Loading model {}", m.name);
+            let mesh = m.mesh;
+            if mesh.normals.is_empty() || mesh.texcoords.is_empty() {
+                print!("Mesh::load_obj error! Normals and texture coordinates are required!");
+                println!("Skipping {}", m.name);
+                continue;
             }
+            println!("{} has {} triangles", m.name, mesh.indices.len() / 3);
+            let positions = Arc::new(
+                mesh.positions
+                    .chunks(3)
+                    .map(|i| Point::new(i[0], i[1], i[2]))
+                    .collect(),
+            );
+            let normals = Arc::new(
+                mesh.normals
+                    .chunks(3)
+                    .map(|i| Normal::new(i[0], i[1], i[2]))
+                    .collect(),
+            );
+            let texcoords = Arc::new(
+                mesh.texcoords
+                    .chunks(2)
+                    .map(|i| Point::new(i[0], i[1], 0.0))
+                    .collect(),
+            );
+            let mut loaded = Mesh::new(positions, normals, texcoords, mesh.indices);
+            loaded.obj = Some((file_name.to_path_buf(), m.name.clone()));
+            meshes.insert(m.name, Arc::new(BoundableGeometry::Mesh(loaded)));
         }
+        Ok(meshes)
     }
 }
 
@@ -126,6 +144,47 @@ impl Boundable for Mesh {
     }
 }
 
+impl Sampleable for Mesh {
+    /// Pick a triangle with probability proportional to its area then uniformly
+    /// sample a point on it, the first sample is re-used for sampling the triangle
+    fn sample_uniform(&self, samples: &(f32, f32)) -> (Point, Normal) {
+        let i = match self
+            .area_cdf
+            .binary_search_by(|a| a.partial_cmp(&samples.0).unwrap())
+        {
+            Ok(i) => i,
+            Err(i) => usize::min(i, self.area_cdf.len() - 1),
+        };
+        let start = if i == 0 { 0.0 } else { self.area_cdf[i - 1] };
+        let u = f32::min((samples.0 - start) / (self.area_cdf[i] - start), 1.0);
+        self.bvh.geometry()[i].sample_uniform(&(u, samples.1))
+    }
+    fn sample(&self, _: &Point, samples: &(f32, f32)) -> (Point, Normal) {
+        self.sample_uniform(samples)
+    }
+    fn surface_area(&self) -> f32 {
+        self.surface_area
+    }
+    /// Compute the PDF that the ray from `p` with direction `w_i` intersects
+    /// the mesh, computed the same as for the disk but with the mesh's total area
+    fn pdf(&self, p: &Point, w_i: &Vector) -> f32 {
+        let mut ray = Ray::segment(p, w_i, 0.001, f32::INFINITY, 0.0);
+        match self.intersect(&mut ray) {
+            Some(d) => {
+                let w = -*w_i;
+                let pdf = p.distance_sqr(&ray.at(ray.max_t))
+                    / (f32::abs(linalg::dot(&d.n, &w)) * self.surface_area);
+                if f32::is_finite(pdf) {
+                    pdf
+                } else {
+                    0.0
+                }
+            }
+            None => 0.0,
+        }
+    }
+}
+
 /// A triangle in some mesh. Just stores a reference to the mesh
 /// and the indices of each vertex
 pub struct Triangle {
@@ -158,6 +217,28 @@ impl Triangle {
     }
 }
 
+impl Triangle {
+    /// Compute the surface area of the triangle
+    pub fn surface_area(&self) -> f32 {
+        let pa = self.positions[self.a];
+        0.5 * linalg::cross(
+            &(self.positions[self.b] - pa),
+            &(self.positions[self.c] - pa),
+        )
+        .length()
+    }
+    /// Uniformly sample a position on the triangle, returning it along with the
+    /// interpolated normal at that point
+    fn sample_uniform(&self, samples: &(f32, f32)) -> (Point, Normal) {
+        let (b0, b1) = mc::uniform_sample_triangle(samples);
+        let b2 = 1.0 - b0 - b1;
+        let p =
+            b0 * self.positions[self.a] + b1 * self.positions[self.b] + b2 * self.positions[self.c];
+        let n = b0 * self.normals[self.a] + b1 * self.normals[self.b] + b2 * self.normals[self.c];
+        (p, n.normalized())
+    }
+}
+
 impl Geometry for Triangle {
     fn intersect(&self, ray: &mut Ray) -> Option<DifferentialGeometry> {
         let pa = &self.positions[self.a];
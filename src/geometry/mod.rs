@@ -105,6 +105,7 @@ impl<T: ?Sized> SampleableGeom for T where T: Geometry + Boundable + Sampleable
 #[enum_dispatch]
 pub enum SampleableGeometry {
     Disk,
+    Mesh,
     Rectangle,
     Sphere,
 }
@@ -52,6 +52,10 @@ impl Receiver {
         dg.dp_dv = transform * dg.dp_dv;
         Some((dg, &*self.material))
     }
+    /// Get the geometry being instanced
+    pub fn geometry(&self) -> &Arc<BoundableGeometry> {
+        &self.geom
+    }
     /// Get the transform to place the receiver into world space
     pub fn get_transform(&self) -> &AnimatedTransform {
         &self.transform
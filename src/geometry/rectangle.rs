@@ -34,6 +34,14 @@ impl Rectangle {
     pub fn new(width: f32, height: f32) -> Self {
         Self { width, height }
     }
+    /// Get the width of the rectangle along the x axis
+    pub fn width(&self) -> f32 {
+        self.width
+    }
+    /// Get the height of the rectangle along the y axis
+    pub fn height(&self) -> f32 {
+        self.height
+    }
 }
 
 impl Geometry for Rectangle {
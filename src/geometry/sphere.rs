@@ -29,6 +29,10 @@ impl Sphere {
     pub fn new(radius: f32) -> Self {
         Self { radius }
     }
+    /// Get the radius of the sphere
+    pub fn radius(&self) -> f32 {
+        self.radius
+    }
 }
 
 impl Geometry for Sphere {
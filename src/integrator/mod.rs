@@ -21,7 +21,7 @@ use std::{cmp, f32};
 
 use crate::{
     bxdf::{BxDFType, BSDF},
-    film::Colorf,
+    film::{Colorf, LightSplit, Wavelengths},
     geometry::{Emitter, Instance, Intersection},
     light::Light,
     linalg::{self, Point, Ray, Vector},
@@ -53,6 +53,28 @@ pub trait Integrator {
         alloc: &Allocator,
     ) -> Colorf;
 
+    /// Compute the illumination at the intersection along with the light split by the
+    /// path it took, for the light AOVs. Integrators which don't track the paths count
+    /// all the light as direct diffuse light
+    fn illumination_split(
+        &self,
+        scene: &Scene,
+        light_list: &[&Emitter],
+        ray: &Ray,
+        hit: &Intersection,
+        sampler: &mut Samplers,
+        rng: &mut StdRng,
+        alloc: &Allocator,
+    ) -> (Colorf, LightSplit) {
+        let illum = self.illumination(scene, light_list, ray, hit, sampler, rng, alloc);
+        let split = LightSplit {
+            direct: illum,
+            diffuse: illum,
+            ..LightSplit::black()
+        };
+        (illum, split)
+    }
+
     /// Compute the color of specularly reflecting light off the intersection
     fn specular_reflection(
         &self,
@@ -138,6 +160,7 @@ pub trait Integrator {
         light_sample: &Sample,
         bsdf_sample: &Sample,
         time: f32,
+        wavelengths: Option<Wavelengths>,
     ) -> Colorf {
         let l = cmp::min(
             (light_sample.one_d * light_list.len() as f32) as usize,
@@ -153,9 +176,50 @@ pub trait Integrator {
             light_list[l],
             BxDFType::non_specular(),
             time,
+            wavelengths,
         )
     }
 
+    /// Sample the contribution of a light chosen like `sample_one_light`, but estimating
+    /// the light reflected by the diffuse and glossy BxDFs separately. Returns the
+    /// diffuse and glossy contributions, which sum to an estimate of the total
+    fn sample_one_light_split(
+        &self,
+        scene: &Scene,
+        light_list: &[&Emitter],
+        w_o: &Vector,
+        p: &Point,
+        bsdf: &BSDF,
+        light_sample: &Sample,
+        bsdf_sample: &Sample,
+        time: f32,
+        wavelengths: Option<Wavelengths>,
+    ) -> (Colorf, Colorf) {
+        let l = cmp::min(
+            (light_sample.one_d * light_list.len() as f32) as usize,
+            light_list.len() - 1,
+        );
+        let estimate = |lobe| {
+            let mut flags = EnumSet::new();
+            flags.insert(lobe);
+            flags.insert(BxDFType::Reflection);
+            flags.insert(BxDFType::Transmission);
+            self.estimate_direct(
+                scene,
+                w_o,
+                p,
+                bsdf,
+                light_sample,
+                bsdf_sample,
+                light_list[l],
+                flags,
+                time,
+                wavelengths,
+            )
+        };
+        (estimate(BxDFType::Diffuse), estimate(BxDFType::Glossy))
+    }
+
     /// Estimate the direct light contribution to the surface being shaded by the light
     /// using multiple importance sampling
     ///
@@ -166,6 +230,7 @@ pub trait Integrator {
     /// - `bsdf_sample` 3 random samples for the bsdf
     /// - `light` light to sample contribution from
     /// - `flags` flags for which BxDF types to sample
+    /// - `wavelengths` wavelengths to sample the light at in spectral mode
     fn estimate_direct(
         &self,
         scene: &Scene,
@@ -177,11 +242,12 @@ pub trait Integrator {
         light: &dyn Light,
         flags: EnumSet<BxDFType>,
         time: f32,
+        wavelengths: Option<Wavelengths>,
     ) -> Colorf {
         let mut direct_light = Colorf::black();
         // Sample the light first
         let (li, w_i, pdf_light, occlusion) =
-            light.sample_incident(&bsdf.p, &light_sample.two_d, time);
+            light.sample_incident(&bsdf.p, &light_sample.two_d, time, wavelengths);
         if pdf_light > 0.0 && !li.is_black() && !occlusion.occluded(scene) {
             let f = bsdf.eval(w_o, &w_i, flags);
             if !f.is_black() {
@@ -216,7 +282,7 @@ pub trait Integrator {
                         // FIXME
                         #[allow(clippy::vtable_address_comparisons)]
                         if std::ptr::eq(e as *const dyn Light, light as *const dyn Light) {
-                            li = e.radiance(&-w_i, &h.dg.p, &h.dg.ng, time)
+                            li = e.radiance(&-w_i, &h.dg.p, &h.dg.ng, time, wavelengths)
                         }
                     }
                 }
@@ -38,6 +38,8 @@ impl Integrator for NormalsDebug {
         alloc: &Allocator,
     ) -> Colorf {
         let bsdf = hit.material.bsdf(hit, alloc);
-        (Colorf::new(bsdf.n.x, bsdf.n.y, bsdf.n.z) + Colorf::broadcast(1.0)) / 2.0
+        let n = (Colorf::new(bsdf.n.x, bsdf.n.y, bsdf.n.z) + Colorf::broadcast(1.0)) / 2.0;
+        // Upsampled in spectral mode, so the film converts back to about the same color
+        hit.upsample(&n)
     }
 }
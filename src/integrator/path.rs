@@ -14,10 +14,14 @@
 //!     "max_depth": 8
 //! }
 //! ```
+//!
+//! The path tracer also splits the light it finds by the path it took, for the
+//! light AOVs (see `film::aov`). The direct light and light reflected by diffuse or
+//! glossy BxDFs at the first surface are estimated separately in that case.
 
 use crate::{
     bxdf::BxDFType,
-    film::Colorf,
+    film::{Colorf, LightSplit},
     geometry::{Emitter, Instance, Intersection},
     integrator::{Integrator, Integrators},
     linalg::{self, Ray},
@@ -44,10 +48,17 @@ impl Path {
             max_depth: max_depth as usize,
         })
     }
-}
-
-impl Integrator for Path {
-    fn illumination(
+    /// Get the minimum length of paths before Russian roulette termination is used
+    pub fn min_depth(&self) -> usize {
+        self.min_depth
+    }
+    /// Get the maximum length of paths
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+    /// Trace a path from the intersection, returning the illumination found along it
+    /// and writing the light split by path to `split` if it's passed
+    fn trace(
         &self,
         scene: &Scene,
         light_list: &[&Emitter],
@@ -56,6 +67,7 @@ impl Integrator for Path {
         sampler: &mut Samplers,
         rng: &mut StdRng,
         alloc: &Allocator,
+        split: Option<&mut LightSplit>,
     ) -> Colorf {
         let num_samples = self.max_depth as usize + 1;
         let l_samples = alloc.alloc_slice::<(f32, f32)>(num_samples);
@@ -72,6 +84,10 @@ impl Integrator for Path {
         sampler.get_samples_1d(path_samples_comp, rng);
 
         let mut illum = Colorf::black();
+        // The direct light and its diffuse and glossy parts, and if the first bounce
+        // was diffuse, when splitting the light by path
+        let mut direct = (Colorf::black(), Colorf::black(), Colorf::black());
+        let mut first_diffuse = true;
         let mut path_throughput = Colorf::broadcast(1.0);
         // Track if the previous bounce was a specular one
         let mut specular_bounce = false;
@@ -82,25 +98,47 @@ impl Integrator for Path {
             if bounce == 0 || specular_bounce {
                 if let Instance::Emitter(ref e) = *current_hit.instance {
                     let w = -ray.d;
-                    illum =
-                        illum + path_throughput * e.radiance(&w, &hit.dg.p, &hit.dg.ng, ray.time);
+                    illum = illum
+                        + path_throughput
+                            * e.radiance(&w, &hit.dg.p, &hit.dg.ng, ray.time, ray.wavelengths);
                 }
             }
             let bsdf = current_hit.material.bsdf(&current_hit, alloc);
             let w_o = -ray.d;
             let light_sample = Sample::new(&l_samples[bounce], l_samples_comp[bounce]);
             let bsdf_sample = Sample::new(&bsdf_samples[bounce], bsdf_samples_comp[bounce]);
-            let li = self.sample_one_light(
-                scene,
-                light_list,
-                &w_o,
-                &current_hit.dg.p,
-                &bsdf,
-                &light_sample,
-                &bsdf_sample,
-                ray.time,
-            );
+            let li = if bounce == 0 && split.is_some() {
+                let (diffuse, glossy) = self.sample_one_light_split(
+                    scene,
+                    light_list,
+                    &w_o,
+                    &current_hit.dg.p,
+                    &bsdf,
+                    &light_sample,
+                    &bsdf_sample,
+                    ray.time,
+                    ray.wavelengths,
+                );
+                direct.1 = diffuse;
+                direct.2 = glossy;
+                diffuse + glossy
+            } else {
+                self.sample_one_light(
+                    scene,
+                    light_list,
+                    &w_o,
+                    &current_hit.dg.p,
+                    &bsdf,
+                    &light_sample,
+                    &bsdf_sample,
+                    ray.time,
+                    ray.wavelengths,
+                )
+            };
             illum = illum + path_throughput * li;
+            if bounce == 0 {
+                direct.0 = illum;
+            }
 
             // Determine the next direction to take the path by sampling the BSDF
             let path_sample = Sample::new(&path_samples[bounce], path_samples_comp[bounce]);
@@ -109,6 +147,9 @@ impl Integrator for Path {
                 break;
             }
             specular_bounce = sampled_type.contains(&BxDFType::Specular);
+            if bounce == 0 {
+                first_diffuse = sampled_type.contains(&BxDFType::Diffuse);
+            }
             path_throughput = path_throughput * f * f32::abs(linalg::dot(&w_i, &bsdf.n)) / pdf;
 
             // Check if we're beyond the min depth at which point we start trying to
@@ -136,6 +177,58 @@ impl Integrator for Path {
             }
             bounce += 1;
         }
+        if let Some(split) = split {
+            let indirect = illum - direct.0;
+            *split = LightSplit {
+                direct: direct.0,
+                indirect,
+                diffuse: direct.1,
+                specular: direct.2,
+            };
+            if first_diffuse {
+                split.diffuse = split.diffuse + indirect;
+            } else {
+                split.specular = split.specular + indirect;
+            }
+        }
         illum
     }
 }
+
+impl Integrator for Path {
+    fn illumination(
+        &self,
+        scene: &Scene,
+        light_list: &[&Emitter],
+        r: &Ray,
+        hit: &Intersection,
+        sampler: &mut Samplers,
+        rng: &mut StdRng,
+        alloc: &Allocator,
+    ) -> Colorf {
+        self.trace(scene, light_list, r, hit, sampler, rng, alloc, None)
+    }
+    fn illumination_split(
+        &self,
+        scene: &Scene,
+        light_list: &[&Emitter],
+        r: &Ray,
+        hit: &Intersection,
+        sampler: &mut Samplers,
+        rng: &mut StdRng,
+        alloc: &Allocator,
+    ) -> (Colorf, LightSplit) {
+        let mut split = LightSplit::black();
+        let illum = self.trace(
+            scene,
+            light_list,
+            r,
+            hit,
+            sampler,
+            rng,
+            alloc,
+            Some(&mut split),
+        );
+        (illum, split)
+    }
+}
@@ -39,6 +39,10 @@ impl Whitted {
     pub fn new_integrator(max_depth: u32) -> Integrators {
         Integrators::Whitted(Self { max_depth })
     }
+    /// Get the maximum recursion depth for rays
+    pub fn max_depth(&self) -> u32 {
+        self.max_depth
+    }
 }
 
 impl Integrator for Whitted {
@@ -60,13 +64,13 @@ impl Integrator for Whitted {
         if ray.depth == 0 {
             if let Instance::Emitter(ref e) = *hit.instance {
                 let w = -ray.d;
-                illum = illum + e.radiance(&w, &hit.dg.p, &hit.dg.ng, ray.time);
+                illum = illum + e.radiance(&w, &hit.dg.p, &hit.dg.ng, ray.time, ray.wavelengths);
             }
         }
 
         for light in light_list {
             let (li, w_i, pdf, occlusion) =
-                light.sample_incident(&hit.dg.p, &sample_2d[0], ray.time);
+                light.sample_incident(&hit.dg.p, &sample_2d[0], ray.time, ray.wavelengths);
             let f = bsdf.eval(&w_o, &w_i, BxDFType::all());
             if !li.is_black() && !f.is_black() && !occlusion.occluded(scene) {
                 illum = illum + f * li * f32::abs(linalg::dot(&w_i, &bsdf.n)) / pdf;
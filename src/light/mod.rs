@@ -5,7 +5,7 @@
 use std::f32;
 
 use crate::{
-    film::Colorf,
+    film::{Colorf, Wavelengths},
     linalg::{Point, Ray, Vector},
     scene::Scene,
 };
@@ -45,12 +45,14 @@ impl OcclusionTester {
 pub trait Light {
     /// Sample the illumination from the light arriving at the point `p`
     /// Returns the color, incident light direction, pdf and occlusion tester object
-    /// `samples` will be used to randomly sample the light.
+    /// `samples` will be used to randomly sample the light. In spectral mode the
+    /// color is returned at the `wavelengths`
     fn sample_incident(
         &self,
         p: &Point,
         samples: &(f32, f32),
         time: f32,
+        wavelengths: Option<Wavelengths>,
     ) -> (Colorf, Vector, f32, OcclusionTester);
     /// Determine if the light is described by a delta distribution
     fn delta_light(&self) -> bool;
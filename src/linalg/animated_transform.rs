@@ -43,6 +43,11 @@ impl AnimatedTransform {
             keyframes: vec![BSpline::new(0, vec![key], vec![0.0, 1.0])],
         }
     }
+    /// Get the animation splines making up the transform, in hierarchical order from the
+    /// object's own transform to its outermost parent's
+    pub fn keyframes(&self) -> &[BSpline<Keyframe>] {
+        &self.keyframes
+    }
     /// Compute the transformation matrix for the animation at some time point using B-Spline
     /// interpolation.
     pub fn transform(&self, time: f32) -> Transform {
@@ -183,6 +183,31 @@ impl Matrix4 {
         inv
     }
 
+    /// Compute the determinant of this matrix, a matrix with a zero determinant
+    /// is singular and can't be inverted
+    pub fn determinant(&self) -> f32 {
+        let m = &self.mat;
+        // Expand along the first two rows using the 2x2 minors of the first two
+        // and last two rows
+        let s = [
+            m[0] * m[5] - m[4] * m[1],
+            m[0] * m[6] - m[4] * m[2],
+            m[0] * m[7] - m[4] * m[3],
+            m[1] * m[6] - m[5] * m[2],
+            m[1] * m[7] - m[5] * m[3],
+            m[2] * m[7] - m[6] * m[3],
+        ];
+        let c = [
+            m[8] * m[13] - m[12] * m[9],
+            m[8] * m[14] - m[12] * m[10],
+            m[8] * m[15] - m[12] * m[11],
+            m[9] * m[14] - m[13] * m[10],
+            m[9] * m[15] - m[13] * m[11],
+            m[10] * m[15] - m[14] * m[11],
+        ];
+        s[0] * c[5] - s[1] * c[4] + s[2] * c[3] + s[3] * c[2] - s[4] * c[1] + s[5] * c[0]
+    }
+
     /// Return an iterator over the matrix's elements. The iterator goes
     /// row by row through the matrix.
     pub fn iter(&self) -> Iter<f32> {
@@ -325,3 +350,19 @@ fn test_mul() {
     ]);
     assert!(a * b == c);
 }
+#[test]
+fn test_determinant() {
+    assert_eq!(Matrix4::identity().determinant(), 1f32);
+    assert_eq!(Matrix4::zero().determinant(), 0f32);
+    let a = Matrix4::new([
+        1f32, 2f32, 1f32, 0f32, 3f32, 1f32, 4f32, 2f32, 1f32, 2f32, -5f32, 4f32, 3f32, 2f32, 4f32,
+        1f32,
+    ]);
+    let b = Matrix4::new([
+        8f32, 0f32, 2f32, 3f32, -2f32, 1f32, 0f32, 1f32, 5f32, -2f32, 3f32, 1f32, 0f32, 0f32, 4f32,
+        1f32,
+    ]);
+    assert_eq!(a.determinant(), -14f32);
+    assert_eq!(b.determinant(), -62f32);
+    assert_eq!((a * b).determinant(), 868f32);
+}
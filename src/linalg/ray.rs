@@ -1,6 +1,9 @@
 use std::f32;
 
-use crate::linalg::{Point, Vector};
+use crate::{
+    film::Wavelengths,
+    linalg::{Point, Vector},
+};
 
 /// Ray is a standard 3D ray, starting at origin `o` and heading in direction `d`
 /// The min and max points along the ray can be specified with `min_t` and `max_t`
@@ -19,6 +22,8 @@ pub struct Ray {
     pub depth: u32,
     /// Time point sampled by this ray
     pub time: f32,
+    /// Wavelengths carried by the ray when rendering in spectral mode
+    pub wavelengths: Option<Wavelengths>,
 }
 
 impl Ray {
@@ -31,6 +36,7 @@ impl Ray {
             max_t: f32::INFINITY,
             depth: 0,
             time,
+            wavelengths: None,
         }
     }
 
@@ -43,6 +49,7 @@ impl Ray {
             max_t,
             depth: 0,
             time,
+            wavelengths: None,
         }
     }
 
@@ -56,6 +63,7 @@ impl Ray {
             max_t: f32::INFINITY,
             depth: self.depth + 1,
             time: self.time,
+            wavelengths: self.wavelengths,
         }
     }
 
@@ -69,6 +77,7 @@ impl Ray {
             max_t,
             depth: self.depth + 1,
             time: self.time,
+            wavelengths: self.wavelengths,
         }
     }
 
@@ -1,49 +1,808 @@
 use aperture::{
-    exec::{Config, Exec, MultiThreaded},
-    scene::Scene,
+    exec::{
+        distrib::{self, Master, Worker},
+        Budget, Checkpoint, Config, Exec, MultiThreaded, Observer, Pass, PrintProgress, Progress,
+    },
+    film::{
+        denoise::Features,
+        hdr::{self, ExrLayer, ExrPixel, HdrFormat},
+        Aov, ColorSpace, Colorf, CropWindow, Denoiser, Image, ImageSample, RenderTarget, Tonemap,
+    },
+    sampler::Region,
+    scene::{lint, schema, Scene},
 };
-use std::{path::PathBuf, time::SystemTime};
+use docopt::Docopt;
+use serde_derive::Deserialize;
+use std::{
+    path::{Path, PathBuf},
+    process,
+    time::{Duration, SystemTime},
+};
+
+const USAGE: &str = "
+Usage: aperture <scenefile> [options]
+       aperture --worker [options]
+       aperture --denoise <image> [-o <path>]
+       aperture --schema
+       aperture (-h | --help)
+
+Scenes can be JSON scene files or pbrt-v3 scene files, which are detected by
+the .pbrt extension.
+
+Options:
+  -o <path>               Specify the output file or directory to save the image or frames.
+                          A run of '#' in the file name is replaced with the zero padded frame
+                          number, e.g. 'out/frame###.png'. If a directory is passed the frames
+                          are saved as 'frame#####.png' in it. Default is the current directory.
+                          A file name without a '#' can only be used to render a single frame.
+                          Files ending in .exr, .hdr or .pfm are saved as high dynamic range
+                          OpenEXR, Radiance RGBE or PFM images. AOV layers selected in the
+                          scene are saved as layers of OpenEXR images, or otherwise as separate
+                          images named after the layer, e.g. 'frame_depth.png'.
+  --exr-float             Save OpenEXR images with 32-bit float channels instead of half floats.
+  --crop <window>         Render only the window of the image given as 'x_min,x_max,y_min,y_max'
+                          fractions of its width and height, overriding the scene file.
+  --crop-pixels <window>  Render only the window of the image given as 'x_min,x_max,y_min,y_max'
+                          pixels, where the max is excluded, overriding the scene file.
+  --crop-output <mode>    Choose how images of renders with a crop window are saved: 'full' saves
+                          the full image, black outside the window, 'cropped' saves only the
+                          window and 'composite' saves the window over the image already in the
+                          output file, which can't be an OpenEXR image [default: full].
+  -n <number>             Specify the number of threads to use for rendering. Defaults to the
+                          number of cores on the system.
+  --start-frame <number>  Specify the frame to start rendering at, overriding the scene file.
+                          The frames rendered are the inclusive range [start, end].
+  --end-frame <number>    Specify the frame to stop rendering at, overriding the scene file.
+  --spp <number>          Specify the samples per pixel, overriding the scene file.
+  --passes <number>       Render progressively in passes of --spp samples per pixel, stopping
+                          after this many passes. The image is saved after each pass.
+  --time-budget <secs>    Render progressively, stopping before a pass would take the render
+                          of the frame past this many seconds.
+  --noise <threshold>     Render progressively, stopping once the estimated relative error of
+                          the image is below the threshold, e.g. 0.01.
+  --checkpoint <file>     Save checkpoints of the render to the file after each pass, and
+                          periodically while rendering.
+  --checkpoint-interval <secs>
+                          Specify the seconds between checkpoints saved while rendering
+                          [default: 60].
+  --resume                Resume the render from the checkpoint file, continuing from the frame,
+                          pass and blocks rendered when it was saved.
+  --block-start <number>  Specify the index of the first image block to render, to render only
+                          part of the image.
+  --block-count <number>  Specify the number of image blocks to render, starting at the block
+                          given by --block-start. Defaults to all remaining blocks.
+  --master <workers>      Render by splitting each frame across worker processes, given as a
+                          comma separated list of host:port addresses. The scene file must be
+                          at the same path on each worker. Progressive rendering, checkpoints,
+                          rendering a range of blocks, AOVs and denoising aren't supported
+                          when distributing.
+  --worker                Run as a worker process, rendering the parts of frames sent to it by
+                          a master process.
+  --port <port>           Specify the port a worker process listens on [default: 63234].
+  --lint                  Check the scene file for likely mistakes, e.g. unused materials or
+                          missing lights, and exit without rendering. Exits with a non-zero
+                          status if any are found.
+  --schema                Print the JSON Schema of scene files, for editors to validate them with.
+  --denoise <image>       Denoise a previously saved PFM or Radiance RGBE image, guided by the
+                          albedo, normal, depth and variance AOV images saved next to it, e.g.
+                          'frame_albedo.pfm'. The result is saved to -o, or next to the image
+                          as e.g. 'frame_denoised.pfm'.
+  -h, --help              Show this message.
+";
+
+#[derive(Deserialize, Debug)]
+struct Args {
+    arg_scenefile: String,
+    flag_o: Option<String>,
+    flag_exr_float: bool,
+    flag_crop: Option<String>,
+    flag_crop_pixels: Option<String>,
+    flag_crop_output: String,
+    flag_n: Option<u32>,
+    flag_start_frame: Option<usize>,
+    flag_end_frame: Option<usize>,
+    flag_spp: Option<usize>,
+    flag_passes: Option<usize>,
+    flag_time_budget: Option<f64>,
+    flag_noise: Option<f32>,
+    flag_checkpoint: Option<String>,
+    flag_checkpoint_interval: f64,
+    flag_resume: bool,
+    flag_block_start: Option<usize>,
+    flag_block_count: Option<usize>,
+    flag_master: Option<String>,
+    flag_worker: bool,
+    flag_port: u16,
+    flag_lint: bool,
+    flag_schema: bool,
+    flag_denoise: Option<String>,
+}
+
+/// Print the error and exit with a non-zero status
+fn fail(msg: &str) -> ! {
+    eprintln!("Error: {}", msg);
+    process::exit(1);
+}
+
+/// Check if each frame is saved to a different file under the output path, which
+/// is true for directories and file names with a run of #'s
+fn names_frames(out_path: &Path) -> bool {
+    out_path.extension().is_none()
+        || out_path
+            .file_name()
+            .is_some_and(|n| n.to_string_lossy().contains('#'))
+}
+
+/// Get the file to save the frame to, based on the output path passed by the user
+fn frame_file(out_path: &Path, frame: usize) -> PathBuf {
+    if out_path.extension().is_none() {
+        return out_path.join(format!("frame{:05}.png", frame));
+    }
+    let name = match out_path.file_name() {
+        Some(n) => n.to_string_lossy(),
+        None => return out_path.to_path_buf(),
+    };
+    // Replace the first run of #'s with the padded frame number
+    match name.find('#') {
+        Some(start) => {
+            let width = name[start..].chars().take_while(|c| *c == '#').count();
+            let name = format!(
+                "{}{:0width$}{}",
+                &name[..start],
+                frame,
+                &name[start + width..],
+                width = width
+            );
+            out_path.with_file_name(name)
+        }
+        None => out_path.to_path_buf(),
+    }
+}
+
+/// How the images of renders with a crop window are saved
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CropOutput {
+    /// The full image, black outside the window
+    Full,
+    /// Only the window
+    Cropped,
+    /// The window over the image already in the file, if there is one
+    Composite,
+}
+
+/// How the rendered images are saved
+#[derive(Debug, Clone, Copy)]
+struct Output {
+    exr_pixel: ExrPixel,
+    /// The crop window of the render and how it's saved
+    crop: Option<(CropWindow, CropOutput)>,
+    /// The color space the image is rendered in, see `film::colorspace`
+    working_space: ColorSpace,
+    /// The color space the image is saved in
+    output_space: ColorSpace,
+}
+
+/// Parse the 4 comma separated values of a crop window
+fn parse_window<T: std::str::FromStr>(s: &str) -> Option<[T; 4]> {
+    let values: Vec<T> = s
+        .split(',')
+        .map(|v| v.trim().parse())
+        .collect::<Result<_, _>>()
+        .ok()?;
+    values.try_into().ok()
+}
+
+/// Images rendered locally or by the workers, which can be saved by `save_image`
+trait Render {
+    fn dimensions(&self) -> (usize, usize);
+    fn linear(&self) -> Vec<f32>;
+    /// The AOV layers rendered along with the image
+    fn aovs(&self) -> Vec<(Aov, Vec<f32>)>;
+}
+
+impl Render for RenderTarget {
+    fn dimensions(&self) -> (usize, usize) {
+        self.dimensions()
+    }
+    fn linear(&self) -> Vec<f32> {
+        self.get_render_denoised()
+            .unwrap_or_else(|| self.get_render_linear())
+    }
+    fn aovs(&self) -> Vec<(Aov, Vec<f32>)> {
+        self.aovs()
+            .into_iter()
+            .map(|a| (a, self.get_aov(a).unwrap()))
+            .collect()
+    }
+}
+
+impl Render for Image {
+    fn dimensions(&self) -> (usize, usize) {
+        self.dimensions()
+    }
+    fn linear(&self) -> Vec<f32> {
+        self.get_linear()
+    }
+    fn aovs(&self) -> Vec<(Aov, Vec<f32>)> {
+        Vec::new()
+    }
+}
+
+/// Save the image to `out_file`, in the HDR format given by its extension or as an
+/// 8-bit image with the display transform applied otherwise, converted to the output
+/// color space. AOVs are saved as layers of OpenEXR images and as separate images in
+/// the other formats
+fn save_image<R: Render>(img: &R, out_file: &Path, output: &Output, tonemap: &Tonemap) {
+    let exr_pixel = output.exr_pixel;
+    let space = output.output_space;
+    let to_output = |mut data: Vec<f32>| {
+        if output.working_space != space {
+            for px in data.chunks_mut(3) {
+                let c = Colorf::new(px[0], px[1], px[2]);
+                let c = output.working_space.convert(&c, space);
+                px.copy_from_slice(&[c.r, c.g, c.b]);
+            }
+        }
+        data
+    };
+    let (cropped, composite) = match output.crop {
+        Some((w, CropOutput::Cropped)) => (Some(w), None),
+        Some((w, CropOutput::Composite)) => (None, Some(w)),
+        _ => (None, None),
+    };
+    let crop = |data: Vec<f32>, channels: usize| match cropped {
+        Some(w) => w.crop(&data, img.dimensions().0, channels),
+        None => data,
+    };
+    let dim = cropped.map_or(img.dimensions(), |w| w.dimensions());
+    let aovs: Vec<_> = img
+        .aovs()
+        .into_iter()
+        .map(|(aov, data)| {
+            // Colors are converted like the image, while the other layers are data
+            let data = if aov.is_light() || aov == Aov::Albedo {
+                to_output(data)
+            } else {
+                data
+            };
+            (aov, crop(data, aov.channels().len()))
+        })
+        .collect();
+    let result = match HdrFormat::from_path(out_file) {
+        Some(HdrFormat::Exr(_)) if composite.is_some() => {
+            Err("compositing into OpenEXR images isn't supported".to_owned())
+        }
+        Some(HdrFormat::Exr(_)) => {
+            let rgb = crop(to_output(img.linear()), 3);
+            let mut layers = vec![ExrLayer {
+                name: "",
+                channels: &["R", "G", "B"],
+                pixel: exr_pixel,
+                data: &rgb,
+            }];
+            for (aov, data) in &aovs {
+                layers.push(ExrLayer {
+                    name: aov.name(),
+                    channels: aov.channels(),
+                    // Ids must be stored exactly
+                    pixel: if aov.is_id() {
+                        ExrPixel::Float
+                    } else {
+                        exr_pixel
+                    },
+                    data,
+                });
+            }
+            hdr::save_exr_layers(out_file, dim, &layers).map_err(|e| e.to_string())
+        }
+        format => {
+            let display = |c: &Colorf| space.encode(&tonemap.apply(c));
+            let rgb = crop(to_output(img.linear()), 3);
+            let mut result = save_rgb(out_file, dim, format, rgb, display, composite);
+            for (aov, data) in &aovs {
+                if result.is_err() {
+                    break;
+                }
+                let aov_file = aov_file(out_file, *aov);
+                let mut rgb = aov_rgb(*aov, data);
+                let signed = format.is_some_and(|f| f.is_signed());
+                if *aov == Aov::Normal && !signed {
+                    remap_normals(&mut rgb, false);
+                }
+                let max = if format.is_some() { f32::INFINITY } else { 1.0 };
+                let is_data = !aov.is_light() && *aov != Aov::Albedo;
+                if is_data && rgb.iter().any(|x| (!signed && *x < 0.0) || *x > max) {
+                    println!(
+                        "Warning: the {} layer has values '{}' can't store and they're clamped, \
+                         save it to a PFM or OpenEXR image to keep them",
+                        aov.name(),
+                        aov_file.display()
+                    );
+                }
+                // Light is displayed like the image, while the other layers are data
+                // and saved as their values
+                let display = |c: &Colorf| match *aov {
+                    _ if aov.is_light() => space.encode(&tonemap.apply(c)),
+                    Aov::Albedo => space.encode(&c.clamp()),
+                    _ => c.clamp(),
+                };
+                result = save_rgb(&aov_file, dim, format, rgb, display, composite);
+            }
+            result
+        }
+    };
+    if let Err(e) = result {
+        fail(&format!(
+            "Failed to save image '{}': {}",
+            out_file.display(),
+            e
+        ));
+    }
+}
+
+/// Save the linear RGB image in the HDR format, or as an 8-bit image if the format
+/// is None, with `display` converting each color to the [0, 1] value to save. If
+/// `composite` is set and the file exists the image outside the window is kept
+/// from the image in the file
+fn save_rgb<F: Fn(&Colorf) -> Colorf>(
+    file: &Path,
+    dim: (usize, usize),
+    format: Option<HdrFormat>,
+    mut rgb: Vec<f32>,
+    display: F,
+    composite: Option<CropWindow>,
+) -> Result<(), String> {
+    let composite = composite.filter(|_| file.exists());
+    let check_dim = |base: (usize, usize)| {
+        if base == dim {
+            Ok(())
+        } else {
+            Err(format!(
+                "the image to composite over is {}x{}, not {}x{}",
+                base.0, base.1, dim.0, dim.1
+            ))
+        }
+    };
+    match format {
+        Some(format) => {
+            if let Some(w) = composite {
+                let (base_dim, base) = format.load(file).map_err(|e| e.to_string())?;
+                check_dim(base_dim)?;
+                w.composite(&mut rgb, &base, dim.0, 3);
+            }
+            format.save(file, dim, &rgb).map_err(|e| e.to_string())
+        }
+        None => {
+            let mut srgb8 = Vec::with_capacity(rgb.len());
+            for px in rgb.chunks(3) {
+                let c = display(&Colorf::new(px[0], px[1], px[2]));
+                for i in 0..3 {
+                    srgb8.push((c[i] * 255.0) as u8);
+                }
+            }
+            if let Some(w) = composite {
+                let base = image::open(file).map_err(|e| e.to_string())?.to_rgb();
+                check_dim((base.width() as usize, base.height() as usize))?;
+                w.composite(&mut srgb8, &base.into_raw(), dim.0, 3);
+            }
+            image::save_buffer(file, &srgb8, dim.0 as u32, dim.1 as u32, image::RGB(8))
+                .map_err(|e| e.to_string())
+        }
+    }
+}
+
+/// Get the file to save the AOV to when it's saved separately from `out_file`
+fn aov_file(out_file: &Path, aov: Aov) -> PathBuf {
+    let stem = out_file
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let name = match out_file.extension() {
+        Some(ext) => format!("{}_{}.{}", stem, aov.name(), ext.to_string_lossy()),
+        None => format!("{}_{}", stem, aov.name()),
+    };
+    out_file.with_file_name(name)
+}
+
+/// Expand the AOV's channels to RGB to save it as an image, with single channel
+/// layers as gray
+fn aov_rgb(aov: Aov, data: &[f32]) -> Vec<f32> {
+    let n = aov.channels().len();
+    let mut rgb = Vec::with_capacity(data.len() / n * 3);
+    for px in data.chunks(n) {
+        match n {
+            1 => rgb.extend_from_slice(&[px[0]; 3]),
+            2 => rgb.extend_from_slice(&[px[0], px[1], 0.0]),
+            _ => rgb.extend_from_slice(px),
+        }
+    }
+    rgb
+}
 
-const SCENE_PATH: &str = "cornell.json";
+/// Map the normals in the RGB data to [0, 1] with `n * 0.5 + 0.5`, to save them in
+/// formats which can't store negative values, or back to [-1, 1] if `decode` is set
+fn remap_normals(rgb: &mut [f32], decode: bool) {
+    for x in rgb {
+        *x = if decode {
+            *x * 2.0 - 1.0
+        } else {
+            *x * 0.5 + 0.5
+        };
+    }
+}
+
+/// Denoise the HDR image saved in `image` with the default denoiser, using the AOV
+/// images saved next to it as features, and save the result to `out`
+fn denoise_image(image: &Path, out: Option<&str>) {
+    let format = match HdrFormat::from_path(image) {
+        Some(f @ HdrFormat::Pfm) | Some(f @ HdrFormat::Rgbe) => f,
+        _ => fail("Only PFM and Radiance RGBE images can be denoised"),
+    };
+    let (dim, rgb) = format
+        .load(image)
+        .unwrap_or_else(|e| fail(&format!("Failed to read '{}': {}", image.display(), e)));
+    let mut layers = Vec::new();
+    for aov in &Denoiser::FEATURES {
+        let file = aov_file(image, *aov);
+        if !file.exists() {
+            println!(
+                "Warning: no {} image '{}', denoising without it",
+                aov.name(),
+                file.display()
+            );
+            continue;
+        }
+        match format.load(&file) {
+            Ok((d, data)) if d == dim => {
+                // Single channel layers are saved as gray, and normals are remapped
+                // in formats which can't store negative values
+                let mut data = match aov.channels().len() {
+                    1 => data.chunks(3).map(|px| px[0]).collect(),
+                    _ => data,
+                };
+                if *aov == Aov::Normal && !format.is_signed() {
+                    remap_normals(&mut data, true);
+                }
+                layers.push((*aov, data));
+            }
+            Ok((d, _)) => fail(&format!(
+                "The {} image '{}' is {}x{}, not {}x{}",
+                aov.name(),
+                file.display(),
+                d.0,
+                d.1,
+                dim.0,
+                dim.1
+            )),
+            Err(e) => fail(&format!("Failed to read '{}': {}", file.display(), e)),
+        }
+    }
+    let layer = |aov: Aov| layers.iter().find(|l| l.0 == aov).map(|l| &l.1[..]);
+    let features = Features {
+        albedo: layer(Aov::Albedo),
+        normal: layer(Aov::Normal),
+        depth: layer(Aov::Depth),
+        variance: layer(Aov::Variance),
+    };
+    let denoised = Denoiser::default().denoise(dim, &rgb, &features);
+    let out_file = match out {
+        Some(o) => PathBuf::from(o),
+        None => {
+            let stem = image.file_stem().unwrap_or_default().to_string_lossy();
+            let ext = image.extension().unwrap_or_default().to_string_lossy();
+            image.with_file_name(format!("{}_denoised.{}", stem, ext))
+        }
+    };
+    let format = HdrFormat::from_path(&out_file);
+    let display = |c: &Colorf| Tonemap::default().apply(c).to_srgb();
+    if let Err(e) = save_rgb(&out_file, dim, format, denoised, display, None) {
+        fail(&format!(
+            "Failed to save image '{}': {}",
+            out_file.display(),
+            e
+        ));
+    }
+    println!("Denoised '{}' to '{}'", image.display(), out_file.display());
+}
+
+/// Prints the progress of the render and saves the image after each pass of
+/// progressive renders
+struct CliObserver {
+    print: PrintProgress,
+    out_path: PathBuf,
+    output: Output,
+}
+
+impl Observer for CliObserver {
+    fn frame_started(&self, frame: usize, time: (f32, f32), threads: u32) {
+        self.print.frame_started(frame, time, threads);
+    }
+    fn block_finished(&self, region: &Region, samples: &[ImageSample], progress: &Progress) {
+        self.print.block_finished(region, samples, progress);
+    }
+    fn pass_finished(&self, pass: &Pass, rt: &RenderTarget) {
+        self.print.pass_finished(pass, rt);
+        let out_file = frame_file(&self.out_path, pass.frame);
+        save_image(rt, &out_file, &self.output, rt.tonemap());
+    }
+    fn frame_finished(&self, frame: usize, time: Duration) {
+        self.print.frame_finished(frame, time);
+    }
+}
 
 fn main() {
-    let num_threads = num_cpus::get() as u32;
-    let out_path = PathBuf::from("./");
+    let args: Args = Docopt::new(USAGE)
+        .and_then(|d| d.deserialize())
+        .unwrap_or_else(|e| e.exit());
+    if args.flag_schema {
+        let schema = serde_json::to_string_pretty(&schema::json_schema()).unwrap();
+        println!("{}", schema);
+        return;
+    }
+    if args.flag_lint {
+        if args.arg_scenefile.ends_with(".pbrt") {
+            fail("Only JSON scene files can be checked");
+        }
+        match lint::lint_file(&args.arg_scenefile) {
+            Ok(ref warnings) if warnings.is_empty() => {
+                println!("No problems found in {}", args.arg_scenefile);
+            }
+            Ok(warnings) => {
+                for w in &warnings {
+                    println!("Warning: {}", w);
+                }
+                process::exit(1);
+            }
+            Err(e) => fail(&format!("Failed to load scene: {}", e)),
+        }
+        return;
+    }
+
+    let num_threads = match args.flag_n {
+        Some(0) => fail("The number of threads must be at least 1"),
+        Some(n) => n,
+        None => num_cpus::get() as u32,
+    };
+    if args.flag_worker {
+        let worker = match Worker::bind(("0.0.0.0", args.flag_port), num_threads) {
+            Ok(w) => w,
+            Err(e) => fail(&format!(
+                "Failed to listen on port {}: {}",
+                args.flag_port, e
+            )),
+        };
+        println!("Worker: listening on port {}", args.flag_port);
+        worker.serve();
+    }
+    if let Some(ref image) = args.flag_denoise {
+        denoise_image(Path::new(image), args.flag_o.as_deref());
+        return;
+    }
+    let out_path = PathBuf::from(args.flag_o.clone().unwrap_or_else(|| "./".to_owned()));
+    let out_dir = if out_path.extension().is_none() {
+        out_path.as_path()
+    } else {
+        out_path.parent().unwrap_or_else(|| Path::new(""))
+    };
+    if !out_dir.as_os_str().is_empty() && !out_dir.is_dir() {
+        fail(&format!(
+            "Output directory '{}' does not exist",
+            out_dir.display()
+        ));
+    }
 
-    let (mut scene, mut rt, spp, frame_info) = Scene::load_file(SCENE_PATH);
+    let loaded = if args.arg_scenefile.ends_with(".pbrt") {
+        Scene::load_pbrt(&args.arg_scenefile)
+    } else {
+        Scene::load_file(&args.arg_scenefile)
+    };
+    let (mut scene, mut rt, mut spp, mut frame_info) = match loaded {
+        Ok(s) => s,
+        Err(e) => fail(&format!("Failed to load scene: {}", e)),
+    };
+    if let Some(s) = args.flag_spp {
+        if s == 0 {
+            fail("The samples per pixel must be at least 1");
+        }
+        spp = s;
+    }
+    if let Some(f) = args.flag_start_frame {
+        frame_info.start = f;
+    }
+    if let Some(f) = args.flag_end_frame {
+        frame_info.end = f;
+    }
+    if frame_info.start > frame_info.end {
+        fail(&format!(
+            "The start frame {} is after the end frame {}",
+            frame_info.start, frame_info.end
+        ));
+    }
+    if frame_info.end >= frame_info.frames {
+        fail(&format!(
+            "The end frame {} is outside the scene's {} frames",
+            frame_info.end, frame_info.frames
+        ));
+    }
+    if frame_info.start != frame_info.end && !names_frames(&out_path) {
+        fail(&format!(
+            "Frames {} to {} would all be saved to '{}', add a run of '#' to the file name \
+             to number them, e.g. 'frame###.png'",
+            frame_info.start,
+            frame_info.end,
+            out_path.display()
+        ));
+    }
+    let select_blocks = match (args.flag_block_start, args.flag_block_count) {
+        (_, Some(0)) => fail("The block count must be at least 1"),
+        (Some(start), Some(count)) => (start, count),
+        (None, Some(count)) => (0, count),
+        (Some(start), None) => (start, usize::MAX),
+        (None, None) => (0, 0),
+    };
+    let progressive = match (args.flag_passes, args.flag_time_budget, args.flag_noise) {
+        (Some(0), _, _) => fail("The number of passes must be at least 1"),
+        (_, Some(t), _) if t.is_nan() || t <= 0.0 => fail("The time budget must be greater than 0"),
+        (_, _, Some(n)) if !n.is_finite() || n <= 0.0 => {
+            fail("The noise threshold must be a finite number greater than 0")
+        }
+        (None, None, None) => None,
+        (passes, time, noise) => Some(Budget {
+            passes,
+            time: time.map(Duration::from_secs_f64),
+            noise,
+        }),
+    };
+    let checkpoint = match args.flag_checkpoint {
+        Some(_)
+            if args.flag_checkpoint_interval.is_nan() || args.flag_checkpoint_interval <= 0.0 =>
+        {
+            fail("The checkpoint interval must be greater than 0")
+        }
+        Some(file) => Some(Checkpoint {
+            file: PathBuf::from(file),
+            interval: Duration::from_secs_f64(args.flag_checkpoint_interval),
+        }),
+        None if args.flag_resume => fail("A --checkpoint file is required to resume from"),
+        None => None,
+    };
     let dim = rt.dimensions();
+    let crop = match (&args.flag_crop, &args.flag_crop_pixels) {
+        (Some(_), Some(_)) => fail("Only one of --crop and --crop-pixels can be given"),
+        (Some(c), None) => match parse_window::<f32>(c) {
+            Some(w) if w.iter().all(|x| (0.0..=1.0).contains(x)) => {
+                Some(CropWindow::from_normalized(dim, (w[0], w[1]), (w[2], w[3])))
+            }
+            _ => fail("The crop window must be 4 comma separated fractions in [0, 1]"),
+        },
+        (None, Some(c)) => match parse_window::<u32>(c) {
+            Some(b) => Some(CropWindow::new((b[0], b[2]), (b[1], b[3]))),
+            None => fail("The crop window must be 4 comma separated pixel coordinates"),
+        },
+        (None, None) => rt.crop(),
+    };
+    if let Some(c) = crop {
+        if !c.fits(dim) {
+            fail(&format!(
+                "The crop window must be inside the {}x{} image and cover at least one pixel",
+                dim.0, dim.1
+            ));
+        }
+    }
+    rt.set_crop(crop);
+    // The checkpoint must be loaded once the crop window is set, as it's checked against it
+    if let (true, Some(c)) = (args.flag_resume, &checkpoint) {
+        match rt.load_checkpoint(&c.file) {
+            Ok(f) if f < frame_info.start || f > frame_info.end => fail(&format!(
+                "The checkpoint is for frame {}, outside the frames {} to {} being rendered",
+                f, frame_info.start, frame_info.end
+            )),
+            Ok(f) => {
+                println!("Resuming frame {} from '{}'", f, c.file.display());
+                frame_info.start = f;
+            }
+            Err(e) => fail(&format!(
+                "Failed to load checkpoint '{}': {}",
+                c.file.display(),
+                e
+            )),
+        }
+    }
+    let crop_output = match &args.flag_crop_output[..] {
+        "full" => CropOutput::Full,
+        "cropped" => CropOutput::Cropped,
+        "composite" => {
+            if let Some(HdrFormat::Exr(_)) = HdrFormat::from_path(&out_path) {
+                fail("Renders can't be composited into OpenEXR images");
+            }
+            CropOutput::Composite
+        }
+        m => fail(&format!(
+            "Unknown crop output '{}', expected full, cropped or composite",
+            m
+        )),
+    };
+    let output = Output {
+        exr_pixel: if args.flag_exr_float {
+            ExrPixel::Float
+        } else {
+            ExrPixel::Half
+        },
+        crop: crop.map(|c| (c, crop_output)),
+        working_space: rt.working_space(),
+        output_space: rt.output_space(),
+    };
 
     let scene_start = SystemTime::now();
     let mut config = Config::new(
         out_path,
-        SCENE_PATH.to_string(),
+        args.arg_scenefile.clone(),
         spp,
         num_threads,
         frame_info,
-        (0, 0),
+        select_blocks,
     );
+    config.progressive = progressive;
+    config.checkpoint = checkpoint;
+    config.sampler = scene.sampler;
+    if let Some(ref workers) = args.flag_master {
+        if config.progressive.is_some() || config.checkpoint.is_some() || select_blocks != (0, 0) {
+            fail("Progressive rendering, checkpoints and block ranges can't be distributed");
+        }
+        // Workers load the crop window from the scene file
+        if args.flag_crop.is_some() || args.flag_crop_pixels.is_some() {
+            fail("Crop windows set on the command line can't be distributed");
+        }
+        // Workers only send back the image
+        if !rt.aovs().is_empty() || rt.denoiser().is_some() {
+            fail("AOVs, variance and denoising can't be distributed");
+        }
+        // Workers may be running in other directories so send them the full path
+        if let Ok(path) = Path::new(&config.scene_file).canonicalize() {
+            config.scene_file = path.to_string_lossy().into_owned();
+        }
+        let workers = workers
+            .split(',')
+            .map(|w| {
+                if w.contains(':') {
+                    w.to_owned()
+                } else {
+                    format!("{}:{}", w, distrib::DEFAULT_PORT)
+                }
+            })
+            .collect();
+        let master = Master::new(workers, config.clone(), rt.dimensions(), rt.crop());
+        let tonemap = *rt.tonemap();
+        let result = master.render(|frame, img| {
+            let out_file = frame_file(&config.out_path, frame);
+            save_image(img, &out_file, &output, &tonemap);
+            println!("Frame {}: rendered to '{}'", frame, out_file.display());
+        });
+        if let Err(e) = result {
+            fail(&format!("Distributed render failed: {}", e));
+        }
+        let time = scene_start.elapsed().expect("Failed to get render time?");
+        println!("Rendering entire sequence took {:4}s", time.as_secs_f64());
+        return;
+    }
     let mut exec = MultiThreaded::new(num_threads);
+    let observer = CliObserver {
+        print: PrintProgress::new(),
+        out_path: config.out_path.clone(),
+        output,
+    };
     for i in frame_info.start..frame_info.end + 1 {
         config.current_frame = i;
-        exec.render(&mut scene, &mut rt, &config);
-
-        let img = rt.get_render();
-        let out_file = match config.out_path.extension() {
-            Some(_) => config.out_path.clone(),
-            None => config
-                .out_path
-                .join(PathBuf::from(format!("frame{:05}.png", i))),
-        };
-        match image::save_buffer(
-            &out_file.as_path(),
-            &img[..],
-            dim.0 as u32,
-            dim.1 as u32,
-            image::RGB(8),
-        ) {
-            Ok(_) => {}
-            Err(e) => println!("Error saving image, {}", e),
-        };
+        exec.render(&mut scene, &mut rt, &config, &observer);
+
+        let out_file = frame_file(&config.out_path, i);
+        save_image(&rt, &out_file, &output, rt.tonemap());
+        if let Some(e) = rt.estimate_error() {
+            println!(
+                "Frame {}: estimated relative error {:.4} from the sample variance",
+                i, e
+            );
+        }
         rt.clear();
         println!(
             "Frame {}: rendered to '{}'\n--------------------",
@@ -57,3 +816,44 @@ fn main() {
         time.as_secs() as f64 + time.subsec_nanos() as f64 * 1e-9
     )
 }
+
+#[test]
+fn test_frame_file() {
+    assert_eq!(
+        frame_file(Path::new("out"), 3),
+        PathBuf::from("out/frame00003.png")
+    );
+    // Files without #'s are only used for single frames
+    assert_eq!(
+        frame_file(Path::new("out/image.png"), 3),
+        PathBuf::from("out/image.png")
+    );
+    assert!(!names_frames(Path::new("out/image.png")));
+    assert!(names_frames(Path::new("out")));
+    assert!(names_frames(Path::new("out/shot_###.png")));
+    assert_eq!(
+        frame_file(Path::new("out/shot_###_v#.jpg"), 42),
+        PathBuf::from("out/shot_042_v#.jpg")
+    );
+    assert_eq!(
+        frame_file(Path::new("f#.png"), 1234),
+        PathBuf::from("f1234.png")
+    );
+}
+
+#[test]
+fn test_remap_normals() {
+    let normals = [-1.0, 0.0, 1.0, 0.6, -0.5, 0.0];
+    let mut rgb = normals.to_vec();
+    remap_normals(&mut rgb, false);
+    assert_eq!(rgb, [0.0, 0.5, 1.0, 0.8, 0.25, 0.5]);
+    // The remapped normals survive a round trip through RGBE
+    let file = std::env::temp_dir().join(format!("aperture_normals_{}.hdr", process::id()));
+    HdrFormat::Rgbe.save(&file, (2, 1), &rgb).unwrap();
+    let (_, mut loaded) = HdrFormat::Rgbe.load(&file).unwrap();
+    remap_normals(&mut loaded, true);
+    for (a, b) in loaded.iter().zip(&normals) {
+        assert!((a - b).abs() < 0.02, "{:?} != {:?}", loaded, normals);
+    }
+    let _ = std::fs::remove_file(file);
+}
@@ -17,9 +17,15 @@
 //!     ...
 //! ]
 //! ```
+//!
+//! When rendering in spectral mode (see `film::spectrum`) the glass can disperse
+//! light by giving its Abbe number, e.g. `"abbe": 64` for crown glass, lower numbers
+//! disperse more. `eta` is then the refractive index at 587.6nm and the index at
+//! other wavelengths follows Cauchy's equation.
 
 use crate::{
     bxdf::{fresnel::Dielectric, BxDFs, SpecularReflection, SpecularTransmission, BSDF},
+    film::Wavelengths,
     geometry::Intersection,
     material::{Material, Materials},
     texture::{Texture, Textures},
@@ -29,9 +35,11 @@ use std::sync::Arc;
 
 /// The Glass material describes specularly transmissive and reflective glass material
 pub struct Glass {
-    reflect: Arc<Textures>,
-    transmit: Arc<Textures>,
-    eta: Arc<Textures>,
+    pub reflect: Arc<Textures>,
+    pub transmit: Arc<Textures>,
+    pub eta: Arc<Textures>,
+    /// The Abbe number of the glass, if it disperses light
+    pub abbe: Option<f32>,
 }
 
 impl Glass {
@@ -39,19 +47,34 @@ impl Glass {
     /// `reflect`: color of reflected light
     /// `transmit`: color of transmitted light
     /// `eta`: refractive index of the material
+    /// `abbe`: Abbe number of the material if it disperses light in spectral mode
     pub fn new_material(
         reflect: Arc<Textures>,
         transmit: Arc<Textures>,
         eta: Arc<Textures>,
+        abbe: Option<f32>,
     ) -> Materials {
         Materials::Glass(Glass {
             reflect,
             transmit,
             eta,
+            abbe,
         })
     }
 }
 
+/// Compute the refractive index at the wavelength `lambda` in nanometers of glass
+/// with refractive index `eta_d` at the Fraunhofer d line and Abbe number `abbe`,
+/// by fitting Cauchy's equation `eta = a + b / lambda^2` to them
+fn cauchy_eta(eta_d: f32, abbe: f32, lambda: f32) -> f32 {
+    // The Fraunhofer d, F and C lines in micrometers
+    let (d, f, c) = (0.5876f32, 0.4861f32, 0.6563f32);
+    let b = (eta_d - 1.0) / (abbe * (1.0 / (f * f) - 1.0 / (c * c)));
+    let a = eta_d - b / (d * d);
+    let l = lambda / 1000.0;
+    a + b / (l * l)
+}
+
 impl Material for Glass {
     fn bsdf<'a, 'b, 'c>(&'a self, hit: &Intersection<'a, 'b>, alloc: &'c Allocator) -> BSDF<'c>
     where
@@ -59,9 +82,17 @@ impl Material for Glass {
     {
         // TODO: I don't like this counting and junk we have to do to figure out
         // the slice size and then the indices. Is there a better way?
-        let reflect = self.reflect.sample_color(hit.dg.u, hit.dg.v, hit.dg.time);
-        let transmit = self.transmit.sample_color(hit.dg.u, hit.dg.v, hit.dg.time);
-        let eta = self.eta.sample_f32(hit.dg.u, hit.dg.v, hit.dg.time);
+        let mut reflect = hit.upsample(&self.reflect.sample_color(hit.dg.u, hit.dg.v, hit.dg.time));
+        let mut transmit =
+            hit.upsample(&self.transmit.sample_color(hit.dg.u, hit.dg.v, hit.dg.time));
+        let mut eta = self.eta.sample_f32(hit.dg.u, hit.dg.v, hit.dg.time);
+        // Dispersive glass refracts each wavelength in a different direction, so
+        // only the hero wavelength continues
+        if let (Some(abbe), Some(w)) = (self.abbe, hit.wavelengths) {
+            eta = cauchy_eta(eta, abbe, w.hero());
+            reflect = reflect * Wavelengths::hero_only();
+            transmit = transmit * Wavelengths::hero_only();
+        }
 
         let mut num_bxdfs = 0;
         if !reflect.is_black() {
@@ -84,3 +115,13 @@ impl Material for Glass {
         BSDF::new(bxdfs, eta, &hit.dg)
     }
 }
+
+#[test]
+fn test_cauchy_eta() {
+    // BK7 crown glass
+    let eta = |lambda| cauchy_eta(1.5168, 64.17, lambda);
+    assert!(f32::abs(eta(587.6) - 1.5168) < 1e-4);
+    // The Abbe number is the ratio of the index above 1 to the spread from F to C
+    assert!(f32::abs((eta(587.6) - 1.0) / (eta(486.1) - eta(656.3)) - 64.17) < 0.01);
+    assert!(eta(400.0) > eta(700.0));
+}
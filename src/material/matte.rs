@@ -32,8 +32,8 @@ use std::sync::Arc;
 /// Oren-Nayar BRDF. The Lambertian BRDF is used for materials with no roughness
 /// while Oren-Nayar is used for those with some roughness.
 pub struct Matte {
-    diffuse: Arc<Textures>,
-    roughness: Arc<Textures>,
+    pub diffuse: Arc<Textures>,
+    pub roughness: Arc<Textures>,
 }
 
 impl Matte {
@@ -48,7 +48,7 @@ impl Material for Matte {
     where
         'a: 'c,
     {
-        let diffuse = self.diffuse.sample_color(hit.dg.u, hit.dg.v, hit.dg.time);
+        let diffuse = hit.upsample(&self.diffuse.sample_color(hit.dg.u, hit.dg.v, hit.dg.time));
         let roughness = self.roughness.sample_f32(hit.dg.u, hit.dg.v, hit.dg.time);
 
         let bsdfs = alloc.alloc_slice::<&'c BxDFs>(1);
@@ -25,7 +25,12 @@ use crate::{
 };
 use byteorder::{LittleEndian, ReadBytesExt};
 use light_arena::Allocator;
-use std::{fs::File, io::BufReader, iter, path::Path};
+use std::{
+    fs::File,
+    io::{self, BufReader},
+    iter,
+    path::{Path, PathBuf},
+};
 
 /// Material that uses measured data to model the surface reflectance properties.
 /// The measured data is from "A Data-Driven Reflectance Model",
@@ -40,33 +45,30 @@ pub struct Merl {
     n_theta_d: usize,
     /// Number of phi_d measurements in `brdf`
     n_phi_d: usize,
+    /// The MERL BRDF database file the data was loaded from
+    pub file: PathBuf,
 }
 
 impl Merl {
     /// Create a new MERL BRDF by loading the refletance data from a MERL BRDF
     /// database file
-    pub fn load_file(path: &Path) -> Materials {
-        let file = match File::open(path) {
-            Ok(f) => f,
-            Err(e) => {
-                panic!(
-                    "material::Merl::load_file - failed to open {:?} due to {}",
-                    path, e
-                );
-            }
-        };
+    pub fn load_file(path: &Path) -> io::Result<Materials> {
+        let file = File::open(path)?;
         let mut reader = BufReader::new(file);
         // Values we expect to read from a MERL BRDF file for each dimension
         let n_theta_h = 90;
         let n_theta_d = 90;
         let n_phi_d = 180;
         let dims = [
-            reader.read_i32::<LittleEndian>().unwrap() as usize,
-            reader.read_i32::<LittleEndian>().unwrap() as usize,
-            reader.read_i32::<LittleEndian>().unwrap() as usize,
+            reader.read_i32::<LittleEndian>()? as usize,
+            reader.read_i32::<LittleEndian>()? as usize,
+            reader.read_i32::<LittleEndian>()? as usize,
         ];
         if n_theta_h != dims[0] || n_theta_d != dims[1] || n_phi_d != dims[2] {
-            panic!("material::Merl::load_file - Invalid MERL file header, aborting");
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Invalid MERL file header",
+            ));
         }
 
         let n_vals = n_theta_h * n_theta_d * n_phi_d;
@@ -78,17 +80,18 @@ impl Merl {
             for i in 0..n_vals {
                 // The BRDF data is stored in double precision with these odd scaling factors
                 // so decode the value
-                let x = (reader.read_f64::<LittleEndian>().unwrap() * s) as f32;
+                let x = (reader.read_f64::<LittleEndian>()? * s) as f32;
                 brdf[3 * i + c] = f32::max(0.0, x);
             }
         }
 
-        Materials::Merl(Merl {
+        Ok(Materials::Merl(Merl {
             brdf,
             n_theta_h,
             n_theta_d,
             n_phi_d,
-        })
+            file: path.to_path_buf(),
+        }))
     }
 }
 
@@ -103,6 +106,7 @@ impl Material for Merl {
             self.n_theta_h,
             self.n_theta_d,
             self.n_phi_d,
+            hit.wavelengths,
         ));
         BSDF::new(bxdfs, 1.0, &hit.dg)
     }
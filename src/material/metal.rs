@@ -32,9 +32,9 @@ use std::sync::Arc;
 
 /// The Metal material describes metals of varying roughness
 pub struct Metal {
-    eta: Arc<Textures>,
-    k: Arc<Textures>,
-    roughness: Arc<Textures>,
+    pub eta: Arc<Textures>,
+    pub k: Arc<Textures>,
+    pub roughness: Arc<Textures>,
 }
 
 impl Metal {
@@ -53,8 +53,8 @@ impl Material for Metal {
     where
         'a: 'c,
     {
-        let eta = self.eta.sample_color(hit.dg.u, hit.dg.v, hit.dg.time);
-        let k = self.k.sample_color(hit.dg.u, hit.dg.v, hit.dg.time);
+        let eta = hit.upsample(&self.eta.sample_color(hit.dg.u, hit.dg.v, hit.dg.time));
+        let k = hit.upsample(&self.k.sample_color(hit.dg.u, hit.dg.v, hit.dg.time));
         let roughness = self.roughness.sample_f32(hit.dg.u, hit.dg.v, hit.dg.time);
 
         let bxdfs = alloc.alloc_slice::<&BxDFs>(1);
@@ -33,9 +33,9 @@ use std::sync::Arc;
 
 /// The Plastic material describes plastic materials of varying roughness
 pub struct Plastic {
-    diffuse: Arc<Textures>,
-    gloss: Arc<Textures>,
-    roughness: Arc<Textures>,
+    pub diffuse: Arc<Textures>,
+    pub gloss: Arc<Textures>,
+    pub roughness: Arc<Textures>,
 }
 
 impl Plastic {
@@ -59,8 +59,8 @@ impl Material for Plastic {
     where
         'a: 'c,
     {
-        let diffuse = self.diffuse.sample_color(hit.dg.u, hit.dg.v, hit.dg.time);
-        let gloss = self.gloss.sample_color(hit.dg.u, hit.dg.v, hit.dg.time);
+        let diffuse = hit.upsample(&self.diffuse.sample_color(hit.dg.u, hit.dg.v, hit.dg.time));
+        let gloss = hit.upsample(&self.gloss.sample_color(hit.dg.u, hit.dg.v, hit.dg.time));
         let roughness = self.roughness.sample_f32(hit.dg.u, hit.dg.v, hit.dg.time);
 
         // TODO: I don't like this counting and junk we have to do to figure out
@@ -33,10 +33,10 @@ use std::sync::Arc;
 
 /// The `RoughGlass` material describes specularly transmissive and reflective glass material
 pub struct RoughGlass {
-    reflect: Arc<Textures>,
-    transmit: Arc<Textures>,
-    eta: Arc<Textures>,
-    roughness: Arc<Textures>,
+    pub reflect: Arc<Textures>,
+    pub transmit: Arc<Textures>,
+    pub eta: Arc<Textures>,
+    pub roughness: Arc<Textures>,
 }
 
 impl RoughGlass {
@@ -65,8 +65,8 @@ impl Material for RoughGlass {
     where
         'a: 'c,
     {
-        let reflect = self.reflect.sample_color(hit.dg.u, hit.dg.v, hit.dg.time);
-        let transmit = self.transmit.sample_color(hit.dg.u, hit.dg.v, hit.dg.time);
+        let reflect = hit.upsample(&self.reflect.sample_color(hit.dg.u, hit.dg.v, hit.dg.time));
+        let transmit = hit.upsample(&self.transmit.sample_color(hit.dg.u, hit.dg.v, hit.dg.time));
         let eta = self.eta.sample_f32(hit.dg.u, hit.dg.v, hit.dg.time);
         let roughness = self.roughness.sample_f32(hit.dg.u, hit.dg.v, hit.dg.time);
 
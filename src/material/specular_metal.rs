@@ -29,8 +29,8 @@ use std::sync::Arc;
 /// The Specular Metal material describes specularly reflective metals using their
 /// refractive index and absorption coefficient
 pub struct SpecularMetal {
-    eta: Arc<Textures>,
-    k: Arc<Textures>,
+    pub eta: Arc<Textures>,
+    pub k: Arc<Textures>,
 }
 
 impl SpecularMetal {
@@ -47,8 +47,8 @@ impl Material for SpecularMetal {
     where
         'a: 'c,
     {
-        let eta = self.eta.sample_color(hit.dg.u, hit.dg.v, hit.dg.time);
-        let k = self.k.sample_color(hit.dg.u, hit.dg.v, hit.dg.time);
+        let eta = hit.upsample(&self.eta.sample_color(hit.dg.u, hit.dg.v, hit.dg.time));
+        let k = hit.upsample(&self.k.sample_color(hit.dg.u, hit.dg.v, hit.dg.time));
 
         let bxdfs = alloc.alloc_slice::<&BxDFs>(1);
         let fresnel = alloc.alloc(Conductor::new(&eta, &k).into());
@@ -102,3 +102,9 @@ pub fn uniform_sample_sphere(samples: &(f32, f32)) -> Vector {
     let phi = f32::consts::PI * 2.0 * samples.1;
     Vector::new(f32::cos(phi) * r, f32::sin(phi) * r, z)
 }
+/// Uniformly sample barycentric coordinates on a triangle, returns the
+/// barycentric coordinates of the first two vertices
+pub fn uniform_sample_triangle(samples: &(f32, f32)) -> (f32, f32) {
+    let su0 = f32::sqrt(samples.0);
+    (1.0 - su0, samples.1 * su0)
+}
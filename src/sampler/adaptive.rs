@@ -5,7 +5,7 @@
 //! number of samples taken per pixel will vary.
 
 use crate::{
-    film::ImageSample,
+    film::{CropWindow, ImageSample},
     sampler::{ld, Region, Sampler},
 };
 use rand::{
@@ -147,8 +147,8 @@ impl Sampler for Adaptive {
         self.region.dim
     }
 
-    fn select_block(&mut self, start: (u32, u32)) {
-        self.region.select_region(start);
+    fn select_block(&mut self, start: (u32, u32), window: &CropWindow) {
+        self.region.select_region(start, window);
     }
 
     fn get_region(&self) -> &Region {
@@ -3,8 +3,9 @@
 //! we simply work through it with an atomic counter to track the index of the next
 //! block to work on
 
-use crate::sampler::morton;
+use crate::{film::CropWindow, sampler::morton};
 use std::{
+    iter,
     sync::atomic::{AtomicUsize, Ordering},
     vec::Vec,
 };
@@ -13,6 +14,8 @@ use std::{
 pub struct BlockQueue {
     /// The block indices of blocks to work on for the image
     blocks: Vec<(u32, u32)>,
+    /// The window of the image being split into blocks
+    window: CropWindow,
     /// Get the dimensions of an individual block
     dimensions: (u32, u32),
     /// Index of the next block to be worked on
@@ -25,20 +28,20 @@ pub struct BlockQueueIterator<'a> {
 }
 
 impl BlockQueue {
-    /// Create a block queue for the image with dimensions `img`.
-    /// Panics if the image is not evenly broken into blocks of dimension `dim`
-    pub fn new(img: (u32, u32), dim: (u32, u32), select_blocks: (usize, usize)) -> Self {
-        if img.0 % dim.0 != 0 || img.1 % dim.1 != 0 {
-            panic!(
-                "Image with dimension {:?} not evenly divided by blocks of {:?}",
-                img, dim
-            );
-        }
-        let num_blocks = (img.0 / dim.0, img.1 / dim.1);
+    /// Create a block queue for the blocks of dimension `dim` overlapping the `window`
+    /// of the image being rendered. Blocks which aren't entirely inside the window,
+    /// e.g. along the right and bottom edges of the image if it isn't evenly broken
+    /// into blocks, only cover the part of the block inside it
+    pub fn new(window: &CropWindow, dim: (u32, u32), select_blocks: (usize, usize)) -> Self {
+        let first = (window.start.0 / dim.0, window.start.1 / dim.1);
+        let num_blocks = (
+            window.end.0.div_ceil(dim.0).saturating_sub(first.0),
+            window.end.1.div_ceil(dim.1).saturating_sub(first.1),
+        );
         // TODO: the .. operator precedence is very low so we need this paren here at the moment
         // once (hopefully) it's raised we can remove the parens
         let mut blocks: Vec<(u32, u32)> = (0..num_blocks.0 * num_blocks.1)
-            .map(|i| (i % num_blocks.0, i / num_blocks.0))
+            .map(|i| (first.0 + i % num_blocks.0, first.1 + i / num_blocks.0))
             .collect();
         blocks.sort_by_key(morton::morton2);
         // If we're only rendering a subset of the blocks then filter our list down
@@ -54,25 +57,44 @@ impl BlockQueue {
         }
         Self {
             blocks,
+            window: *window,
             dimensions: dim,
             next: AtomicUsize::new(0),
         }
     }
+    /// Remove the `done` blocks from the queue, e.g. the blocks already rendered
+    /// in a pass being resumed from a checkpoint
+    pub fn skip_blocks(&mut self, done: &[(u32, u32)]) {
+        self.blocks.retain(|b| !done.contains(b));
+    }
     /// Get the dimensions of an individual block in the queue
     pub fn block_dim(&self) -> (u32, u32) {
         self.dimensions
     }
+    /// Get the window of the image the blocks cover
+    pub fn window(&self) -> &CropWindow {
+        &self.window
+    }
     /// Get an iterator to work through the queue
     pub fn iter(&self) -> BlockQueueIterator {
         BlockQueueIterator { queue: self }
     }
+    /// Get an iterator to work through the queue which also gives the index of each
+    /// block in the queue
+    pub fn iter_indexed(&self) -> impl Iterator<Item = (usize, (u32, u32))> + '_ {
+        iter::from_fn(move || self.next_indexed())
+    }
     /// Get the next block in the queue or None if the queue is finished
     fn next(&self) -> Option<(u32, u32)> {
+        self.next_indexed().map(|(_, b)| b)
+    }
+    /// Get the next block in the queue along with its index
+    fn next_indexed(&self) -> Option<(usize, (u32, u32))> {
         let i = self.next.fetch_add(1, Ordering::AcqRel);
         if i >= self.blocks.len() {
             None
         } else {
-            Some(self.blocks[i])
+            Some((i, self.blocks[i]))
         }
     }
     /// Get the length of the queue
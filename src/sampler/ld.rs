@@ -7,7 +7,10 @@ use rand::{
 };
 use std::{f32, iter, u32};
 
-use crate::sampler::{Region, Sampler};
+use crate::{
+    film::CropWindow,
+    sampler::{Region, Sampler},
+};
 
 /// Low discrepancy sampler that makes use of the (0, 2) sequence to generate
 /// well distributed samples
@@ -84,8 +87,8 @@ impl Sampler for LowDiscrepancy {
         self.region.dim
     }
 
-    fn select_block(&mut self, start: (u32, u32)) {
-        self.region.select_region(start);
+    fn select_block(&mut self, start: (u32, u32), window: &CropWindow) {
+        self.region.select_region(start, window);
     }
 
     fn get_region(&self) -> &Region {
@@ -1,9 +1,38 @@
 //! Provides the Sampler trait which is implemented by the various samplers
 //! to provide stratified, low-discrepancy, adaptive sampling methods and so
 //! on through a simple trait interface
+//!
+//! # Scene Usage Example
+//! The sampler to render with and the size of the blocks of pixels the image is
+//! split into are set by the optional `"sampler"` object in the scene. Scenes
+//! without one use the low discrepancy sampler with 8x8 blocks. The low discrepancy
+//! sampler takes the film's `samples` per pixel, the adaptive sampler takes
+//! `min_spp` samples and then more up to `max_spp` in pixels which need them, and
+//! the uniform sampler takes one sample at the center of each pixel. The
+//! `block_size` is optional, if the image dimensions aren't a multiple of it the
+//! blocks along the right and bottom edges of the image are cut short.
+//!
+//! The optional `seed` makes renders reproducible: the random numbers used for each
+//! pixel are derived from the seed, frame, pass and pixel, so rendering the scene
+//! again gives the same image bit-for-bit, whatever the number of threads.
+//! Without a seed each thread seeds itself from the OS.
+//!
+//! ```json
+//! "sampler": {
+//!     "type": "adaptive",
+//!     "min_spp": 4,
+//!     "max_spp": 64,
+//!     "block_size": [16, 16],
+//!     "seed": 42
+//! }
+//! ```
 
-use crate::film::ImageSample;
+use crate::{
+    exec::BLOCK_DIM,
+    film::{CropWindow, ImageSample},
+};
 use rand::StdRng;
+use std::cmp;
 
 pub use self::{adaptive::Adaptive, block_queue::BlockQueue, ld::LowDiscrepancy, uniform::Uniform};
 
@@ -35,8 +64,9 @@ pub trait Sampler {
     fn dimensions(&self) -> (u32, u32);
     /// Move to a new block of the image to sample with this sampler by specifying
     /// the starting `(x, y)` block index for the new block. The block starting
-    /// position will be calculated as `dimensions * start`
-    fn select_block(&mut self, start: (u32, u32));
+    /// position will be calculated as `dimensions * start`, and the block is cut
+    /// short where it extends outside the `window` of the image being rendered
+    fn select_block(&mut self, start: (u32, u32), window: &CropWindow);
     /// Get the region being samples
     fn get_region(&self) -> &Region;
     /// Let the sampler inspect the results of sampling the pixel so it can
@@ -55,6 +85,58 @@ pub enum Samplers {
     Uniform,
 }
 
+/// The types of sampler which can be rendered with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SamplerType {
+    LowDiscrepancy,
+    /// Take `min_spp` to `max_spp` samples per pixel, depending on the pixel's contrast
+    Adaptive {
+        min_spp: usize,
+        max_spp: usize,
+    },
+    Uniform,
+}
+
+/// The sampler to render with and the dimensions of the blocks of pixels it samples
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SamplerConfig {
+    pub sampler: SamplerType,
+    pub block_dim: (u32, u32),
+    /// Seed of the random numbers used to sample each pixel, for reproducible renders
+    pub seed: Option<u64>,
+}
+
+impl SamplerConfig {
+    pub fn new(sampler: SamplerType, block_dim: (u32, u32)) -> SamplerConfig {
+        SamplerConfig {
+            sampler,
+            block_dim,
+            seed: None,
+        }
+    }
+    /// Check if the blocks have a non-zero size, so the image can be split into them
+    pub fn valid_block_dim(&self) -> bool {
+        self.block_dim.0 > 0 && self.block_dim.1 > 0
+    }
+    /// Create the sampler, which takes `spp` samples per pixel if its type doesn't
+    /// set its own number of samples
+    pub fn create(&self, spp: usize) -> Samplers {
+        match self.sampler {
+            SamplerType::LowDiscrepancy => LowDiscrepancy::new(self.block_dim, spp).into(),
+            SamplerType::Adaptive { min_spp, max_spp } => {
+                Adaptive::new(self.block_dim, min_spp, max_spp).into()
+            }
+            SamplerType::Uniform => Uniform::new(self.block_dim).into(),
+        }
+    }
+}
+
+impl Default for SamplerConfig {
+    fn default() -> SamplerConfig {
+        SamplerConfig::new(SamplerType::LowDiscrepancy, BLOCK_DIM)
+    }
+}
+
 /// Provides a simple way to pass around a 3 component sample consisting of one 2D and
 /// one 1D sample
 #[derive(Debug)]
@@ -86,6 +168,9 @@ pub struct Region {
     pub end: (u32, u32),
     /// Dimensions of the region being sampled
     pub dim: (u32, u32),
+    /// Dimensions of the blocks the image is split into. Regions at the right and
+    /// bottom edges of the image may be smaller than a block
+    pub block_dim: (u32, u32),
 }
 
 impl Region {
@@ -96,18 +181,29 @@ impl Region {
             start,
             end: (start.0 + dim.0, start.1 + dim.1),
             dim,
+            block_dim: dim,
         }
     }
 
-    /// Select a new region starting at region indices `start` with the same dimensions
+    /// Select a new region starting at region indices `start` with the same block dimensions
     /// eg. with blocks of width 8 the 2nd region along x is at 16 so to get
-    /// this block you'd set start.0 = 2
-    pub fn select_region(&mut self, start: (u32, u32)) {
-        self.start.0 = start.0 * self.dim.0;
-        self.start.1 = start.1 * self.dim.1;
-        self.end.0 = self.start.0 + self.dim.0;
-        self.end.1 = self.start.1 + self.dim.1;
+    /// this block you'd set start.0 = 2. The region is clipped to the `window` of the
+    /// image being rendered
+    pub fn select_region(&mut self, start: (u32, u32), window: &CropWindow) {
+        let block_start = (start.0 * self.block_dim.0, start.1 * self.block_dim.1);
+        self.start.0 = cmp::max(block_start.0, window.start.0);
+        self.start.1 = cmp::max(block_start.1, window.start.1);
+        self.end.0 = cmp::min(block_start.0 + self.block_dim.0, window.end.0);
+        self.end.1 = cmp::min(block_start.1 + self.block_dim.1, window.end.1);
+        self.dim = (self.end.0 - self.start.0, self.end.1 - self.start.1);
         self.current.0 = self.start.0;
         self.current.1 = self.start.1;
     }
+    /// Get the index of the block the region is in
+    pub fn block_index(&self) -> (u32, u32) {
+        (
+            self.start.0 / self.block_dim.0,
+            self.start.1 / self.block_dim.1,
+        )
+    }
 }
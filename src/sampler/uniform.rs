@@ -6,7 +6,10 @@ use rand::{
     StdRng,
 };
 
-use crate::sampler::{Region, Sampler};
+use crate::{
+    film::CropWindow,
+    sampler::{Region, Sampler},
+};
 
 /// Uniform sampler that takes one sample per pixel at the center of each pixel
 pub struct Uniform {
@@ -60,8 +63,8 @@ impl Sampler for Uniform {
     fn dimensions(&self) -> (u32, u32) {
         self.region.dim
     }
-    fn select_block(&mut self, start: (u32, u32)) {
-        self.region.select_region(start);
+    fn select_block(&mut self, start: (u32, u32), window: &CropWindow) {
+        self.region.select_region(start, window);
     }
     fn get_region(&self) -> &Region {
         &self.region
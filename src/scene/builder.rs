@@ -0,0 +1,412 @@
+//! Provides `SceneBuilder` for creating scenes directly from Rust values, e.g. when
+//! generating scenes procedurally, instead of writing out and loading a JSON scene file.
+//! The scene and render target built are the same as those loaded from the equivalent
+//! scene file.
+//!
+//! # Example
+//! ```
+//! use std::sync::Arc;
+//!
+//! use aperture::{
+//!     film::{AnimatedColor, Camera, ColorKeyframe, Colorf},
+//!     geometry::Sphere,
+//!     integrator,
+//!     linalg::{AnimatedTransform, Transform, Vector},
+//!     material::Matte,
+//!     scene::SceneBuilder,
+//!     texture::{ConstantColor, ConstantScalar},
+//! };
+//!
+//! let mut builder = SceneBuilder::new((64, 64), 4);
+//! let camera_world = Transform::translate(&Vector::new(0.0, 0.0, -10.0));
+//! builder.camera(Camera::new(
+//!     AnimatedTransform::unanimated(&camera_world),
+//!     30.0,
+//!     builder.dimensions(),
+//!     0.5,
+//!     0,
+//! ));
+//! builder.integrator(integrator::Path::new_integrator(3, 8));
+//! let red = builder.texture("red", ConstantColor::new_texture(Colorf::new(1.0, 0.0, 0.0)));
+//! let roughness = Arc::new(ConstantScalar::new_texture(1.0));
+//! let matte = builder.material("matte", Matte::new_material(red, roughness));
+//! builder.receiver(
+//!     Arc::new(Sphere::new(1.0).into()),
+//!     matte,
+//!     AnimatedTransform::unanimated(&Transform::identity()),
+//!     "ball".to_owned(),
+//! );
+//! builder.point_light(
+//!     AnimatedTransform::unanimated(&Transform::translate(&Vector::new(0.0, 5.0, 0.0))),
+//!     AnimatedColor::with_keyframes(vec![ColorKeyframe::new(&Colorf::broadcast(50.0), 0.0)]),
+//!     "light".to_owned(),
+//! );
+//! let (scene, rt, spp, frame_info) = builder.build().unwrap();
+//! assert_eq!(scene.bvh.iter().count(), 2);
+//! ```
+
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{
+    film::{
+        filter::{self, Filters},
+        AnimatedColor, Aov, Camera, ColorSpace, CropWindow, FrameInfo, RenderTarget, Tonemap,
+    },
+    geometry::{BoundableGeometry, Instance, SampleableGeometry},
+    integrator::Integrators,
+    linalg::AnimatedTransform,
+    material::Materials,
+    sampler::SamplerConfig,
+    scene::{Scene, SceneError},
+    texture::Textures,
+};
+
+/// Collects the parts of a scene as they're created, the scene is assembled and
+/// checked when `build` is called
+pub struct SceneBuilder {
+    dims: (usize, usize),
+    spp: usize,
+    filter: Filters,
+    aovs: Vec<Aov>,
+    max_sample_luminance: Option<f32>,
+    tonemap: Tonemap,
+    /// The working and output color spaces
+    color_spaces: (ColorSpace, ColorSpace),
+    crop: Option<CropWindow>,
+    sampler: SamplerConfig,
+    frame_info: FrameInfo,
+    cameras: Vec<Camera>,
+    integrator: Option<Integrators>,
+    textures: HashMap<String, Arc<Textures>>,
+    materials: HashMap<String, Arc<Materials>>,
+    instances: Vec<Instance>,
+    /// Names of textures or materials which were added more than once
+    conflicts: Vec<String>,
+}
+
+impl SceneBuilder {
+    /// Start building a scene rendered to an image of `dims` pixels with `spp` samples
+    /// per pixel. The scene uses a Mitchell-Netravali filter and has a single frame
+    /// unless they're changed with `filter` and `frames`.
+    pub fn new(dims: (usize, usize), spp: usize) -> SceneBuilder {
+        SceneBuilder {
+            dims,
+            spp,
+            filter: filter::MitchellNetravali::new_filter(2.0, 2.0, 1.0 / 3.0, 1.0 / 3.0),
+            aovs: Vec::new(),
+            max_sample_luminance: None,
+            tonemap: Tonemap::default(),
+            color_spaces: (ColorSpace::LinearSrgb, ColorSpace::Srgb),
+            crop: None,
+            sampler: SamplerConfig::default(),
+            frame_info: FrameInfo::new(1, 0.0, 0, 0),
+            cameras: Vec::new(),
+            integrator: None,
+            textures: HashMap::new(),
+            materials: HashMap::new(),
+            instances: Vec::new(),
+            conflicts: Vec::new(),
+        }
+    }
+    /// Get the dimensions of the image being rendered, which are needed to create cameras
+    pub fn dimensions(&self) -> (usize, usize) {
+        self.dims
+    }
+    /// Set the filter used to reconstruct the image from the samples taken
+    pub fn filter(&mut self, filter: Filters) {
+        self.filter = filter;
+    }
+    /// Set the AOV layers to render along with the image, see `film::aov`
+    pub fn aovs(&mut self, aovs: &[Aov]) {
+        self.aovs = aovs.to_vec();
+    }
+    /// Set the luminance to clamp samples to, see `RenderTarget::set_max_sample_luminance`
+    pub fn max_sample_luminance(&mut self, max: Option<f32>) {
+        self.max_sample_luminance = max;
+    }
+    /// Set the display transform for 8-bit images, see `film::tonemap`
+    pub fn tonemap(&mut self, tonemap: Tonemap) {
+        self.tonemap = tonemap;
+    }
+    /// Set the color spaces the image is rendered and saved in, see `film::colorspace`.
+    /// Colors of the textures and lights added should be in the `working` space
+    pub fn color_spaces(&mut self, working: ColorSpace, output: ColorSpace) {
+        self.color_spaces = (working, output);
+    }
+    /// Set the window of the image to render, see `film::crop`
+    pub fn crop(&mut self, crop: Option<CropWindow>) {
+        self.crop = crop;
+    }
+    /// Set the sampler to render with, see `sampler`
+    pub fn sampler(&mut self, sampler: SamplerConfig) {
+        self.sampler = sampler;
+    }
+    /// Set the frames of the animation to render
+    pub fn frames(&mut self, frame_info: FrameInfo) {
+        self.frame_info = frame_info;
+    }
+    /// Add a camera to render with. Scenes with multiple cameras switch to each
+    /// camera at the frame it becomes active at.
+    pub fn camera(&mut self, camera: Camera) {
+        self.cameras.push(camera);
+    }
+    /// Set the integrator used to render the scene
+    pub fn integrator(&mut self, integrator: Integrators) {
+        self.integrator = Some(integrator);
+    }
+    /// Add a named texture to the scene, returning it so materials can use it
+    pub fn texture(&mut self, name: &str, texture: Textures) -> Arc<Textures> {
+        let texture = Arc::new(texture);
+        if self
+            .textures
+            .insert(name.to_owned(), texture.clone())
+            .is_some()
+        {
+            self.conflicts.push(format!("texture '{}'", name));
+        }
+        texture
+    }
+    /// Add a named material to the scene, returning it so objects can use it
+    pub fn material(&mut self, name: &str, material: Materials) -> Arc<Materials> {
+        let material = Arc::new(material);
+        if self
+            .materials
+            .insert(name.to_owned(), material.clone())
+            .is_some()
+        {
+            self.conflicts.push(format!("material '{}'", name));
+        }
+        material
+    }
+    /// Add an instance of the geometry to the scene which only receives light
+    pub fn receiver(
+        &mut self,
+        geom: Arc<BoundableGeometry>,
+        material: Arc<Materials>,
+        transform: AnimatedTransform,
+        tag: String,
+    ) {
+        self.instances
+            .push(Instance::receiver(geom, material, transform, tag));
+    }
+    /// Add an instance of the geometry to the scene which emits and receives light
+    pub fn area_light(
+        &mut self,
+        geom: Arc<SampleableGeometry>,
+        material: Arc<Materials>,
+        emission: AnimatedColor,
+        transform: AnimatedTransform,
+        tag: String,
+    ) {
+        self.instances.push(Instance::area_light(
+            geom, material, emission, transform, tag,
+        ));
+    }
+    /// Add a point light at the origin, transformed by `transform` to its location in the world
+    pub fn point_light(
+        &mut self,
+        transform: AnimatedTransform,
+        emission: AnimatedColor,
+        tag: String,
+    ) {
+        self.instances
+            .push(Instance::point_light(transform, emission, tag));
+    }
+    /// Add an already created instance to the scene
+    pub fn instance(&mut self, instance: Instance) {
+        self.instances.push(instance);
+    }
+    /// Build the scene, returning it along with the render target to write to, the samples
+    /// per pixel and the frame information. Returns an error if the scene is missing its
+    /// camera, integrator, objects or lights, or if names were re-used for textures or materials.
+    pub fn build(self) -> Result<(Scene, RenderTarget, usize, FrameInfo), SceneError> {
+        if let Some(name) = self.conflicts.first() {
+            return Err(SceneError::Build {
+                msg: format!("The {} was added to the scene more than once", name),
+            });
+        }
+        if self.cameras.is_empty() {
+            return Err(SceneError::Build {
+                msg: "The scene must have a camera".to_owned(),
+            });
+        }
+        let integrator = self.integrator.ok_or_else(|| SceneError::Build {
+            msg: "The scene must specify the integrator to render with".to_owned(),
+        })?;
+        if self.instances.is_empty() {
+            return Err(SceneError::Build {
+                msg: "The scene does not have any objects".to_owned(),
+            });
+        }
+        if !self.instances.iter().any(Instance::is_emitter) {
+            return Err(SceneError::Build {
+                msg: "The scene does not have any lights".to_owned(),
+            });
+        }
+        if self.frame_info.end < self.frame_info.start {
+            return Err(SceneError::Build {
+                msg: "End frame must be greater or equal to the starting frame".to_owned(),
+            });
+        }
+        let mut cameras = self.cameras;
+        cameras.sort_by_key(|c| c.active_at);
+        if !self.sampler.valid_block_dim() {
+            return Err(SceneError::Build {
+                msg: format!(
+                    "Block size {:?} must be greater than 0",
+                    self.sampler.block_dim
+                ),
+            });
+        }
+        if let Some(c) = self.crop.filter(|c| !c.fits(self.dims)) {
+            return Err(SceneError::Build {
+                msg: format!(
+                    "The crop window {:?} must be inside the image and cover at least one pixel",
+                    c
+                ),
+            });
+        }
+        let mut scene = Scene::new(
+            cameras,
+            Box::new(integrator),
+            self.materials,
+            self.textures,
+            self.instances,
+            self.frame_info.time,
+        );
+        scene.sampler = self.sampler;
+        let mut rt = RenderTarget::new(self.dims, (2, 2), Box::new(self.filter));
+        rt.set_aovs(&self.aovs);
+        rt.set_max_sample_luminance(self.max_sample_luminance);
+        rt.set_tonemap(self.tonemap);
+        rt.set_color_spaces(self.color_spaces.0, self.color_spaces.1);
+        rt.set_crop(self.crop);
+        Ok((scene, rt, self.spp, self.frame_info))
+    }
+}
+
+#[cfg(test)]
+use crate::{
+    film::{ColorKeyframe, Colorf},
+    geometry::Rectangle,
+    integrator,
+    linalg::{Transform, Vector},
+    material::Matte,
+    texture::{ConstantColor, ConstantScalar},
+};
+
+/// Build the same scene as the JSON `scene::test_scene`
+#[cfg(test)]
+fn test_scene_builder() -> SceneBuilder {
+    let mut builder = SceneBuilder::new((16, 16), 1);
+    builder.filter(filter::MitchellNetravali::new_filter(
+        2.0, 2.0, 0.3333, 0.3333,
+    ));
+    let camera_world = Transform::translate(&Vector::new(0.0, 12.0, -60.0));
+    builder.camera(Camera::new(
+        AnimatedTransform::unanimated(&camera_world),
+        30.0,
+        builder.dimensions(),
+        0.5,
+        0,
+    ));
+    builder.integrator(integrator::Path::new_integrator(3, 8));
+    let white = builder.material(
+        "white",
+        Matte::new_material(
+            Arc::new(ConstantColor::new_texture(Colorf::broadcast(1.0))),
+            Arc::new(ConstantScalar::new_texture(1.0)),
+        ),
+    );
+    builder.receiver(
+        Arc::new(Rectangle::new(2.0, 2.0).into()),
+        white,
+        AnimatedTransform::unanimated(&Transform::scale(&Vector::broadcast(15.0))),
+        "floor".to_owned(),
+    );
+    builder.point_light(
+        AnimatedTransform::unanimated(&Transform::translate(&Vector::new(0.0, 10.0, 0.0))),
+        AnimatedColor::with_keyframes(vec![ColorKeyframe::new(&Colorf::broadcast(100.0), 0.0)]),
+        "light".to_owned(),
+    );
+    builder
+}
+
+#[test]
+fn test_build_matches_json() {
+    let (mut built, built_rt, built_spp, built_frames) = match test_scene_builder().build() {
+        Ok(s) => s,
+        Err(e) => panic!("{}", e),
+    };
+    let (mut loaded, loaded_rt, loaded_spp, loaded_frames) =
+        match Scene::load_value(&super::test_scene(), std::path::Path::new("test.json")) {
+            Ok(s) => s,
+            Err(e) => panic!("{}", e),
+        };
+    assert_eq!(built_rt.dimensions(), loaded_rt.dimensions());
+    assert_eq!(built_spp, loaded_spp);
+    assert_eq!(built_frames.frames, loaded_frames.frames);
+    assert_eq!(built_frames.end, loaded_frames.end);
+    assert_eq!(built.materials.len(), loaded.materials.len());
+    assert_eq!(built.bvh.iter().count(), loaded.bvh.iter().count());
+    built.update_frame(0, 0.0, 0.0);
+    loaded.update_frame(0, 0.0, 0.0);
+    // Both scenes should see the same thing through the camera
+    for y in 0..16 {
+        for x in 0..16 {
+            let px = (x as f32 + 0.5, y as f32 + 0.5);
+            let mut built_ray = built.active_camera().generate_ray(&px, 0.0);
+            let mut loaded_ray = loaded.active_camera().generate_ray(&px, 0.0);
+            let built_hit = built.intersect(&mut built_ray).map(|h| h.instance.tag());
+            let loaded_hit = loaded.intersect(&mut loaded_ray).map(|h| h.instance.tag());
+            assert_eq!(built_hit, loaded_hit, "at pixel {:?}", px);
+            assert_eq!(built_ray.max_t, loaded_ray.max_t, "at pixel {:?}", px);
+        }
+    }
+}
+
+#[test]
+fn test_build_errors() {
+    let build_error = |builder: SceneBuilder| match builder.build() {
+        Ok(_) => panic!("Scene was expected to fail to build"),
+        Err(e) => e.message(),
+    };
+    let mut builder = test_scene_builder();
+    builder.material(
+        "white",
+        Matte::new_material(
+            Arc::new(ConstantColor::new_texture(Colorf::black())),
+            Arc::new(ConstantScalar::new_texture(1.0)),
+        ),
+    );
+    assert_eq!(
+        build_error(builder),
+        "The material 'white' was added to the scene more than once"
+    );
+
+    let mut builder = test_scene_builder();
+    builder.cameras.clear();
+    assert_eq!(build_error(builder), "The scene must have a camera");
+
+    let mut builder = test_scene_builder();
+    builder.integrator = None;
+    assert_eq!(
+        build_error(builder),
+        "The scene must specify the integrator to render with"
+    );
+
+    let mut builder = test_scene_builder();
+    builder.instances.clear();
+    assert_eq!(build_error(builder), "The scene does not have any objects");
+
+    let mut builder = test_scene_builder();
+    builder.instances.retain(|i| !i.is_emitter());
+    assert_eq!(build_error(builder), "The scene does not have any lights");
+
+    let mut builder = test_scene_builder();
+    builder.frames(FrameInfo::new(4, 1.0, 3, 1));
+    assert_eq!(
+        build_error(builder),
+        "End frame must be greater or equal to the starting frame"
+    );
+}
@@ -0,0 +1,466 @@
+//! Provides the deserializer scene files are read into the description in `desc` with.
+//! It reads from the parsed JSON document and tracks the path to the element being read,
+//! so errors in the structure of the file are reported with their location, e.g.
+//! `objects[3].geometry.radius`, and the name of the texture, material or object they're
+//! in.
+//!
+//! Objects picked with a `"type"` member are read as enums whose variant is named by the
+//! type and whose fields are the other members, so the members of each variant are
+//! tracked like any other. The enums in `desc` are declared externally tagged for this
+//! and can only be read with this deserializer. A few enums are told apart by the shape
+//! of their value instead, see `desc::variant_by_shape`.
+
+use std::{error, fmt};
+
+use serde::de::{
+    self, value::StrDeserializer, DeserializeOwned, DeserializeSeed, IntoDeserializer, Unexpected,
+    Visitor,
+};
+use serde_json::{map, Map, Value};
+
+use crate::scene::{desc, Location, SceneError};
+
+/// Read the description `T` from the JSON `value`, which is at `loc` in its file
+pub fn from_value<T: DeserializeOwned>(value: &Value, loc: &Location) -> Result<T, SceneError> {
+    let root = ValueDeserializer {
+        value,
+        path: &Path::Root,
+        name: None,
+    };
+    T::deserialize(root).map_err(|e| e.at(&Path::Root, None).into_scene_error(loc))
+}
+
+/// The path to an element, as a list linked through the deserializers of its parents
+enum Path<'a> {
+    Root,
+    Member(&'a Path<'a>, &'a str),
+    Index(&'a Path<'a>, usize),
+}
+
+#[derive(Debug)]
+enum Segment {
+    Member(String),
+    Index(usize),
+}
+
+impl<'a> Path<'a> {
+    fn segments(&self) -> Vec<Segment> {
+        let mut segments = Vec::new();
+        let mut path = self;
+        loop {
+            path = match *path {
+                Path::Root => break,
+                Path::Member(parent, key) => {
+                    segments.push(Segment::Member(key.to_owned()));
+                    parent
+                }
+                Path::Index(parent, i) => {
+                    segments.push(Segment::Index(i));
+                    parent
+                }
+            };
+        }
+        segments.reverse();
+        segments
+    }
+}
+
+/// An error in the structure of the scene file, which gets the path to the element it's
+/// about as it's passed up through the deserializers
+#[derive(Debug)]
+pub struct Error {
+    msg: String,
+    /// The member of the element the error is about, for missing and unknown members
+    member: Option<String>,
+    missing: bool,
+    path: Option<(Vec<Segment>, Option<String>)>,
+}
+
+impl Error {
+    /// Attach the path of the element being read to the error, if it doesn't have one
+    /// from an element within it
+    fn at(mut self, path: &Path, name: Option<&str>) -> Error {
+        if self.path.is_none() {
+            let mut segments = path.segments();
+            if let Some(member) = self.member.take() {
+                segments.push(Segment::Member(member));
+            }
+            self.path = Some((segments, name.map(str::to_owned)));
+        }
+        self
+    }
+    fn into_scene_error(self, base: &Location) -> SceneError {
+        let (segments, name) = self.path.unwrap_or_default();
+        let mut loc = base.clone();
+        for s in segments {
+            loc = match s {
+                Segment::Member(key) => loc.member(&key),
+                Segment::Index(i) => loc.index(i),
+            };
+        }
+        if let Some(name) = name {
+            loc = loc.named(&name);
+        }
+        if self.missing {
+            loc.missing(self.msg)
+        } else {
+            loc.invalid(self.msg)
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.msg)
+    }
+}
+
+impl error::Error for Error {}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Error {
+        Error {
+            msg: msg.to_string(),
+            member: None,
+            missing: false,
+            path: None,
+        }
+    }
+    fn missing_field(field: &'static str) -> Error {
+        Error {
+            member: Some(field.to_owned()),
+            missing: true,
+            ..Error::custom(format_args!("missing field `{}`", field))
+        }
+    }
+    fn unknown_field(field: &str, expected: &'static [&'static str]) -> Error {
+        // Use serde's message, which lists the expected fields
+        let msg = <de::value::Error as de::Error>::unknown_field(field, expected);
+        Error {
+            member: Some(field.to_owned()),
+            ..Error::custom(msg)
+        }
+    }
+}
+
+/// Deserializes a JSON value at `path`, within the texture, material or object `name`
+struct ValueDeserializer<'a, 'p> {
+    value: &'a Value,
+    path: &'p Path<'p>,
+    name: Option<&'a str>,
+}
+
+impl<'a, 'p> ValueDeserializer<'a, 'p> {
+    fn unexpected(&self) -> Unexpected<'a> {
+        match *self.value {
+            Value::Null => Unexpected::Unit,
+            Value::Bool(b) => Unexpected::Bool(b),
+            Value::Number(ref n) => match (n.as_u64(), n.as_i64()) {
+                (Some(x), _) => Unexpected::Unsigned(x),
+                (_, Some(x)) => Unexpected::Signed(x),
+                _ => Unexpected::Float(n.as_f64().unwrap_or(f64::NAN)),
+            },
+            Value::String(ref s) => Unexpected::Str(s),
+            Value::Array(_) => Unexpected::Seq,
+            Value::Object(_) => Unexpected::Map,
+        }
+    }
+}
+
+impl<'de, 'a, 'p> de::Deserializer<'de> for ValueDeserializer<'a, 'p> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match *self.value {
+            Value::Null => visitor.visit_unit(),
+            Value::Bool(b) => visitor.visit_bool(b),
+            Value::Number(ref n) => match (n.as_u64(), n.as_i64(), n.as_f64()) {
+                (Some(x), _, _) => visitor.visit_u64(x),
+                (_, Some(x), _) => visitor.visit_i64(x),
+                (_, _, Some(x)) => visitor.visit_f64(x),
+                _ => Err(de::Error::custom("invalid number")),
+            },
+            Value::String(ref s) => visitor.visit_str(s),
+            Value::Array(ref a) => {
+                let mut seq = SeqAccess {
+                    iter: a.iter(),
+                    index: 0,
+                    path: self.path,
+                    name: self.name,
+                };
+                let v = visitor.visit_seq(&mut seq)?;
+                match seq.iter.len() {
+                    0 => Ok(v),
+                    _ => Err(de::Error::invalid_length(
+                        a.len(),
+                        &"fewer elements in array",
+                    )),
+                }
+            }
+            Value::Object(ref o) => visitor.visit_map(MapAccess::new(o, None, self)),
+        }
+    }
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match *self.value {
+            Value::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        _: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        if let Some(shape) = desc::variant_by_shape(name, self.value) {
+            let (variant, member) = shape.map_err(de::Error::custom)?;
+            return match member {
+                Some(key) => {
+                    let path = Path::Member(self.path, key);
+                    let content = ValueDeserializer {
+                        value: &self.value[key],
+                        path: &path,
+                        name: self.name,
+                    };
+                    visitor.visit_enum(ShapeAccess { variant, content })
+                }
+                None => visitor.visit_enum(ShapeAccess {
+                    variant,
+                    content: self,
+                }),
+            };
+        }
+        if !desc::is_typed(name) {
+            // Enums of names, e.g. color spaces
+            return match *self.value {
+                Value::String(ref s) => visitor.visit_enum(s.as_str().into_deserializer()),
+                _ => Err(de::Error::invalid_type(self.unexpected(), &"a string")),
+            };
+        }
+        let object = match *self.value {
+            Value::Object(ref o) => o,
+            _ => {
+                let err = de::Error::invalid_type(self.unexpected(), &"an object with a type");
+                return Err(err);
+            }
+        };
+        let variant = match object.get("type") {
+            Some(Value::String(t)) => t.as_str(),
+            Some(t) => {
+                let path = Path::Member(self.path, "type");
+                let ty = ValueDeserializer {
+                    value: t,
+                    path: &path,
+                    name: self.name,
+                };
+                let err = de::Error::invalid_type(ty.unexpected(), &"a type name");
+                return Err(Error::at(err, &path, self.name));
+            }
+            None => return Err(de::Error::missing_field("type")),
+        };
+        // Objects with a type and a name are textures, materials or objects, whose name
+        // is reported for errors within them
+        let name = object.get("name").and_then(Value::as_str).or(self.name);
+        let path = self.path;
+        let content = ValueDeserializer { name, ..self };
+        visitor
+            .visit_enum(TaggedAccess { variant, content })
+            .map_err(|e| e.at(path, name))
+    }
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        unit unit_struct seq tuple tuple_struct map struct identifier
+    }
+}
+
+struct SeqAccess<'a, 'p> {
+    iter: std::slice::Iter<'a, Value>,
+    index: usize,
+    path: &'p Path<'p>,
+    name: Option<&'a str>,
+}
+
+impl<'de, 'a, 'p> de::SeqAccess<'de> for SeqAccess<'a, 'p> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        let value = match self.iter.next() {
+            Some(v) => v,
+            None => return Ok(None),
+        };
+        let path = Path::Index(self.path, self.index);
+        self.index += 1;
+        let element = ValueDeserializer {
+            value,
+            path: &path,
+            name: self.name,
+        };
+        seed.deserialize(element)
+            .map(Some)
+            .map_err(|e| e.at(&path, self.name))
+    }
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+/// Reads the members of an object, skipping the member `skip` if set
+struct MapAccess<'a, 'p> {
+    iter: map::Iter<'a>,
+    skip: Option<&'static str>,
+    value: Option<(&'a str, &'a Value)>,
+    path: &'p Path<'p>,
+    name: Option<&'a str>,
+}
+
+impl<'a, 'p> MapAccess<'a, 'p> {
+    fn new(
+        object: &'a Map<String, Value>,
+        skip: Option<&'static str>,
+        de: ValueDeserializer<'a, 'p>,
+    ) -> MapAccess<'a, 'p> {
+        MapAccess {
+            iter: object.iter(),
+            skip,
+            value: None,
+            path: de.path,
+            name: de.name,
+        }
+    }
+}
+
+impl<'de, 'a, 'p> de::MapAccess<'de> for MapAccess<'a, 'p> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        for (key, value) in self.iter.by_ref() {
+            if Some(key.as_str()) == self.skip {
+                continue;
+            }
+            self.value = Some((key, value));
+            let key: StrDeserializer<Error> = key.as_str().into_deserializer();
+            return seed.deserialize(key).map(Some);
+        }
+        Ok(None)
+    }
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let (key, value) = self
+            .value
+            .take()
+            .expect("next_value_seed called before next_key_seed");
+        let path = Path::Member(self.path, key);
+        let member = ValueDeserializer {
+            value,
+            path: &path,
+            name: self.name,
+        };
+        seed.deserialize(member).map_err(|e| e.at(&path, self.name))
+    }
+}
+
+/// Reads the variant of an object picked by its `"type"` member, whose fields are the
+/// object's other members
+struct TaggedAccess<'a, 'p> {
+    variant: &'a str,
+    content: ValueDeserializer<'a, 'p>,
+}
+
+impl<'de, 'a, 'p> de::EnumAccess<'de> for TaggedAccess<'a, 'p> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self), Error> {
+        let variant: StrDeserializer<Error> = self.variant.into_deserializer();
+        match seed.deserialize(variant) {
+            Ok(v) => Ok((v, self)),
+            Err(e) => {
+                let path = Path::Member(self.content.path, "type");
+                Err(e.at(&path, self.content.name))
+            }
+        }
+    }
+}
+
+impl<'de, 'a, 'p> de::VariantAccess<'de> for TaggedAccess<'a, 'p> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        match self.content.value.as_object() {
+            Some(o) => match o.keys().find(|k| *k != "type") {
+                Some(k) => Err(de::Error::unknown_field(k, &[])),
+                None => Ok(()),
+            },
+            None => Ok(()),
+        }
+    }
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, _: T) -> Result<T::Value, Error> {
+        Err(de::Error::custom("typed objects can't be newtype variants"))
+    }
+    fn tuple_variant<V: Visitor<'de>>(self, _: usize, _: V) -> Result<V::Value, Error> {
+        Err(de::Error::custom("typed objects can't be tuple variants"))
+    }
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let object = self.content.value.as_object().unwrap();
+        visitor.visit_map(MapAccess::new(object, Some("type"), self.content))
+    }
+}
+
+/// Reads the variant of an enum picked by the shape of its value, whose content is the
+/// value or one of its members
+struct ShapeAccess<'a, 'p> {
+    variant: &'static str,
+    content: ValueDeserializer<'a, 'p>,
+}
+
+impl<'de, 'a, 'p> de::EnumAccess<'de> for ShapeAccess<'a, 'p> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self), Error> {
+        let variant: StrDeserializer<Error> = self.variant.into_deserializer();
+        seed.deserialize(variant).map(|v| (v, self))
+    }
+}
+
+impl<'de, 'a, 'p> de::VariantAccess<'de> for ShapeAccess<'a, 'p> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        Err(de::Error::custom("shaped enums can't have unit variants"))
+    }
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        let (path, name) = (self.content.path, self.content.name);
+        seed.deserialize(self.content).map_err(|e| e.at(path, name))
+    }
+    fn tuple_variant<V: Visitor<'de>>(self, _: usize, _: V) -> Result<V::Value, Error> {
+        Err(de::Error::custom("shaped enums can't have tuple variants"))
+    }
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _: &'static [&'static str],
+        _: V,
+    ) -> Result<V::Value, Error> {
+        Err(de::Error::custom("shaped enums can't have struct variants"))
+    }
+}
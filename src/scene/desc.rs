@@ -0,0 +1,651 @@
+//! Defines the typed description of a JSON scene file, which is deserialized from the
+//! file before the scene is loaded. Each object in the file is a struct here, objects
+//! picked with a `"type"` member, like materials or geometry, are enums whose variants
+//! are named by it. Members which aren't part of an object are reported as errors so
+//! misspelled keys aren't silently ignored.
+//!
+//! The description is read with the deserializer in `de`, which reads the typed objects
+//! into their enums and reports errors with the path to the element they're in.
+//!
+//! The description only checks the structure of the file, things like names referring
+//! to materials or textures which exist are checked when loading the scene from it.
+//! `schema::json_schema` describes the same format as a JSON Schema for editors.
+
+use std::convert::TryFrom;
+
+use serde::de::DeserializeOwned;
+use serde_derive::Deserialize;
+use serde_json::Value;
+
+use crate::film::Colorf;
+
+/// A scene file, or a scene file included by another. Everything is optional here
+/// as the film, camera and so on can come from any of the files making up the scene.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields, expecting = "a scene file object")]
+pub struct SceneFile {
+    /// The JSON Schema of the file, for editors
+    #[serde(rename = "$schema")]
+    pub schema: Option<String>,
+    /// Scene files whose textures, materials and objects are added to this one
+    pub include: Option<Files>,
+    pub film: Option<Film>,
+    pub camera: Option<Camera>,
+    pub cameras: Option<Vec<Camera>>,
+    pub integrator: Option<Integrator>,
+    pub sampler: Option<Sampler>,
+    pub textures: Option<Vec<Entry<Texture>>>,
+    pub materials: Option<Vec<Entry<Material>>>,
+    pub objects: Option<Vec<Entry<Object>>>,
+}
+
+/// The files named by an `"include"` member
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged, expecting = "expected a file name or an array of file names")]
+pub enum Files {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// An entry in the textures, materials or objects arrays, which is either the
+/// description of one or `{ "include": ... }` to add the entries of other files.
+/// Which one it is is picked by `variant_by_shape`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Entry<T> {
+    Include(Files),
+    Item(T),
+}
+
+/// Check if the enum `name` is picked by the `"type"` member of an object, instead of
+/// being the name of the variant, like color spaces
+pub(crate) fn is_typed(name: &str) -> bool {
+    matches!(
+        name,
+        "Filter"
+            | "Sampler"
+            | "Integrator"
+            | "Texture"
+            | "Material"
+            | "Object"
+            | "Geometry"
+            | "Transform"
+    )
+}
+
+/// Pick the variant of the enums which are told apart by the shape of their value
+/// instead of a `"type"` member. Gives the variant and the member of the value it's read
+/// from, or the whole value if `None`, and `None` for other enums.
+pub(crate) fn variant_by_shape(
+    name: &str,
+    value: &Value,
+) -> Option<Result<(&'static str, Option<&'static str>), &'static str>> {
+    match name {
+        "Entry" => Some(match value.as_object() {
+            Some(o) if o.contains_key("include") => match o.len() {
+                1 => Ok(("include", Some("include"))),
+                _ => Err("an include entry can't have members other than 'include'"),
+            },
+            _ => Ok(("item", None)),
+        }),
+        // Pick the form by the first element instead of trying each, so errors in
+        // the keyframes are reported as such
+        "Emission" => {
+            let keyframes = value
+                .as_array()
+                .and_then(|a| a.first())
+                .is_some_and(Value::is_object);
+            Some(Ok(if keyframes {
+                ("keyframes", None)
+            } else if value.is_object() {
+                ("blackbody", None)
+            } else {
+                ("color", None)
+            }))
+        }
+        _ => None,
+    }
+}
+
+/// The types of entries in the textures, materials and objects arrays of a scene file
+pub trait Collection: DeserializeOwned {
+    /// The name of the array in the scene file
+    const KEY: &'static str;
+    /// Get the entries of this type in the scene file, if it has the array
+    fn entries(scene: &SceneFile) -> Option<&[Entry<Self>]>;
+    /// Get the entries contained in this one, e.g. the objects of a group
+    fn children(&self) -> Option<&[Entry<Self>]> {
+        None
+    }
+}
+
+impl Collection for Texture {
+    const KEY: &'static str = "textures";
+    fn entries(scene: &SceneFile) -> Option<&[Entry<Texture>]> {
+        scene.textures.as_deref()
+    }
+}
+
+impl Collection for Material {
+    const KEY: &'static str = "materials";
+    fn entries(scene: &SceneFile) -> Option<&[Entry<Material>]> {
+        scene.materials.as_deref()
+    }
+}
+
+impl Collection for Object {
+    const KEY: &'static str = "objects";
+    fn entries(scene: &SceneFile) -> Option<&[Entry<Object>]> {
+        scene.objects.as_deref()
+    }
+    fn children(&self) -> Option<&[Entry<Object>]> {
+        match *self {
+            Object::Group { ref objects, .. } => Some(objects),
+            _ => None,
+        }
+    }
+}
+
+/// The film to render to, see `film`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Film {
+    pub width: usize,
+    pub height: usize,
+    pub samples: usize,
+    pub frames: usize,
+    pub start_frame: usize,
+    pub end_frame: usize,
+    pub scene_time: f32,
+    pub filter: Filter,
+    /// Names of the AOV layers to render, see `film::aov`
+    pub aovs: Option<Vec<String>>,
+    /// Luminance to clamp samples to, to remove fireflies
+    pub max_sample_luminance: Option<f32>,
+    pub tonemap: Option<Tonemap>,
+    pub denoise: Option<Denoise>,
+    /// The window of the image to render as fractions of its size, see `film::crop`
+    pub crop_window: Option<[f32; 4]>,
+    /// The window of the image to render in pixels
+    pub pixel_bounds: Option<[u32; 4]>,
+    /// Render with wavelengths instead of RGB, see `film::spectrum`
+    pub spectral: Option<bool>,
+    pub colorspace: Option<ColorSpaces>,
+}
+
+/// The color spaces the image is rendered and saved in, see `film::colorspace`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ColorSpaces {
+    pub working: Option<ColorSpace>,
+    pub output: Option<ColorSpace>,
+}
+
+/// A color space, `raw` is only for textures holding data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorSpace {
+    Srgb,
+    LinearSrgb,
+    Acescg,
+    Rec2020,
+    DisplayP3,
+    Raw,
+}
+
+/// The display transform for 8-bit images, see `film::tonemap`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Tonemap {
+    pub operator: Option<TonemapOperator>,
+    pub exposure: Option<f32>,
+    pub white_balance: Option<[f32; 3]>,
+}
+
+/// The denoiser run on the image, see `film::denoise`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Denoise {
+    pub radius: Option<u32>,
+    pub color_sigma: Option<f32>,
+    pub albedo_sigma: Option<f32>,
+    pub normal_sigma: Option<f32>,
+    pub depth_sigma: Option<f32>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TonemapOperator {
+    Clamp,
+    Reinhard,
+    Hable,
+    Aces,
+}
+
+/// The reconstruction filter, see `film::filter`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum Filter {
+    MitchellNetravali {
+        width: f32,
+        height: f32,
+        b: f32,
+        c: f32,
+    },
+    Gaussian {
+        width: f32,
+        height: f32,
+        alpha: f32,
+    },
+    Box {
+        width: f32,
+        height: f32,
+    },
+    Triangle {
+        width: f32,
+        height: f32,
+    },
+    Lanczos {
+        width: f32,
+        height: f32,
+        tau: f32,
+    },
+    BlackmanHarris {
+        width: f32,
+        height: f32,
+    },
+}
+
+/// The sampler to render with, see `sampler`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum Sampler {
+    LowDiscrepancy {
+        block_size: Option<[u32; 2]>,
+        seed: Option<u64>,
+    },
+    Adaptive {
+        min_spp: usize,
+        max_spp: usize,
+        block_size: Option<[u32; 2]>,
+        seed: Option<u64>,
+    },
+    Uniform {
+        block_size: Option<[u32; 2]>,
+        seed: Option<u64>,
+    },
+}
+
+/// A camera, see `film::camera`. The camera is placed with a transform, keyframes or
+/// the deprecated position, target and up vectors.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Camera {
+    pub transform: Option<Vec<Transform>>,
+    pub keyframes: Option<Keyframes>,
+    pub position: Option<[f32; 3]>,
+    pub target: Option<[f32; 3]>,
+    pub up: Option<[f32; 3]>,
+    pub fov: Fov,
+    /// Knots of the spline for an animated field of view
+    pub fov_knots: Option<Vec<f32>>,
+    pub fov_spline_degree: Option<usize>,
+    pub shutter_size: Option<f32>,
+    /// The frame the camera becomes active at, when the scene has multiple cameras
+    pub active_at: Option<usize>,
+}
+
+/// The camera field of view in degrees, which can be animated along a spline
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged, expecting = "expected a field of view or an array of them")]
+pub enum Fov {
+    Constant(f32),
+    Animated(Vec<f32>),
+}
+
+/// The integrator to render with, see `integrator`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum Integrator {
+    Pathtracer {
+        min_depth: u32,
+        max_depth: u32,
+    },
+    /// The Whitted integrator's `min_depth` is the maximum ray depth
+    Whitted {
+        min_depth: u32,
+    },
+    NormalsDebug,
+}
+
+/// A named texture materials can use, see `texture`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum Texture {
+    Image {
+        name: String,
+        file: String,
+        colorspace: Option<ColorSpace>,
+    },
+    AnimatedImage {
+        name: String,
+        keyframes: Vec<ImageKeyframe>,
+        colorspace: Option<ColorSpace>,
+    },
+    /// An animated image whose frames are the files `{file_prefix}{frame:05}{file_suffix}`
+    Movie {
+        name: String,
+        file_prefix: String,
+        file_suffix: String,
+        frames: u64,
+        framerate: u64,
+        colorspace: Option<ColorSpace>,
+    },
+}
+
+impl Texture {
+    pub fn name(&self) -> &str {
+        match *self {
+            Texture::Image { ref name, .. }
+            | Texture::AnimatedImage { ref name, .. }
+            | Texture::Movie { ref name, .. } => name,
+        }
+    }
+    /// Get the color space the texture's images are tagged with, if any
+    pub fn colorspace(&self) -> Option<ColorSpace> {
+        match *self {
+            Texture::Image { colorspace, .. }
+            | Texture::AnimatedImage { colorspace, .. }
+            | Texture::Movie { colorspace, .. } => colorspace,
+        }
+    }
+}
+
+/// An image shown from `time` in an animated image
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ImageKeyframe {
+    pub file: String,
+    pub time: f32,
+}
+
+/// A named material objects can use, see `material`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum Material {
+    Glass {
+        name: String,
+        reflect: ColorParam,
+        transmit: ColorParam,
+        eta: ScalarParam,
+        /// Abbe number of the glass, for dispersion in spectral mode
+        abbe: Option<f32>,
+    },
+    RoughGlass {
+        name: String,
+        reflect: ColorParam,
+        transmit: ColorParam,
+        eta: ScalarParam,
+        roughness: ScalarParam,
+    },
+    Matte {
+        name: String,
+        diffuse: ColorParam,
+        roughness: ScalarParam,
+    },
+    Merl {
+        name: String,
+        file: String,
+    },
+    Metal {
+        name: String,
+        refractive_index: ColorParam,
+        absorption_coefficient: ColorParam,
+        roughness: ScalarParam,
+    },
+    Plastic {
+        name: String,
+        diffuse: ColorParam,
+        gloss: ColorParam,
+        roughness: ScalarParam,
+    },
+    SpecularMetal {
+        name: String,
+        refractive_index: ColorParam,
+        absorption_coefficient: ColorParam,
+    },
+}
+
+impl Material {
+    pub fn name(&self) -> &str {
+        match *self {
+            Material::Glass { ref name, .. }
+            | Material::RoughGlass { ref name, .. }
+            | Material::Matte { ref name, .. }
+            | Material::Merl { ref name, .. }
+            | Material::Metal { ref name, .. }
+            | Material::Plastic { ref name, .. }
+            | Material::SpecularMetal { ref name, .. } => name,
+        }
+    }
+}
+
+/// A color material parameter, either a constant color or the name of a texture
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged, expecting = "expected a color or the name of a texture")]
+pub enum ColorParam {
+    Texture(String),
+    Color(Color),
+}
+
+/// A scalar material parameter, either a constant or the name of a texture
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged, expecting = "expected a number or the name of a texture")]
+pub enum ScalarParam {
+    Texture(String),
+    Value(f32),
+}
+
+/// An RGB color, which is written as `[r, g, b]` or `[r, g, b, strength]` where the
+/// color is scaled by the strength
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(try_from = "Vec<f32>")]
+pub struct Color(pub Colorf);
+
+impl TryFrom<Vec<f32>> for Color {
+    type Error = String;
+    fn try_from(v: Vec<f32>) -> Result<Color, String> {
+        match v.len() {
+            3 => Ok(Color(Colorf::new(v[0], v[1], v[2]))),
+            4 => Ok(Color(Colorf::new(v[0], v[1], v[2]) * v[3])),
+            n => Err(format!(
+                "a color must be 3 numbers, optionally followed by its strength, not {}",
+                n
+            )),
+        }
+    }
+}
+
+/// An object in the scene, see `geometry`. Objects are placed with a transform or keyframes
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum Object {
+    /// A light, area lights also need the material and geometry of the light
+    Emitter {
+        name: String,
+        emitter: EmitterKind,
+        emission: Emission,
+        material: Option<String>,
+        geometry: Option<Geometry>,
+        transform: Option<Vec<Transform>>,
+        keyframes: Option<Keyframes>,
+    },
+    Receiver {
+        name: String,
+        material: String,
+        geometry: Geometry,
+        transform: Option<Vec<Transform>>,
+        keyframes: Option<Keyframes>,
+    },
+    /// A group of objects which are placed by the group's transform
+    Group {
+        name: String,
+        objects: Vec<Entry<Object>>,
+        transform: Option<Vec<Transform>>,
+        keyframes: Option<Keyframes>,
+    },
+}
+
+impl Object {
+    pub fn name(&self) -> &str {
+        match *self {
+            Object::Emitter { ref name, .. }
+            | Object::Receiver { ref name, .. }
+            | Object::Group { ref name, .. } => name,
+        }
+    }
+    /// Get the transform and keyframes placing the object, if specified
+    pub fn placement(&self) -> (Option<&[Transform]>, Option<&Keyframes>) {
+        match *self {
+            Object::Emitter {
+                ref transform,
+                ref keyframes,
+                ..
+            }
+            | Object::Receiver {
+                ref transform,
+                ref keyframes,
+                ..
+            }
+            | Object::Group {
+                ref transform,
+                ref keyframes,
+                ..
+            } => (transform.as_ref().map(|t| &t[..]), keyframes.as_ref()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EmitterKind {
+    Point,
+    Area,
+}
+
+/// The emission of a light, either a color, a list of colors to animate between or
+/// the temperature of a blackbody. Which one it is is picked by `variant_by_shape`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Emission {
+    Color(Color),
+    Keyframes(Vec<ColorKeyframe>),
+    Blackbody(Blackbody),
+}
+
+/// A blackbody emitter, with its temperature in Kelvin, see `film::spectrum`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Blackbody {
+    pub temperature: f32,
+    pub strength: Option<f32>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ColorKeyframe {
+    pub time: f32,
+    pub color: Color,
+}
+
+/// The geometry of an object, see `geometry`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum Geometry {
+    Sphere {
+        radius: f32,
+    },
+    Disk {
+        radius: f32,
+        inner_radius: f32,
+    },
+    Plane,
+    Rectangle {
+        width: f32,
+        height: f32,
+    },
+    /// The model named `model` in the OBJ file `file`
+    Mesh {
+        file: String,
+        model: String,
+    },
+}
+
+impl Geometry {
+    /// Get the `"type"` of the geometry
+    pub fn type_name(&self) -> &'static str {
+        match *self {
+            Geometry::Sphere { .. } => "sphere",
+            Geometry::Disk { .. } => "disk",
+            Geometry::Plane => "plane",
+            Geometry::Rectangle { .. } => "rectangle",
+            Geometry::Mesh { .. } => "mesh",
+        }
+    }
+}
+
+/// One transformation in a list of them, the list is applied from first to last.
+/// Rotations are in degrees
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum Transform {
+    Translate {
+        translation: [f32; 3],
+    },
+    Scale {
+        scaling: Scaling,
+    },
+    RotateX {
+        rotation: f32,
+    },
+    RotateY {
+        rotation: f32,
+    },
+    RotateZ {
+        rotation: f32,
+    },
+    Rotate {
+        rotation: f32,
+        axis: [f32; 3],
+    },
+    /// A transformation matrix, given as an array of its rows
+    Matrix {
+        matrix: [[f32; 4]; 4],
+    },
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(
+    untagged,
+    expecting = "expected a scaling factor or an array of 3 scaling factors"
+)]
+pub enum Scaling {
+    Uniform(f32),
+    Vector([f32; 3]),
+}
+
+/// Transforms animated along a B-spline, see `linalg::AnimatedTransform`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Keyframes {
+    pub control_points: Vec<ControlPoint>,
+    pub knots: Vec<f32>,
+    /// The degree of the spline, 3 if not specified
+    pub degree: Option<usize>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ControlPoint {
+    pub transform: Vec<Transform>,
+}
@@ -0,0 +1,133 @@
+//! Defines the errors that can be returned when loading a scene. Errors that
+//! come from the content of the scene file carry a `Location` describing where
+//! in the file the problem was found, e.g. `objects[3].material`, along
+//! with the name of the material, texture or object being loaded.
+
+use std::{error, fmt, io, path::PathBuf};
+
+use serde_json;
+
+/// The location of an element within a scene file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Location {
+    /// The scene file the element was read from
+    pub file: PathBuf,
+    /// Path to the element within the JSON document, e.g. `objects[3].material`,
+    /// or the line for pbrt scene files, e.g. `line 12`
+    pub path: String,
+    /// Name of the material, texture or object containing the element, if known
+    pub name: Option<String>,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.file.display())?;
+        if !self.path.is_empty() {
+            write!(f, ": {}", self.path)?;
+        }
+        if let Some(ref name) = self.name {
+            write!(f, " ('{}')", name)?;
+        }
+        Ok(())
+    }
+}
+
+/// Errors that can occur while loading or saving a scene
+#[derive(Debug)]
+pub enum SceneError {
+    /// The scene file could not be opened or read
+    Io { file: PathBuf, err: io::Error },
+    /// The scene file is not valid JSON
+    Parse {
+        file: PathBuf,
+        err: serde_json::Error,
+    },
+    /// A required element is missing
+    Missing { loc: Location, msg: String },
+    /// An element has the wrong type, an unrecognized value or member, or
+    /// refers to something which doesn't exist
+    Invalid { loc: Location, msg: String },
+    /// A file referenced by the scene, e.g. an image texture, could not be loaded
+    Resource {
+        loc: Location,
+        file: PathBuf,
+        msg: String,
+    },
+    /// The scene does not contain any objects to render
+    NoObjects { file: PathBuf },
+    /// The scene does not contain any lights to illuminate it
+    NoLights { file: PathBuf },
+    /// The scene file could not be written
+    Save { file: PathBuf, err: io::Error },
+    /// The scene contains something which can't be described in a scene file,
+    /// e.g. a mesh which wasn't loaded from a file
+    Unsupported { msg: String },
+    /// A scene created with `SceneBuilder` is incomplete or inconsistent, e.g. it has
+    /// no camera or two materials with the same name
+    Build { msg: String },
+}
+
+impl SceneError {
+    /// Get the location in the scene file the error occured at, if the error
+    /// is about a specific element of the scene
+    pub fn location(&self) -> Option<&Location> {
+        match *self {
+            SceneError::Missing { ref loc, .. }
+            | SceneError::Invalid { ref loc, .. }
+            | SceneError::Resource { ref loc, .. } => Some(loc),
+            _ => None,
+        }
+    }
+    /// Get the error message without the location information
+    pub fn message(&self) -> String {
+        match *self {
+            SceneError::Io { ref err, .. } => format!("Failed to read scene file: {}", err),
+            SceneError::Parse { ref err, .. } => format!("JSON parsing error: {}", err),
+            SceneError::Missing { ref msg, .. } | SceneError::Invalid { ref msg, .. } => {
+                msg.clone()
+            }
+            SceneError::Resource {
+                ref file, ref msg, ..
+            } => format!("Failed to load '{}': {}", file.display(), msg),
+            SceneError::NoObjects { .. } => {
+                "Aborting: the scene does not have any objects!".to_owned()
+            }
+            SceneError::NoLights { .. } => {
+                "Aborting: the scene does not have any lights!".to_owned()
+            }
+            SceneError::Save { ref err, .. } => format!("Failed to write scene file: {}", err),
+            SceneError::Unsupported { ref msg } => format!("Unable to save the scene: {}", msg),
+            SceneError::Build { ref msg } => msg.clone(),
+        }
+    }
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SceneError::Io { ref file, .. }
+            | SceneError::Parse { ref file, .. }
+            | SceneError::NoObjects { ref file }
+            | SceneError::NoLights { ref file }
+            | SceneError::Save { ref file, .. } => {
+                write!(f, "{}: {}", file.display(), self.message())
+            }
+            SceneError::Missing { ref loc, .. }
+            | SceneError::Invalid { ref loc, .. }
+            | SceneError::Resource { ref loc, .. } => write!(f, "{}: {}", loc, self.message()),
+            SceneError::Unsupported { .. } | SceneError::Build { .. } => {
+                write!(f, "{}", self.message())
+            }
+        }
+    }
+}
+
+impl error::Error for SceneError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            SceneError::Io { ref err, .. } | SceneError::Save { ref err, .. } => Some(err),
+            SceneError::Parse { ref err, .. } => Some(err),
+            _ => None,
+        }
+    }
+}
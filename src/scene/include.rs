@@ -0,0 +1,474 @@
+//! Support for splitting a scene across multiple JSON files. An `"include"` member
+//! can name a single file or an array of files, relative paths are relative to the
+//! file containing the include.
+//!
+//! At the root of a scene file the included files are scene files themselves, whose
+//! textures, materials and objects are added to the scene. They may also specify the
+//! film, camera, integrator or sampler, as long as only one file does so.
+//!
+//! ```json
+//! {
+//!     "include": ["materials.json", "props.json"],
+//!     ...
+//! }
+//! ```
+//!
+//! Within the `textures`, `materials` or `objects` arrays (including the objects of a
+//! group) an entry of the form `{ "include": "file.json" }` is replaced by the entries
+//! from the file, which is either an array of entries or an object with an array of the
+//! same name, e.g. `"materials"`. This lets a library file be included at the root or
+//! just for one of its arrays.
+//!
+//! Each file is read once, before the scene is loaded, so that include cycles can be
+//! reported along with where the offending include is.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde_json::Value;
+
+#[cfg(test)]
+use super::edit_scene;
+use super::{
+    de,
+    desc::{Collection, Entry, Files, Material, Object, SceneFile, Texture},
+    Location, SceneError,
+};
+
+/// The files included by a scene, either directly or by other included files
+#[derive(Default)]
+pub struct Includes {
+    /// The included files, keyed by their path and the array they provide entries
+    /// for, or None for scene files included at the root
+    files: HashMap<(PathBuf, Option<&'static str>), Included>,
+}
+
+/// The content of an included file
+struct Included {
+    scene: SceneFile,
+    /// If the file is an array of entries, which are put in the matching array of `scene`
+    array: bool,
+}
+
+impl Includes {
+    /// Read all the files included by the scene file `file` whose content is `root`
+    pub fn load(root: &SceneFile, file: &Path) -> Result<Includes, SceneError> {
+        let mut includes = Includes::default();
+        let mut stack = vec![fs::canonicalize(file).unwrap_or_else(|_| file.to_path_buf())];
+        includes.walk_scene(root, &Location::root(file), &mut stack)?;
+        Ok(includes)
+    }
+    /// Get the path and content of the scene file `name` included at `loc`
+    pub fn scene(&self, loc: &Location, name: &str) -> Result<(&Path, &SceneFile), SceneError> {
+        let path = resolve(&loc.file, name);
+        self.files
+            .get_key_value(&(path, None))
+            .map(|(k, inc)| (k.0.as_path(), &inc.scene))
+            .ok_or_else(|| loc.resource(Path::new(name), "The file was not loaded"))
+    }
+    /// Get the entries of the array `list` found at `loc`, replacing any include entries
+    /// with the entries of the included files. Returns the entries along with where each is
+    pub fn entries<'a, T: Collection>(
+        &'a self,
+        list: &'a [Entry<T>],
+        loc: &Location,
+    ) -> Result<Vec<(Location, &'a T)>, SceneError> {
+        let mut entries = Vec::new();
+        for (i, e) in list.iter().enumerate() {
+            match *e {
+                Entry::Include(ref files) => {
+                    for (inc_loc, name) in named_files(files, &loc.index(i).member("include")) {
+                        let path = resolve(&inc_loc.file, name);
+                        let (path, inc) = self
+                            .files
+                            .get_key_value(&(path, Some(T::KEY)))
+                            .map(|(k, inc)| (k.0.as_path(), inc))
+                            .ok_or_else(|| {
+                                inc_loc.resource(Path::new(name), "The file was not loaded")
+                            })?;
+                        let root = Location::root(path);
+                        let root = if inc.array { root } else { root.member(T::KEY) };
+                        // Checked when the file was loaded
+                        let list = T::entries(&inc.scene).unwrap_or(&[]);
+                        entries.extend(self.entries(list, &root)?);
+                    }
+                }
+                Entry::Item(ref t) => entries.push((loc.index(i), t)),
+            }
+        }
+        Ok(entries)
+    }
+    /// Find the files included by the scene file and load them
+    fn walk_scene(
+        &mut self,
+        scene: &SceneFile,
+        loc: &Location,
+        stack: &mut Vec<PathBuf>,
+    ) -> Result<(), SceneError> {
+        if let Some(ref files) = scene.include {
+            for (inc_loc, name) in named_files(files, &loc.member("include")) {
+                self.include(&inc_loc, name, None, stack)?;
+            }
+        }
+        self.walk_collections(scene, loc, stack)
+    }
+    /// Find the files included by the textures, materials and objects of the scene file
+    fn walk_collections(
+        &mut self,
+        scene: &SceneFile,
+        loc: &Location,
+        stack: &mut Vec<PathBuf>,
+    ) -> Result<(), SceneError> {
+        for key in &[Texture::KEY, Material::KEY, Object::KEY] {
+            self.walk_key(scene, key, &loc.member(key), stack)?;
+        }
+        Ok(())
+    }
+    /// Find the files included by the entries of the array `key` of the scene file,
+    /// where the array is at `loc`
+    fn walk_key(
+        &mut self,
+        scene: &SceneFile,
+        key: &str,
+        loc: &Location,
+        stack: &mut Vec<PathBuf>,
+    ) -> Result<(), SceneError> {
+        match key {
+            Texture::KEY => self.walk_list(scene.textures.as_deref().unwrap_or(&[]), loc, stack),
+            Material::KEY => self.walk_list(scene.materials.as_deref().unwrap_or(&[]), loc, stack),
+            _ => self.walk_list(scene.objects.as_deref().unwrap_or(&[]), loc, stack),
+        }
+    }
+    /// Find the files included by the entries of the array at `loc`
+    fn walk_list<T: Collection>(
+        &mut self,
+        list: &[Entry<T>],
+        loc: &Location,
+        stack: &mut Vec<PathBuf>,
+    ) -> Result<(), SceneError> {
+        for (i, e) in list.iter().enumerate() {
+            match *e {
+                Entry::Include(ref files) => {
+                    for (inc_loc, name) in named_files(files, &loc.index(i).member("include")) {
+                        self.include(&inc_loc, name, Some(T::KEY), stack)?;
+                    }
+                }
+                Entry::Item(ref t) => {
+                    if let Some(children) = t.children() {
+                        self.walk_list(children, &loc.index(i).member(T::KEY), stack)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+    /// Read the file `name` included at `loc` and load any files it includes in turn.
+    /// `key` is the array the file provides entries for, or None if it's a scene file
+    fn include(
+        &mut self,
+        loc: &Location,
+        name: &str,
+        key: Option<&'static str>,
+        stack: &mut Vec<PathBuf>,
+    ) -> Result<(), SceneError> {
+        let path = resolve(&loc.file, name);
+        let canonical = fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+        if let Some(i) = stack.iter().position(|p| *p == canonical) {
+            let cycle: Vec<_> = stack[i..]
+                .iter()
+                .chain(Some(&canonical))
+                .map(|p| p.display().to_string())
+                .collect();
+            return Err(loc.invalid(format!("Include cycle detected: {}", cycle.join(" -> "))));
+        }
+        let file_key = (path.clone(), key);
+        if self.files.contains_key(&file_key) {
+            return Ok(());
+        }
+
+        let content = fs::read_to_string(&path)
+            .map_err(|e| loc.resource(&path, format!("Failed to read included file: {}", e)))?;
+        let array = content.trim_start().starts_with('[');
+        if array && key.is_none() {
+            return Err(loc.invalid("An included scene file must be a JSON object"));
+        }
+        let root = Location::root(&path);
+        let scene = parse(&content, key, array, &root)?;
+        stack.push(canonical);
+        match key {
+            None => self.walk_scene(&scene, &root, stack)?,
+            Some(key) => {
+                if !array && !has_array(&scene, key) {
+                    return Err(root.member(key).missing(format!(
+                        "An included file must be an array of {} or an object with a '{}' array",
+                        key, key
+                    )));
+                }
+                let list_loc = if array { root } else { root.member(key) };
+                self.walk_key(&scene, key, &list_loc, stack)?
+            }
+        }
+        stack.pop();
+        self.files.insert(file_key, Included { scene, array });
+        Ok(())
+    }
+}
+
+/// Parse an included file, `key` is the array the file provides entries for or None if
+/// it's a scene file. If `array` is set the file is an array of those entries.
+fn parse(
+    content: &str,
+    key: Option<&str>,
+    array: bool,
+    root: &Location,
+) -> Result<SceneFile, SceneError> {
+    let value: Value = serde_json::from_str(content).map_err(|err| SceneError::Parse {
+        file: root.file.clone(),
+        err,
+    })?;
+    if !array {
+        return de::from_value(&value, root);
+    }
+    let mut scene = SceneFile::default();
+    match key {
+        Some(Texture::KEY) => scene.textures = Some(de::from_value(&value, root)?),
+        Some(Material::KEY) => scene.materials = Some(de::from_value(&value, root)?),
+        _ => scene.objects = Some(de::from_value(&value, root)?),
+    }
+    Ok(scene)
+}
+
+/// Check if the scene file has the array of entries `key`
+fn has_array(scene: &SceneFile, key: &str) -> bool {
+    match key {
+        Texture::KEY => scene.textures.is_some(),
+        Material::KEY => scene.materials.is_some(),
+        _ => scene.objects.is_some(),
+    }
+}
+
+/// Get the file names of an `"include"` member at `loc`, along with where each is
+pub fn named_files<'a>(files: &'a Files, loc: &Location) -> Vec<(Location, &'a str)> {
+    match *files {
+        Files::One(ref f) => vec![(loc.clone(), f.as_str())],
+        Files::Many(ref fs) => fs
+            .iter()
+            .enumerate()
+            .map(|(i, f)| (loc.index(i), f.as_str()))
+            .collect(),
+    }
+}
+
+/// Find the file `name` referenced by the scene file `file`, relative paths are
+/// relative to the directory containing `file`
+pub fn resolve(file: &Path, name: &str) -> PathBuf {
+    let path = PathBuf::from(name);
+    match file.parent() {
+        Some(dir) if path.is_relative() => dir.join(path),
+        _ => path,
+    }
+}
+
+/// Write the JSON files to a new temporary directory, returning the directory
+#[cfg(test)]
+fn write_files(test: &str, files: &[(&str, Value)]) -> PathBuf {
+    let dir =
+        std::env::temp_dir().join(format!("aperture_include_{}_{}", test, std::process::id()));
+    for (name, value) in files {
+        let path = dir.join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, value.to_string()).unwrap();
+    }
+    dir
+}
+
+#[cfg(test)]
+fn include_test_scene() -> Value {
+    serde_json::json!({
+        "include": "lib/materials.json",
+        "film": {
+            "width": 4, "height": 4, "samples": 1, "frames": 1,
+            "start_frame": 0, "end_frame": 0, "scene_time": 0,
+            "filter": { "type": "gaussian", "width": 1.0, "height": 1.0, "alpha": 2.0 }
+        },
+        "camera": { "fov": 30, "transform": [] },
+        "integrator": { "type": "pathtracer", "min_depth": 1, "max_depth": 2 },
+        "materials": [
+            { "name": "white", "type": "matte", "diffuse": [1, 1, 1], "roughness": 1.0 }
+        ],
+        "objects": [
+            {
+                "name": "floor",
+                "type": "receiver",
+                "material": "white",
+                "geometry": { "type": "plane" },
+                "transform": []
+            },
+            { "include": "lib/props.json" },
+            {
+                "name": "group",
+                "type": "group",
+                "transform": [],
+                "objects": [{ "include": ["lib/props.json"] }]
+            }
+        ]
+    })
+}
+
+#[test]
+fn test_includes() {
+    let dir = write_files(
+        "load",
+        &[
+            ("scene.json", include_test_scene()),
+            (
+                "lib/materials.json",
+                serde_json::json!({
+                    "textures": [{ "name": "checker", "type": "image", "file": "tex.png" }],
+                    "materials": [
+                        { "name": "red", "type": "matte", "diffuse": "checker", "roughness": 1.0 }
+                    ]
+                }),
+            ),
+            (
+                "lib/props.json",
+                serde_json::json!([
+                    {
+                        "name": "ball",
+                        "type": "receiver",
+                        "material": "red",
+                        "geometry": { "type": "sphere", "radius": 1.0 },
+                        "transform": []
+                    },
+                    { "include": "lights.json" }
+                ]),
+            ),
+            (
+                "lib/lights.json",
+                serde_json::json!({ "objects": [{
+                    "name": "light",
+                    "type": "emitter",
+                    "emitter": "point",
+                    "emission": [1, 1, 1, 10],
+                    "transform": []
+                }]}),
+            ),
+        ],
+    );
+    // The image is found relative to the library file including it
+    image::save_buffer(dir.join("lib/tex.png"), &[255; 12], 2, 2, image::RGB(8)).unwrap();
+
+    let (scene, ..) = match super::Scene::load_file(dir.join("scene.json").to_str().unwrap()) {
+        Ok(s) => s,
+        Err(e) => panic!("{}", e),
+    };
+    let mut names: Vec<_> = scene.bvh.iter().map(|i| i.tag().to_owned()).collect();
+    names.sort();
+    assert_eq!(names, vec!["ball", "ball", "floor", "light", "light"]);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_include_errors() {
+    let error = |test: &str, files: &[(&str, Value)]| {
+        let dir = write_files(test, files);
+        let err = match super::Scene::load_file(dir.join("scene.json").to_str().unwrap()) {
+            Ok(_) => panic!("Scene was expected to fail to load"),
+            Err(e) => e,
+        };
+        let loc = err.location().cloned().map(|l| {
+            let file = l.file.strip_prefix(&dir).unwrap().to_path_buf();
+            (file, l.path)
+        });
+        let msg = err.message().replace(&format!("{}/", dir.display()), "");
+        fs::remove_dir_all(dir).unwrap();
+        (loc, msg)
+    };
+    // Only include the materials, the props are tested above
+    let base = edit_scene(include_test_scene(), "/objects/2", None);
+    let base = edit_scene(base, "/objects/1", None);
+    let lib = serde_json::json!({
+        "materials": [{ "name": "red", "type": "matte", "diffuse": [1, 0, 0], "roughness": 1.0 }]
+    });
+
+    let scene = edit_scene(
+        base.clone(),
+        "/materials/0/name",
+        Some(serde_json::json!("red")),
+    );
+    assert_eq!(
+        error(
+            "conflict",
+            &[("scene.json", scene), ("lib/materials.json", lib.clone())]
+        ),
+        (
+            Some((
+                PathBuf::from("lib/materials.json"),
+                "materials[0]".to_owned()
+            )),
+            "name conflicts with the entry at scene.json: materials[0]".to_owned()
+        )
+    );
+
+    let mut film_lib = lib.clone();
+    film_lib["film"] = base.clone()["film"].clone();
+    assert_eq!(
+        error(
+            "film",
+            &[
+                ("scene.json", base.clone()),
+                ("lib/materials.json", film_lib)
+            ]
+        ),
+        (
+            Some((PathBuf::from("lib/materials.json"), "film".to_owned())),
+            "'film' conflicts with the one specified in scene.json".to_owned()
+        )
+    );
+
+    let (loc, msg) = error("missing", &[("scene.json", base.clone())]);
+    assert_eq!(
+        loc,
+        Some((PathBuf::from("scene.json"), "include".to_owned()))
+    );
+    assert!(msg.starts_with("Failed to load 'lib/materials.json': Failed to read included file"));
+
+    let (loc, msg) = error(
+        "cycle",
+        &[
+            ("scene.json", base.clone()),
+            (
+                "lib/materials.json",
+                serde_json::json!({ "include": "../scene.json" }),
+            ),
+        ],
+    );
+    assert_eq!(
+        loc,
+        Some((PathBuf::from("lib/materials.json"), "include".to_owned()))
+    );
+    assert_eq!(
+        msg,
+        "Include cycle detected: scene.json -> lib/materials.json -> scene.json"
+    );
+
+    let scene = edit_scene(
+        base,
+        "/objects/1",
+        Some(serde_json::json!({ "include": "lib/materials.json" })),
+    );
+    assert_eq!(
+        error(
+            "entries",
+            &[("scene.json", scene), ("lib/materials.json", lib)]
+        ),
+        (
+            Some((PathBuf::from("lib/materials.json"), "objects".to_owned())),
+            "An included file must be an array of objects or an object with a 'objects' array"
+                .to_owned()
+        )
+    );
+}
@@ -0,0 +1,330 @@
+//! Checks a scene description for problems which don't stop it from loading but are
+//! likely mistakes, or would only show up partway through a long render. The checks are:
+//!
+//! - Materials which aren't used by any object
+//! - Lights with black emission, and area lights whose geometry has no area
+//! - Scenes without any lights
+//! - Singular transforms, e.g. scaling by 0, which can't be inverted
+//! - Cameras which become active after the last frame, or a first camera which becomes
+//!   active after the first frame, leaving no camera to render it with
+//!
+//! Scene files can be checked from the command line with `aperture <scenefile> --lint`.
+
+use std::{collections::HashSet, fmt, path::Path, slice};
+
+use crate::{
+    linalg::Matrix4,
+    scene::{
+        desc::{self, SceneFile},
+        find_root, load_transform, read_desc, root_entries, root_files, Includes, Location,
+        SceneError,
+    },
+};
+
+/// A likely mistake found in the scene file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    /// Where in the scene file the problem was found
+    pub loc: Location,
+    pub msg: String,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.loc, self.msg)
+    }
+}
+
+/// Read the JSON scene file and check it for problems. Returns an error if the file
+/// can't be read or doesn't have the structure of a scene file.
+pub fn lint_file(file: &str) -> Result<Vec<Warning>, SceneError> {
+    let file_path = Path::new(file);
+    lint(&read_desc(file_path)?, file_path)
+}
+
+/// Check the description of the scene read from `file` and the files it includes for
+/// problems. Returns an error if an included file can't be read.
+pub fn lint(desc: &SceneFile, file: &Path) -> Result<Vec<Warning>, SceneError> {
+    let includes = Includes::load(desc, file)?;
+    let roots = root_files(&includes, desc, file)?;
+    let mut linter = Linter {
+        includes: &includes,
+        used_materials: HashSet::new(),
+        lights: 0,
+        warnings: Vec::new(),
+    };
+
+    let (_, root) = find_root(&roots, &["film"])?;
+    let film = root.film.as_ref();
+    let (loc, root) = find_root(&roots, &["cameras", "camera"])?;
+    let cameras = match (&root.cameras, &root.camera) {
+        (Some(c), _) => c
+            .iter()
+            .enumerate()
+            .map(|(i, c)| (loc.member("cameras").index(i), c))
+            .collect(),
+        (None, Some(c)) => vec![(loc.member("camera"), c)],
+        (None, None) => Vec::new(),
+    };
+    linter.cameras(&cameras, film);
+
+    linter.objects(&root_entries::<desc::Object>(&includes, &roots, None)?)?;
+    for (loc, m) in root_entries::<desc::Material>(&includes, &roots, None)? {
+        if !linter.used_materials.contains(m.name()) {
+            linter.warn(
+                &loc.named(m.name()),
+                "The material isn't used by any object",
+            );
+        }
+    }
+    if linter.lights == 0 {
+        linter.warn(
+            &roots[0].0.member("objects"),
+            "The scene has no lights, at least one is required to render it",
+        );
+    }
+    Ok(linter.warnings)
+}
+
+/// Tracks what's been found while walking through the scene
+struct Linter<'a> {
+    includes: &'a Includes,
+    /// Names of the materials referenced by objects
+    used_materials: HashSet<&'a str>,
+    /// Number of lights in the scene
+    lights: usize,
+    warnings: Vec<Warning>,
+}
+
+impl<'a> Linter<'a> {
+    fn warn<S: Into<String>>(&mut self, loc: &Location, msg: S) {
+        self.warnings.push(Warning {
+            loc: loc.clone(),
+            msg: msg.into(),
+        });
+    }
+    fn cameras(&mut self, cameras: &[(Location, &desc::Camera)], film: Option<&desc::Film>) {
+        for (loc, cam) in cameras {
+            if let Some(ref t) = cam.transform {
+                self.transform(t, &loc.member("transform"));
+            }
+            if let Some(ref k) = cam.keyframes {
+                self.keyframes(k, &loc.member("keyframes"));
+            }
+        }
+        let film = match film {
+            Some(f) => f,
+            None => return,
+        };
+        let active_at = |cam: &desc::Camera| cam.active_at.unwrap_or(0);
+        for (loc, cam) in cameras {
+            if active_at(cam) > film.end_frame {
+                self.warn(
+                    &loc.member("active_at"),
+                    format!(
+                        "The camera becomes active at frame {}, after the last frame {}",
+                        active_at(cam),
+                        film.end_frame
+                    ),
+                );
+            }
+        }
+        if let Some((loc, first)) = cameras.iter().min_by_key(|c| active_at(c.1)) {
+            if active_at(first) > film.start_frame {
+                self.warn(
+                    &loc.member("active_at"),
+                    format!(
+                        "No camera is active at the first frame {}, the first camera becomes \
+                         active at frame {}",
+                        film.start_frame,
+                        active_at(first)
+                    ),
+                );
+            }
+        }
+    }
+    fn objects(&mut self, objects: &[(Location, &'a desc::Object)]) -> Result<(), SceneError> {
+        for &(ref loc, o) in objects {
+            let loc = loc.clone().named(o.name());
+            match o.placement() {
+                (_, Some(k)) => self.keyframes(k, &loc.member("keyframes")),
+                (Some(t), None) => self.transform(t, &loc.member("transform")),
+                (None, None) => {}
+            }
+            match *o {
+                desc::Object::Emitter {
+                    emitter,
+                    ref emission,
+                    ref material,
+                    ref geometry,
+                    ..
+                } => {
+                    self.lights += 1;
+                    let black = match *emission {
+                        desc::Emission::Color(c) => c.0.is_black(),
+                        desc::Emission::Keyframes(ref keys) => {
+                            keys.iter().all(|k| k.color.0.is_black())
+                        }
+                        desc::Emission::Blackbody(ref b) => b.strength == Some(0.0),
+                    };
+                    if black {
+                        self.warn(&loc.member("emission"), "The light's emission is black");
+                    }
+                    if emitter == desc::EmitterKind::Area {
+                        if let Some(ref m) = *material {
+                            self.used_materials.insert(m.as_str());
+                        }
+                        if geometry.as_ref().is_some_and(zero_area) {
+                            self.warn(
+                                &loc.member("geometry"),
+                                "The area light's geometry has zero area",
+                            );
+                        }
+                    }
+                }
+                desc::Object::Receiver { ref material, .. } => {
+                    self.used_materials.insert(material.as_str());
+                }
+                desc::Object::Group { ref objects, .. } => {
+                    let includes = self.includes;
+                    self.objects(&includes.entries(objects, &loc.member("objects"))?)?;
+                }
+            }
+        }
+        Ok(())
+    }
+    fn keyframes(&mut self, keyframes: &desc::Keyframes, loc: &Location) {
+        for (i, p) in keyframes.control_points.iter().enumerate() {
+            let loc = loc.member("control_points").index(i).member("transform");
+            self.transform(&p.transform, &loc);
+        }
+    }
+    fn transform(&mut self, transforms: &[desc::Transform], loc: &Location) {
+        let det = transform_matrix(transforms).determinant();
+        if det.is_nan() || det.abs() < 1e-12 {
+            self.warn(
+                loc,
+                "The transform is singular and can't be inverted, e.g. it scales by 0",
+            );
+        }
+    }
+}
+
+/// Compute the matrix of the transforms without computing its inverse, which
+/// `load_transform` would fail to do for a singular matrix
+fn transform_matrix(transforms: &[desc::Transform]) -> Matrix4 {
+    transforms.iter().fold(Matrix4::identity(), |m, t| {
+        let next = match *t {
+            desc::Transform::Matrix { ref matrix } => matrix.iter().flatten().collect(),
+            _ => load_transform(slice::from_ref(t)).mat,
+        };
+        next * m
+    })
+}
+
+/// Check if the geometry has no surface area
+fn zero_area(geometry: &desc::Geometry) -> bool {
+    match *geometry {
+        desc::Geometry::Sphere { radius } => radius == 0.0,
+        desc::Geometry::Disk {
+            radius,
+            inner_radius,
+        } => radius <= inner_radius,
+        desc::Geometry::Rectangle { width, height } => width == 0.0 || height == 0.0,
+        desc::Geometry::Plane | desc::Geometry::Mesh { .. } => false,
+    }
+}
+
+#[cfg(test)]
+use serde_json::{json, Value};
+
+/// Check the scene produces warnings at exactly the JSON `paths`
+#[cfg(test)]
+fn assert_warnings(scene: &Value, paths: &[&str]) {
+    let desc: SceneFile =
+        super::de::from_value(scene, &Location::root(Path::new("test.json"))).unwrap();
+    let warnings = match lint(&desc, Path::new("test.json")) {
+        Ok(w) => w,
+        Err(e) => panic!("{}", e),
+    };
+    let found: Vec<_> = warnings.iter().map(|w| &w.loc.path[..]).collect();
+    assert_eq!(found, paths, "for warnings {:?}", warnings);
+}
+
+#[test]
+fn test_lint() {
+    use crate::scene::{edit_scene, test_scene};
+    let scene = test_scene();
+    assert_warnings(&scene, &[]);
+
+    let unused = json!({ "name": "unused", "type": "matte", "diffuse": [1, 0, 0], "roughness": 1 });
+    assert_warnings(
+        &edit_scene(scene.clone(), "/materials/1", Some(unused)),
+        &["materials[1]"],
+    );
+
+    assert_warnings(
+        &edit_scene(
+            scene.clone(),
+            "/objects/1/emission",
+            Some(json!([1, 1, 1, 0])),
+        ),
+        &["objects[1].emission"],
+    );
+    let keyframes = json!([
+        { "time": 0, "color": [0, 0, 0] },
+        { "time": 1, "color": [1, 1, 1] }
+    ]);
+    assert_warnings(
+        &edit_scene(scene.clone(), "/objects/1/emission", Some(keyframes)),
+        &[],
+    );
+    let area_light = json!({
+        "name": "area",
+        "type": "emitter",
+        "emitter": "area",
+        "emission": [1, 1, 1],
+        "material": "white",
+        "geometry": { "type": "disk", "radius": 1, "inner_radius": 1 },
+        "transform": []
+    });
+    assert_warnings(
+        &edit_scene(scene.clone(), "/objects/1", Some(area_light)),
+        &["objects[1].geometry"],
+    );
+    assert_warnings(&edit_scene(scene.clone(), "/objects/1", None), &["objects"]);
+
+    let zero_scale = json!([
+        { "type": "translate", "translation": [0, 1, 0] },
+        { "type": "scale", "scaling": [1, 0, 1] }
+    ]);
+    assert_warnings(
+        &edit_scene(scene.clone(), "/objects/0/transform", Some(zero_scale)),
+        &["objects[0].transform"],
+    );
+    let matrix = json!([{
+        "type": "matrix",
+        "matrix": [[1, 0, 0, 0], [0, 1, 0, 0], [1, 0, 0, 0], [0, 0, 0, 1]]
+    }]);
+    assert_warnings(
+        &edit_scene(scene.clone(), "/camera/transform", Some(matrix)),
+        &["camera.transform"],
+    );
+
+    assert_warnings(
+        &edit_scene(scene.clone(), "/camera/active_at", Some(json!(1))),
+        &["camera.active_at", "camera.active_at"],
+    );
+    let scene = edit_scene(scene, "/film/frames", Some(json!(4)));
+    let scene = edit_scene(scene, "/film/end_frame", Some(json!(3)));
+    let mut cameras = vec![scene["camera"].clone(); 3];
+    cameras[1]["active_at"] = json!(2);
+    cameras[2]["active_at"] = json!(5);
+    let scene = edit_scene(scene, "/camera", None);
+    let scene = edit_scene(scene, "/cameras", Some(Value::Array(cameras)));
+    assert_warnings(&scene, &["cameras[2].active_at"]);
+
+    // Images don't need to be a multiple of the block size
+    let scene = edit_scene(scene, "/film/width", Some(json!(20)));
+    assert_warnings(&scene, &["cameras[2].active_at"]);
+}
@@ -0,0 +1,2592 @@
+//! Defines the scene struct which contains the various objects defining the scene.
+//! This includes the geometry, instances of the geometry, the camera and so on.
+//!
+//! # Scene JSON Files
+//! The scene file format has four required sections: a camera, an integrator,
+//! a list of materials and a list of objects and lights. The root object in the
+//! JSON file should contain one of each of these.
+//!
+//! ```json
+//! {
+//!     "camera": {...},
+//!     "integrator": {...},
+//!     "materials": [...],
+//!     "objects": [...]
+//! }
+//! ```
+//!
+//! For more information on each object see the corresponding modules:
+//!
+//! - Camera: See film/camera
+//! - Integrator: See integrator
+//! - Sampler: See sampler, this section is optional
+//! - Materials: See materials
+//! - Objects: See geometry
+//!
+//! Scene files can be checked for likely mistakes before rendering them with `lint`.
+//! Scenes can also be created directly from Rust values with `SceneBuilder`.
+//! Scenes can be split across multiple files with `"include"`, see the include module.
+//! A loaded scene can be written back out to a JSON scene file with `Scene::save_file`.
+//! The file is first read into the typed description in `desc`, whose structure is
+//! also available as a JSON Schema for editors from `schema::json_schema`.
+//!
+//! # Errors
+//! Loading returns a `SceneError` describing what went wrong and where in the
+//! scene file instead of aborting the process. Files which don't match the structure
+//! of a scene file, e.g. with a missing or misspelled member, and other problems are
+//! reported with the path to the offending element, e.g. `objects[3].material` for a
+//! material which doesn't exist. Only files which aren't valid JSON are reported with
+//! the line and column.
+//!
+
+use std::{collections::HashMap, fs::File, io::prelude::*, path::Path, sync::Arc};
+
+use serde_json::Value;
+
+use crate::{
+    exec::BLOCK_DIM,
+    film::{
+        filter::{self, Filters},
+        tonemap::{self, Tonemap},
+        AnimatedColor, Aov, Blackbody, Camera, ColorKeyframe, ColorSpace, Colorf, CropWindow,
+        Denoiser, FrameInfo, RenderTarget,
+    },
+    geometry::{
+        BoundableGeometry, Disk, Instance, Intersection, Mesh, Rectangle, SampleableGeometry,
+        Sphere, BVH,
+    },
+    integrator::{self, Integrators},
+    linalg::{AnimatedTransform, Keyframe, Point, Ray, Transform, Vector},
+    material::{Glass, Materials, Matte, Merl, Metal, Plastic, RoughGlass, SpecularMetal},
+    sampler::{SamplerConfig, SamplerType},
+    texture::{self, Textures},
+};
+
+use self::desc::{Collection, SceneFile};
+use self::include::Includes;
+pub use self::{
+    builder::SceneBuilder,
+    error::{Location, SceneError},
+};
+
+pub mod builder;
+pub mod de;
+pub mod desc;
+pub mod error;
+mod include;
+pub mod lint;
+pub mod pbrt;
+mod save;
+pub mod schema;
+
+impl Location {
+    /// The location of the root of the scene file `file`
+    fn root(file: &Path) -> Location {
+        Location {
+            file: file.to_path_buf(),
+            path: String::new(),
+            name: None,
+        }
+    }
+    /// The location of the member `key` of the element at this location
+    fn member(&self, key: &str) -> Location {
+        let path = if self.path.is_empty() {
+            key.to_owned()
+        } else {
+            format!("{}.{}", self.path, key)
+        };
+        Location {
+            path,
+            ..self.clone()
+        }
+    }
+    /// The location of the element `i` of the array at this location
+    fn index(&self, i: usize) -> Location {
+        Location {
+            path: format!("{}[{}]", self.path, i),
+            ..self.clone()
+        }
+    }
+    /// Attach the name of the material, texture or object being loaded to
+    /// this location, which is kept for the locations beneath it
+    fn named(mut self, name: &str) -> Location {
+        self.name = Some(name.to_owned());
+        self
+    }
+    /// Create a `SceneError::Missing` error for the element at this location
+    fn missing<S: Into<String>>(&self, msg: S) -> SceneError {
+        SceneError::Missing {
+            loc: self.clone(),
+            msg: msg.into(),
+        }
+    }
+    /// Create a `SceneError::Invalid` error for the element at this location
+    fn invalid<S: Into<String>>(&self, msg: S) -> SceneError {
+        SceneError::Invalid {
+            loc: self.clone(),
+            msg: msg.into(),
+        }
+    }
+    /// Create a `SceneError::Resource` error for a file referenced by the element
+    fn resource<S: Into<String>>(&self, file: &Path, msg: S) -> SceneError {
+        SceneError::Resource {
+            loc: self.clone(),
+            file: file.to_path_buf(),
+            msg: msg.into(),
+        }
+    }
+}
+
+/// The textures loaded for the scene, which materials refer to by name
+struct LoadedTextures {
+    textures: HashMap<String, Arc<Textures>>,
+}
+impl LoadedTextures {
+    /// Get the color texture for the parameter at `loc`, which is either the name
+    /// of a texture to look up or a constant color
+    fn color(&self, param: &desc::ColorParam, loc: &Location) -> Result<Arc<Textures>, SceneError> {
+        match *param {
+            desc::ColorParam::Texture(ref name) => self.find(name, loc),
+            desc::ColorParam::Color(c) => Ok(Arc::new(texture::ConstantColor::new_texture(c.0))),
+        }
+    }
+    /// Get the scalar texture for the parameter at `loc`, which is either the name
+    /// of a texture to look up or a constant value
+    fn scalar(
+        &self,
+        param: &desc::ScalarParam,
+        loc: &Location,
+    ) -> Result<Arc<Textures>, SceneError> {
+        match *param {
+            desc::ScalarParam::Texture(ref name) => self.find(name, loc),
+            desc::ScalarParam::Value(x) => Ok(Arc::new(texture::ConstantScalar::new_texture(x))),
+        }
+    }
+    fn find(&self, name: &str, loc: &Location) -> Result<Arc<Textures>, SceneError> {
+        self.textures.get(name).cloned().ok_or_else(|| {
+            loc.invalid(format!(
+                "Texture '{}' was not found in the texture list",
+                name
+            ))
+        })
+    }
+}
+
+/// The scene containing the objects and camera configuration we'd like to render,
+/// shared immutably among the ray tracing threads
+pub struct Scene {
+    pub cameras: Vec<Camera>,
+    active_camera: Option<usize>,
+    pub bvh: BVH<Instance>,
+    pub integrator: Box<Integrators>,
+    /// The named materials objects in the scene can use
+    pub materials: HashMap<String, Arc<Materials>>,
+    /// The named textures materials in the scene can use
+    pub textures: HashMap<String, Arc<Textures>>,
+    /// The sampler the scene should be rendered with
+    pub sampler: SamplerConfig,
+}
+
+impl Scene {
+    /// Load the scene described by the JSON scene file. Returns the scene along with
+    /// the render target to write to, the samples per pixel and the frame information
+    pub fn load_file(file: &str) -> Result<(Scene, RenderTarget, usize, FrameInfo), SceneError> {
+        let file_path = Path::new(file);
+        Scene::load_desc(&read_desc(file_path)?, file_path)
+    }
+    /// Load the scene from an already parsed JSON document, used by the tests to
+    /// load scenes they've edited
+    #[cfg(test)]
+    fn load_value(
+        data: &Value,
+        file: &Path,
+    ) -> Result<(Scene, RenderTarget, usize, FrameInfo), SceneError> {
+        let desc = de::from_value(data, &Location::root(file))?;
+        Scene::load_desc(&desc, file)
+    }
+    /// Load the scene from the description of a scene file. `file` is the path the
+    /// description was read from, used to find files referenced relative to the scene
+    /// and to report errors.
+    pub fn load_desc(
+        desc: &SceneFile,
+        file: &Path,
+    ) -> Result<(Scene, RenderTarget, usize, FrameInfo), SceneError> {
+        let includes = Includes::load(desc, file)?;
+        let roots = root_files(&includes, desc, file)?;
+
+        let (loc, root) = find_root(&roots, &["film"])?;
+        let film = root.film.as_ref().ok_or_else(|| {
+            loc.member("film")
+                .missing("The scene must specify a film to write to")
+        })?;
+        let (rt, spp, frame_info) = load_film(film, &loc.member("film"))?;
+        let (loc, root) = find_root(&roots, &["cameras", "camera"])?;
+        let cameras = load_cameras(root, loc, rt.dimensions())?;
+        let (loc, root) = find_root(&roots, &["integrator"])?;
+        let integrator = root.integrator.as_ref().ok_or_else(|| {
+            loc.member("integrator")
+                .missing("The scene must specify the integrator to render with")
+        })?;
+        let integrator = load_integrator(integrator);
+        let (loc, root) = find_root(&roots, &["sampler"])?;
+        let sampler = match root.sampler {
+            Some(ref s) => load_sampler(s, &loc.member("sampler"))?,
+            None => SamplerConfig::default(),
+        };
+        let textures = load_textures(
+            &root_entries::<desc::Texture>(&includes, &roots, None)?,
+            rt.working_space(),
+        )?;
+        let materials = load_materials(
+            &root_entries::<desc::Material>(
+                &includes,
+                &roots,
+                Some("An array of materials is required"),
+            )?,
+            &textures,
+        )?;
+        // mesh cache is a map of file_name -> (map of mesh name -> mesh)
+        let mut mesh_cache = HashMap::new();
+        let instances = load_objects(
+            &includes,
+            &materials,
+            rt.working_space(),
+            &mut mesh_cache,
+            &root_entries::<desc::Object>(
+                &includes,
+                &roots,
+                Some("The scene must specify a list of objects"),
+            )?,
+        )?;
+
+        if instances.is_empty() {
+            return Err(SceneError::NoObjects {
+                file: file.to_path_buf(),
+            });
+        }
+        if !instances.iter().any(Instance::is_emitter) {
+            return Err(SceneError::NoLights {
+                file: file.to_path_buf(),
+            });
+        }
+        let mut scene = Scene::new(
+            cameras,
+            integrator,
+            materials,
+            textures.textures,
+            instances,
+            frame_info.time,
+        );
+        scene.sampler = sampler;
+        Ok((scene, rt, spp, frame_info))
+    }
+    /// Assemble the scene from its loaded parts, building the BVH over the instances
+    /// for the time span of the scene
+    fn new(
+        cameras: Vec<Camera>,
+        integrator: Box<Integrators>,
+        materials: HashMap<String, Arc<Materials>>,
+        textures: HashMap<String, Arc<Textures>>,
+        instances: Vec<Instance>,
+        time: f32,
+    ) -> Scene {
+        Scene {
+            cameras,
+            active_camera: None,
+            // TODO: Read time parameters from the scene file, update BVH every few frames
+            bvh: BVH::new(4, instances, 0.0, time),
+            integrator,
+            materials,
+            textures,
+            sampler: SamplerConfig::default(),
+        }
+    }
+
+    /// Test the ray for intersections against the objects in the scene.
+    /// Returns Some(Intersection) if an intersection was found and None if not.
+    pub fn intersect(&self, ray: &mut Ray) -> Option<Intersection> {
+        let wavelengths = ray.wavelengths;
+        self.bvh
+            .intersect(ray, |r, i| i.intersect(r))
+            .map(|mut hit| {
+                hit.wavelengths = wavelengths;
+                hit
+            })
+    }
+    /// Advance the time the scene is currently displaying to the time range passed
+    pub fn update_frame(&mut self, frame: usize, start: f32, end: f32) {
+        let cam = match self.active_camera {
+            Some(c) => {
+                if c != self.cameras.len() - 1 && self.cameras[c + 1].active_at == frame {
+                    println!("Changing to camera {}", c + 1);
+                    c + 1
+                } else {
+                    c
+                }
+            }
+            None => {
+                // If there's no active camera we need to find the right one to start with
+                // based on what frame we're beginning the rendering at. e.g. if you have a
+                // camera become active at frame 5 and pass --start-frame 5, you should render
+                // from that camera. Frames before any camera is active use the first one.
+                let c = self
+                    .cameras
+                    .iter()
+                    .take_while(|x| x.active_at <= frame)
+                    .count()
+                    .saturating_sub(1);
+                println!("Selecting starting camera {}", c);
+                c
+            }
+        };
+        self.active_camera = Some(cam);
+        self.cameras[cam].update_frame(start, end);
+        // TODO: How often to re-build the BVH?
+        let shutter_time = self.cameras[cam].shutter_time();
+        println!(
+            "Frame {}: re-building bvh for {} to {}",
+            frame, shutter_time.0, shutter_time.1
+        );
+        self.bvh.rebuild(shutter_time.0, shutter_time.1);
+    }
+    /// Get the active camera for the current frame
+    pub fn active_camera(&self) -> &Camera {
+        &self.cameras[self
+            .active_camera
+            .expect("Update frame must be called before active_camera")]
+    }
+}
+
+/// Read the description of the scene from the JSON scene file
+fn read_desc(file: &Path) -> Result<SceneFile, SceneError> {
+    let mut content = String::new();
+    if let Err(err) = File::open(file).and_then(|mut f| f.read_to_string(&mut content)) {
+        return Err(SceneError::Io {
+            file: file.to_path_buf(),
+            err,
+        });
+    }
+    let value: Value = serde_json::from_str(&content[..]).map_err(|err| SceneError::Parse {
+        file: file.to_path_buf(),
+        err,
+    })?;
+    de::from_value(&value, &Location::root(file))
+}
+
+/// Get the scene file followed by the scene files it includes, along with where each is
+fn root_files<'a>(
+    includes: &'a Includes,
+    root: &'a SceneFile,
+    file: &'a Path,
+) -> Result<Vec<(Location, &'a SceneFile)>, SceneError> {
+    let loc = Location::root(file);
+    let mut roots = Vec::new();
+    if let Some(ref files) = root.include {
+        for (inc_loc, name) in include::named_files(files, &loc.member("include")) {
+            let (file, scene) = includes.scene(&inc_loc, name)?;
+            roots.extend(root_files(includes, scene, file)?);
+        }
+    }
+    roots.insert(0, (loc, root));
+    Ok(roots)
+}
+
+/// Check if the scene file specifies the member `key`
+fn has_member(scene: &SceneFile, key: &str) -> bool {
+    match key {
+        "film" => scene.film.is_some(),
+        "camera" => scene.camera.is_some(),
+        "cameras" => scene.cameras.is_some(),
+        "integrator" => scene.integrator.is_some(),
+        "sampler" => scene.sampler.is_some(),
+        _ => false,
+    }
+}
+
+/// Find the scene file specifying one of the `keys`, e.g. the film, returning an error
+/// if more than one of the files specifies it. Returns the root scene file if none do
+fn find_root<'b, 'a>(
+    roots: &'b [(Location, &'a SceneFile)],
+    keys: &[&str],
+) -> Result<&'b (Location, &'a SceneFile), SceneError> {
+    let find_key = |r: &SceneFile| keys.iter().find(|k| has_member(r, k));
+    let mut found = roots.iter().filter(|r| find_key(r.1).is_some());
+    match (found.next(), found.next()) {
+        (Some(first), Some(second)) => {
+            let key = find_key(second.1).unwrap();
+            Err(second.0.member(key).invalid(format!(
+                "'{}' conflicts with the one specified in {}",
+                keys.join("' or '"),
+                first.0.file.display()
+            )))
+        }
+        (Some(r), None) => Ok(r),
+        _ => Ok(&roots[0]),
+    }
+}
+
+/// Get the entries of type `T` from the scene file and the scene files it includes.
+/// If `missing` is passed the array is required and it's returned as the error if no
+/// file has it
+fn root_entries<'a, T: Collection>(
+    includes: &'a Includes,
+    roots: &[(Location, &'a SceneFile)],
+    missing: Option<&str>,
+) -> Result<Vec<(Location, &'a T)>, SceneError> {
+    let mut entries = Vec::new();
+    let mut found = false;
+    for (loc, r) in roots {
+        if let Some(list) = T::entries(r) {
+            entries.extend(includes.entries(list, &loc.member(T::KEY))?);
+            found = true;
+        }
+    }
+    match (found, missing) {
+        (false, Some(missing)) => Err(roots[0].0.member(T::KEY).missing(missing)),
+        _ => Ok(entries),
+    }
+}
+
+/// Load the film described at `loc`. Returns the render target along with the
+/// samples per pixel and frame information
+fn load_film(
+    film: &desc::Film,
+    loc: &Location,
+) -> Result<(RenderTarget, usize, FrameInfo), SceneError> {
+    if film.end_frame < film.start_frame {
+        return Err(loc
+            .member("end_frame")
+            .invalid("End frame must be greater or equal to the starting frame"));
+    }
+    let frame_info = FrameInfo::new(
+        film.frames,
+        film.scene_time,
+        film.start_frame,
+        film.end_frame,
+    );
+    let mut rt = RenderTarget::new((film.width, film.height), (2, 2), load_filter(&film.filter));
+    if let Some(ref names) = film.aovs {
+        let mut aovs = Vec::new();
+        for (i, name) in names.iter().enumerate() {
+            match Aov::from_name(name) {
+                Some(a) => aovs.push(a),
+                None => {
+                    let expected: Vec<_> = Aov::ALL.iter().map(Aov::name).collect();
+                    return Err(loc.member("aovs").index(i).invalid(format!(
+                        "Unknown AOV '{}', expected one of: {}",
+                        name,
+                        expected.join(", ")
+                    )));
+                }
+            }
+        }
+        rt.set_aovs(&aovs);
+    }
+    if let Some(max) = film.max_sample_luminance {
+        if max.is_nan() || max <= 0.0 {
+            return Err(loc
+                .member("max_sample_luminance")
+                .invalid("Max sample luminance must be greater than 0"));
+        }
+        rt.set_max_sample_luminance(Some(max));
+    }
+    if let Some(ref t) = film.tonemap {
+        rt.set_tonemap(load_tonemap(t, &loc.member("tonemap"))?);
+    }
+    if let Some(ref d) = film.denoise {
+        rt.set_denoiser(Some(load_denoiser(d, &loc.member("denoise"))?));
+    }
+    rt.set_spectral(film.spectral.unwrap_or(false));
+    if let Some(ref c) = film.colorspace {
+        let loc = loc.member("colorspace");
+        let space = |c: Option<desc::ColorSpace>, key: &str, default: ColorSpace| match c {
+            Some(c) => load_color_space(c).ok_or_else(|| {
+                loc.member(key)
+                    .invalid("The raw color space can only be used for textures")
+            }),
+            None => Ok(default),
+        };
+        rt.set_color_spaces(
+            space(c.working, "working", ColorSpace::LinearSrgb)?,
+            space(c.output, "output", ColorSpace::Srgb)?,
+        );
+    }
+    let dim = (film.width, film.height);
+    let crop = match (film.crop_window, film.pixel_bounds) {
+        (Some(_), Some(_)) => {
+            return Err(loc
+                .member("pixel_bounds")
+                .invalid("Only one of crop_window and pixel_bounds can be set"));
+        }
+        (Some(w), None) => {
+            let window = CropWindow::from_normalized(dim, (w[0], w[1]), (w[2], w[3]));
+            if w.iter().any(|x| !(0.0..=1.0).contains(x)) || !window.fits(dim) {
+                return Err(loc
+                    .member("crop_window")
+                    .invalid("Crop window must be inside [0, 1] and cover at least one pixel"));
+            }
+            Some(window)
+        }
+        (None, Some(b)) => {
+            let window = CropWindow::new((b[0], b[2]), (b[1], b[3]));
+            if !window.fits(dim) {
+                return Err(loc.member("pixel_bounds").invalid(format!(
+                    "Pixel bounds must be inside the {}x{} image and cover at least one pixel",
+                    dim.0, dim.1
+                )));
+            }
+            Some(window)
+        }
+        (None, None) => None,
+    };
+    rt.set_crop(crop);
+    Ok((rt, film.samples, frame_info))
+}
+/// Create the sampler described
+fn load_sampler(sampler: &desc::Sampler, loc: &Location) -> Result<SamplerConfig, SceneError> {
+    let (sampler, block_size, seed) = match *sampler {
+        desc::Sampler::LowDiscrepancy { block_size, seed } => {
+            (SamplerType::LowDiscrepancy, block_size, seed)
+        }
+        desc::Sampler::Adaptive {
+            min_spp,
+            max_spp,
+            block_size,
+            seed,
+        } => {
+            if min_spp == 0 {
+                return Err(loc
+                    .member("min_spp")
+                    .invalid("Adaptive sampler must take at least 1 sample per pixel"));
+            }
+            if max_spp < min_spp {
+                return Err(loc
+                    .member("max_spp")
+                    .invalid("Max samples per pixel must be greater or equal to min_spp"));
+            }
+            (SamplerType::Adaptive { min_spp, max_spp }, block_size, seed)
+        }
+        desc::Sampler::Uniform { block_size, seed } => (SamplerType::Uniform, block_size, seed),
+    };
+    let mut config = SamplerConfig::new(sampler, block_size.map_or(BLOCK_DIM, |b| (b[0], b[1])));
+    config.seed = seed;
+    if !config.valid_block_dim() {
+        return Err(loc
+            .member("block_size")
+            .invalid("Block size must be greater than 0"));
+    }
+    Ok(config)
+}
+/// Create the display transform described, missing properties are left as the default
+fn load_tonemap(desc: &desc::Tonemap, loc: &Location) -> Result<Tonemap, SceneError> {
+    let mut tonemap = Tonemap::default();
+    if let Some(op) = desc.operator {
+        tonemap.operator = match op {
+            desc::TonemapOperator::Clamp => tonemap::Operator::Clamp,
+            desc::TonemapOperator::Reinhard => tonemap::Operator::Reinhard,
+            desc::TonemapOperator::Hable => tonemap::Operator::Hable,
+            desc::TonemapOperator::Aces => tonemap::Operator::Aces,
+        };
+    }
+    if let Some(ev) = desc.exposure {
+        tonemap.exposure = ev;
+    }
+    if let Some(wb) = desc.white_balance {
+        if wb.iter().any(|c| c.is_nan() || *c <= 0.0) {
+            return Err(loc
+                .member("white_balance")
+                .invalid("White balance must be greater than 0"));
+        }
+        tonemap.white_balance = Colorf::new(wb[0], wb[1], wb[2]);
+    }
+    Ok(tonemap)
+}
+/// Load the denoiser, using the defaults for the properties not set
+fn load_denoiser(desc: &desc::Denoise, loc: &Location) -> Result<Denoiser, SceneError> {
+    let mut denoiser = Denoiser::default();
+    if let Some(r) = desc.radius {
+        denoiser.radius = r;
+    }
+    let sigmas = [
+        ("color_sigma", desc.color_sigma, &mut denoiser.color_sigma),
+        (
+            "albedo_sigma",
+            desc.albedo_sigma,
+            &mut denoiser.albedo_sigma,
+        ),
+        (
+            "normal_sigma",
+            desc.normal_sigma,
+            &mut denoiser.normal_sigma,
+        ),
+        ("depth_sigma", desc.depth_sigma, &mut denoiser.depth_sigma),
+    ];
+    for (name, value, sigma) in sigmas {
+        if let Some(s) = value {
+            if s.is_nan() || s <= 0.0 {
+                return Err(loc
+                    .member(name)
+                    .invalid("Denoiser sigmas must be greater than 0"));
+            }
+            *sigma = s;
+        }
+    }
+    Ok(denoiser)
+}
+/// Create the reconstruction filter described
+fn load_filter(filter: &desc::Filter) -> Box<Filters> {
+    match *filter {
+        desc::Filter::MitchellNetravali {
+            width,
+            height,
+            b,
+            c,
+        } => Box::new(filter::MitchellNetravali::new_filter(width, height, b, c)),
+        desc::Filter::Gaussian {
+            width,
+            height,
+            alpha,
+        } => Box::new(filter::Gaussian::new_filter(width, height, alpha)),
+        desc::Filter::Box { width, height } => {
+            Box::new(filter::BoxFilter::new_filter(width, height))
+        }
+        desc::Filter::Triangle { width, height } => {
+            Box::new(filter::Triangle::new_filter(width, height))
+        }
+        desc::Filter::Lanczos { width, height, tau } => {
+            Box::new(filter::Lanczos::new_filter(width, height, tau))
+        }
+        desc::Filter::BlackmanHarris { width, height } => {
+            Box::new(filter::BlackmanHarris::new_filter(width, height))
+        }
+    }
+}
+
+/// Load the cameras or single camera specified by the scene file at `loc`
+fn load_cameras(
+    scene: &SceneFile,
+    loc: &Location,
+    dim: (usize, usize),
+) -> Result<Vec<Camera>, SceneError> {
+    match (&scene.cameras, &scene.camera) {
+        (Some(c), _) => {
+            let mut cameras = Vec::new();
+            for (i, cam) in c.iter().enumerate() {
+                cameras.push(load_camera(cam, &loc.member("cameras").index(i), dim)?);
+            }
+            cameras.sort_by_key(|c| c.active_at);
+            Ok(cameras)
+        }
+        (None, Some(c)) => Ok(vec![load_camera(c, &loc.member("camera"), dim)?]),
+        (None, None) => Err(loc.member("camera").missing("Error: A camera is required!")),
+    }
+}
+/// Load the camera described at `loc`, returns an error if the camera is incorrectly specified
+fn load_camera(
+    cam: &desc::Camera,
+    loc: &Location,
+    dim: (usize, usize),
+) -> Result<Camera, SceneError> {
+    let shutter_size = cam.shutter_size.unwrap_or(0.5);
+    let active_at = cam.active_at.unwrap_or(0);
+    let transform = match (&cam.keyframes, &cam.transform) {
+        (Some(k), _) => load_keyframes(k),
+        (None, Some(t)) => AnimatedTransform::unanimated(&load_transform(t)),
+        (None, None) => {
+            println!(
+                "Warning! Specifying transforms with pos, target and up vectors is deprecated!"
+            );
+            let pos = cam.position.ok_or_else(|| {
+                loc.member("position")
+                    .missing("The camera must specify a position")
+            })?;
+            let target = cam.target.ok_or_else(|| {
+                loc.member("target")
+                    .missing("The camera must specify a target")
+            })?;
+            let up = cam.up.ok_or_else(|| {
+                loc.member("up")
+                    .missing("The camera must specify an up vector")
+            })?;
+            let t = Transform::look_at(
+                &Point::new(pos[0], pos[1], pos[2]),
+                &Point::new(target[0], target[1], target[2]),
+                &vector(up),
+            );
+            AnimatedTransform::unanimated(&t)
+        }
+    };
+    match cam.fov {
+        desc::Fov::Animated(ref fovs) => {
+            let fov_knots = cam.fov_knots.clone().ok_or_else(|| {
+                loc.member("fov_knots")
+                    .missing("Animated field of view must specify spline knots")
+            })?;
+            let fov_spline_degree = cam.fov_spline_degree.ok_or_else(|| {
+                loc.member("fov_spline_degree")
+                    .missing("Animated fov spline must have degree")
+            })?;
+            Ok(Camera::animated_fov(
+                transform,
+                fovs.clone(),
+                fov_knots,
+                fov_spline_degree,
+                dim,
+                shutter_size,
+                active_at,
+            ))
+        }
+        desc::Fov::Constant(fov) => Ok(Camera::new(transform, fov, dim, shutter_size, active_at)),
+    }
+}
+
+/// Create the integrator described
+fn load_integrator(integrator: &desc::Integrator) -> Box<Integrators> {
+    match *integrator {
+        desc::Integrator::Pathtracer {
+            min_depth,
+            max_depth,
+        } => Box::new(integrator::Path::new_integrator(min_depth, max_depth)),
+        desc::Integrator::Whitted { min_depth } => {
+            Box::new(integrator::Whitted::new_integrator(min_depth))
+        }
+        desc::Integrator::NormalsDebug => {
+            Box::new(Integrators::NormalsDebug(integrator::NormalsDebug))
+        }
+    }
+}
+
+/// Get the color space described, or None if it's raw
+fn load_color_space(c: desc::ColorSpace) -> Option<ColorSpace> {
+    match c {
+        desc::ColorSpace::Srgb => Some(ColorSpace::Srgb),
+        desc::ColorSpace::LinearSrgb => Some(ColorSpace::LinearSrgb),
+        desc::ColorSpace::Acescg => Some(ColorSpace::Acescg),
+        desc::ColorSpace::Rec2020 => Some(ColorSpace::Rec2020),
+        desc::ColorSpace::DisplayP3 => Some(ColorSpace::DisplayP3),
+        desc::ColorSpace::Raw => None,
+    }
+}
+
+/// Load the image file referenced at `loc`, file paths are relative to the scene file.
+/// The image is in `color_space`, or None if it's raw, and its colors are converted
+/// to the `working` color space. Scalars are decoded as well if `decode_scalars` is set
+fn load_image(
+    loc: &Location,
+    file: &str,
+    color_space: Option<ColorSpace>,
+    decode_scalars: bool,
+    working: ColorSpace,
+) -> Result<Textures, SceneError> {
+    let file_path = include::resolve(&loc.file, file);
+    texture::Image::load_texture(&file_path, color_space, decode_scalars, working)
+        .map_err(|e| loc.resource(&file_path, format!("Failed to load image file: {}", e)))
+}
+
+/// Load the textures used by the scene's materials, returns an error if a texture can't be
+/// loaded. Image files are found relative to the file the texture is in and their colors
+/// converted to the `working` color space.
+fn load_textures(
+    entries: &[(Location, &desc::Texture)],
+    working: ColorSpace,
+) -> Result<LoadedTextures, SceneError> {
+    let mut textures = HashMap::new();
+    let mut defined = HashMap::new();
+    for (loc, t) in entries {
+        let name = t.name();
+        let loc = loc.clone().named(name);
+        // Make sure names are unique to avoid people accidently overwriting textures
+        if let Some(other) = defined.insert(name, loc.clone()) {
+            return Err(loc.invalid(name_conflict(&other)));
+        }
+        // Untagged images are sRGB colors, but scalars read from them are data
+        let (color_space, decode_scalars) = match t.colorspace() {
+            Some(c) => (load_color_space(c), true),
+            None => (Some(ColorSpace::Srgb), false),
+        };
+        let image = |loc: &Location, file: &str| {
+            load_image(loc, file, color_space, decode_scalars, working)
+        };
+        let tex = match **t {
+            desc::Texture::Image { ref file, .. } => image(&loc.member("file"), file)?,
+            desc::Texture::AnimatedImage { ref keyframes, .. } => {
+                if keyframes.len() < 2 {
+                    return Err(loc
+                        .member("keyframes")
+                        .invalid("animated_image must have at least 2 frames"));
+                }
+                let mut frames = Vec::with_capacity(keyframes.len());
+                for (i, f) in keyframes.iter().enumerate() {
+                    let file_loc = loc.member("keyframes").index(i).member("file");
+                    frames.push((f.time, image(&file_loc, &f.file)?));
+                }
+                texture::AnimatedImage::new_texture(frames)
+            }
+            desc::Texture::Movie {
+                ref file_prefix,
+                ref file_suffix,
+                frames: total_frames,
+                framerate,
+                ..
+            } => {
+                // A movie is a generated animated_image, based on a format string to find the
+                // keyframes and a framerate to play back at
+                if total_frames < 2 {
+                    return Err(loc
+                        .member("frames")
+                        .invalid("A movie must have at least 2 frames"));
+                }
+                let mut frames = Vec::with_capacity(total_frames as usize);
+                for frame in 0..total_frames {
+                    // There's no support for runtime-string formatting, maybe some lib out there
+                    // for it but a lot of them seem targetted for web development and are too heavy.
+                    let file = format!("{}{:05}{}", file_prefix, frame, file_suffix);
+                    let time = frame as f32 / framerate as f32;
+                    frames.push((time, image(&loc, &file)?));
+                }
+                texture::AnimatedImage::new_texture(frames)
+            }
+        };
+        textures.insert(name.to_owned(), Arc::new(tex));
+    }
+    Ok(LoadedTextures { textures })
+}
+
+/// Load the materials used in the scene, returns an error if a material is specified
+/// incorrectly. Referenced material data is found relative to the file the material is in.
+fn load_materials(
+    entries: &[(Location, &desc::Material)],
+    textures: &LoadedTextures,
+) -> Result<HashMap<String, Arc<Materials>>, SceneError> {
+    let mut materials = HashMap::new();
+    let mut defined = HashMap::new();
+    for (loc, m) in entries {
+        let name = m.name();
+        let loc = loc.clone().named(name);
+        // Make sure names are unique to avoid people accidently overwriting materials
+        if let Some(other) = defined.insert(name, loc.clone()) {
+            return Err(loc.invalid(name_conflict(&other)));
+        }
+        let color = |p, key| textures.color(p, &loc.member(key));
+        let scalar = |p, key| textures.scalar(p, &loc.member(key));
+        let material = match **m {
+            desc::Material::Glass {
+                ref reflect,
+                ref transmit,
+                ref eta,
+                abbe,
+                ..
+            } => {
+                if abbe.is_some_and(|a| a <= 0.0) {
+                    return Err(loc
+                        .member("abbe")
+                        .invalid("The Abbe number must be greater than 0"));
+                }
+                Glass::new_material(
+                    color(reflect, "reflect")?,
+                    color(transmit, "transmit")?,
+                    scalar(eta, "eta")?,
+                    abbe,
+                )
+            }
+            desc::Material::RoughGlass {
+                ref reflect,
+                ref transmit,
+                ref eta,
+                ref roughness,
+                ..
+            } => RoughGlass::new_material(
+                color(reflect, "reflect")?,
+                color(transmit, "transmit")?,
+                scalar(eta, "eta")?,
+                scalar(roughness, "roughness")?,
+            ),
+            desc::Material::Matte {
+                ref diffuse,
+                ref roughness,
+                ..
+            } => Matte::new_material(color(diffuse, "diffuse")?, scalar(roughness, "roughness")?),
+            desc::Material::Merl { ref file, .. } => {
+                let file_loc = loc.member("file");
+                let file_path = include::resolve(&file_loc.file, file);
+                Merl::load_file(&file_path)
+                    .map_err(|e| file_loc.resource(&file_path, e.to_string()))?
+            }
+            desc::Material::Metal {
+                ref refractive_index,
+                ref absorption_coefficient,
+                ref roughness,
+                ..
+            } => Metal::new_material(
+                color(refractive_index, "refractive_index")?,
+                color(absorption_coefficient, "absorption_coefficient")?,
+                scalar(roughness, "roughness")?,
+            ),
+            desc::Material::Plastic {
+                ref diffuse,
+                ref gloss,
+                ref roughness,
+                ..
+            } => Plastic::new_material(
+                color(diffuse, "diffuse")?,
+                color(gloss, "gloss")?,
+                scalar(roughness, "roughness")?,
+            ),
+            desc::Material::SpecularMetal {
+                ref refractive_index,
+                ref absorption_coefficient,
+                ..
+            } => SpecularMetal::new_material(
+                color(refractive_index, "refractive_index")?,
+                color(absorption_coefficient, "absorption_coefficient")?,
+            ),
+        };
+        materials.insert(name.to_owned(), Arc::new(material));
+    }
+    Ok(materials)
+}
+
+/// The error message for a texture or material with the same name as the one at `other`
+fn name_conflict(other: &Location) -> String {
+    format!(
+        "name conflicts with the entry at {}: {}",
+        other.file.display(),
+        other.path
+    )
+}
+
+/// Look up the material `name` used by the object at `loc`
+fn find_material(
+    materials: &HashMap<String, Arc<Materials>>,
+    name: Option<&String>,
+    loc: &Location,
+) -> Result<Arc<Materials>, SceneError> {
+    let loc = loc.member("material");
+    let name = name.ok_or_else(|| loc.missing("A material is required for an object"))?;
+    materials.get(name).cloned().ok_or_else(|| {
+        loc.invalid(format!(
+            "Material {} was not found in the material list",
+            name
+        ))
+    })
+}
+
+/// Loads the objects in the scene, assigning them materials from the materials map.
+/// Returns an error if an incorrectly specified object is found.
+fn load_objects(
+    includes: &Includes,
+    materials: &HashMap<String, Arc<Materials>>,
+    working: ColorSpace,
+    mesh_cache: &mut HashMap<String, HashMap<String, Arc<BoundableGeometry>>>,
+    objects: &[(Location, &desc::Object)],
+) -> Result<Vec<Instance>, SceneError> {
+    let mut instances = Vec::new();
+    for (loc, o) in objects {
+        let name = o.name();
+        let loc = loc.clone().named(name);
+        let transform = match o.placement() {
+            (_, Some(k)) => load_keyframes(k),
+            (Some(t), None) => AnimatedTransform::unanimated(&load_transform(t)),
+            (None, None) => {
+                return Err(loc.missing(format!(
+                    "No keyframes or transform specified for object {}",
+                    name
+                )))
+            }
+        };
+        match **o {
+            desc::Object::Emitter {
+                emitter,
+                ref emission,
+                ref material,
+                ref geometry,
+                ..
+            } => {
+                let (emission, blackbody) =
+                    load_emission(emission, working, &loc.member("emission"))?;
+                let mut light = if emitter == desc::EmitterKind::Point {
+                    Instance::point_light(transform, emission, name.to_owned())
+                } else {
+                    let mat = find_material(materials, material.as_ref(), &loc)?;
+                    let geom_loc = loc.member("geometry");
+                    let geom = geometry
+                        .as_ref()
+                        .ok_or_else(|| geom_loc.missing("Geometry is required for area lights"))?;
+                    let geom = load_sampleable_geometry(geom, &geom_loc)?;
+                    Instance::area_light(geom, mat, emission, transform, name.to_owned())
+                };
+                if let (Instance::Emitter(ref mut e), Some(b)) = (&mut light, blackbody) {
+                    e.set_blackbody(b, working);
+                }
+                instances.push(light);
+            }
+            desc::Object::Receiver {
+                ref material,
+                ref geometry,
+                ..
+            } => {
+                let mat = find_material(materials, Some(material), &loc)?;
+                let geom = load_geometry(mesh_cache, geometry, &loc.member("geometry"))?;
+                instances.push(Instance::receiver(geom, mat, transform, name.to_owned()));
+            }
+            desc::Object::Group { ref objects, .. } => {
+                let group_instances = load_objects(
+                    includes,
+                    materials,
+                    working,
+                    mesh_cache,
+                    &includes.entries(objects, &loc.member("objects"))?,
+                )?;
+                for mut gi in group_instances {
+                    {
+                        let t = gi.get_transform().clone();
+                        gi.set_transform(transform.clone() * t);
+                    }
+                    instances.push(gi);
+                }
+            }
+        }
+    }
+    Ok(instances)
+}
+
+/// Load the geometry described at `loc`. Will re-use any already loaded meshes
+/// and will place newly loaded meshees in the mesh cache.
+fn load_geometry(
+    meshes: &mut HashMap<String, HashMap<String, Arc<BoundableGeometry>>>,
+    geometry: &desc::Geometry,
+    loc: &Location,
+) -> Result<Arc<BoundableGeometry>, SceneError> {
+    match *geometry {
+        desc::Geometry::Sphere { radius } => Ok(Arc::new(Sphere::new(radius).into())),
+        desc::Geometry::Disk {
+            radius,
+            inner_radius,
+        } => Ok(Arc::new(Disk::new(radius, inner_radius).into())),
+        // We just treat plane as a special case of Rectangle now
+        desc::Geometry::Plane => Ok(Arc::new(Rectangle::new(2.0, 2.0).into())),
+        desc::Geometry::Rectangle { width, height } => {
+            Ok(Arc::new(Rectangle::new(width, height).into()))
+        }
+        desc::Geometry::Mesh {
+            ref file,
+            ref model,
+        } => {
+            let file = include::resolve(&loc.file, file);
+            let file_string = file
+                .to_str()
+                .ok_or_else(|| loc.member("file").invalid("Invalid file name"))?;
+            if meshes.get(file_string).is_none() {
+                let loaded = Mesh::load_obj(&file).map_err(|e| {
+                    loc.member("file")
+                        .resource(&file, format!("Failed to load OBJ file: {}", e))
+                })?;
+                meshes.insert(file_string.to_owned(), loaded);
+            }
+            let file_meshes = &meshes[file_string];
+            match file_meshes.get(model) {
+                Some(m) => Ok(m.clone()),
+                None => Err(loc.member("model").invalid(format!(
+                    "Requested model '{}' was not found in '{:?}'",
+                    model, file
+                ))),
+            }
+        }
+    }
+}
+
+/// Load the sampleable geometry described at `loc`. Returns an error if the geometry
+/// is not sampleable.
+fn load_sampleable_geometry(
+    geometry: &desc::Geometry,
+    loc: &Location,
+) -> Result<Arc<SampleableGeometry>, SceneError> {
+    match *geometry {
+        desc::Geometry::Sphere { radius } => Ok(Arc::new(Sphere::new(radius).into())),
+        desc::Geometry::Disk {
+            radius,
+            inner_radius,
+        } => Ok(Arc::new(Disk::new(radius, inner_radius).into())),
+        desc::Geometry::Rectangle { width, height } => {
+            Ok(Arc::new(Rectangle::new(width, height).into()))
+        }
+        ref g => Err(loc.member("type").invalid(format!(
+            "Geometry of type '{}' is not sampleable and can't be used for area light geometry",
+            g.type_name()
+        ))),
+    }
+}
+
+fn vector(v: [f32; 3]) -> Vector {
+    Vector::new(v[0], v[1], v[2])
+}
+
+/// Create the emission of a light described, along with its blackbody spectrum
+/// if it was given a temperature. Colors are in the `working` color space
+fn load_emission(
+    emission: &desc::Emission,
+    working: ColorSpace,
+    loc: &Location,
+) -> Result<(AnimatedColor, Option<Blackbody>), SceneError> {
+    let (keyframes, blackbody) = match *emission {
+        desc::Emission::Color(c) => (vec![ColorKeyframe::new(&c.0, 0.0)], None),
+        desc::Emission::Keyframes(ref keys) => (
+            keys.iter()
+                .map(|k| ColorKeyframe::new(&k.color.0, k.time))
+                .collect(),
+            None,
+        ),
+        desc::Emission::Blackbody(ref b) => {
+            if b.temperature.is_nan() || b.temperature <= 0.0 {
+                return Err(loc
+                    .member("temperature")
+                    .invalid("Blackbody temperatures must be greater than 0"));
+            }
+            let blackbody = Blackbody::new(b.temperature, b.strength.unwrap_or(1.0));
+            let color = ColorKeyframe::new(&blackbody.to_rgb(working), 0.0);
+            (vec![color], Some(blackbody))
+        }
+    };
+    Ok((AnimatedColor::with_keyframes(keyframes), blackbody))
+}
+
+/// Create the transform described by the list of transforms, which are applied in order
+fn load_transform(transforms: &[desc::Transform]) -> Transform {
+    let mut transform = Transform::identity();
+    for t in transforms {
+        let next = match *t {
+            desc::Transform::Translate { translation } => {
+                Transform::translate(&vector(translation))
+            }
+            desc::Transform::Scale {
+                scaling: desc::Scaling::Uniform(s),
+            } => Transform::scale(&Vector::broadcast(s)),
+            desc::Transform::Scale {
+                scaling: desc::Scaling::Vector(v),
+            } => Transform::scale(&vector(v)),
+            desc::Transform::RotateX { rotation } => Transform::rotate_x(rotation),
+            desc::Transform::RotateY { rotation } => Transform::rotate_y(rotation),
+            desc::Transform::RotateZ { rotation } => Transform::rotate_z(rotation),
+            desc::Transform::Rotate { rotation, axis } => {
+                Transform::rotate(&vector(axis), rotation)
+            }
+            // User has specified a pre-computed matrix for the transform
+            desc::Transform::Matrix { ref matrix } => {
+                Transform::from_mat(&matrix.iter().flatten().collect())
+            }
+        };
+        transform = next * transform;
+    }
+    transform
+}
+
+/// Create the animated transform described by the keyframes
+fn load_keyframes(keyframes: &desc::Keyframes) -> AnimatedTransform {
+    let control_points = keyframes
+        .control_points
+        .iter()
+        .map(|p| Keyframe::new(&load_transform(&p.transform)))
+        .collect();
+    AnimatedTransform::with_keyframes(
+        control_points,
+        keyframes.knots.clone(),
+        keyframes.degree.unwrap_or(3),
+    )
+}
+
+#[cfg(test)]
+pub(crate) fn test_scene() -> Value {
+    serde_json::json!({
+        "film": {
+            "width": 16,
+            "height": 16,
+            "samples": 1,
+            "frames": 1,
+            "start_frame": 0,
+            "end_frame": 0,
+            "scene_time": 0,
+            "filter": {
+                "type": "mitchell_netravali",
+                "width": 2.0,
+                "height": 2.0,
+                "b": 0.3333,
+                "c": 0.3333
+            }
+        },
+        "camera": {
+            "fov": 30,
+            "transform": [{ "type": "translate", "translation": [0, 12, -60] }]
+        },
+        "integrator": { "type": "pathtracer", "min_depth": 3, "max_depth": 8 },
+        "materials": [
+            { "name": "white", "type": "matte", "diffuse": [1, 1, 1], "roughness": 1.0 }
+        ],
+        "objects": [
+            {
+                "name": "floor",
+                "type": "receiver",
+                "material": "white",
+                "geometry": { "type": "plane" },
+                "transform": [{ "type": "scale", "scaling": 15 }]
+            },
+            {
+                "name": "light",
+                "type": "emitter",
+                "emitter": "point",
+                "emission": [1, 1, 1, 100],
+                "transform": [{ "type": "translate", "translation": [0, 10, 0] }]
+            }
+        ]
+    })
+}
+
+/// Set the value at the JSON `pointer` in the scene, removing it if `value` is None
+#[cfg(test)]
+fn edit_scene(mut scene: Value, pointer: &str, value: Option<Value>) -> Value {
+    let split = pointer.rfind('/').unwrap();
+    let (parent, key) = (&pointer[..split], &pointer[split + 1..]);
+    match (scene.pointer_mut(parent).unwrap(), value) {
+        (&mut Value::Object(ref mut m), Some(v)) => {
+            m.insert(key.to_owned(), v);
+        }
+        (&mut Value::Object(ref mut m), None) => {
+            m.remove(key);
+        }
+        (&mut Value::Array(ref mut a), Some(v)) => {
+            let i = key.parse::<usize>().unwrap();
+            if i == a.len() {
+                a.push(v);
+            } else {
+                a[i] = v;
+            }
+        }
+        (&mut Value::Array(ref mut a), None) => {
+            a.remove(key.parse::<usize>().unwrap());
+        }
+        _ => panic!("Can't edit {}", pointer),
+    }
+    scene
+}
+
+#[cfg(test)]
+fn load_error(scene: &Value) -> SceneError {
+    match Scene::load_value(scene, Path::new("test.json")) {
+        Ok(_) => panic!("Scene was expected to fail to load"),
+        Err(e) => e,
+    }
+}
+
+/// Check that loading the scene fails at the JSON `path` with the message `msg`
+#[cfg(test)]
+fn assert_scene_error(scene: &Value, path: &str, msg: &str) {
+    let err = load_error(scene);
+    let loc = match err.location() {
+        Some(l) => l,
+        None => panic!("Error '{}' should have a location", err),
+    };
+    assert_eq!(loc.file, Path::new("test.json"));
+    assert_eq!(loc.path, path, "for error '{}'", err);
+    assert_eq!(err.message(), msg, "at {}", path);
+}
+
+/// Check that loading the scene fails at the JSON `path` with a message containing
+/// `msg`, for errors in the structure of the file whose messages come from serde
+#[cfg(test)]
+fn assert_structure_error(scene: &Value, path: &str, msg: &str) {
+    let err = load_error(scene);
+    let loc = match err.location() {
+        Some(l) => l,
+        None => panic!("Error '{}' should have a location", err),
+    };
+    assert_eq!(loc.file, Path::new("test.json"));
+    assert_eq!(loc.path, path, "for error '{}'", err);
+    assert!(
+        err.message().contains(msg),
+        "'{}' should contain '{}'",
+        err,
+        msg
+    );
+}
+
+/// Check the errors produced by removing each member of the object at the JSON `pointer`,
+/// which is at `path`, and by setting it to an invalid value, and that unknown members
+/// are rejected. `members` lists the key, the invalid value and part of the message for it
+#[cfg(test)]
+fn assert_member_errors(scene: &Value, pointer: &str, path: &str, members: &[(&str, Value, &str)]) {
+    let member_path = |key: &str| {
+        if path.is_empty() {
+            key.to_owned()
+        } else {
+            format!("{}.{}", path, key)
+        }
+    };
+    for &(key, ref bad, invalid) in members {
+        let member_ptr = format!("{}/{}", pointer, key);
+        assert_scene_error(
+            &edit_scene(scene.clone(), &member_ptr, None),
+            &member_path(key),
+            &format!("missing field `{}`", key),
+        );
+        assert_structure_error(
+            &edit_scene(scene.clone(), &member_ptr, Some(bad.clone())),
+            &member_path(key),
+            invalid,
+        );
+    }
+    assert_structure_error(
+        &edit_scene(
+            scene.clone(),
+            &format!("{}/misspelled", pointer),
+            Some(serde_json::json!(1)),
+        ),
+        &member_path("misspelled"),
+        "unknown field `misspelled`",
+    );
+}
+
+#[test]
+fn test_load_scene() {
+    let (scene, rt, spp, frame_info) =
+        match Scene::load_value(&test_scene(), Path::new("test.json")) {
+            Ok(s) => s,
+            Err(e) => panic!("{}", e),
+        };
+    assert_eq!(scene.cameras.len(), 1);
+    assert_eq!(scene.bvh.iter().count(), 2);
+    assert_eq!(rt.dimensions(), (16, 16));
+    assert_eq!(spp, 1);
+    assert_eq!(frame_info.end, 0);
+}
+
+#[test]
+fn test_file_errors() {
+    match Scene::load_file("does_not_exist.json") {
+        Err(SceneError::Io { file, .. }) => assert_eq!(file, Path::new("does_not_exist.json")),
+        _ => panic!("Expected an IO error"),
+    }
+    match Scene::load_file("README.md") {
+        Err(e @ SceneError::Parse { .. }) => assert!(e.message().starts_with("JSON parsing error")),
+        _ => panic!("Expected a parse error"),
+    }
+    assert_structure_error(&serde_json::json!([]), "", "expected a scene file object");
+    let no_objects = edit_scene(test_scene(), "/objects", Some(serde_json::json!([])));
+    match load_error(&no_objects) {
+        e @ SceneError::NoObjects { .. } => assert_eq!(
+            e.to_string(),
+            "test.json: Aborting: the scene does not have any objects!"
+        ),
+        e => panic!("Unexpected error {}", e),
+    }
+    let no_lights = edit_scene(test_scene(), "/objects/1", None);
+    match load_error(&no_lights) {
+        e @ SceneError::NoLights { .. } => assert_eq!(
+            e.to_string(),
+            "test.json: Aborting: the scene does not have any lights!"
+        ),
+        e => panic!("Unexpected error {}", e),
+    }
+}
+
+#[test]
+fn test_root_errors() {
+    let scene = test_scene();
+    for &(key, path, msg) in &[
+        ("film", "film", "The scene must specify a film to write to"),
+        (
+            "integrator",
+            "integrator",
+            "The scene must specify the integrator to render with",
+        ),
+        (
+            "materials",
+            "materials",
+            "An array of materials is required",
+        ),
+        (
+            "objects",
+            "objects",
+            "The scene must specify a list of objects",
+        ),
+        ("camera", "camera", "Error: A camera is required!"),
+    ] {
+        let missing = edit_scene(scene.clone(), &format!("/{}", key), None);
+        match load_error(&missing) {
+            SceneError::Missing {
+                ref loc,
+                msg: ref m,
+            } => {
+                assert_eq!((loc.path.as_str(), m.as_str()), (path, msg))
+            }
+            e => panic!("Unexpected error {}", e),
+        }
+    }
+    for key in &["textures", "materials", "objects", "cameras"] {
+        assert_structure_error(
+            &edit_scene(
+                scene.clone(),
+                &format!("/{}", key),
+                Some(serde_json::json!({})),
+            ),
+            key,
+            "invalid type: map, expected a sequence",
+        );
+    }
+    assert_structure_error(
+        &edit_scene(scene, "/lights", Some(serde_json::json!([]))),
+        "lights",
+        "unknown field `lights`, expected one of `$schema`, `include`, `film`",
+    );
+}
+
+#[test]
+fn test_film_errors() {
+    let s = serde_json::json!("x");
+    let invalid = "invalid type: string \"x\"";
+    assert_member_errors(
+        &test_scene(),
+        "/film",
+        "film",
+        &[
+            ("width", s.clone(), invalid),
+            ("height", s.clone(), invalid),
+            (
+                "samples",
+                serde_json::json!(-1),
+                "invalid value: integer `-1`",
+            ),
+            ("start_frame", s.clone(), invalid),
+            ("end_frame", s.clone(), invalid),
+            ("frames", s.clone(), invalid),
+            ("scene_time", s.clone(), invalid),
+            ("filter", s, invalid),
+        ],
+    );
+    assert_scene_error(
+        &edit_scene(
+            test_scene(),
+            "/film/start_frame",
+            Some(serde_json::json!(2)),
+        ),
+        "film.end_frame",
+        "End frame must be greater or equal to the starting frame",
+    );
+    assert_scene_error(
+        &edit_scene(
+            test_scene(),
+            "/film/aovs",
+            Some(serde_json::json!(["depth", "beauty"])),
+        ),
+        "film.aovs[1]",
+        "Unknown AOV 'beauty', expected one of: albedo, normal, position, depth, uv, \
+         instance_id, material_id, direct, indirect, diffuse, specular, variance",
+    );
+    assert_scene_error(
+        &edit_scene(
+            test_scene(),
+            "/film/max_sample_luminance",
+            Some(serde_json::json!(0.0)),
+        ),
+        "film.max_sample_luminance",
+        "Max sample luminance must be greater than 0",
+    );
+    assert_scene_error(
+        &edit_scene(
+            test_scene(),
+            "/film/tonemap",
+            Some(serde_json::json!({ "operator": "aces", "white_balance": [1, 0, 1] })),
+        ),
+        "film.tonemap.white_balance",
+        "White balance must be greater than 0",
+    );
+    let denoise = edit_scene(
+        test_scene(),
+        "/film/denoise",
+        Some(serde_json::json!({ "radius": 3, "depth_sigma": 0.5 })),
+    );
+    let (_, rt, ..) = Scene::load_value(&denoise, Path::new("test.json")).unwrap();
+    let expected = Denoiser {
+        radius: 3,
+        depth_sigma: 0.5,
+        ..Denoiser::default()
+    };
+    assert_eq!(rt.denoiser(), Some(&expected));
+    assert_eq!(rt.aovs(), []);
+    assert_eq!(rt.accumulated_aovs(), Denoiser::FEATURES);
+    assert_scene_error(
+        &edit_scene(
+            denoise,
+            "/film/denoise/color_sigma",
+            Some(serde_json::json!(0)),
+        ),
+        "film.denoise.color_sigma",
+        "Denoiser sigmas must be greater than 0",
+    );
+    let crop = edit_scene(
+        test_scene(),
+        "/film/crop_window",
+        Some(serde_json::json!([0.25, 0.75, 0.0, 0.5])),
+    );
+    let (_, rt, ..) = Scene::load_value(&crop, Path::new("test.json")).unwrap();
+    assert_eq!(rt.crop(), Some(CropWindow::new((4, 0), (12, 8))));
+    assert_scene_error(
+        &edit_scene(
+            crop.clone(),
+            "/film/pixel_bounds",
+            Some(serde_json::json!([0, 4, 0, 4])),
+        ),
+        "film.pixel_bounds",
+        "Only one of crop_window and pixel_bounds can be set",
+    );
+    assert_scene_error(
+        &edit_scene(
+            crop,
+            "/film/crop_window",
+            Some(serde_json::json!([0.5, 0.5, 0.0, 1.5])),
+        ),
+        "film.crop_window",
+        "Crop window must be inside [0, 1] and cover at least one pixel",
+    );
+    let bounds = edit_scene(
+        test_scene(),
+        "/film/pixel_bounds",
+        Some(serde_json::json!([2, 5, 3, 16])),
+    );
+    let (_, rt, ..) = Scene::load_value(&bounds, Path::new("test.json")).unwrap();
+    assert_eq!(rt.crop(), Some(CropWindow::new((2, 3), (5, 16))));
+    assert_scene_error(
+        &edit_scene(
+            bounds,
+            "/film/pixel_bounds",
+            Some(serde_json::json!([2, 5, 3, 17])),
+        ),
+        "film.pixel_bounds",
+        "Pixel bounds must be inside the 16x16 image and cover at least one pixel",
+    );
+    let spaces = edit_scene(
+        test_scene(),
+        "/film/colorspace",
+        Some(serde_json::json!({ "working": "acescg" })),
+    );
+    let (_, rt, ..) = Scene::load_value(&spaces, Path::new("test.json")).unwrap();
+    assert_eq!(rt.working_space(), ColorSpace::Acescg);
+    assert_eq!(rt.output_space(), ColorSpace::Srgb);
+    assert_scene_error(
+        &edit_scene(
+            spaces.clone(),
+            "/film/colorspace/output",
+            Some(serde_json::json!("raw")),
+        ),
+        "film.colorspace.output",
+        "The raw color space can only be used for textures",
+    );
+    assert_structure_error(
+        &edit_scene(
+            spaces,
+            "/film/colorspace/working",
+            Some(serde_json::json!("cmyk")),
+        ),
+        "film.colorspace.working",
+        "unknown variant `cmyk`, expected one of `srgb`, `linear_srgb`, `acescg`",
+    );
+}
+
+#[test]
+fn test_filter_errors() {
+    let s = serde_json::json!("x");
+    let invalid = "invalid type: string \"x\"";
+    let scene = test_scene();
+    assert_member_errors(
+        &scene,
+        "/film/filter",
+        "film.filter",
+        &[
+            ("width", s.clone(), invalid),
+            ("height", s.clone(), invalid),
+            ("b", s.clone(), invalid),
+            ("c", s.clone(), invalid),
+            (
+                "type",
+                serde_json::json!(true),
+                "invalid type: boolean `true`",
+            ),
+        ],
+    );
+    let gaussian = edit_scene(
+        scene.clone(),
+        "/film/filter",
+        Some(serde_json::json!({ "type": "gaussian", "width": 2.0, "height": 2.0, "alpha": 2.0 })),
+    );
+    assert_member_errors(
+        &gaussian,
+        "/film/filter",
+        "film.filter",
+        &[("alpha", s.clone(), invalid)],
+    );
+    let lanczos = edit_scene(
+        scene.clone(),
+        "/film/filter",
+        Some(serde_json::json!({ "type": "lanczos", "width": 4.0, "height": 4.0, "tau": 3.0 })),
+    );
+    assert_member_errors(
+        &lanczos,
+        "/film/filter",
+        "film.filter",
+        &[("tau", s, invalid)],
+    );
+    assert_structure_error(&edit_scene(scene, "/film/filter/type", Some(serde_json::json!("sinc"))), "film.filter.type", "unknown variant `sinc`, expected one of `mitchell_netravali`, `gaussian`, `box`, `triangle`, `lanczos`, `blackman_harris`");
+}
+
+#[test]
+fn test_camera_errors() {
+    let s = serde_json::json!("x");
+    let scene = test_scene();
+    assert_member_errors(
+        &scene,
+        "/camera",
+        "camera",
+        &[(
+            "fov",
+            s.clone(),
+            "expected a field of view or an array of them",
+        )],
+    );
+    for key in &["shutter_size", "active_at"] {
+        assert_structure_error(
+            &edit_scene(scene.clone(), &format!("/camera/{}", key), Some(s.clone())),
+            &format!("camera.{}", key),
+            "invalid type: string \"x\"",
+        );
+    }
+    let look_at = edit_scene(
+        edit_scene(scene.clone(), "/camera/transform", None),
+        "/camera",
+        Some(
+            serde_json::json!({ "fov": 30, "position": [0, 0, 0], "target": [0, 0, 1], "up": [0, 1, 0] }),
+        ),
+    );
+    for &(key, msg) in &[
+        ("position", "The camera must specify a position"),
+        ("target", "The camera must specify a target"),
+        ("up", "The camera must specify an up vector"),
+    ] {
+        let camera_ptr = format!("/camera/{}", key);
+        assert_scene_error(
+            &edit_scene(look_at.clone(), &camera_ptr, None),
+            &format!("camera.{}", key),
+            msg,
+        );
+        assert_structure_error(
+            &edit_scene(look_at.clone(), &camera_ptr, Some(serde_json::json!([1]))),
+            &format!("camera.{}", key),
+            "invalid length 1, expected an array of length 3",
+        );
+    }
+    let animated_fov = edit_scene(
+        scene.clone(),
+        "/camera",
+        Some(serde_json::json!({
+            "fov": [30, 40],
+            "fov_knots": [0, 0, 1, 1],
+            "fov_spline_degree": 1,
+            "transform": []
+        })),
+    );
+    for &(key, msg) in &[
+        (
+            "fov_knots",
+            "Animated field of view must specify spline knots",
+        ),
+        ("fov_spline_degree", "Animated fov spline must have degree"),
+    ] {
+        assert_scene_error(
+            &edit_scene(animated_fov.clone(), &format!("/camera/{}", key), None),
+            &format!("camera.{}", key),
+            msg,
+        );
+    }
+    assert_structure_error(
+        &edit_scene(animated_fov.clone(), "/camera/fov/1", Some(s.clone())),
+        "camera.fov",
+        "expected a field of view or an array of them",
+    );
+    assert_structure_error(
+        &edit_scene(animated_fov, "/camera/fov_knots/2", Some(s)),
+        "camera.fov_knots[2]",
+        "invalid type: string \"x\", expected f32",
+    );
+    let cameras = edit_scene(
+        edit_scene(scene, "/camera", None),
+        "/cameras",
+        Some(serde_json::json!([{ "fov": 30, "transform": [] }, { "transform": [] }])),
+    );
+    assert_scene_error(&cameras, "cameras[1].fov", "missing field `fov`");
+    let late = edit_scene(
+        cameras,
+        "/cameras",
+        Some(serde_json::json!([
+            { "fov": 30, "transform": [], "active_at": 5 },
+            { "fov": 30, "transform": [], "active_at": 8 }
+        ])),
+    );
+    let (mut loaded, ..) = Scene::load_value(&late, Path::new("test.json")).unwrap();
+    loaded.update_frame(0, 0.0, 1.0);
+    assert_eq!(loaded.active_camera().active_at, 5);
+}
+
+#[test]
+fn test_integrator_errors() {
+    let s = serde_json::json!("x");
+    let invalid = "invalid type: string \"x\"";
+    let scene = test_scene();
+    assert_member_errors(
+        &scene,
+        "/integrator",
+        "integrator",
+        &[
+            (
+                "type",
+                serde_json::json!(true),
+                "invalid type: boolean `true`",
+            ),
+            ("min_depth", s.clone(), invalid),
+            ("max_depth", s.clone(), invalid),
+        ],
+    );
+    let whitted = edit_scene(
+        scene.clone(),
+        "/integrator",
+        Some(serde_json::json!({ "type": "whitted", "min_depth": 4 })),
+    );
+    assert_member_errors(
+        &whitted,
+        "/integrator",
+        "integrator",
+        &[("min_depth", s, invalid)],
+    );
+    assert_structure_error(
+        &edit_scene(whitted, "/integrator/max_depth", Some(serde_json::json!(4))),
+        "integrator.max_depth",
+        "unknown field `max_depth`, expected `min_depth`",
+    );
+    assert_structure_error(
+        &edit_scene(scene, "/integrator/type", Some(serde_json::json!("bdpt"))),
+        "integrator.type",
+        "unknown variant `bdpt`",
+    );
+}
+
+#[test]
+fn test_sampler() {
+    let adaptive = serde_json::json!({
+        "type": "adaptive",
+        "min_spp": 4,
+        "max_spp": 16,
+        "block_size": [16, 4],
+        "seed": 7
+    });
+    let scene = edit_scene(test_scene(), "/sampler", Some(adaptive));
+    let (loaded, ..) = match Scene::load_value(&scene, Path::new("test.json")) {
+        Ok(s) => s,
+        Err(e) => panic!("{}", e),
+    };
+    let expected = SamplerType::Adaptive {
+        min_spp: 4,
+        max_spp: 16,
+    };
+    let mut config = SamplerConfig::new(expected, (16, 4));
+    config.seed = Some(7);
+    assert_eq!(loaded.sampler, config);
+    let (loaded, ..) = Scene::load_value(&test_scene(), Path::new("test.json")).unwrap();
+    assert_eq!(loaded.sampler, SamplerConfig::default());
+
+    let s = serde_json::json!("x");
+    let invalid = "invalid type: string \"x\"";
+    assert_member_errors(
+        &scene,
+        "/sampler",
+        "sampler",
+        &[("min_spp", s.clone(), invalid), ("max_spp", s, invalid)],
+    );
+    assert_scene_error(
+        &edit_scene(
+            scene.clone(),
+            "/sampler/max_spp",
+            Some(serde_json::json!(2)),
+        ),
+        "sampler.max_spp",
+        "Max samples per pixel must be greater or equal to min_spp",
+    );
+    assert_scene_error(
+        &edit_scene(
+            scene,
+            "/sampler/block_size",
+            Some(serde_json::json!([16, 0])),
+        ),
+        "sampler.block_size",
+        "Block size must be greater than 0",
+    );
+    assert_structure_error(
+        &edit_scene(
+            test_scene(),
+            "/sampler",
+            Some(serde_json::json!({ "type": "stratified" })),
+        ),
+        "sampler.type",
+        "unknown variant `stratified`",
+    );
+}
+
+#[test]
+fn test_texture_errors() {
+    let s = serde_json::json!(1);
+    let invalid = "invalid type: integer `1`, expected a string";
+    let scene = edit_scene(
+        test_scene(),
+        "/textures",
+        Some(serde_json::json!([{ "name": "tex", "type": "image", "file": "missing.png" }])),
+    );
+    let err = load_error(&scene);
+    match err {
+        SceneError::Resource {
+            ref loc, ref file, ..
+        } => {
+            assert_eq!(loc.path, "textures[0].file");
+            assert_eq!(loc.name.as_ref().unwrap(), "tex");
+            assert_eq!(file, Path::new("missing.png"));
+        }
+        ref e => panic!("Unexpected error {}", e),
+    }
+    assert!(err
+        .message()
+        .starts_with("Failed to load 'missing.png': Failed to load image file"));
+    assert_member_errors(
+        &scene,
+        "/textures/0",
+        "textures[0]",
+        &[
+            ("name", s.clone(), invalid),
+            ("type", s.clone(), "invalid type: integer `1`"),
+            ("file", s.clone(), invalid),
+        ],
+    );
+    assert_structure_error(
+        &edit_scene(
+            scene.clone(),
+            "/textures/0/type",
+            Some(serde_json::json!("noise")),
+        ),
+        "textures[0].type",
+        "unknown variant `noise`",
+    );
+    let movie = edit_scene(
+        scene.clone(),
+        "/textures/0",
+        Some(serde_json::json!({
+            "name": "tex",
+            "type": "movie",
+            "file_prefix": "frame",
+            "file_suffix": ".png",
+            "frames": 2,
+            "framerate": 24
+        })),
+    );
+    assert_scene_error(
+        &edit_scene(
+            movie.clone(),
+            "/textures/0/frames",
+            Some(serde_json::json!(1)),
+        ),
+        "textures[0].frames",
+        "A movie must have at least 2 frames",
+    );
+    // The first texture has to load for the second to conflict with it
+    let image = std::env::temp_dir().join(format!("aperture_texture_{}.png", std::process::id()));
+    image::save_buffer(&image, &[255; 12], 2, 2, image::RGB(8)).unwrap();
+    let conflict = edit_scene(
+        scene.clone(),
+        "/textures/0/file",
+        Some(serde_json::json!(image.to_str().unwrap())),
+    );
+    let conflict = edit_scene(
+        conflict.clone(),
+        "/textures/1",
+        Some(conflict["textures"][0].clone()),
+    );
+    assert_scene_error(
+        &conflict,
+        "textures[1]",
+        "name conflicts with the entry at test.json: textures[0]",
+    );
+    // Textures are sRGB unless they're tagged with their color space, with scalars read
+    // from untagged textures as data
+    let loaded = edit_scene(conflict, "/textures/1", None);
+    let color_space = |scene: &Value| {
+        let (scene, ..) = Scene::load_value(scene, Path::new("test.json")).unwrap();
+        match *scene.textures["tex"] {
+            Textures::Image(ref img) => (img.color_space, img.decode_scalars),
+            _ => panic!("Expected an image texture"),
+        }
+    };
+    assert_eq!(color_space(&loaded), (Some(ColorSpace::Srgb), false));
+    let tagged = |c: &str| {
+        let scene = edit_scene(loaded.clone(), "/textures/0/colorspace", Some(c.into()));
+        color_space(&scene)
+    };
+    assert_eq!(tagged("srgb"), (Some(ColorSpace::Srgb), true));
+    assert_eq!(tagged("display_p3"), (Some(ColorSpace::DisplayP3), true));
+    assert_eq!(tagged("raw"), (None, true));
+    std::fs::remove_file(image).unwrap();
+    assert_member_errors(
+        &movie,
+        "/textures/0",
+        "textures[0]",
+        &[
+            ("file_prefix", s.clone(), invalid),
+            ("file_suffix", s.clone(), invalid),
+            (
+                "frames",
+                serde_json::json!(-1),
+                "invalid value: integer `-1`",
+            ),
+            (
+                "framerate",
+                serde_json::json!(0.5),
+                "invalid type: floating point `0.5`",
+            ),
+        ],
+    );
+
+    let animated = edit_scene(
+        scene,
+        "/textures/0",
+        Some(serde_json::json!({
+            "name": "tex",
+            "type": "animated_image",
+            "keyframes": [{ "file": "a.png", "time": 0 }, { "file": "b.png", "time": 1 }]
+        })),
+    );
+    assert_member_errors(
+        &animated,
+        "/textures/0",
+        "textures[0]",
+        &[("keyframes", s.clone(), "expected a sequence")],
+    );
+    assert_scene_error(
+        &edit_scene(animated.clone(), "/textures/0/keyframes/1", None),
+        "textures[0].keyframes",
+        "animated_image must have at least 2 frames",
+    );
+    assert_member_errors(
+        &animated,
+        "/textures/0/keyframes/0",
+        "textures[0].keyframes[0]",
+        &[
+            ("file", s, invalid),
+            ("time", serde_json::json!("x"), "invalid type: string \"x\""),
+        ],
+    );
+}
+
+#[test]
+fn test_material_errors() {
+    let scene = test_scene();
+    assert_member_errors(
+        &scene,
+        "/materials/0",
+        "materials[0]",
+        &[
+            (
+                "name",
+                serde_json::json!(1),
+                "invalid type: integer `1`, expected a string",
+            ),
+            (
+                "type",
+                serde_json::json!(true),
+                "invalid type: boolean `true`",
+            ),
+        ],
+    );
+    assert_structure_error(
+        &edit_scene(
+            scene.clone(),
+            "/materials/0/type",
+            Some(serde_json::json!("velvet")),
+        ),
+        "materials[0].type",
+        "unknown variant `velvet`",
+    );
+    let err = load_error(&edit_scene(scene.clone(), "/materials/0/roughness", None));
+    assert_eq!(
+        err.to_string(),
+        "test.json: materials[0].roughness ('white'): missing field `roughness`"
+    );
+    let conflict = edit_scene(
+        scene.clone(),
+        "/materials/1",
+        Some(
+            serde_json::json!({ "name": "white", "type": "matte", "diffuse": [1, 1, 1], "roughness": 1.0 }),
+        ),
+    );
+    assert_scene_error(
+        &conflict,
+        "materials[1]",
+        "name conflicts with the entry at test.json: materials[0]",
+    );
+    assert_structure_error(
+        &edit_scene(
+            scene.clone(),
+            "/materials/0/diffuse",
+            Some(serde_json::json!(true)),
+        ),
+        "materials[0].diffuse",
+        "expected a color or the name of a texture",
+    );
+    assert_structure_error(
+        &edit_scene(
+            scene.clone(),
+            "/materials/0/diffuse",
+            Some(serde_json::json!([1, 1])),
+        ),
+        "materials[0].diffuse",
+        "expected a color or the name of a texture",
+    );
+    assert_structure_error(
+        &edit_scene(
+            scene.clone(),
+            "/materials/0/roughness",
+            Some(serde_json::json!([1])),
+        ),
+        "materials[0].roughness",
+        "expected a number or the name of a texture",
+    );
+    let err = load_error(&edit_scene(
+        scene.clone(),
+        "/materials/0/diffuse",
+        Some(serde_json::json!("checker")),
+    ));
+    assert_eq!(
+        err.to_string(),
+        "test.json: materials[0].diffuse ('white'): Texture 'checker' was not found in the texture list"
+    );
+
+    let materials = vec![
+        (
+            serde_json::json!({ "type": "glass", "reflect": [1, 1, 1], "transmit": [1, 1, 1], "eta": 1.5 }),
+            vec!["reflect", "transmit", "eta"],
+        ),
+        (
+            serde_json::json!({ "type": "rough_glass", "reflect": [1, 1, 1], "transmit": [1, 1, 1], "eta": 1.5, "roughness": 0.5 }),
+            vec!["reflect", "transmit", "eta", "roughness"],
+        ),
+        (
+            serde_json::json!({ "type": "matte", "diffuse": [1, 1, 1], "roughness": 0.5 }),
+            vec!["diffuse", "roughness"],
+        ),
+        (
+            serde_json::json!({ "type": "metal", "refractive_index": [1, 1, 1], "absorption_coefficient": [1, 1, 1], "roughness": 0.5 }),
+            vec!["refractive_index", "absorption_coefficient", "roughness"],
+        ),
+        (
+            serde_json::json!({ "type": "plastic", "diffuse": [1, 1, 1], "gloss": [1, 1, 1], "roughness": 0.5 }),
+            vec!["diffuse", "gloss", "roughness"],
+        ),
+        (
+            serde_json::json!({ "type": "specular_metal", "refractive_index": [1, 1, 1], "absorption_coefficient": [1, 1, 1] }),
+            vec!["refractive_index", "absorption_coefficient"],
+        ),
+    ];
+    for (mut mat, members) in materials {
+        mat["name"] = serde_json::json!("white");
+        let scene = edit_scene(scene.clone(), "/materials/0", Some(mat));
+        let member_errors: Vec<_> = members
+            .iter()
+            .map(|&k| (k, serde_json::json!(true), "the name of a texture"))
+            .collect();
+        assert_member_errors(&scene, "/materials/0", "materials[0]", &member_errors);
+        // Referring to a texture which doesn't exist
+        for key in members {
+            assert_scene_error(
+                &edit_scene(
+                    scene.clone(),
+                    &format!("/materials/0/{}", key),
+                    Some(serde_json::json!("missing")),
+                ),
+                &format!("materials[0].{}", key),
+                "Texture 'missing' was not found in the texture list",
+            );
+        }
+    }
+
+    let glass = edit_scene(
+        scene.clone(),
+        "/materials/0",
+        Some(serde_json::json!({
+            "name": "white",
+            "type": "glass",
+            "reflect": [1, 1, 1],
+            "transmit": [1, 1, 1],
+            "eta": 1.5,
+            "abbe": -1
+        })),
+    );
+    assert_scene_error(
+        &glass,
+        "materials[0].abbe",
+        "The Abbe number must be greater than 0",
+    );
+
+    let merl = edit_scene(
+        scene,
+        "/materials/0",
+        Some(serde_json::json!({ "name": "white", "type": "merl", "file": "missing.binary" })),
+    );
+    assert_member_errors(
+        &merl,
+        "/materials/0",
+        "materials[0]",
+        &[("file", serde_json::json!(1), "expected a string")],
+    );
+    match load_error(&merl) {
+        SceneError::Resource {
+            ref loc, ref file, ..
+        } => {
+            assert_eq!(loc.path, "materials[0].file");
+            assert_eq!(file, Path::new("missing.binary"));
+        }
+        e => panic!("Unexpected error {}", e),
+    }
+}
+
+#[test]
+fn test_object_errors() {
+    let s = serde_json::json!(1);
+    let invalid = "invalid type: integer `1`";
+    let scene = test_scene();
+    assert_member_errors(
+        &scene,
+        "/objects/0",
+        "objects[0]",
+        &[
+            ("name", s.clone(), invalid),
+            ("type", s.clone(), invalid),
+            ("material", s.clone(), invalid),
+            ("geometry", s.clone(), invalid),
+        ],
+    );
+    assert_scene_error(
+        &edit_scene(scene.clone(), "/objects/0/transform", None),
+        "objects[0]",
+        "No keyframes or transform specified for object floor",
+    );
+    assert_scene_error(
+        &edit_scene(
+            scene.clone(),
+            "/objects/0/material",
+            Some(serde_json::json!("black")),
+        ),
+        "objects[0].material",
+        "Material black was not found in the material list",
+    );
+    assert_structure_error(
+        &edit_scene(
+            scene.clone(),
+            "/objects/0/type",
+            Some(serde_json::json!("camera")),
+        ),
+        "objects[0].type",
+        "unknown variant `camera`, expected one of `emitter`, `receiver`, `group`",
+    );
+    assert_member_errors(
+        &scene,
+        "/objects/1",
+        "objects[1]",
+        &[
+            ("emitter", s.clone(), invalid),
+            (
+                "emission",
+                s.clone(),
+                "invalid type: integer `1`, expected a sequence",
+            ),
+        ],
+    );
+    assert_structure_error(
+        &edit_scene(
+            scene.clone(),
+            "/objects/1/emitter",
+            Some(serde_json::json!("spot")),
+        ),
+        "objects[1].emitter",
+        "unknown variant `spot`, expected `point` or `area`",
+    );
+    assert_structure_error(
+        &edit_scene(
+            scene.clone(),
+            "/objects/1/emission",
+            Some(serde_json::json!([])),
+        ),
+        "objects[1].emission",
+        "a color must be 3 numbers, optionally followed by its strength, not 0",
+    );
+    let blackbody = edit_scene(
+        scene.clone(),
+        "/objects/1/emission",
+        Some(serde_json::json!({ "temperature": 3000, "strength": 10 })),
+    );
+    let (loaded, ..) = Scene::load_value(&blackbody, Path::new("test.json")).unwrap();
+    match loaded.bvh.iter().find(|i| i.tag() == "light") {
+        Some(Instance::Emitter(e)) => {
+            let b = e.blackbody().unwrap();
+            assert_eq!((b.temperature, b.strength), (3000.0, 10.0));
+            assert_eq!(e.emission.color(0.0), b.to_rgb(ColorSpace::LinearSrgb));
+        }
+        _ => panic!("The light is missing"),
+    }
+    assert_scene_error(
+        &edit_scene(
+            blackbody.clone(),
+            "/objects/1/emission/temperature",
+            Some(serde_json::json!(0)),
+        ),
+        "objects[1].emission.temperature",
+        "Blackbody temperatures must be greater than 0",
+    );
+    assert_structure_error(
+        &edit_scene(
+            blackbody,
+            "/objects/1/emission/color",
+            Some(serde_json::json!([1, 1, 1])),
+        ),
+        "objects[1].emission.color",
+        "unknown field `color`, expected `temperature` or `strength`",
+    );
+    let keyed_emission = edit_scene(
+        scene.clone(),
+        "/objects/1/emission",
+        Some(
+            serde_json::json!([{ "time": 0, "color": [1, 1, 1] }, { "time": 1, "color": [1, 1, 1] }]),
+        ),
+    );
+    assert_member_errors(
+        &keyed_emission,
+        "/objects/1/emission/1",
+        "objects[1].emission[1]",
+        &[
+            ("time", serde_json::json!("x"), "invalid type: string \"x\""),
+            ("color", serde_json::json!([1]), "a color must be 3 numbers"),
+        ],
+    );
+    let area = edit_scene(
+        scene.clone(),
+        "/objects/1",
+        Some(serde_json::json!({
+            "name": "light",
+            "type": "emitter",
+            "emitter": "area",
+            "emission": [1, 1, 1, 10],
+            "material": "white",
+            "geometry": { "type": "sphere", "radius": 1 },
+            "transform": []
+        })),
+    );
+    assert_scene_error(
+        &edit_scene(area.clone(), "/objects/1/material", None),
+        "objects[1].material",
+        "A material is required for an object",
+    );
+    assert_scene_error(
+        &edit_scene(area, "/objects/1/geometry", None),
+        "objects[1].geometry",
+        "Geometry is required for area lights",
+    );
+    let group = edit_scene(
+        scene,
+        "/objects/0",
+        Some(
+            serde_json::json!({ "name": "group", "type": "group", "transform": [], "objects": [] }),
+        ),
+    );
+    assert_member_errors(
+        &group,
+        "/objects/0",
+        "objects[0]",
+        &[("objects", s, "expected a sequence")],
+    );
+    let nested = edit_scene(
+        group,
+        "/objects/0/objects",
+        Some(serde_json::json!([{ "name": "nested", "type": "receiver", "transform": [] }])),
+    );
+    let err = load_error(&nested);
+    assert_eq!(
+        err.to_string(),
+        "test.json: objects[0].objects[0].material ('nested'): missing field `material`"
+    );
+    let nested = edit_scene(
+        nested,
+        "/objects/0/objects/0/material",
+        Some(serde_json::json!("black")),
+    );
+    let nested = edit_scene(
+        nested,
+        "/objects/0/objects/0/geometry",
+        Some(serde_json::json!({ "type": "plane" })),
+    );
+    let err = load_error(&nested);
+    assert_eq!(
+        err.location().unwrap().path,
+        "objects[0].objects[0].material"
+    );
+    assert_eq!(err.location().unwrap().name.as_ref().unwrap(), "nested");
+}
+
+#[test]
+fn test_geometry_errors() {
+    let s = serde_json::json!("x");
+    let invalid = "invalid type: string \"x\", expected f32";
+    let scene = test_scene();
+    let geometries = vec![
+        (
+            serde_json::json!({ "type": "sphere", "radius": 1 }),
+            vec!["radius"],
+        ),
+        (
+            serde_json::json!({ "type": "disk", "radius": 1, "inner_radius": 0 }),
+            vec!["radius", "inner_radius"],
+        ),
+        (
+            serde_json::json!({ "type": "rectangle", "width": 1, "height": 1 }),
+            vec!["width", "height"],
+        ),
+    ];
+    // Check both receiver geometry and sampleable area light geometry
+    let area = edit_scene(
+        scene.clone(),
+        "/objects/1",
+        Some(serde_json::json!({
+            "name": "light",
+            "type": "emitter",
+            "emitter": "area",
+            "emission": [1, 1, 1, 10],
+            "material": "white",
+            "geometry": { "type": "sphere", "radius": 1 },
+            "transform": []
+        })),
+    );
+    for (base, obj) in [(scene.clone(), 0), (area.clone(), 1)] {
+        for (geom, members) in &geometries {
+            let ptr = format!("/objects/{}/geometry", obj);
+            let path = format!("objects[{}].geometry", obj);
+            let scene = edit_scene(base.clone(), &ptr, Some(geom.clone()));
+            let mut members: Vec<_> = members.iter().map(|&k| (k, s.clone(), invalid)).collect();
+            members.push((
+                "type",
+                serde_json::json!(true),
+                "invalid type: boolean `true`",
+            ));
+            assert_member_errors(&scene, &ptr, &path, &members);
+        }
+    }
+    assert_structure_error(
+        &edit_scene(
+            scene.clone(),
+            "/objects/0/geometry/type",
+            Some(serde_json::json!("torus")),
+        ),
+        "objects[0].geometry.type",
+        "unknown variant `torus`",
+    );
+    assert_scene_error(
+        &edit_scene(
+            area,
+            "/objects/1/geometry",
+            Some(serde_json::json!({ "type": "plane" })),
+        ),
+        "objects[1].geometry.type",
+        "Geometry of type 'plane' is not sampleable and can't be used for area light geometry",
+    );
+    let mesh = edit_scene(
+        scene,
+        "/objects/0/geometry",
+        Some(serde_json::json!({ "type": "mesh", "file": "models/cube.obj", "model": "Cube" })),
+    );
+    assert_member_errors(
+        &mesh,
+        "/objects/0/geometry",
+        "objects[0].geometry",
+        &[
+            ("file", serde_json::json!(1), "expected a string"),
+            ("model", serde_json::json!(1), "expected a string"),
+        ],
+    );
+    assert_scene_error(
+        &edit_scene(
+            mesh.clone(),
+            "/objects/0/geometry/model",
+            Some(serde_json::json!("Sphere")),
+        ),
+        "objects[0].geometry.model",
+        "Requested model 'Sphere' was not found in '\"models/cube.obj\"'",
+    );
+    let missing = edit_scene(
+        mesh,
+        "/objects/0/geometry/file",
+        Some(serde_json::json!("models/missing.obj")),
+    );
+    match load_error(&missing) {
+        SceneError::Resource {
+            ref loc, ref file, ..
+        } => {
+            assert_eq!(loc.path, "objects[0].geometry.file");
+            assert_eq!(file, Path::new("models/missing.obj"));
+        }
+        e => panic!("Unexpected error {}", e),
+    }
+}
+
+#[test]
+fn test_transform_errors() {
+    let s = serde_json::json!("x");
+    let invalid = "invalid type: string \"x\", expected f32";
+    let vector = "invalid length 1, expected an array of length 3";
+    let scene = test_scene();
+    assert_structure_error(
+        &edit_scene(
+            scene.clone(),
+            "/objects/0/transform",
+            Some(serde_json::json!({})),
+        ),
+        "objects[0].transform",
+        "invalid type: map, expected a sequence",
+    );
+    let transforms = vec![
+        (
+            serde_json::json!({ "type": "translate", "translation": [0, 0, 1] }),
+            vec![("translation", serde_json::json!([1]), vector)],
+        ),
+        (
+            serde_json::json!({ "type": "scale", "scaling": [1, 1, 1] }),
+            vec![(
+                "scaling",
+                serde_json::json!([1]),
+                "expected a scaling factor or an array of 3 scaling factors",
+            )],
+        ),
+        (
+            serde_json::json!({ "type": "rotate_x", "rotation": 1 }),
+            vec![("rotation", s.clone(), invalid)],
+        ),
+        (
+            serde_json::json!({ "type": "rotate_y", "rotation": 1 }),
+            vec![("rotation", s.clone(), invalid)],
+        ),
+        (
+            serde_json::json!({ "type": "rotate_z", "rotation": 1 }),
+            vec![("rotation", s.clone(), invalid)],
+        ),
+        (
+            serde_json::json!({ "type": "rotate", "rotation": 1, "axis": [0, 1, 0] }),
+            vec![
+                ("rotation", s.clone(), invalid),
+                ("axis", serde_json::json!([1]), vector),
+            ],
+        ),
+        (
+            serde_json::json!({ "type": "matrix", "matrix": [[1, 0, 0, 0], [0, 1, 0, 0], [0, 0, 1, 0], [0, 0, 0, 1]] }),
+            vec![
+                (
+                    "matrix",
+                    s.clone(),
+                    "invalid type: string \"x\", expected an array of length 4",
+                ),
+                (
+                    "type",
+                    serde_json::json!(true),
+                    "invalid type: boolean `true`",
+                ),
+            ],
+        ),
+    ];
+    for (t, members) in transforms {
+        let scene = edit_scene(scene.clone(), "/objects/0/transform/0", Some(t));
+        assert_member_errors(
+            &scene,
+            "/objects/0/transform/0",
+            "objects[0].transform[0]",
+            &members,
+        );
+    }
+    let matrix = edit_scene(
+        scene.clone(),
+        "/objects/0/transform/0",
+        Some(
+            serde_json::json!({ "type": "matrix", "matrix": [[1, 0, 0, 0], [0, 1, 0, 0], [0, 0, 1, 0], [0, 0, 0, 1]] }),
+        ),
+    );
+    assert_structure_error(
+        &edit_scene(
+            matrix.clone(),
+            "/objects/0/transform/0/matrix/1",
+            Some(serde_json::json!([1, 0])),
+        ),
+        "objects[0].transform[0].matrix[1]",
+        "invalid length 2, expected an array of length 4",
+    );
+    assert_structure_error(
+        &edit_scene(matrix, "/objects/0/transform/0/matrix/1/2", Some(s)),
+        "objects[0].transform[0].matrix[1][2]",
+        invalid,
+    );
+    assert_structure_error(
+        &edit_scene(
+            scene,
+            "/objects/0/transform/0/type",
+            Some(serde_json::json!("shear")),
+        ),
+        "objects[0].transform[0].type",
+        "unknown variant `shear`",
+    );
+}
+
+#[test]
+fn test_keyframe_errors() {
+    let s = serde_json::json!("x");
+    let scene = edit_scene(
+        test_scene(),
+        "/objects/0/keyframes",
+        Some(serde_json::json!({
+            "control_points": [{ "transform": [] }, { "transform": [] }],
+            "knots": [0, 0, 1, 1],
+            "degree": 1
+        })),
+    );
+    assert_member_errors(
+        &scene,
+        "/objects/0/keyframes",
+        "objects[0].keyframes",
+        &[
+            ("control_points", s.clone(), "expected a sequence"),
+            ("knots", s.clone(), "expected a sequence"),
+        ],
+    );
+    assert_structure_error(
+        &edit_scene(
+            scene.clone(),
+            "/objects/0/keyframes/degree",
+            Some(s.clone()),
+        ),
+        "objects[0].keyframes.degree",
+        "invalid type: string \"x\", expected usize",
+    );
+    assert_structure_error(
+        &edit_scene(scene.clone(), "/objects/0/keyframes/knots/1", Some(s)),
+        "objects[0].keyframes.knots[1]",
+        "invalid type: string \"x\", expected f32",
+    );
+    assert_member_errors(
+        &scene,
+        "/objects/0/keyframes/control_points/1",
+        "objects[0].keyframes.control_points[1]",
+        &[("transform", serde_json::json!({}), "expected a sequence")],
+    );
+}
@@ -0,0 +1,1501 @@
+//! Imports scenes described in the [pbrt-v3 scene format](https://www.pbrt.org/fileformat-v3.html),
+//! mapping the directives to the corresponding aperture types so the standard pbrt
+//! test scenes can be rendered.
+//!
+//! # Supported Directives
+//! - Options: `Camera` (perspective), `Film` (image), `PixelFilter` (box, gaussian, mitchell,
+//!   sinc, triangle), `Sampler`, `Integrator` (path, whitted, directlighting)
+//! - Transforms: `Identity`, `Translate`, `Scale`, `Rotate`, `LookAt`, `Transform`,
+//!   `ConcatTransform`, `CoordinateSystem`, `CoordSysTransform` along with
+//!   `AttributeBegin/End` and `TransformBegin/End` stacks
+//! - Shapes: `sphere`, `disk`, `trianglemesh` and `plymesh`
+//! - `Material` (matte, plastic, metal, glass, mirror), `MakeNamedMaterial`, `NamedMaterial`
+//! - `Texture` (constant, imagemap)
+//! - `LightSource` (point) and `AreaLightSource` (diffuse)
+//! - `ObjectBegin/End`, `ObjectInstance` and `Include`
+//!
+//! Anything else, e.g. an unsupported light type, shape or parameter, produces a
+//! warning and is ignored so the rest of the scene can still be rendered, unless that
+//! leaves the scene without any lights, which is an error. Relative
+//! paths for included files, meshes and images are resolved relative to the
+//! directory containing the main scene file, as pbrt does.
+//!
+//! Triangle meshes are transformed into world space when they're loaded, since
+//! arbitrary pbrt transformation matrices can't always be represented by the
+//! decomposed keyframe transforms used for instances.
+//!
+//! Spectra given as `blackbody` or sampled `spectrum` values are converted to RGB,
+//! except for lights with a `blackbody` spectrum, which emit it when rendering in
+//! spectral mode (see `film::spectrum`). Spectrum files aren't supported.
+//!
+//! Scenes are rendered in linear sRGB. As in pbrt, `imagemap` textures are decoded
+//! from sRGB if they're PNG or TGA images or their `gamma` parameter is set.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use image;
+
+use crate::{
+    film::{
+        filter, AnimatedColor, Blackbody, Camera, ColorKeyframe, ColorSpace, Colorf, CropWindow,
+        FrameInfo, RenderTarget,
+    },
+    geometry::{BoundableGeometry, Disk, Instance, Mesh, SampleableGeometry, Sphere},
+    integrator::{self, Integrators},
+    linalg::{self, AnimatedTransform, Matrix4, Normal, Point, Transform, Vector},
+    material::{Glass, Materials, Matte, Metal, Plastic, RoughGlass, SpecularMetal},
+    scene::{Location, Scene, SceneError},
+    texture::{self, Textures},
+};
+
+use self::{
+    params::{Param, ParamSet, Values},
+    tokenizer::{Token, TokenKind},
+};
+
+pub mod params;
+pub mod ply;
+pub mod tokenizer;
+
+impl Scene {
+    /// Load the scene described by the pbrt-v3 scene file. Returns the scene along with
+    /// the render target to write to, the samples per pixel and the frame information.
+    /// Warnings about unsupported features of the scene are printed.
+    pub fn load_pbrt(file: &str) -> Result<(Scene, RenderTarget, usize, FrameInfo), SceneError> {
+        let file_path = Path::new(file);
+        let text = fs::read_to_string(file_path).map_err(|err| SceneError::Io {
+            file: file_path.to_path_buf(),
+            err,
+        })?;
+        let mut importer = Importer::new(file_path);
+        let result = importer.parse(&text);
+        for w in &importer.warnings {
+            println!("Warning: {}", w);
+        }
+        result?;
+        importer.finish()
+    }
+}
+
+/// The attributes applied to shapes, saved and restored by `AttributeBegin/End`
+#[derive(Clone)]
+struct GraphicsState {
+    material: Arc<Materials>,
+    /// Emission of the active area light, if any
+    area_light: Option<(Colorf, Option<Blackbody>)>,
+    reverse_orientation: bool,
+    float_textures: HashMap<String, Arc<Textures>>,
+    color_textures: HashMap<String, Arc<Textures>>,
+    named_materials: HashMap<String, Arc<Materials>>,
+}
+
+/// The kind of block pushed onto the transform stack
+#[derive(Copy, Clone, PartialEq)]
+enum Block {
+    Attribute,
+    Transform,
+}
+
+/// The geometry created by a `Shape` directive
+enum Shape {
+    Sphere(Sphere),
+    Disk(Disk),
+    Mesh(Mesh),
+}
+
+impl Shape {
+    fn boundable(self) -> BoundableGeometry {
+        match self {
+            Shape::Sphere(s) => BoundableGeometry::Sphere(s),
+            Shape::Disk(d) => BoundableGeometry::Disk(d),
+            Shape::Mesh(m) => BoundableGeometry::Mesh(m),
+        }
+    }
+    fn sampleable(self) -> SampleableGeometry {
+        match self {
+            Shape::Sphere(s) => SampleableGeometry::Sphere(s),
+            Shape::Disk(d) => SampleableGeometry::Disk(d),
+            Shape::Mesh(m) => SampleableGeometry::Mesh(m),
+        }
+    }
+}
+
+/// A shape within an object definition, placed in the scene by `ObjectInstance`
+struct ObjectShape {
+    geom: Arc<BoundableGeometry>,
+    material: Arc<Materials>,
+    transform: Transform,
+}
+
+/// The options set by the camera directive
+struct CameraDesc {
+    cam_world: Transform,
+    fov: f32,
+}
+
+/// Tracks the state of the scene while walking through the directives
+struct Importer {
+    /// Directory of the main scene file, relative paths are resolved from here
+    base_dir: PathBuf,
+    /// The main scene file and any files included from it being parsed
+    files: Vec<PathBuf>,
+    /// Line of the directive being processed
+    line: usize,
+    ctm: Transform,
+    named_coords: HashMap<String, Transform>,
+    stack: Vec<(Block, Transform, Option<GraphicsState>)>,
+    gs: GraphicsState,
+    camera: Option<CameraDesc>,
+    dims: (usize, usize),
+    crop: Option<CropWindow>,
+    filter: Option<Box<filter::Filters>>,
+    spp: usize,
+    integrator: Option<Box<Integrators>>,
+    objects: HashMap<String, Vec<ObjectShape>>,
+    current_object: Option<(String, Vec<ObjectShape>)>,
+    instances: Vec<Instance>,
+    num_lights: usize,
+    warnings: Vec<String>,
+}
+
+impl Importer {
+    fn new(file: &Path) -> Importer {
+        let base_dir = match file.parent() {
+            Some(p) => p.to_path_buf(),
+            None => PathBuf::new(),
+        };
+        let default_material = Arc::new(Matte::new_material(
+            Arc::new(texture::ConstantColor::new_texture(Colorf::broadcast(0.5))),
+            Arc::new(texture::ConstantScalar::new_texture(0.0)),
+        ));
+        Importer {
+            base_dir,
+            files: vec![file.to_path_buf()],
+            line: 0,
+            ctm: Transform::identity(),
+            named_coords: HashMap::new(),
+            stack: Vec::new(),
+            gs: GraphicsState {
+                material: default_material,
+                area_light: None,
+                reverse_orientation: false,
+                float_textures: HashMap::new(),
+                color_textures: HashMap::new(),
+                named_materials: HashMap::new(),
+            },
+            camera: None,
+            dims: (640, 480),
+            crop: None,
+            filter: None,
+            spp: 16,
+            integrator: None,
+            objects: HashMap::new(),
+            current_object: None,
+            instances: Vec::new(),
+            num_lights: 0,
+            warnings: Vec::new(),
+        }
+    }
+    fn location(&self) -> Location {
+        Location {
+            file: self.files.last().unwrap().clone(),
+            path: format!("line {}", self.line),
+            name: None,
+        }
+    }
+    fn error<S: Into<String>>(&self, msg: S) -> SceneError {
+        SceneError::Invalid {
+            loc: self.location(),
+            msg: msg.into(),
+        }
+    }
+    fn warn<S: Into<String>>(&mut self, msg: S) {
+        let w = format!("{}: {}", self.location(), msg.into());
+        self.warnings.push(w);
+    }
+    fn resolve(&self, file: &str) -> PathBuf {
+        self.base_dir.join(file)
+    }
+    /// Parse the text of a scene file, running each directive
+    fn parse(&mut self, text: &str) -> Result<(), SceneError> {
+        let tokens = tokenizer::tokenize(text).map_err(|(line, msg)| {
+            self.line = line;
+            self.error(msg)
+        })?;
+        let mut i = 0;
+        while i < tokens.len() {
+            let token = &tokens[i];
+            self.line = token.line;
+            let name = match token.kind {
+                TokenKind::Ident(ref n) => n,
+                _ => return Err(self.error("Expected a directive")),
+            };
+            // The directive's arguments run until the next directive
+            let start = i + 1;
+            i = start;
+            while i < tokens.len() {
+                match tokens[i].kind {
+                    TokenKind::Ident(ref s) if s != "true" && s != "false" => break,
+                    _ => i += 1,
+                }
+            }
+            let mut args = Args {
+                tokens: &tokens[start..i],
+                pos: 0,
+            };
+            self.directive(name, &mut args)?;
+        }
+        Ok(())
+    }
+    fn directive(&mut self, name: &str, args: &mut Args) -> Result<(), SceneError> {
+        match name {
+            "Identity" => self.ctm = Transform::identity(),
+            "Translate" => {
+                let v = args.nums(3).map_err(|e| self.error(e))?;
+                self.ctm = self.ctm * Transform::translate(&Vector::new(v[0], v[1], v[2]));
+            }
+            "Scale" => {
+                let v = args.nums(3).map_err(|e| self.error(e))?;
+                self.ctm = self.ctm * Transform::scale(&Vector::new(v[0], v[1], v[2]));
+            }
+            "Rotate" => {
+                let v = args.nums(4).map_err(|e| self.error(e))?;
+                self.ctm = self.ctm * Transform::rotate(&Vector::new(v[1], v[2], v[3]), v[0]);
+            }
+            "LookAt" => {
+                let v = args.nums(9).map_err(|e| self.error(e))?;
+                let pos = Point::new(v[0], v[1], v[2]);
+                let target = Point::new(v[3], v[4], v[5]);
+                let up = Vector::new(v[6], v[7], v[8]);
+                let dir = (target - pos).normalized();
+                if linalg::cross(&up.normalized(), &dir).length() == 0.0 {
+                    self.warn("LookAt up vector and viewing direction are parallel, ignoring it");
+                } else {
+                    // pbrt's camera space is left handed, so flip the x axis of our
+                    // look at transform to match
+                    let cam_world = Transform::look_at(&pos, &target, &up)
+                        * Transform::scale(&Vector::new(-1.0, 1.0, 1.0));
+                    self.ctm = self.ctm * cam_world.inverse();
+                }
+            }
+            "Transform" | "ConcatTransform" => {
+                let v = args.num_list(16).map_err(|e| self.error(e))?;
+                let mut m = [0.0; 16];
+                m.copy_from_slice(&v[..]);
+                // pbrt matrices are specified in column major order
+                let t = Transform::from_mat(&Matrix4::new(m).transpose());
+                self.ctm = if name == "Transform" { t } else { self.ctm * t };
+            }
+            "CoordinateSystem" => {
+                let n = args.string().map_err(|e| self.error(e))?;
+                self.named_coords.insert(n, self.ctm);
+            }
+            "CoordSysTransform" => {
+                let n = args.string().map_err(|e| self.error(e))?;
+                match self.named_coords.get(&n) {
+                    Some(t) => self.ctm = *t,
+                    None => self.warn(format!("Couldn't find named coordinate system '{}'", n)),
+                }
+            }
+            "ReverseOrientation" => {
+                self.gs.reverse_orientation = !self.gs.reverse_orientation;
+            }
+            "WorldBegin" => {
+                self.ctm = Transform::identity();
+                self.named_coords
+                    .insert("world".to_owned(), Transform::identity());
+            }
+            "WorldEnd" => {
+                if !self.stack.is_empty() {
+                    self.warn("Missing end to AttributeBegin or TransformBegin");
+                }
+            }
+            "AttributeBegin" => {
+                self.stack
+                    .push((Block::Attribute, self.ctm, Some(self.gs.clone())));
+            }
+            "TransformBegin" => self.stack.push((Block::Transform, self.ctm, None)),
+            "AttributeEnd" | "TransformEnd" => self.pop_block(name)?,
+            "Camera" => self.camera(args)?,
+            "Film" => self.film(args)?,
+            "PixelFilter" => self.pixel_filter(args)?,
+            "Sampler" => {
+                args.string().map_err(|e| self.error(e))?;
+                let params = self.params(args)?;
+                self.spp = params.find_i32("pixelsamples", 16).max(1) as usize;
+                self.warn_unused(name, &params);
+            }
+            "Integrator" => self.integrator(args)?,
+            "Texture" => self.texture(args)?,
+            "Material" => {
+                let ty = args.string().map_err(|e| self.error(e))?;
+                let params = self.params(args)?;
+                self.gs.material = self.material(&ty, &params)?;
+                self.warn_unused(name, &params);
+            }
+            "MakeNamedMaterial" => {
+                let n = args.string().map_err(|e| self.error(e))?;
+                let params = self.params(args)?;
+                let ty = match params.find_string("type") {
+                    Some(t) => t.to_owned(),
+                    None => {
+                        return Err(self.error("No \"string type\" given for MakeNamedMaterial"))
+                    }
+                };
+                let mat = self.material(&ty, &params)?;
+                if self.gs.named_materials.insert(n.clone(), mat).is_some() {
+                    self.warn(format!("Named material '{}' redefined", n));
+                }
+                self.warn_unused(name, &params);
+            }
+            "NamedMaterial" => {
+                let n = args.string().map_err(|e| self.error(e))?;
+                match self.gs.named_materials.get(&n) {
+                    Some(m) => self.gs.material = m.clone(),
+                    None => self.warn(format!(
+                        "Named material '{}' not defined, using the current material",
+                        n
+                    )),
+                }
+            }
+            "LightSource" => self.light_source(args)?,
+            "AreaLightSource" => {
+                let ty = args.string().map_err(|e| self.error(e))?;
+                let params = self.params(args)?;
+                if ty == "diffuse" || ty == "area" {
+                    let l = self.light_spectrum(&params, "L")?;
+                    if params.find_bool("twosided", false) {
+                        self.warn("Two sided area lights are not supported, only the front side will emit");
+                    }
+                    self.gs.area_light = Some(l);
+                } else {
+                    self.warn(format!("AreaLightSource '{}' is not supported", ty));
+                }
+                self.warn_unused(name, &params);
+            }
+            "Shape" => self.shape(args)?,
+            "ObjectBegin" => {
+                let n = args.string().map_err(|e| self.error(e))?;
+                self.stack
+                    .push((Block::Attribute, self.ctm, Some(self.gs.clone())));
+                if self.current_object.is_some() {
+                    return Err(
+                        self.error("ObjectBegin called inside of another object definition")
+                    );
+                }
+                self.current_object = Some((n, Vec::new()));
+            }
+            "ObjectEnd" => {
+                match self.current_object.take() {
+                    Some((n, shapes)) => {
+                        self.objects.insert(n, shapes);
+                    }
+                    None => self.warn("ObjectEnd called outside of an object definition"),
+                }
+                self.pop_block("AttributeEnd")?;
+            }
+            "ObjectInstance" => {
+                let n = args.string().map_err(|e| self.error(e))?;
+                let shapes = match self.objects.get(&n) {
+                    Some(s) => s,
+                    None => {
+                        self.warn(format!("Object '{}' is not defined", n));
+                        return Ok(());
+                    }
+                };
+                for s in shapes {
+                    let tag = format!("{}_{}", n, self.instances.len());
+                    self.instances.push(Instance::receiver(
+                        s.geom.clone(),
+                        s.material.clone(),
+                        AnimatedTransform::unanimated(&(self.ctm * s.transform)),
+                        tag,
+                    ));
+                }
+            }
+            "Include" => {
+                let f = args.string().map_err(|e| self.error(e))?;
+                let path = self.resolve(&f);
+                if self.files.contains(&path) {
+                    return Err(self.error(format!("Include of '{}' is recursive", f)));
+                }
+                let text = fs::read_to_string(&path).map_err(|e| SceneError::Resource {
+                    loc: self.location(),
+                    file: path.clone(),
+                    msg: e.to_string(),
+                })?;
+                let line = self.line;
+                self.files.push(path);
+                self.parse(&text)?;
+                self.files.pop();
+                self.line = line;
+            }
+            _ => {
+                self.warn(format!("Unsupported directive '{}' was ignored", name));
+                return Ok(());
+            }
+        }
+        if args.pos < args.tokens.len() {
+            self.warn(format!("Unexpected extra arguments to {}", name));
+        }
+        Ok(())
+    }
+    fn pop_block(&mut self, name: &str) -> Result<(), SceneError> {
+        let expected = if name == "AttributeEnd" {
+            Block::Attribute
+        } else {
+            Block::Transform
+        };
+        match self.stack.pop() {
+            Some((block, ctm, gs)) => {
+                if block != expected {
+                    self.warn(format!("Mismatched nesting of {}", name));
+                }
+                self.ctm = ctm;
+                if let Some(gs) = gs {
+                    self.gs = gs;
+                }
+            }
+            None => self.warn(format!("Unmatched {} encountered, ignoring it", name)),
+        }
+        Ok(())
+    }
+    /// Parse the remaining arguments of the directive as a parameter list
+    fn params(&self, args: &mut Args) -> Result<ParamSet, SceneError> {
+        args.params().map_err(|e| self.error(e))
+    }
+    fn warn_unused(&mut self, directive: &str, params: &ParamSet) {
+        let unused: Vec<_> = params
+            .unused()
+            .map(|p| format!("{} {}", p.ty, p.name))
+            .collect();
+        for p in unused {
+            self.warn(format!(
+                "Parameter '{}' of {} is not supported",
+                p, directive
+            ));
+        }
+    }
+    /// Look up a spectrum parameter as an RGB color
+    fn spectrum(
+        &mut self,
+        params: &ParamSet,
+        name: &str,
+        default: Colorf,
+    ) -> Result<Colorf, SceneError> {
+        match params.find_spectrum(name).map_err(|e| self.error(e))? {
+            Some((c, warning)) => {
+                if let Some(w) = warning {
+                    self.warn(w);
+                }
+                Ok(c)
+            }
+            None => Ok(default),
+        }
+    }
+    /// Look up the spectrum `name` emitted by a light scaled by its `scale`, along with
+    /// the blackbody spectrum emitted if it's a blackbody
+    fn light_spectrum(
+        &mut self,
+        params: &ParamSet,
+        name: &str,
+    ) -> Result<(Colorf, Option<Blackbody>), SceneError> {
+        let l = self.spectrum(params, name, Colorf::broadcast(1.0))?;
+        let scale = self.spectrum(params, "scale", Colorf::broadcast(1.0))?;
+        let blackbody = params
+            .find_blackbody(name)
+            .map_err(|e| self.error(e))?
+            .map(|b| Blackbody::new(b.temperature, b.strength * scale.luminance()));
+        Ok((l * scale, blackbody))
+    }
+    /// Look up a spectrum parameter which may be bound to a texture
+    fn color_texture(
+        &mut self,
+        params: &ParamSet,
+        name: &str,
+        default: Colorf,
+    ) -> Result<Arc<Textures>, SceneError> {
+        if let Some(t) = params.find_texture(name) {
+            match self.gs.color_textures.get(t).cloned() {
+                Some(tex) => return Ok(tex),
+                None => self.warn(format!(
+                    "Couldn't find spectrum texture '{}' for parameter '{}'",
+                    t, name
+                )),
+            }
+        }
+        let c = self.spectrum(params, name, default)?;
+        Ok(Arc::new(texture::ConstantColor::new_texture(c)))
+    }
+    /// Look up a float parameter which may be bound to a texture. If `remap` is set
+    /// a constant value is treated as a pbrt microfacet roughness and remapped to the
+    /// distribution's alpha parameter
+    fn scalar_texture(
+        &mut self,
+        params: &ParamSet,
+        name: &str,
+        default: f32,
+        remap: bool,
+    ) -> Result<Arc<Textures>, SceneError> {
+        if let Some(t) = params.find_texture(name) {
+            match self.gs.float_textures.get(t).cloned() {
+                Some(tex) => {
+                    if remap {
+                        self.warn(format!("Roughness texture '{}' will not be remapped", t));
+                    }
+                    return Ok(tex);
+                }
+                None => self.warn(format!(
+                    "Couldn't find float texture '{}' for parameter '{}'",
+                    t, name
+                )),
+            }
+        }
+        let mut v = params.find_f32(name, default);
+        if remap {
+            v = roughness_to_alpha(v);
+        }
+        Ok(Arc::new(texture::ConstantScalar::new_texture(v)))
+    }
+    fn camera(&mut self, args: &mut Args) -> Result<(), SceneError> {
+        let ty = args.string().map_err(|e| self.error(e))?;
+        let params = self.params(args)?;
+        if ty != "perspective" {
+            self.warn(format!(
+                "Camera '{}' is not supported, using a perspective camera",
+                ty
+            ));
+        }
+        let fov = params.find_f32("fov", 90.0);
+        // The CTM is the world to camera transform at this point
+        let cam_world = self.ctm.inverse();
+        self.named_coords.insert("camera".to_owned(), cam_world);
+        self.camera = Some(CameraDesc { cam_world, fov });
+        self.warn_unused("Camera", &params);
+        Ok(())
+    }
+    fn film(&mut self, args: &mut Args) -> Result<(), SceneError> {
+        let ty = args.string().map_err(|e| self.error(e))?;
+        let params = self.params(args)?;
+        if ty != "image" {
+            self.warn(format!("Film '{}' is not supported", ty));
+        }
+        let x = params.find_i32("xresolution", 640);
+        let y = params.find_i32("yresolution", 480);
+        if x <= 0 || y <= 0 {
+            return Err(self.error(format!("Film resolution {}x{} must be positive", x, y)));
+        }
+        self.dims = (x as usize, y as usize);
+        let window = match (
+            params.find_floats("cropwindow"),
+            params.find_ints("pixelbounds"),
+        ) {
+            (Some(&[x0, x1, y0, y1]), _) => Some((
+                "cropwindow",
+                CropWindow::from_normalized(self.dims, (x0, x1), (y0, y1)),
+            )),
+            (_, Some(b)) if b.len() == 4 && b.iter().all(|&x| x >= 0) => Some((
+                "pixelbounds",
+                CropWindow::new((b[0] as u32, b[2] as u32), (b[1] as u32, b[3] as u32)),
+            )),
+            (None, None) => None,
+            _ => {
+                return Err(self.error("Film cropwindow and pixelbounds must have 4 values"));
+            }
+        };
+        self.crop = match window {
+            Some((name, w)) if !w.fits(self.dims) => {
+                return Err(self.error(format!(
+                    "Film {} must be inside the image and cover at least one pixel",
+                    name
+                )));
+            }
+            Some((_, w)) => Some(w),
+            None => None,
+        };
+        // The output file is chosen when running the renderer instead
+        params.find_string("filename");
+        self.warn_unused("Film", &params);
+        Ok(())
+    }
+    fn pixel_filter(&mut self, args: &mut Args) -> Result<(), SceneError> {
+        let ty = args.string().map_err(|e| self.error(e))?;
+        let params = self.params(args)?;
+        match &ty[..] {
+            "gaussian" => {
+                self.filter = Some(Box::new(filter::Gaussian::new_filter(
+                    params.find_f32("xwidth", 2.0),
+                    params.find_f32("ywidth", 2.0),
+                    params.find_f32("alpha", 2.0),
+                )))
+            }
+            "mitchell" => {
+                self.filter = Some(Box::new(filter::MitchellNetravali::new_filter(
+                    params.find_f32("xwidth", 2.0),
+                    params.find_f32("ywidth", 2.0),
+                    params.find_f32("B", 1.0 / 3.0),
+                    params.find_f32("C", 1.0 / 3.0),
+                )))
+            }
+            "box" => {
+                self.filter = Some(Box::new(filter::BoxFilter::new_filter(
+                    params.find_f32("xwidth", 0.5),
+                    params.find_f32("ywidth", 0.5),
+                )))
+            }
+            "triangle" => {
+                self.filter = Some(Box::new(filter::Triangle::new_filter(
+                    params.find_f32("xwidth", 2.0),
+                    params.find_f32("ywidth", 2.0),
+                )))
+            }
+            "sinc" => {
+                self.filter = Some(Box::new(filter::Lanczos::new_filter(
+                    params.find_f32("xwidth", 4.0),
+                    params.find_f32("ywidth", 4.0),
+                    params.find_f32("tau", 3.0),
+                )))
+            }
+            _ => {
+                self.warn(format!(
+                    "PixelFilter '{}' is not supported, using the Mitchell-Netravali filter",
+                    ty
+                ));
+                return Ok(());
+            }
+        }
+        self.warn_unused("PixelFilter", &params);
+        Ok(())
+    }
+    fn integrator(&mut self, args: &mut Args) -> Result<(), SceneError> {
+        let ty = args.string().map_err(|e| self.error(e))?;
+        let params = self.params(args)?;
+        let max_depth = params.find_i32("maxdepth", 5).max(0) as u32;
+        let integrator = match &ty[..] {
+            "path" => integrator::Path::new_integrator(u32::min(3, max_depth), max_depth),
+            "whitted" | "directlighting" => integrator::Whitted::new_integrator(max_depth),
+            _ => {
+                self.warn(format!(
+                    "Integrator '{}' is not supported, using the path tracer",
+                    ty
+                ));
+                integrator::Path::new_integrator(u32::min(3, max_depth), max_depth)
+            }
+        };
+        self.integrator = Some(Box::new(integrator));
+        self.warn_unused("Integrator", &params);
+        Ok(())
+    }
+    fn texture(&mut self, args: &mut Args) -> Result<(), SceneError> {
+        let name = args.string().map_err(|e| self.error(e))?;
+        let ty = args.string().map_err(|e| self.error(e))?;
+        let class = args.string().map_err(|e| self.error(e))?;
+        let params = self.params(args)?;
+        let is_color = match &ty[..] {
+            "spectrum" | "color" => true,
+            "float" => false,
+            _ => {
+                return Err(self.error(format!(
+                    "Texture type '{}' is unrecognized, it must be 'float' or 'spectrum'",
+                    ty
+                )))
+            }
+        };
+        let tex = match &class[..] {
+            "constant" => {
+                if is_color {
+                    let c = self.spectrum(&params, "value", Colorf::broadcast(1.0))?;
+                    texture::ConstantColor::new_texture(c)
+                } else {
+                    texture::ConstantScalar::new_texture(params.find_f32("value", 1.0))
+                }
+            }
+            "imagemap" => {
+                let file = match params.find_string("filename") {
+                    Some(f) => self.resolve(f),
+                    None => return Err(self.error("imagemap textures require a filename")),
+                };
+                let img = image::open(&file).map_err(|e| SceneError::Resource {
+                    loc: self.location(),
+                    file: file.clone(),
+                    msg: format!("Failed to load image file: {}", e),
+                })?;
+                let gamma = params.find_bool(
+                    "gamma",
+                    file.extension().is_some_and(|e| e == "png" || e == "tga"),
+                );
+                let color_space = if gamma {
+                    ColorSpace::Srgb
+                } else {
+                    ColorSpace::LinearSrgb
+                };
+                texture::Image::new_texture(img, Some(color_space), true, ColorSpace::LinearSrgb)
+            }
+            _ => {
+                self.warn(format!(
+                    "Texture class '{}' is not supported, texture '{}' was not created",
+                    class, name
+                ));
+                return Ok(());
+            }
+        };
+        let textures = if is_color {
+            &mut self.gs.color_textures
+        } else {
+            &mut self.gs.float_textures
+        };
+        textures.insert(name, Arc::new(tex));
+        self.warn_unused("Texture", &params);
+        Ok(())
+    }
+    fn material(&mut self, ty: &str, params: &ParamSet) -> Result<Arc<Materials>, SceneError> {
+        let remap = params.find_bool("remaproughness", true);
+        let mat = match ty {
+            "matte" => Matte::new_material(
+                self.color_texture(params, "Kd", Colorf::broadcast(0.5))?,
+                self.scalar_texture(params, "sigma", 0.0, false)?,
+            ),
+            "plastic" => Plastic::new_material(
+                self.color_texture(params, "Kd", Colorf::broadcast(0.25))?,
+                self.color_texture(params, "Ks", Colorf::broadcast(0.25))?,
+                self.scalar_texture(params, "roughness", 0.1, remap)?,
+            ),
+            "metal" => Metal::new_material(
+                // pbrt's default metal is copper
+                self.color_texture(params, "eta", Colorf::new(0.200_438, 0.924_033, 1.102_212))?,
+                self.color_texture(params, "k", Colorf::new(3.912_949, 2.452_848, 2.142_188))?,
+                self.scalar_texture(params, "roughness", 0.01, remap)?,
+            ),
+            "glass" => {
+                let reflect = self.color_texture(params, "Kr", Colorf::broadcast(1.0))?;
+                let transmit = self.color_texture(params, "Kt", Colorf::broadcast(1.0))?;
+                let eta = if params.find_floats("eta").is_some() {
+                    self.scalar_texture(params, "eta", 1.5, false)?
+                } else {
+                    self.scalar_texture(params, "index", 1.5, false)?
+                };
+                let rough = params.find_f32("uroughness", 0.0);
+                if rough > 0.0 {
+                    RoughGlass::new_material(
+                        reflect,
+                        transmit,
+                        eta,
+                        self.scalar_texture(params, "uroughness", 0.0, remap)?,
+                    )
+                } else {
+                    Glass::new_material(reflect, transmit, eta, None)
+                }
+            }
+            "mirror" => {
+                self.warn("Material 'mirror' is approximated by a specular silver metal");
+                params.find_spectrum("Kr").map_err(|e| self.error(e))?;
+                SpecularMetal::new_material(
+                    Arc::new(texture::ConstantColor::new_texture(Colorf::new(
+                        0.155, 0.117, 0.138,
+                    ))),
+                    Arc::new(texture::ConstantColor::new_texture(Colorf::new(
+                        4.828, 3.122, 2.147,
+                    ))),
+                )
+            }
+            _ => {
+                self.warn(format!(
+                    "Material '{}' is not supported, using a matte material",
+                    ty
+                ));
+                Matte::new_material(
+                    self.color_texture(params, "Kd", Colorf::broadcast(0.5))?,
+                    Arc::new(texture::ConstantScalar::new_texture(0.0)),
+                )
+            }
+        };
+        // The type is read by MakeNamedMaterial
+        params.find_string("type");
+        Ok(Arc::new(mat))
+    }
+    fn light_source(&mut self, args: &mut Args) -> Result<(), SceneError> {
+        let ty = args.string().map_err(|e| self.error(e))?;
+        let params = self.params(args)?;
+        if ty != "point" {
+            self.warn(format!(
+                "LightSource '{}' is not supported and was ignored",
+                ty
+            ));
+            return Ok(());
+        }
+        let (intensity, blackbody) = self.light_spectrum(&params, "I")?;
+        let from = params
+            .find_point3("from")
+            .map_err(|e| self.error(e))?
+            .unwrap_or_else(|| Point::broadcast(0.0));
+        let transform = self.ctm * Transform::translate(&Vector::new(from.x, from.y, from.z));
+        let tag = format!("point_light_{}", self.instances.len());
+        let mut light = Instance::point_light(
+            AnimatedTransform::unanimated(&transform),
+            AnimatedColor::with_keyframes(vec![ColorKeyframe::new(&intensity, 0.0)]),
+            tag,
+        );
+        if let (Instance::Emitter(ref mut e), Some(b)) = (&mut light, blackbody) {
+            e.set_blackbody(b, ColorSpace::LinearSrgb);
+        }
+        self.instances.push(light);
+        self.num_lights += 1;
+        self.warn_unused("LightSource", &params);
+        Ok(())
+    }
+    fn shape(&mut self, args: &mut Args) -> Result<(), SceneError> {
+        let ty = args.string().map_err(|e| self.error(e))?;
+        let params = self.params(args)?;
+        let mut transform = self.ctm;
+        let shape = match &ty[..] {
+            "sphere" => Shape::Sphere(Sphere::new(params.find_f32("radius", 1.0))),
+            "disk" => {
+                let height = params.find_f32("height", 0.0);
+                transform = transform * Transform::translate(&Vector::new(0.0, 0.0, height));
+                Shape::Disk(Disk::new(
+                    params.find_f32("radius", 1.0),
+                    params.find_f32("innerradius", 0.0),
+                ))
+            }
+            "trianglemesh" => {
+                let positions = params
+                    .find_point3s("point3", "P")
+                    .map_err(|e| self.error(e))?;
+                let positions = match positions {
+                    Some(p) => p,
+                    None => return Err(self.error("trianglemesh requires vertex positions \"P\"")),
+                };
+                let indices = match params.find_ints("indices") {
+                    Some(i) => i,
+                    None if positions.len() == 3 => vec![0, 1, 2],
+                    None => return Err(self.error("trianglemesh requires vertex \"indices\"")),
+                };
+                let normals = params
+                    .find_point3s("normal3", "N")
+                    .map_err(|e| self.error(e))?
+                    .map(|n| n.iter().map(|n| Normal::new(n.x, n.y, n.z)).collect());
+                let mut texcoords = params.find_point2s("uv").map_err(|e| self.error(e))?;
+                if texcoords.is_none() {
+                    texcoords = params.find_point2s("st").map_err(|e| self.error(e))?;
+                }
+                if indices.iter().any(|&i| i < 0) {
+                    return Err(self.error("trianglemesh has negative vertex indices"));
+                }
+                let indices = indices.iter().map(|&i| i as u32).collect();
+                let mesh = self.mesh(positions, normals, texcoords, indices, &transform)?;
+                transform = Transform::identity();
+                Shape::Mesh(mesh)
+            }
+            "plymesh" => {
+                let file = match params.find_string("filename") {
+                    Some(f) => self.resolve(f),
+                    None => return Err(self.error("plymesh requires a filename")),
+                };
+                let ply = ply::load(&file).map_err(|msg| SceneError::Resource {
+                    loc: self.location(),
+                    file: file.clone(),
+                    msg,
+                })?;
+                let mesh = self.mesh(
+                    ply.positions,
+                    ply.normals,
+                    ply.texcoords,
+                    ply.indices,
+                    &transform,
+                )?;
+                transform = Transform::identity();
+                Shape::Mesh(mesh)
+            }
+            _ => {
+                self.warn(format!("Shape '{}' is not supported and was ignored", ty));
+                return Ok(());
+            }
+        };
+        self.warn_unused("Shape", &params);
+
+        let material = self.gs.material.clone();
+        if self.current_object.is_some() && self.gs.area_light.is_some() {
+            self.warn(
+                "Area lights are not supported with object instancing, the shape will not emit",
+            );
+        }
+        if let Some((_, ref mut shapes)) = self.current_object {
+            shapes.push(ObjectShape {
+                geom: Arc::new(shape.boundable()),
+                material,
+                transform,
+            });
+            return Ok(());
+        }
+        let tag = format!("{}_{}", ty, self.instances.len());
+        let transform = AnimatedTransform::unanimated(&transform);
+        match self.gs.area_light {
+            Some((emission, blackbody)) => {
+                let mut light = Instance::area_light(
+                    Arc::new(shape.sampleable()),
+                    material,
+                    AnimatedColor::with_keyframes(vec![ColorKeyframe::new(&emission, 0.0)]),
+                    transform,
+                    tag,
+                );
+                if let (Instance::Emitter(ref mut e), Some(b)) = (&mut light, blackbody) {
+                    e.set_blackbody(b, ColorSpace::LinearSrgb);
+                }
+                self.instances.push(light);
+                self.num_lights += 1;
+            }
+            None => self.instances.push(Instance::receiver(
+                Arc::new(shape.boundable()),
+                material,
+                transform,
+                tag,
+            )),
+        }
+        Ok(())
+    }
+    /// Build a triangle mesh from the vertex data, transforming it to world space.
+    /// If normals or texture coordinates aren't given the triangles are split apart
+    /// to give each one a face normal and pbrt's default texture coordinates
+    fn mesh(
+        &mut self,
+        positions: Vec<Point>,
+        normals: Option<Vec<Normal>>,
+        texcoords: Option<Vec<Point>>,
+        indices: Vec<u32>,
+        transform: &Transform,
+    ) -> Result<Mesh, SceneError> {
+        if indices.is_empty() || !indices.len().is_multiple_of(3) {
+            return Err(self.error("Triangle mesh indices must be a non-empty multiple of 3"));
+        }
+        if indices.iter().any(|&i| i as usize >= positions.len()) {
+            return Err(self.error("Triangle mesh index is out of bounds"));
+        }
+        let normals = normals.filter(|n| n.len() == positions.len());
+        let texcoords = texcoords.filter(|t| t.len() == positions.len());
+        let positions: Vec<_> = positions.iter().map(|p| *transform * *p).collect();
+        let mesh = match (normals, texcoords) {
+            (Some(n), Some(t)) => Mesh::new(
+                Arc::new(positions),
+                Arc::new(n.iter().map(|n| *transform * *n).collect()),
+                Arc::new(t),
+                indices,
+            ),
+            (normals, texcoords) => {
+                // pbrt flips the face normal if the transform changes the handedness
+                // of the coordinate system or the orientation is reversed
+                let m = &transform.mat;
+                let det = m.at(0, 0) * (m.at(1, 1) * m.at(2, 2) - m.at(1, 2) * m.at(2, 1))
+                    - m.at(0, 1) * (m.at(1, 0) * m.at(2, 2) - m.at(1, 2) * m.at(2, 0))
+                    + m.at(0, 2) * (m.at(1, 0) * m.at(2, 1) - m.at(1, 1) * m.at(2, 0));
+                let flip = (det < 0.0) != self.gs.reverse_orientation;
+                let default_uv = [
+                    Point::new(0.0, 0.0, 0.0),
+                    Point::new(1.0, 0.0, 0.0),
+                    Point::new(1.0, 1.0, 0.0),
+                ];
+                let mut p = Vec::with_capacity(indices.len());
+                let mut n = Vec::with_capacity(indices.len());
+                let mut t = Vec::with_capacity(indices.len());
+                for tri in indices.chunks(3) {
+                    let v = [tri[0] as usize, tri[1] as usize, tri[2] as usize];
+                    let face = linalg::cross(
+                        &(positions[v[1]] - positions[v[0]]),
+                        &(positions[v[2]] - positions[v[0]]),
+                    )
+                    .normalized();
+                    let face = if flip { -face } else { face };
+                    for (j, &i) in v.iter().enumerate() {
+                        p.push(positions[i]);
+                        n.push(match normals {
+                            Some(ref normals) => *transform * normals[i],
+                            None => Normal::new(face.x, face.y, face.z),
+                        });
+                        t.push(match texcoords {
+                            Some(ref t) => t[i],
+                            None => default_uv[j],
+                        });
+                    }
+                }
+                let indices = (0..p.len() as u32).collect();
+                Mesh::new(Arc::new(p), Arc::new(n), Arc::new(t), indices)
+            }
+        };
+        Ok(mesh)
+    }
+    /// Build the scene from the directives that have been run
+    fn finish(mut self) -> Result<(Scene, RenderTarget, usize, FrameInfo), SceneError> {
+        if self.instances.is_empty() {
+            return Err(SceneError::NoObjects {
+                file: self.files[0].clone(),
+            });
+        }
+        // Unsupported lights are skipped, which can leave nothing to render with
+        if self.num_lights == 0 {
+            return Err(SceneError::NoLights {
+                file: self.files[0].clone(),
+            });
+        }
+        let filter = self.filter.take().unwrap_or_else(|| {
+            Box::new(filter::MitchellNetravali::new_filter(
+                2.0,
+                2.0,
+                1.0 / 3.0,
+                1.0 / 3.0,
+            ))
+        });
+        let integrator = self
+            .integrator
+            .take()
+            .unwrap_or_else(|| Box::new(integrator::Path::new_integrator(3, 5)));
+        let camera = match self.camera.take() {
+            Some(c) => c,
+            None => CameraDesc {
+                cam_world: Transform::identity(),
+                fov: 90.0,
+            },
+        };
+        let camera = Camera::new(
+            AnimatedTransform::unanimated(&camera.cam_world),
+            camera.fov,
+            self.dims,
+            0.0,
+            0,
+        );
+        let mut rt = RenderTarget::new(self.dims, (2, 2), filter);
+        rt.set_crop(self.crop);
+        let scene = Scene::new(
+            vec![camera],
+            integrator,
+            self.gs.named_materials,
+            HashMap::new(),
+            self.instances,
+            0.0,
+        );
+        Ok((scene, rt, self.spp, FrameInfo::new(1, 0.0, 0, 0)))
+    }
+}
+
+/// Convert pbrt's microfacet roughness to the alpha parameter of the distribution
+fn roughness_to_alpha(roughness: f32) -> f32 {
+    let x = f32::ln(f32::max(roughness, 1e-3));
+    1.62142
+        + 0.819_955 * x
+        + 0.1734 * x * x
+        + 0.017_120_1 * x * x * x
+        + 0.000_640_711 * x * x * x * x
+}
+
+/// The arguments following a directive
+struct Args<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+}
+
+impl<'a> Args<'a> {
+    fn next(&mut self) -> Option<&'a TokenKind> {
+        let t = self.tokens.get(self.pos).map(|t| &t.kind);
+        self.pos += 1;
+        t
+    }
+    fn string(&mut self) -> Result<String, String> {
+        match self.next() {
+            Some(TokenKind::Str(s)) => Ok(s.clone()),
+            _ => Err("Expected a string argument".to_owned()),
+        }
+    }
+    fn nums(&mut self, n: usize) -> Result<Vec<f32>, String> {
+        let mut v = Vec::with_capacity(n);
+        for _ in 0..n {
+            match self.next() {
+                Some(&TokenKind::Num(x)) => v.push(x),
+                _ => return Err(format!("Expected {} numbers", n)),
+            }
+        }
+        Ok(v)
+    }
+    /// Read a list of `n` numbers, which may be enclosed in brackets
+    fn num_list(&mut self, n: usize) -> Result<Vec<f32>, String> {
+        if let Some(&TokenKind::OpenBracket) = self.tokens.get(self.pos).map(|t| &t.kind) {
+            self.pos += 1;
+            let v = self.nums(n)?;
+            match self.next() {
+                Some(&TokenKind::CloseBracket) => Ok(v),
+                _ => Err(format!("Expected {} numbers", n)),
+            }
+        } else {
+            self.nums(n)
+        }
+    }
+    /// Read the remaining arguments as a parameter list
+    fn params(&mut self) -> Result<ParamSet, String> {
+        let mut params = Vec::new();
+        while let Some(t) = self.next() {
+            let decl = match *t {
+                TokenKind::Str(ref s) => s,
+                _ => return Err("Expected a parameter declaration string".to_owned()),
+            };
+            let mut values = Vec::new();
+            match self.next() {
+                Some(&TokenKind::OpenBracket) => loop {
+                    match self.next() {
+                        Some(&TokenKind::CloseBracket) => break,
+                        Some(&TokenKind::OpenBracket) | None => {
+                            return Err(format!("Unterminated value list for '{}'", decl))
+                        }
+                        Some(v) => values.push(v),
+                    }
+                },
+                Some(&TokenKind::CloseBracket) | None => {
+                    return Err(format!("Expected a value for parameter '{}'", decl))
+                }
+                Some(v) => values.push(v),
+            }
+            let is_bool = decl.split_whitespace().next() == Some("bool");
+            let values = if is_bool {
+                let b: Option<Vec<_>> = values
+                    .iter()
+                    .map(|v| match **v {
+                        TokenKind::Str(ref s) | TokenKind::Ident(ref s) if s == "true" => {
+                            Some(true)
+                        }
+                        TokenKind::Str(ref s) | TokenKind::Ident(ref s) if s == "false" => {
+                            Some(false)
+                        }
+                        _ => None,
+                    })
+                    .collect();
+                b.map(Values::Bools)
+            } else if values.iter().all(|v| matches!(**v, TokenKind::Num(_))) {
+                Some(Values::Nums(
+                    values
+                        .iter()
+                        .filter_map(|v| match **v {
+                            TokenKind::Num(x) => Some(x),
+                            _ => None,
+                        })
+                        .collect(),
+                ))
+            } else if values.iter().all(|v| matches!(**v, TokenKind::Str(_))) {
+                Some(Values::Strs(
+                    values
+                        .iter()
+                        .filter_map(|v| match **v {
+                            TokenKind::Str(ref s) => Some(s.clone()),
+                            _ => None,
+                        })
+                        .collect(),
+                ))
+            } else {
+                None
+            };
+            match values {
+                Some(v) => params.push(Param::new(decl, v)?),
+                None => return Err(format!("Invalid values for parameter '{}'", decl)),
+            }
+        }
+        Ok(ParamSet::new(params))
+    }
+}
+
+#[cfg(test)]
+fn import(text: &str) -> Importer {
+    let mut importer = Importer::new(Path::new("test.pbrt"));
+    if let Err(e) = importer.parse(text) {
+        panic!("Failed to import: {}", e);
+    }
+    importer
+}
+
+#[cfg(test)]
+fn import_error(text: &str) -> SceneError {
+    match Importer::new(Path::new("test.pbrt")).parse(text) {
+        Ok(_) => panic!("Import was expected to fail"),
+        Err(e) => e,
+    }
+}
+
+#[test]
+fn test_load_cornell() {
+    let (scene, rt, spp, frame_info) = match Scene::load_pbrt("cornell.pbrt") {
+        Ok(s) => s,
+        Err(e) => panic!("{}", e),
+    };
+    assert_eq!(rt.dimensions(), (1000, 1000));
+    assert_eq!(spp, 512);
+    assert_eq!(frame_info.end, 0);
+    assert_eq!(scene.bvh.iter().count(), 6);
+    let lights = scene
+        .bvh
+        .iter()
+        .filter(|i| matches!(**i, Instance::Emitter(_)))
+        .count();
+    assert_eq!(lights, 1);
+}
+
+#[test]
+fn test_unsupported_warnings() {
+    let text = fs::read_to_string("hello.pbrt").unwrap();
+    let importer = import(&text);
+    let expected = [
+        "line 14: LightSource 'infinite' is not supported and was ignored",
+        "line 17: LightSource 'distant' is not supported and was ignored",
+        "line 26: Texture class 'checkerboard' is not supported, texture 'checks' was not created",
+        "line 29: Couldn't find spectrum texture 'checks' for parameter 'Kd'",
+    ];
+    for e in &expected {
+        assert!(
+            importer.warnings.iter().any(|w| w.ends_with(e)),
+            "Missing warning '{}' in {:?}",
+            e,
+            importer.warnings
+        );
+    }
+    assert_eq!(importer.warnings.len(), expected.len());
+    assert_eq!(importer.instances.len(), 2);
+    assert_eq!(importer.num_lights, 0);
+    assert_eq!(importer.spp, 128);
+    assert_eq!(importer.dims, (400, 400));
+    assert_eq!(importer.crop, None);
+    let importer = import("Film \"image\" \"float cropwindow\" [0.25 0.75 0 0.5]");
+    assert_eq!(importer.crop, Some(CropWindow::new((160, 0), (480, 240))));
+
+    let importer = import("Foo 1 2 \"bar\"\nShape \"cylinder\" \"float radius\" 1\nShape \"sphere\" \"float zmax\" 0.5");
+    assert_eq!(
+        importer.warnings,
+        vec![
+            "test.pbrt: line 1: Unsupported directive 'Foo' was ignored",
+            "test.pbrt: line 2: Shape 'cylinder' is not supported and was ignored",
+            "test.pbrt: line 3: Parameter 'float zmax' of Shape is not supported",
+        ]
+    );
+}
+
+#[test]
+fn test_transform_stack() {
+    let importer = import(
+        "Translate 1 2 3
+        AttributeBegin
+          Scale 2 2 2
+          Material \"glass\"
+          TransformBegin
+            Rotate 90 0 0 1
+          TransformEnd
+          Transform [2 0 0 0  0 2 0 0  0 0 2 0  1 0 0 1]
+        AttributeEnd
+        TransformEnd",
+    );
+    assert_eq!(
+        importer.ctm.mat,
+        Transform::translate(&Vector::new(1.0, 2.0, 3.0)).mat
+    );
+    assert!(matches!(*importer.gs.material, Materials::Matte(_)));
+    assert_eq!(importer.warnings.len(), 1);
+    assert!(importer.warnings[0].ends_with("Unmatched TransformEnd encountered, ignoring it"));
+
+    let importer = import("Transform [2 0 0 0  0 2 0 0  0 0 2 0  1 0 0 1]");
+    let p = importer.ctm * Point::new(1.0, 1.0, 1.0);
+    assert_eq!(p, Point::new(3.0, 2.0, 2.0));
+}
+
+#[test]
+fn test_camera() {
+    let importer = import(
+        "Scale -1 1 1
+        LookAt 0 0 -5  0 0 0  0 1 0
+        Camera \"perspective\" \"float fov\" 45",
+    );
+    let camera = importer.camera.as_ref().unwrap();
+    assert_eq!(camera.fov, 45.0);
+    let pos = camera.cam_world * Point::broadcast(0.0);
+    assert!(pos.distance(&Point::new(0.0, 0.0, -5.0)) < 1e-5);
+    // The camera looks down +z and the flip of x is kept in the camera transform
+    let dir = camera.cam_world * Vector::new(0.0, 0.0, 1.0);
+    assert!((dir - Vector::new(0.0, 0.0, 1.0)).length() < 1e-5);
+    let right = camera.cam_world * Vector::new(1.0, 0.0, 0.0);
+    assert!((right - Vector::new(-1.0, 0.0, 0.0)).length() < 1e-5);
+}
+
+#[test]
+fn test_shapes() {
+    let mut importer = import(
+        "AttributeBegin
+          AreaLightSource \"diffuse\" \"rgb L\" [1 2 3]
+          Shape \"disk\" \"float radius\" 2
+          Shape \"trianglemesh\" \"point P\" [0 0 0  1 0 0  1 1 0  0 1 0]
+            \"integer indices\" [0 1 2  0 2 3]
+        AttributeEnd
+        MakeNamedMaterial \"red\" \"string type\" \"plastic\" \"rgb Kd\" [1 0 0]
+        NamedMaterial \"red\"
+        Shape \"sphere\"
+        LightSource \"point\" \"point from\" [0 5 0] \"rgb I\" [10 10 10]",
+    );
+    assert!(importer.warnings.is_empty(), "{:?}", importer.warnings);
+    assert_eq!(importer.num_lights, 3);
+    assert!(importer.gs.area_light.is_none());
+    assert!(matches!(*importer.gs.material, Materials::Plastic(_)));
+    let tags: Vec<_> = importer
+        .instances
+        .iter()
+        .map(|i| i.tag().to_owned())
+        .collect();
+    assert_eq!(
+        tags,
+        vec!["disk_0", "trianglemesh_1", "sphere_2", "point_light_3"]
+    );
+    let mesh = match importer.instances.remove(1) {
+        Instance::Emitter(e) => e,
+        _ => panic!("Expected the mesh to be an area light"),
+    };
+    let mut ray = linalg::Ray::new(
+        &Point::new(0.5, 0.25, 1.0),
+        &Vector::new(0.0, 0.0, -1.0),
+        0.0,
+    );
+    let (dg, _) = mesh.intersect(&mut ray).unwrap();
+    // Without normals the face normal is used
+    assert_eq!(dg.n, Normal::new(0.0, 0.0, 1.0));
+    assert_eq!(ray.max_t, 1.0);
+}
+
+#[test]
+fn test_object_instancing() {
+    let importer = import(
+        "ObjectBegin \"pair\"
+          Shape \"sphere\"
+          Translate 2 0 0
+          Shape \"sphere\"
+        ObjectEnd
+        Translate 0 1 0
+        ObjectInstance \"pair\"
+        ObjectInstance \"pair\"
+        ObjectInstance \"missing\"",
+    );
+    assert_eq!(importer.instances.len(), 4);
+    assert_eq!(
+        importer.ctm.mat,
+        Transform::translate(&Vector::new(0.0, 1.0, 0.0)).mat
+    );
+    let t = importer.instances[1].get_transform().transform(0.0);
+    let p = t * Point::broadcast(0.0);
+    assert!(p.distance(&Point::new(2.0, 1.0, 0.0)) < 1e-5);
+    assert_eq!(importer.warnings.len(), 1);
+    assert!(importer.warnings[0].ends_with("line 9: Object 'missing' is not defined"));
+}
+
+#[test]
+fn test_include_and_ply() {
+    let dir = std::env::temp_dir().join(format!("aperture_pbrt_test_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let ply = "ply\nformat ascii 1.0\nelement vertex 3\nproperty float x\n\
+               property float y\nproperty float z\nelement face 1\n\
+               property list uchar int vertex_indices\nend_header\n\
+               0 0 0\n1 0 0\n0 1 0\n3 0 1 2\n";
+    fs::write(dir.join("tri.ply"), ply).unwrap();
+    fs::write(
+        dir.join("geom.pbrt"),
+        "Shape \"plymesh\" \"string filename\" \"tri.ply\"\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("scene.pbrt"),
+        "WorldBegin\nLightSource \"point\"\nInclude \"geom.pbrt\"\nWorldEnd\n",
+    )
+    .unwrap();
+    fs::write(dir.join("loop.pbrt"), "Include \"loop.pbrt\"\n").unwrap();
+    let main = dir.join("scene.pbrt");
+    let result = Scene::load_pbrt(main.to_str().unwrap());
+    let looped = Scene::load_pbrt(dir.join("loop.pbrt").to_str().unwrap());
+    let missing = Scene::load_pbrt(dir.join("missing.pbrt").to_str().unwrap());
+    fs::remove_dir_all(&dir).unwrap();
+
+    match result {
+        Ok((scene, ..)) => assert_eq!(scene.bvh.iter().count(), 2),
+        Err(e) => panic!("{}", e),
+    }
+    match looped {
+        Err(e) => assert_eq!(e.message(), "Include of 'loop.pbrt' is recursive"),
+        Ok(_) => panic!("Recursive include should fail"),
+    }
+    assert!(matches!(missing, Err(SceneError::Io { .. })));
+}
+
+#[test]
+fn test_import_errors() {
+    let errors = [
+        (
+            "Shape \"sphere\"\n\"float radius\" [1",
+            "line 1",
+            "Unterminated value list for 'float radius'",
+        ),
+        ("\n\n\"sphere\"", "line 3", "Expected a directive"),
+        ("Translate 1 2", "line 1", "Expected 3 numbers"),
+        (
+            "Shape \"sphere\" \"quaternion q\" [1 0 0 0]",
+            "line 1",
+            "Unrecognized parameter type 'quaternion'",
+        ),
+        (
+            "Shape \"sphere\" \"float radius\" \"big\"",
+            "line 1",
+            "Invalid values specified for parameter 'float radius'",
+        ),
+        (
+            "Shape \"trianglemesh\" \"integer indices\" [0 1 2]",
+            "line 1",
+            "trianglemesh requires vertex positions \"P\"",
+        ),
+        (
+            "Shape \"trianglemesh\" \"point P\" [0 0 0 1 0 0 1 1 0] \"integer indices\" [0 1 3]",
+            "line 1",
+            "Triangle mesh index is out of bounds",
+        ),
+        (
+            "MakeNamedMaterial \"a\" \"rgb Kd\" [1 1 1]",
+            "line 1",
+            "No \"string type\" given for MakeNamedMaterial",
+        ),
+        (
+            "Film \"image\" \"integer xresolution\" 0",
+            "line 1",
+            "Film resolution 0x480 must be positive",
+        ),
+        (
+            "Film \"image\" \"float cropwindow\" [0.5 0.5 0 1]",
+            "line 1",
+            "Film cropwindow must be inside the image and cover at least one pixel",
+        ),
+        (
+            "Film \"image\" \"integer pixelbounds\" [0 10 0]",
+            "line 1",
+            "Film cropwindow and pixelbounds must have 4 values",
+        ),
+        (
+            "Shape \"plymesh\" \"string filename\" \"missing.ply\"",
+            "line 1",
+            "Failed to load 'missing.ply': No such file or directory (os error 2)",
+        ),
+        ("Shape\n\"sphere", "line 2", "Unterminated string"),
+    ];
+    for &(text, path, msg) in &errors {
+        let e = import_error(text);
+        assert_eq!(e.location().unwrap().path, path, "for '{}'", e);
+        assert_eq!(e.message(), msg);
+    }
+    match Importer::new(Path::new("test.pbrt")).finish() {
+        Err(SceneError::NoObjects { .. }) => {}
+        _ => panic!("Expected an error for a scene without objects"),
+    }
+    // The lights in hello.pbrt are all unsupported
+    match Scene::load_pbrt("hello.pbrt") {
+        Err(e @ SceneError::NoLights { .. }) => assert_eq!(
+            e.to_string(),
+            "hello.pbrt: Aborting: the scene does not have any lights!"
+        ),
+        _ => panic!("Expected an error for a scene without lights"),
+    }
+}
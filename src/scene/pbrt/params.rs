@@ -0,0 +1,327 @@
+//! Parameter lists passed to pbrt directives, e.g. `"float radius" [1.5]`. Each
+//! parameter declares its type and name in a string followed by a single value
+//! or a bracketed list of values. The parameter set tracks which parameters were
+//! looked up so unsupported ones can be reported.
+
+use std::cell::Cell;
+
+use crate::{
+    film::{spectrum, Blackbody, ColorSpace, Colorf},
+    linalg::Point,
+};
+
+/// The values of a parameter, stored based on the declared type
+#[derive(Debug, Clone, PartialEq)]
+pub enum Values {
+    Nums(Vec<f32>),
+    Strs(Vec<String>),
+    Bools(Vec<bool>),
+}
+
+/// A single parameter, e.g. `"rgb Kd" [0.5 0.5 0.5]`
+#[derive(Debug)]
+pub struct Param {
+    /// The declared type, with pbrt's synonyms mapped to a single name, e.g. `color` to `rgb`
+    pub ty: String,
+    pub name: String,
+    pub values: Values,
+    used: Cell<bool>,
+}
+
+impl Param {
+    /// Create the parameter from its `"type name"` declaration and values
+    pub fn new(decl: &str, values: Values) -> Result<Param, String> {
+        let mut parts = decl.split_whitespace();
+        let (ty, name) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(t), Some(n), None) => (t, n),
+            _ => {
+                return Err(format!(
+                    "Invalid parameter declaration '{}', expected \"type name\"",
+                    decl
+                ))
+            }
+        };
+        let ty = match ty {
+            "point" => "point3",
+            "vector" => "vector3",
+            "normal" => "normal3",
+            "color" => "rgb",
+            t => t,
+        };
+        let valid = match (ty, &values) {
+            ("integer", &Values::Nums(_))
+            | ("float", &Values::Nums(_))
+            | ("point2", &Values::Nums(_))
+            | ("vector2", &Values::Nums(_))
+            | ("point3", &Values::Nums(_))
+            | ("vector3", &Values::Nums(_))
+            | ("normal3", &Values::Nums(_))
+            | ("rgb", &Values::Nums(_))
+            | ("xyz", &Values::Nums(_))
+            | ("blackbody", &Values::Nums(_))
+            | ("spectrum", &Values::Nums(_))
+            | ("spectrum", &Values::Strs(_))
+            | ("string", &Values::Strs(_))
+            | ("texture", &Values::Strs(_))
+            | ("bool", &Values::Bools(_)) => true,
+            ("integer", _)
+            | ("float", _)
+            | ("point2", _)
+            | ("vector2", _)
+            | ("point3", _)
+            | ("vector3", _)
+            | ("normal3", _)
+            | ("rgb", _)
+            | ("xyz", _)
+            | ("blackbody", _)
+            | ("string", _)
+            | ("texture", _)
+            | ("bool", _) => false,
+            _ => return Err(format!("Unrecognized parameter type '{}'", ty)),
+        };
+        if !valid {
+            return Err(format!(
+                "Invalid values specified for parameter '{} {}'",
+                ty, name
+            ));
+        }
+        Ok(Param {
+            ty: ty.to_owned(),
+            name: name.to_owned(),
+            values,
+            used: Cell::new(false),
+        })
+    }
+}
+
+/// The list of parameters passed to a directive
+#[derive(Debug, Default)]
+pub struct ParamSet {
+    params: Vec<Param>,
+}
+
+impl ParamSet {
+    pub fn new(params: Vec<Param>) -> ParamSet {
+        ParamSet { params }
+    }
+    /// Find the parameter with the name and one of the types passed, marking it as used
+    fn find(&self, types: &[&str], name: &str) -> Option<&Param> {
+        let p = self
+            .params
+            .iter()
+            .find(|p| p.name == name && types.contains(&&p.ty[..]));
+        if let Some(p) = p {
+            p.used.set(true);
+        }
+        p
+    }
+    /// Find the numeric values of the parameter with one of the types passed
+    fn find_nums(&self, types: &[&str], name: &str) -> Option<&[f32]> {
+        match self.find(types, name).map(|p| &p.values) {
+            Some(Values::Nums(v)) => Some(&v[..]),
+            _ => None,
+        }
+    }
+    pub fn find_f32(&self, name: &str, default: f32) -> f32 {
+        self.find_nums(&["float"], name)
+            .and_then(|v| v.first().cloned())
+            .unwrap_or(default)
+    }
+    pub fn find_i32(&self, name: &str, default: i32) -> i32 {
+        self.find_nums(&["integer"], name)
+            .and_then(|v| v.first().map(|x| *x as i32))
+            .unwrap_or(default)
+    }
+    pub fn find_bool(&self, name: &str, default: bool) -> bool {
+        match self.find(&["bool"], name).map(|p| &p.values) {
+            Some(Values::Bools(v)) if !v.is_empty() => v[0],
+            _ => default,
+        }
+    }
+    pub fn find_string(&self, name: &str) -> Option<&str> {
+        self.find(&["string"], name)
+            .and_then(|p| match p.values {
+                Values::Strs(ref v) => v.first(),
+                _ => None,
+            })
+            .map(|s| &s[..])
+    }
+    /// Find the name of the texture bound to the parameter
+    pub fn find_texture(&self, name: &str) -> Option<&str> {
+        self.find(&["texture"], name)
+            .and_then(|p| match p.values {
+                Values::Strs(ref v) => v.first(),
+                _ => None,
+            })
+            .map(|s| &s[..])
+    }
+    pub fn find_floats(&self, name: &str) -> Option<&[f32]> {
+        self.find_nums(&["float"], name)
+    }
+    pub fn find_ints(&self, name: &str) -> Option<Vec<i32>> {
+        self.find_nums(&["integer"], name)
+            .map(|v| v.iter().map(|x| *x as i32).collect())
+    }
+    /// Find a list of 3 component points, vectors or normals. Returns an error
+    /// if the number of values isn't a multiple of 3
+    pub fn find_point3s(&self, ty: &str, name: &str) -> Result<Option<Vec<Point>>, String> {
+        match self.find_nums(&[ty], name) {
+            Some(v) if v.len() % 3 == 0 => Ok(Some(
+                v.chunks(3).map(|p| Point::new(p[0], p[1], p[2])).collect(),
+            )),
+            Some(_) => Err(format!(
+                "The values of '{} {}' must be a multiple of 3",
+                ty, name
+            )),
+            None => Ok(None),
+        }
+    }
+    pub fn find_point3(&self, name: &str) -> Result<Option<Point>, String> {
+        self.find_point3s("point3", name)
+            .map(|v| v.and_then(|v| v.first().cloned()))
+    }
+    /// Find a list of 2 component points, e.g. texture coordinates. Returns an error
+    /// if the number of values isn't a multiple of 2
+    pub fn find_point2s(&self, name: &str) -> Result<Option<Vec<Point>>, String> {
+        match self.find_nums(&["point2", "float"], name) {
+            Some(v) if v.len() % 2 == 0 => Ok(Some(
+                v.chunks(2).map(|p| Point::new(p[0], p[1], 0.0)).collect(),
+            )),
+            Some(_) => Err(format!("The values of '{}' must be a multiple of 2", name)),
+            None => Ok(None),
+        }
+    }
+    /// Find a spectrum parameter, converting it to RGB. The second value
+    /// of the tuple is a warning if the spectrum could only be approximated.
+    pub fn find_spectrum(&self, name: &str) -> Result<Option<(Colorf, Option<String>)>, String> {
+        let p = match self.find(&["rgb", "xyz", "blackbody", "spectrum"], name) {
+            Some(p) => p,
+            None => return Ok(None),
+        };
+        match (&p.ty[..], &p.values) {
+            ("rgb", Values::Nums(v)) if v.len() == 3 => {
+                Ok(Some((Colorf::new(v[0], v[1], v[2]), None)))
+            }
+            ("xyz", Values::Nums(v)) if v.len() == 3 => Ok(Some((
+                Colorf::new(
+                    3.240479 * v[0] - 1.53715 * v[1] - 0.498535 * v[2],
+                    -0.969256 * v[0] + 1.875991 * v[1] + 0.041556 * v[2],
+                    0.055648 * v[0] - 0.204043 * v[1] + 1.057311 * v[2],
+                ),
+                None,
+            ))),
+            ("blackbody", Values::Nums(v)) => {
+                let rgb = blackbody(name, v)?.to_rgb(ColorSpace::LinearSrgb);
+                Ok(Some((rgb, None)))
+            }
+            ("spectrum", Values::Nums(v)) if v.len() >= 2 && v.len() % 2 == 0 => {
+                if v.iter().any(|x| !x.is_finite()) {
+                    return Err(format!(
+                        "'spectrum {}' wavelengths and values must be finite numbers",
+                        name
+                    ));
+                }
+                // Samples are (wavelength, value) pairs
+                let mut samples: Vec<_> = v.chunks(2).map(|s| (s[0], s[1])).collect();
+                samples.sort_by(|a, b| a.0.total_cmp(&b.0));
+                Ok(Some((spectrum::sampled_to_rgb(&samples), None)))
+            }
+            ("spectrum", Values::Strs(v)) => Ok(Some((
+                Colorf::broadcast(1.0),
+                Some(format!(
+                    "Spectrum files are not supported, '{}' from '{}' will be white",
+                    name,
+                    v.first().map(|s| &s[..]).unwrap_or("")
+                )),
+            ))),
+            (ty, _) => Err(format!("Invalid number of values for '{} {}'", ty, name)),
+        }
+    }
+    /// Find a blackbody spectrum parameter, given as its temperature in Kelvin and
+    /// optionally its strength
+    pub fn find_blackbody(&self, name: &str) -> Result<Option<Blackbody>, String> {
+        self.find_nums(&["blackbody"], name)
+            .map(|v| blackbody(name, v))
+            .transpose()
+    }
+    /// Get the parameters which were never looked up
+    pub fn unused(&self) -> impl Iterator<Item = &Param> {
+        self.params.iter().filter(|p| !p.used.get())
+    }
+}
+
+/// Create the blackbody spectrum from the values of the parameter `name`, its
+/// temperature followed by its optional strength
+fn blackbody(name: &str, v: &[f32]) -> Result<Blackbody, String> {
+    if (v.len() == 1 || v.len() == 2) && v[0] > 0.0 {
+        Ok(Blackbody::new(v[0], v.get(1).cloned().unwrap_or(1.0)))
+    } else {
+        Err(format!(
+            "'blackbody {}' must be a temperature greater than 0, optionally followed by its strength",
+            name
+        ))
+    }
+}
+
+#[test]
+fn test_params() {
+    let params = ParamSet::new(vec![
+        Param::new("float radius", Values::Nums(vec![2.0])).unwrap(),
+        Param::new("color Kd", Values::Nums(vec![0.1, 0.2, 0.3])).unwrap(),
+        Param::new("point P", Values::Nums(vec![0.0, 1.0, 2.0, 3.0, 4.0, 5.0])).unwrap(),
+        Param::new("bool twosided", Values::Bools(vec![true])).unwrap(),
+        Param::new("string filename", Values::Strs(vec!["a.ply".to_owned()])).unwrap(),
+    ]);
+    assert_eq!(params.find_f32("radius", 1.0), 2.0);
+    assert_eq!(params.find_f32("missing", 1.0), 1.0);
+    // Type must match as well as the name
+    assert_eq!(params.find_i32("radius", 3), 3);
+    assert_eq!(
+        params.find_spectrum("Kd").unwrap().unwrap().0,
+        Colorf::new(0.1, 0.2, 0.3)
+    );
+    let p = params.find_point3s("point3", "P").unwrap().unwrap();
+    assert_eq!(
+        p,
+        vec![Point::new(0.0, 1.0, 2.0), Point::new(3.0, 4.0, 5.0)]
+    );
+    assert!(params.find_bool("twosided", false));
+    let spectra = ParamSet::new(vec![
+        Param::new("blackbody L", Values::Nums(vec![3000.0, 2.0])).unwrap(),
+        Param::new("spectrum Kd", Values::Nums(vec![400.0, 0.5, 700.0, 0.5])).unwrap(),
+    ]);
+    let (l, warning) = spectra.find_spectrum("L").unwrap().unwrap();
+    let blackbody = spectra.find_blackbody("L").unwrap().unwrap();
+    assert_eq!((blackbody.temperature, blackbody.strength), (3000.0, 2.0));
+    assert_eq!(l, blackbody.to_rgb(ColorSpace::LinearSrgb));
+    assert!(warning.is_none() && l.r > l.b);
+    let (kd, _) = spectra.find_spectrum("Kd").unwrap().unwrap();
+    assert!((0..3).all(|i| f32::abs(kd[i] - 0.5) < 0.01));
+    let unused: Vec<_> = params.unused().map(|p| &p.name[..]).collect();
+    assert_eq!(unused, vec!["filename"]);
+}
+
+#[test]
+fn test_param_errors() {
+    assert!(Param::new("float", Values::Nums(vec![1.0])).is_err());
+    assert!(Param::new("quaternion q", Values::Nums(vec![1.0])).is_err());
+    assert!(Param::new("float radius", Values::Strs(vec!["a".to_owned()])).is_err());
+    let params = ParamSet::new(vec![
+        Param::new("rgb L", Values::Nums(vec![1.0, 2.0])).unwrap(),
+        Param::new("normal N", Values::Nums(vec![1.0, 2.0])).unwrap(),
+    ]);
+    assert!(params.find_spectrum("L").is_err());
+    assert!(params.find_point3s("normal3", "N").is_err());
+    let params = ParamSet::new(vec![
+        Param::new("blackbody L", Values::Nums(vec![-300.0])).unwrap()
+    ]);
+    assert!(params.find_spectrum("L").is_err());
+    assert!(params.find_blackbody("L").is_err());
+    let nan = vec![f32::NAN, 0.5, 400.0, 0.5, 500.0, 0.5];
+    let params = ParamSet::new(vec![
+        Param::new("spectrum Kd", Values::Nums(nan)).unwrap(),
+        Param::new("spectrum Ks", Values::Nums(vec![400.0, f32::INFINITY])).unwrap(),
+    ]);
+    assert!(params.find_spectrum("Kd").is_err());
+    assert!(params.find_spectrum("Ks").is_err());
+}
@@ -0,0 +1,357 @@
+//! A loader for triangle meshes stored in PLY files, as referenced by pbrt's
+//! `plymesh` shape. ASCII and binary little and big endian files are supported.
+//! Vertex positions are required while normals and texture coordinates are
+//! loaded if present, polygon faces are triangulated as fans.
+
+use std::{fs, io::Cursor, marker::PhantomData, path::Path, str::SplitWhitespace};
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt};
+
+use crate::linalg::{Normal, Point};
+
+/// The triangle data loaded from a PLY file
+pub struct PlyMesh {
+    pub positions: Vec<Point>,
+    pub normals: Option<Vec<Normal>>,
+    pub texcoords: Option<Vec<Point>>,
+    pub indices: Vec<u32>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn parse(s: &str) -> Result<Scalar, String> {
+        match s {
+            "char" | "int8" => Ok(Scalar::I8),
+            "uchar" | "uint8" => Ok(Scalar::U8),
+            "short" | "int16" => Ok(Scalar::I16),
+            "ushort" | "uint16" => Ok(Scalar::U16),
+            "int" | "int32" => Ok(Scalar::I32),
+            "uint" | "uint32" => Ok(Scalar::U32),
+            "float" | "float32" => Ok(Scalar::F32),
+            "double" | "float64" => Ok(Scalar::F64),
+            _ => Err(format!("Unrecognized property type '{}'", s)),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Property {
+    Scalar(Scalar, String),
+    /// A list with the count type, item type and name
+    List(Scalar, Scalar, String),
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+/// Reads the scalar values from the body of the file
+trait Reader {
+    fn read(&mut self, ty: Scalar) -> Result<f64, String>;
+}
+
+struct AsciiReader<'a> {
+    words: SplitWhitespace<'a>,
+}
+
+impl<'a> Reader for AsciiReader<'a> {
+    fn read(&mut self, _: Scalar) -> Result<f64, String> {
+        match self.words.next() {
+            Some(w) => w
+                .parse::<f64>()
+                .map_err(|_| format!("Invalid value '{}' in PLY file", w)),
+            None => Err("Unexpected end of PLY file".to_owned()),
+        }
+    }
+}
+
+struct BinaryReader<'a, B: ByteOrder> {
+    cursor: Cursor<&'a [u8]>,
+    order: PhantomData<B>,
+}
+
+impl<'a, B: ByteOrder> Reader for BinaryReader<'a, B> {
+    fn read(&mut self, ty: Scalar) -> Result<f64, String> {
+        let c = &mut self.cursor;
+        let v = match ty {
+            Scalar::I8 => c.read_i8().map(f64::from),
+            Scalar::U8 => c.read_u8().map(f64::from),
+            Scalar::I16 => c.read_i16::<B>().map(f64::from),
+            Scalar::U16 => c.read_u16::<B>().map(f64::from),
+            Scalar::I32 => c.read_i32::<B>().map(f64::from),
+            Scalar::U32 => c.read_u32::<B>().map(f64::from),
+            Scalar::F32 => c.read_f32::<B>().map(f64::from),
+            Scalar::F64 => c.read_f64::<B>(),
+        };
+        v.map_err(|_| "Unexpected end of PLY file".to_owned())
+    }
+}
+
+/// Load the triangle mesh in the PLY file
+pub fn load(path: &Path) -> Result<PlyMesh, String> {
+    let data = fs::read(path).map_err(|e| e.to_string())?;
+    parse(&data)
+}
+
+/// Parse the triangle mesh from the contents of a PLY file
+pub fn parse(data: &[u8]) -> Result<PlyMesh, String> {
+    let (format, elements, body) = parse_header(data)?;
+    match format {
+        Format::Ascii => {
+            let text = std::str::from_utf8(body).map_err(|_| "PLY file is not valid ASCII")?;
+            let mut reader = AsciiReader {
+                words: text.split_whitespace(),
+            };
+            read_body(&elements, &mut reader)
+        }
+        Format::BinaryLittleEndian => read_body(
+            &elements,
+            &mut BinaryReader::<LittleEndian> {
+                cursor: Cursor::new(body),
+                order: PhantomData,
+            },
+        ),
+        Format::BinaryBigEndian => read_body(
+            &elements,
+            &mut BinaryReader::<BigEndian> {
+                cursor: Cursor::new(body),
+                order: PhantomData,
+            },
+        ),
+    }
+}
+
+/// Parse the header, returning the file format, elements and the remaining body of the file
+fn parse_header(data: &[u8]) -> Result<(Format, Vec<Element>, &[u8]), String> {
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    let mut pos = 0;
+    let mut first = true;
+    loop {
+        let end = match data[pos..].iter().position(|&b| b == b'\n') {
+            Some(e) => pos + e,
+            None => return Err("PLY header is missing end_header".to_owned()),
+        };
+        let line = std::str::from_utf8(&data[pos..end]).map_err(|_| "Invalid PLY header")?;
+        pos = end + 1;
+        let words: Vec<_> = line.split_whitespace().collect();
+        if first {
+            if words != ["ply"] {
+                return Err("Not a PLY file".to_owned());
+            }
+            first = false;
+            continue;
+        }
+        match words.first().cloned() {
+            Some("format") => {
+                format = match words.get(1).cloned() {
+                    Some("ascii") => Some(Format::Ascii),
+                    Some("binary_little_endian") => Some(Format::BinaryLittleEndian),
+                    Some("binary_big_endian") => Some(Format::BinaryBigEndian),
+                    _ => return Err(format!("Unrecognized PLY format '{}'", line)),
+                }
+            }
+            Some("element") if words.len() == 3 => {
+                let count = words[2]
+                    .parse()
+                    .map_err(|_| format!("Invalid element count in '{}'", line))?;
+                elements.push(Element {
+                    name: words[1].to_owned(),
+                    count,
+                    properties: Vec::new(),
+                });
+            }
+            Some("property") => {
+                let elem = match elements.last_mut() {
+                    Some(e) => e,
+                    None => return Err("PLY property found before any element".to_owned()),
+                };
+                let prop = match words.len() {
+                    3 => Property::Scalar(Scalar::parse(words[1])?, words[2].to_owned()),
+                    5 if words[1] == "list" => Property::List(
+                        Scalar::parse(words[2])?,
+                        Scalar::parse(words[3])?,
+                        words[4].to_owned(),
+                    ),
+                    _ => return Err(format!("Invalid PLY property '{}'", line)),
+                };
+                elem.properties.push(prop);
+            }
+            Some("end_header") => break,
+            Some("comment") | Some("obj_info") | None => {}
+            _ => return Err(format!("Invalid PLY header line '{}'", line)),
+        }
+    }
+    match format {
+        Some(f) => Ok((f, elements, &data[pos..])),
+        None => Err("PLY header does not specify the format".to_owned()),
+    }
+}
+
+fn read_body<R: Reader>(elements: &[Element], reader: &mut R) -> Result<PlyMesh, String> {
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut texcoords = Vec::new();
+    let mut indices = Vec::new();
+    let mut has_normals = false;
+    let mut has_texcoords = false;
+    for elem in elements {
+        if elem.name == "vertex" {
+            let find = |names: &[&str]| {
+                elem.properties.iter().position(|p| match *p {
+                    Property::Scalar(_, ref n) => names.contains(&&n[..]),
+                    _ => false,
+                })
+            };
+            let (x, y, z) = match (find(&["x"]), find(&["y"]), find(&["z"])) {
+                (Some(x), Some(y), Some(z)) => (x, y, z),
+                _ => return Err("PLY vertices must have x, y and z positions".to_owned()),
+            };
+            let n = (find(&["nx"]), find(&["ny"]), find(&["nz"]));
+            let uv = (
+                find(&["u", "s", "texture_u", "texture_s"]),
+                find(&["v", "t", "texture_v", "texture_t"]),
+            );
+            has_normals = n.0.is_some() && n.1.is_some() && n.2.is_some();
+            has_texcoords = uv.0.is_some() && uv.1.is_some();
+            let mut vals = vec![0.0; elem.properties.len()];
+            for _ in 0..elem.count {
+                for (i, p) in elem.properties.iter().enumerate() {
+                    vals[i] = match *p {
+                        Property::Scalar(ty, _) => reader.read(ty)? as f32,
+                        Property::List(count_ty, ty, _) => {
+                            skip_list(reader, count_ty, ty)?;
+                            0.0
+                        }
+                    };
+                }
+                positions.push(Point::new(vals[x], vals[y], vals[z]));
+                if let (Some(nx), Some(ny), Some(nz)) = n {
+                    normals.push(Normal::new(vals[nx], vals[ny], vals[nz]));
+                }
+                if let (Some(u), Some(v)) = uv {
+                    texcoords.push(Point::new(vals[u], vals[v], 0.0));
+                }
+            }
+        } else if elem.name == "face" {
+            let mut face = Vec::new();
+            for _ in 0..elem.count {
+                for p in &elem.properties {
+                    match *p {
+                        Property::List(count_ty, ty, ref name)
+                            if name == "vertex_indices" || name == "vertex_index" =>
+                        {
+                            let count = reader.read(count_ty)? as usize;
+                            face.clear();
+                            for _ in 0..count {
+                                face.push(reader.read(ty)? as u32);
+                            }
+                            // Triangulate the polygon as a fan around the first vertex
+                            for i in 2..count {
+                                indices.push(face[0]);
+                                indices.push(face[i - 1]);
+                                indices.push(face[i]);
+                            }
+                        }
+                        Property::List(count_ty, ty, _) => skip_list(reader, count_ty, ty)?,
+                        Property::Scalar(ty, _) => {
+                            reader.read(ty)?;
+                        }
+                    }
+                }
+            }
+        } else {
+            for _ in 0..elem.count {
+                for p in &elem.properties {
+                    match *p {
+                        Property::List(count_ty, ty, _) => skip_list(reader, count_ty, ty)?,
+                        Property::Scalar(ty, _) => {
+                            reader.read(ty)?;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    if positions.is_empty() || indices.is_empty() {
+        return Err("PLY file does not contain any triangles".to_owned());
+    }
+    Ok(PlyMesh {
+        positions,
+        normals: if has_normals { Some(normals) } else { None },
+        texcoords: if has_texcoords { Some(texcoords) } else { None },
+        indices,
+    })
+}
+
+fn skip_list<R: Reader>(reader: &mut R, count_ty: Scalar, ty: Scalar) -> Result<(), String> {
+    let count = reader.read(count_ty)? as usize;
+    for _ in 0..count {
+        reader.read(ty)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn test_ascii_ply() {
+    let ply = b"ply\nformat ascii 1.0\ncomment a quad\nelement vertex 4\n\
+        property float x\nproperty float y\nproperty float z\n\
+        property float u\nproperty float v\n\
+        element face 1\nproperty list uchar int vertex_indices\nend_header\n\
+        0 0 0 0 0\n1 0 0 1 0\n1 1 0 1 1\n0 1 0 0 1\n4 0 1 2 3\n";
+    let mesh = parse(&ply[..]).unwrap();
+    assert_eq!(mesh.positions.len(), 4);
+    assert_eq!(mesh.positions[2], Point::new(1.0, 1.0, 0.0));
+    assert!(mesh.normals.is_none());
+    assert_eq!(mesh.texcoords.unwrap()[3], Point::new(0.0, 1.0, 0.0));
+    assert_eq!(mesh.indices, vec![0, 1, 2, 0, 2, 3]);
+}
+
+#[test]
+fn test_binary_ply() {
+    use byteorder::WriteBytesExt;
+
+    let mut ply = b"ply\nformat binary_big_endian 1.0\nelement vertex 3\n\
+        property float x\nproperty float y\nproperty float z\n\
+        property float nx\nproperty float ny\nproperty float nz\n\
+        element face 1\nproperty list uchar uint vertex_indices\nproperty int flags\n\
+        end_header\n"
+        .to_vec();
+    for p in &[[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]] {
+        for x in p.iter().chain(&[0.0, 0.0, 1.0]) {
+            ply.write_f32::<BigEndian>(*x).unwrap();
+        }
+    }
+    ply.write_u8(3).unwrap();
+    for i in 0..3 {
+        ply.write_u32::<BigEndian>(i).unwrap();
+    }
+    ply.write_i32::<BigEndian>(7).unwrap();
+    let mesh = parse(&ply[..]).unwrap();
+    assert_eq!(mesh.positions[1], Point::new(1.0, 0.0, 0.0));
+    assert_eq!(mesh.normals.unwrap()[2], Normal::new(0.0, 0.0, 1.0));
+    assert_eq!(mesh.indices, vec![0, 1, 2]);
+    // Truncating the file should be reported
+    assert!(parse(&ply[..ply.len() - 6]).is_err());
+}
@@ -0,0 +1,148 @@
+//! Splits a pbrt-v3 scene file into its tokens: directive names, quoted strings,
+//! numbers and the brackets delimiting parameter value lists. Comments starting
+//! with `#` run to the end of the line and are skipped.
+
+use std::{iter::Peekable, str::CharIndices};
+
+/// The kinds of tokens found in a pbrt file
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    /// A bare word, e.g. a directive name like `Shape`
+    Ident(String),
+    /// A quoted string with any escapes resolved
+    Str(String),
+    Num(f32),
+    OpenBracket,
+    CloseBracket,
+}
+
+/// A token along with the line it was found on
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub line: usize,
+}
+
+/// Split the text into tokens. Returns the line and a description of the
+/// problem if invalid input is found
+pub fn tokenize(text: &str) -> Result<Vec<Token>, (usize, String)> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    let mut line = 1;
+    while let Some(&(start, c)) = chars.peek() {
+        match c {
+            '\n' => {
+                line += 1;
+                chars.next();
+            }
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '#' => {
+                while let Some(&(_, c)) = chars.peek() {
+                    if c == '\n' {
+                        break;
+                    }
+                    chars.next();
+                }
+            }
+            '[' | ']' => {
+                chars.next();
+                let kind = if c == '[' {
+                    TokenKind::OpenBracket
+                } else {
+                    TokenKind::CloseBracket
+                };
+                tokens.push(Token { kind, line });
+            }
+            '"' => {
+                chars.next();
+                let s = read_string(&mut chars, line)?;
+                tokens.push(Token {
+                    kind: TokenKind::Str(s),
+                    line,
+                });
+            }
+            _ => {
+                let end = read_word(&mut chars, text.len());
+                let word = &text[start..end];
+                let kind = if c.is_ascii_digit() || c == '-' || c == '+' || c == '.' {
+                    match word.parse::<f32>() {
+                        Ok(f) => TokenKind::Num(f),
+                        Err(_) => return Err((line, format!("Invalid number '{}'", word))),
+                    }
+                } else {
+                    TokenKind::Ident(word.to_owned())
+                };
+                tokens.push(Token { kind, line });
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Read a quoted string, the opening quote should already have been consumed
+fn read_string(chars: &mut Peekable<CharIndices>, line: usize) -> Result<String, (usize, String)> {
+    let mut s = String::new();
+    while let Some((_, c)) = chars.next() {
+        match c {
+            '"' => return Ok(s),
+            '\n' => break,
+            '\\' => match chars.next() {
+                Some((_, 'n')) => s.push('\n'),
+                Some((_, 't')) => s.push('\t'),
+                Some((_, c)) => s.push(c),
+                None => break,
+            },
+            c => s.push(c),
+        }
+    }
+    Err((line, "Unterminated string".to_owned()))
+}
+
+/// Advance past the current word, returning the byte offset it ends at
+fn read_word(chars: &mut Peekable<CharIndices>, len: usize) -> usize {
+    while let Some(&(i, c)) = chars.peek() {
+        if c.is_whitespace() || c == '"' || c == '[' || c == ']' || c == '#' {
+            return i;
+        }
+        chars.next();
+    }
+    len
+}
+
+#[test]
+fn test_tokenize() {
+    let tokens = tokenize("Shape \"sphere\" # comment [ \n \"float radius\" [-1.5e1] .5").unwrap();
+    let kinds: Vec<_> = tokens.iter().map(|t| t.kind.clone()).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            TokenKind::Ident("Shape".to_owned()),
+            TokenKind::Str("sphere".to_owned()),
+            TokenKind::Str("float radius".to_owned()),
+            TokenKind::OpenBracket,
+            TokenKind::Num(-15.0),
+            TokenKind::CloseBracket,
+            TokenKind::Num(0.5),
+        ]
+    );
+    assert_eq!(tokens[0].line, 1);
+    assert_eq!(tokens[2].line, 2);
+}
+
+#[test]
+fn test_tokenize_errors() {
+    assert_eq!(
+        tokenize("Shape\n\"sphere"),
+        Err((2, "Unterminated string".to_owned()))
+    );
+    assert_eq!(
+        tokenize("Translate 1 2.0.0 3"),
+        Err((1, "Invalid number '2.0.0'".to_owned()))
+    );
+    assert_eq!(
+        tokenize("Texture \"a\\\"b\"").unwrap()[1].kind,
+        TokenKind::Str("a\"b".to_owned())
+    );
+}
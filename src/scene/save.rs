@@ -0,0 +1,857 @@
+//! Writes a scene back out as a JSON scene file which can be loaded with
+//! `Scene::load_file`, so tools which generate or modify scenes can save them.
+//!
+//! Materials and textures keep the names they were loaded with, any which the objects
+//! use but which aren't in the scene's named materials or textures are given a name based
+//! on what uses them. Groups are flattened when loading, objects in groups with animated
+//! transforms are written in a group of their own to keep the animation of each level.
+//! Files referenced by the scene, like image textures or OBJ meshes, are written relative
+//! to the directory the scene is saved in if they're within it and as absolute paths if not.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use bspline::BSpline;
+use serde_json::{self, Map, Value};
+
+use crate::{
+    film::{
+        camera::CameraFov,
+        filter::{Filter, Filters},
+        AnimatedColor, Camera, ColorSpace, Colorf, FrameInfo, RenderTarget, Tonemap,
+    },
+    geometry::{BoundableGeometry, Instance, SampleableGeometry},
+    integrator::Integrators,
+    linalg::{AnimatedTransform, Keyframe, Transform},
+    material::Materials,
+    sampler::{SamplerConfig, SamplerType},
+    texture::Textures,
+};
+
+use super::{Scene, SceneError};
+
+impl Scene {
+    /// Save the scene to the JSON scene file `file`, along with the render target settings,
+    /// samples per pixel and frame information it should be rendered with
+    pub fn save_file(
+        &self,
+        file: &str,
+        rt: &RenderTarget,
+        spp: usize,
+        frame_info: &FrameInfo,
+    ) -> Result<(), SceneError> {
+        let file_path = Path::new(file);
+        let dir = match file_path.parent() {
+            Some(p) if !p.as_os_str().is_empty() => p,
+            _ => Path::new("."),
+        };
+        let json = self.to_json(rt, spp, frame_info, dir)?;
+        let content = serde_json::to_string_pretty(&json).expect("Scene JSON is always valid");
+        fs::write(file_path, content).map_err(|err| SceneError::Save {
+            file: file_path.to_path_buf(),
+            err,
+        })
+    }
+    /// Describe the scene as a JSON scene document. Files referenced by the scene are
+    /// written relative to `dir`, the directory the document will be saved in
+    pub fn to_json(
+        &self,
+        rt: &RenderTarget,
+        spp: usize,
+        frame_info: &FrameInfo,
+        dir: &Path,
+    ) -> Result<Value, SceneError> {
+        let mut writer = Writer::new(self, dir);
+        let mut root = Map::new();
+        root.insert("film".to_owned(), save_film(rt, spp, frame_info));
+        if self.cameras.len() == 1 {
+            root.insert("camera".to_owned(), save_camera(&self.cameras[0])?);
+        } else {
+            let cameras = self
+                .cameras
+                .iter()
+                .map(save_camera)
+                .collect::<Result<_, _>>()?;
+            root.insert("cameras".to_owned(), Value::Array(cameras));
+        }
+        root.insert("integrator".to_owned(), save_integrator(&self.integrator));
+        if self.sampler != SamplerConfig::default() {
+            root.insert("sampler".to_owned(), save_sampler(&self.sampler));
+        }
+        let objects = self
+            .bvh
+            .iter()
+            .map(|i| writer.object(i))
+            .collect::<Result<_, _>>()?;
+        root.insert("objects".to_owned(), Value::Array(objects));
+        let materials = writer.materials()?;
+        root.insert("materials".to_owned(), Value::Array(materials));
+        let textures = writer.textures()?;
+        if !textures.is_empty() {
+            root.insert("textures".to_owned(), Value::Array(textures));
+        }
+        Ok(Value::Object(root))
+    }
+}
+
+/// Tracks the names given to the materials and textures being written
+struct Writer<'a> {
+    dir: PathBuf,
+    materials: Vec<(String, &'a Arc<Materials>)>,
+    textures: Vec<(String, &'a Arc<Textures>)>,
+}
+
+impl<'a> Writer<'a> {
+    fn new(scene: &'a Scene, dir: &Path) -> Writer<'a> {
+        let mut materials: Vec<_> = scene
+            .materials
+            .iter()
+            .map(|(n, m)| (n.clone(), m))
+            .collect();
+        materials.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut textures: Vec<_> = scene.textures.iter().map(|(n, t)| (n.clone(), t)).collect();
+        textures.sort_by(|a, b| a.0.cmp(&b.0));
+        Writer {
+            dir: fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf()),
+            materials,
+            textures,
+        }
+    }
+    /// Get the name of the material, naming it after `user` if it isn't named yet
+    fn material_name(&mut self, material: &'a Arc<Materials>, user: &str) -> String {
+        if let Some((name, _)) = self.materials.iter().find(|m| Arc::ptr_eq(m.1, material)) {
+            return name.clone();
+        }
+        let name = unique_name(&self.materials, &format!("{}_material", user));
+        self.materials.push((name.clone(), material));
+        name
+    }
+    /// Get the name of the texture, naming it after `user` if it isn't named yet
+    fn texture_name(&mut self, texture: &'a Arc<Textures>, user: &str) -> String {
+        if let Some((name, _)) = self.textures.iter().find(|t| Arc::ptr_eq(t.1, texture)) {
+            return name.clone();
+        }
+        let name = unique_name(&self.textures, user);
+        self.textures.push((name.clone(), texture));
+        name
+    }
+    /// Get the path to write for a file referenced by the scene
+    fn file(&self, file: &Path) -> Value {
+        let file = fs::canonicalize(file).unwrap_or_else(|_| file.to_path_buf());
+        let file = file.strip_prefix(&self.dir).unwrap_or(&file);
+        Value::String(file.to_string_lossy().into_owned())
+    }
+    /// Write the color texture used for `param` of the material `user`, constant colors
+    /// are written inline while others are referenced by name
+    fn color(&mut self, texture: &'a Arc<Textures>, user: &str, param: &str) -> Value {
+        match **texture {
+            Textures::ConstantColor(ref c) => color(&c.val),
+            Textures::ConstantScalar(ref s) => color(&Colorf::broadcast(s.val)),
+            _ => Value::String(self.texture_name(texture, &format!("{}_{}", user, param))),
+        }
+    }
+    /// Write the scalar texture used for `param` of the material `user`, constant values
+    /// are written inline while others are referenced by name
+    fn scalar(&mut self, texture: &'a Arc<Textures>, user: &str, param: &str) -> Value {
+        match **texture {
+            Textures::ConstantScalar(ref s) => num(s.val),
+            Textures::ConstantColor(ref c) => num(c.val.luminance()),
+            _ => Value::String(self.texture_name(texture, &format!("{}_{}", user, param))),
+        }
+    }
+    /// Write the object, if the object is in animated groups it's written within
+    /// a group for each level of the animation
+    fn object(&mut self, instance: &'a Instance) -> Result<Value, SceneError> {
+        let tag = instance.tag();
+        let mut obj = Map::new();
+        obj.insert("name".to_owned(), Value::String(tag.to_owned()));
+        match *instance {
+            Instance::Receiver(ref r) => {
+                obj.insert("type".to_owned(), Value::String("receiver".to_owned()));
+                let material = self.material_name(&r.material, tag);
+                obj.insert("material".to_owned(), Value::String(material));
+                obj.insert("geometry".to_owned(), self.geometry(r.geometry(), tag)?);
+            }
+            Instance::Emitter(ref e) => {
+                obj.insert("type".to_owned(), Value::String("emitter".to_owned()));
+                let emission = match e.blackbody() {
+                    Some(b) => serde_json::json!({
+                        "temperature": num(b.temperature),
+                        "strength": num(b.strength),
+                    }),
+                    None => save_animated_color(&e.emission),
+                };
+                obj.insert("emission".to_owned(), emission);
+                match (e.geometry(), e.material()) {
+                    (Some(geom), Some(mat)) => {
+                        obj.insert("emitter".to_owned(), Value::String("area".to_owned()));
+                        let material = self.material_name(mat, tag);
+                        obj.insert("material".to_owned(), Value::String(material));
+                        obj.insert("geometry".to_owned(), save_sampleable_geometry(geom, tag)?);
+                    }
+                    _ => {
+                        obj.insert("emitter".to_owned(), Value::String("point".to_owned()));
+                    }
+                }
+            }
+        }
+
+        let transform = instance.get_transform();
+        if !transform.is_animated() || transform.keyframes().len() == 1 {
+            let (key, value) = save_animated_transform(transform);
+            obj.insert(key.to_owned(), value);
+            return Ok(Value::Object(obj));
+        }
+        // Rebuild the groups so each level of the animation is kept
+        let levels = transform.keyframes();
+        let (key, value) = save_spline(&levels[0]);
+        obj.insert(key.to_owned(), value);
+        let mut obj = Value::Object(obj);
+        for (i, level) in levels.iter().enumerate().skip(1) {
+            let (key, value) = save_spline(level);
+            obj = serde_json::json!({
+                "name": format!("{}_group{}", tag, i),
+                "type": "group",
+                key: value,
+                "objects": [obj],
+            });
+        }
+        Ok(obj)
+    }
+    fn geometry(&self, geom: &BoundableGeometry, tag: &str) -> Result<Value, SceneError> {
+        match *geom {
+            BoundableGeometry::Sphere(ref s) => Ok(serde_json::json!({
+                "type": "sphere",
+                "radius": num(s.radius()),
+            })),
+            BoundableGeometry::Disk(ref d) => Ok(serde_json::json!({
+                "type": "disk",
+                "radius": num(d.radius()),
+                "inner_radius": num(d.inner_radius()),
+            })),
+            BoundableGeometry::Rectangle(ref r) => Ok(serde_json::json!({
+                "type": "rectangle",
+                "width": num(r.width()),
+                "height": num(r.height()),
+            })),
+            BoundableGeometry::Mesh(ref m) => match m.obj {
+                Some((ref file, ref model)) => Ok(serde_json::json!({
+                    "type": "mesh",
+                    "file": self.file(file),
+                    "model": model,
+                })),
+                None => Err(unsupported(format!(
+                    "the mesh of '{}' was not loaded from an OBJ file",
+                    tag
+                ))),
+            },
+            _ => Err(unsupported(format!(
+                "the geometry of '{}' can't be described in a scene file",
+                tag
+            ))),
+        }
+    }
+    /// Write the materials used by the scene, naming any textures they use
+    fn materials(&mut self) -> Result<Vec<Value>, SceneError> {
+        let mut materials = Vec::with_capacity(self.materials.len());
+        for i in 0..self.materials.len() {
+            let (name, material) = (self.materials[i].0.clone(), self.materials[i].1);
+            let mut mat = match **material {
+                Materials::Glass(ref g) => {
+                    let mut glass = serde_json::json!({
+                        "type": "glass",
+                        "reflect": self.color(&g.reflect, &name, "reflect"),
+                        "transmit": self.color(&g.transmit, &name, "transmit"),
+                        "eta": self.scalar(&g.eta, &name, "eta"),
+                    });
+                    if let Some(abbe) = g.abbe {
+                        glass["abbe"] = num(abbe);
+                    }
+                    glass
+                }
+                Materials::RoughGlass(ref g) => serde_json::json!({
+                    "type": "rough_glass",
+                    "reflect": self.color(&g.reflect, &name, "reflect"),
+                    "transmit": self.color(&g.transmit, &name, "transmit"),
+                    "eta": self.scalar(&g.eta, &name, "eta"),
+                    "roughness": self.scalar(&g.roughness, &name, "roughness"),
+                }),
+                Materials::Matte(ref m) => serde_json::json!({
+                    "type": "matte",
+                    "diffuse": self.color(&m.diffuse, &name, "diffuse"),
+                    "roughness": self.scalar(&m.roughness, &name, "roughness"),
+                }),
+                Materials::Merl(ref m) => serde_json::json!({
+                    "type": "merl",
+                    "file": self.file(&m.file),
+                }),
+                Materials::Metal(ref m) => serde_json::json!({
+                    "type": "metal",
+                    "refractive_index": self.color(&m.eta, &name, "refractive_index"),
+                    "absorption_coefficient": self.color(&m.k, &name, "absorption_coefficient"),
+                    "roughness": self.scalar(&m.roughness, &name, "roughness"),
+                }),
+                Materials::Plastic(ref p) => serde_json::json!({
+                    "type": "plastic",
+                    "diffuse": self.color(&p.diffuse, &name, "diffuse"),
+                    "gloss": self.color(&p.gloss, &name, "gloss"),
+                    "roughness": self.scalar(&p.roughness, &name, "roughness"),
+                }),
+                Materials::SpecularMetal(ref m) => serde_json::json!({
+                    "type": "specular_metal",
+                    "refractive_index": self.color(&m.eta, &name, "refractive_index"),
+                    "absorption_coefficient": self.color(&m.k, &name, "absorption_coefficient"),
+                }),
+            };
+            mat["name"] = Value::String(name);
+            materials.push(mat);
+        }
+        Ok(materials)
+    }
+    /// Write the named textures, these must be written after the materials so
+    /// any textures they use are named
+    fn textures(&self) -> Result<Vec<Value>, SceneError> {
+        let mut textures = Vec::with_capacity(self.textures.len());
+        for &(ref name, texture) in &self.textures {
+            let image_file = |file: &Option<PathBuf>| {
+                file.as_ref().map(|f| self.file(f)).ok_or_else(|| {
+                    unsupported(format!(
+                        "the image of texture '{}' was not loaded from a file",
+                        name
+                    ))
+                })
+            };
+            let mut tex = match **texture {
+                Textures::Image(ref img) => serde_json::json!({
+                    "name": name,
+                    "type": "image",
+                    "file": image_file(&img.file)?,
+                }),
+                Textures::AnimatedImage(ref anim) => {
+                    let keyframes = anim
+                        .frames()
+                        .iter()
+                        .map(|&(time, ref img)| {
+                            Ok(serde_json::json!({
+                                "file": image_file(&img.file)?,
+                                "time": num(time),
+                            }))
+                        })
+                        .collect::<Result<Vec<_>, SceneError>>()?;
+                    serde_json::json!({
+                        "name": name,
+                        "type": "animated_image",
+                        "keyframes": keyframes,
+                    })
+                }
+                _ => {
+                    return Err(unsupported(format!(
+                        "texture '{}' can't be described in a scene file",
+                        name
+                    )))
+                }
+            };
+            let image = match **texture {
+                Textures::Image(ref img) => Some(img),
+                Textures::AnimatedImage(ref anim) => Some(&anim.frames()[0].1),
+                _ => None,
+            };
+            // Untagged images are sRGB with their scalars read as data
+            match image.map(|i| (i.color_space, i.decode_scalars)) {
+                None | Some((Some(ColorSpace::Srgb), false)) => {}
+                Some((Some(c), _)) => tex["colorspace"] = Value::from(c.name()),
+                Some((None, _)) => tex["colorspace"] = Value::from("raw"),
+            }
+            textures.push(tex);
+        }
+        Ok(textures)
+    }
+}
+
+fn unsupported(msg: String) -> SceneError {
+    SceneError::Unsupported { msg }
+}
+
+/// Find a name starting with `base` which isn't already in `names`
+fn unique_name<T>(names: &[(String, T)], base: &str) -> String {
+    let taken = |n: &str| names.iter().any(|x| x.0 == n);
+    if !taken(base) {
+        return base.to_owned();
+    }
+    (1..)
+        .map(|i| format!("{}_{}", base, i))
+        .find(|n| !taken(n))
+        .unwrap()
+}
+
+/// Write a float using the shortest decimal which reads back as the same float
+fn num(x: f32) -> Value {
+    x.to_string()
+        .parse::<f64>()
+        .map(Value::from)
+        .unwrap_or(Value::Null)
+}
+
+fn color(c: &Colorf) -> Value {
+    Value::Array(vec![num(c.r), num(c.g), num(c.b)])
+}
+
+fn save_film(rt: &RenderTarget, spp: usize, frame_info: &FrameInfo) -> Value {
+    let (width, height) = rt.dimensions();
+    let f = rt.filter();
+    let mut filter = match *f {
+        Filters::MitchellNetravali(ref m) => serde_json::json!({
+            "type": "mitchell_netravali",
+            "b": num(m.b()),
+            "c": num(m.c()),
+        }),
+        Filters::Gaussian(ref g) => serde_json::json!({
+            "type": "gaussian",
+            "alpha": num(g.alpha()),
+        }),
+        Filters::BoxFilter(_) => serde_json::json!({ "type": "box" }),
+        Filters::Triangle(_) => serde_json::json!({ "type": "triangle" }),
+        Filters::Lanczos(ref l) => serde_json::json!({
+            "type": "lanczos",
+            "tau": num(l.tau()),
+        }),
+        Filters::BlackmanHarris(_) => serde_json::json!({ "type": "blackman_harris" }),
+    };
+    filter["width"] = num(f.width());
+    filter["height"] = num(f.height());
+    let mut film = serde_json::json!({
+        "width": width,
+        "height": height,
+        "samples": spp,
+        "frames": frame_info.frames,
+        "start_frame": frame_info.start,
+        "end_frame": frame_info.end,
+        "scene_time": num(frame_info.time),
+        "filter": filter,
+    });
+    let aovs = rt.aovs();
+    if !aovs.is_empty() {
+        film["aovs"] = aovs.iter().map(|a| Value::from(a.name())).collect();
+    }
+    if let Some(max) = rt.max_sample_luminance() {
+        film["max_sample_luminance"] = num(max);
+    }
+    let tonemap = rt.tonemap();
+    if *tonemap != Tonemap::default() {
+        let wb = &tonemap.white_balance;
+        film["tonemap"] = serde_json::json!({
+            "operator": tonemap.operator.name(),
+            "exposure": num(tonemap.exposure),
+            "white_balance": [num(wb.r), num(wb.g), num(wb.b)],
+        });
+    }
+    if let Some(d) = rt.denoiser() {
+        film["denoise"] = serde_json::json!({
+            "radius": d.radius,
+            "color_sigma": num(d.color_sigma),
+            "albedo_sigma": num(d.albedo_sigma),
+            "normal_sigma": num(d.normal_sigma),
+            "depth_sigma": num(d.depth_sigma),
+        });
+    }
+    if let Some(c) = rt.crop() {
+        film["pixel_bounds"] = serde_json::json!([c.start.0, c.end.0, c.start.1, c.end.1]);
+    }
+    if rt.spectral() {
+        film["spectral"] = Value::Bool(true);
+    }
+    if rt.working_space() != ColorSpace::LinearSrgb || rt.output_space() != ColorSpace::Srgb {
+        film["colorspace"] = serde_json::json!({
+            "working": rt.working_space().name(),
+            "output": rt.output_space().name(),
+        });
+    }
+    film
+}
+
+fn save_camera(camera: &Camera) -> Result<Value, SceneError> {
+    let transform = camera.transform();
+    if transform.keyframes().len() > 1 && transform.is_animated() {
+        return Err(unsupported(
+            "the camera has multiple levels of animated transforms".to_owned(),
+        ));
+    }
+    let (key, value) = save_animated_transform(transform);
+    let mut cam = serde_json::json!({
+        key: value,
+        "shutter_size": num(camera.shutter_size()),
+        "active_at": camera.active_at,
+    });
+    match *camera.fov() {
+        CameraFov::Unanimated(fov) => cam["fov"] = num(fov),
+        CameraFov::Animated(ref spline) => {
+            cam["fov"] = spline.control_points().map(|f| num(*f)).collect();
+            cam["fov_knots"] = spline.knots().map(|k| num(*k)).collect();
+            cam["fov_spline_degree"] = Value::from(degree(spline));
+        }
+    }
+    Ok(cam)
+}
+
+fn save_integrator(integrator: &Integrators) -> Value {
+    match *integrator {
+        Integrators::Path(ref p) => serde_json::json!({
+            "type": "pathtracer",
+            "min_depth": p.min_depth(),
+            "max_depth": p.max_depth(),
+        }),
+        // The Whitted integrator reads its maximum depth from `min_depth`
+        Integrators::Whitted(ref w) => serde_json::json!({
+            "type": "whitted",
+            "min_depth": w.max_depth(),
+        }),
+        Integrators::NormalsDebug(_) => serde_json::json!({ "type": "normals_debug" }),
+    }
+}
+
+fn save_sampler(sampler: &SamplerConfig) -> Value {
+    let mut desc = match sampler.sampler {
+        SamplerType::LowDiscrepancy => serde_json::json!({ "type": "low_discrepancy" }),
+        SamplerType::Adaptive { min_spp, max_spp } => serde_json::json!({
+            "type": "adaptive",
+            "min_spp": min_spp,
+            "max_spp": max_spp,
+        }),
+        SamplerType::Uniform => serde_json::json!({ "type": "uniform" }),
+    };
+    desc["block_size"] = serde_json::json!([sampler.block_dim.0, sampler.block_dim.1]);
+    if let Some(seed) = sampler.seed {
+        desc["seed"] = serde_json::json!(seed);
+    }
+    desc
+}
+
+fn save_sampleable_geometry(geom: &SampleableGeometry, tag: &str) -> Result<Value, SceneError> {
+    match *geom {
+        SampleableGeometry::Sphere(ref s) => Ok(serde_json::json!({
+            "type": "sphere",
+            "radius": num(s.radius()),
+        })),
+        SampleableGeometry::Disk(ref d) => Ok(serde_json::json!({
+            "type": "disk",
+            "radius": num(d.radius()),
+            "inner_radius": num(d.inner_radius()),
+        })),
+        SampleableGeometry::Rectangle(ref r) => Ok(serde_json::json!({
+            "type": "rectangle",
+            "width": num(r.width()),
+            "height": num(r.height()),
+        })),
+        SampleableGeometry::Mesh(_) => Err(unsupported(format!(
+            "the area light '{}' uses a mesh, which scene files don't support for area lights",
+            tag
+        ))),
+    }
+}
+
+fn save_animated_color(color_anim: &AnimatedColor) -> Value {
+    match *color_anim.keyframes() {
+        [ref key] if key.time == 0.0 => color(&key.color),
+        ref keys => keys
+            .iter()
+            .map(|k| serde_json::json!({ "time": num(k.time), "color": color(&k.color) }))
+            .collect(),
+    }
+}
+
+/// Write an animated transform with a single level of animation, or which isn't
+/// animated, as the `transform` or `keyframes` member of an object
+fn save_animated_transform(transform: &AnimatedTransform) -> (&'static str, Value) {
+    if transform.is_animated() {
+        save_spline(&transform.keyframes()[0])
+    } else {
+        ("transform", save_transform(&transform.transform(0.0)))
+    }
+}
+
+/// Write one level of an animated transform as a `transform` if it's a single
+/// keyframe or `keyframes` if it's animated
+fn save_spline(spline: &BSpline<Keyframe>) -> (&'static str, Value) {
+    if spline.control_points().count() == 1 {
+        let key = spline.control_points().next().unwrap();
+        return ("transform", save_transform(&key.transform()));
+    }
+    let points: Vec<_> = spline
+        .control_points()
+        .map(|k| serde_json::json!({ "transform": save_transform(&k.transform()) }))
+        .collect();
+    (
+        "keyframes",
+        serde_json::json!({
+            "control_points": points,
+            "knots": spline.knots().map(|k| num(*k)).collect::<Vec<_>>(),
+            "degree": degree(spline),
+        }),
+    )
+}
+
+/// Write the transform as a list containing its matrix
+fn save_transform(transform: &Transform) -> Value {
+    let rows: Vec<Value> = (0..4)
+        .map(|i| (0..4).map(|j| num(*transform.mat.at(i, j))).collect())
+        .collect();
+    serde_json::json!([{ "type": "matrix", "matrix": rows }])
+}
+
+/// Get the degree of the spline from its number of knots and control points
+fn degree<T>(spline: &BSpline<T>) -> usize
+where
+    T: bspline::Interpolate + Copy,
+{
+    spline.knots().count() - spline.control_points().count() - 1
+}
+
+/// Check that loading, saving and loading again gives an equivalent scene
+#[test]
+fn test_round_trip_cornell() {
+    use crate::linalg::Vector;
+
+    let (scene, rt, spp, frame_info) = Scene::load_file("cornell.json").unwrap();
+    let dir = std::env::temp_dir().join(format!("aperture_save_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let out = dir.join("cornell.json");
+    scene
+        .save_file(out.to_str().unwrap(), &rt, spp, &frame_info)
+        .unwrap();
+    let (saved, saved_rt, saved_spp, saved_info) = match Scene::load_file(out.to_str().unwrap()) {
+        Ok(s) => s,
+        Err(e) => panic!("{}", e),
+    };
+
+    assert_eq!(saved_rt.dimensions(), rt.dimensions());
+    assert_eq!(saved_rt.filter().width(), rt.filter().width());
+    assert_eq!(saved_spp, spp);
+    assert_eq!(
+        (
+            saved_info.frames,
+            saved_info.start,
+            saved_info.end,
+            saved_info.time
+        ),
+        (
+            frame_info.frames,
+            frame_info.start,
+            frame_info.end,
+            frame_info.time
+        )
+    );
+    assert_eq!(saved.cameras.len(), scene.cameras.len());
+    let ray = |s: &Scene| s.cameras[0].generate_ray(&(10.0, 20.0), 0.0);
+    let (a, b) = (ray(&scene), ray(&saved));
+    assert!((a.o - b.o).length() < 1e-4 && (a.d - b.d).length() < 1e-4);
+
+    let mut names: Vec<_> = scene.materials.keys().collect();
+    let mut saved_names: Vec<_> = saved.materials.keys().collect();
+    names.sort();
+    saved_names.sort();
+    assert_eq!(names, saved_names);
+
+    // Objects keep their geometry, material and placement
+    let objects = |s: &Scene| {
+        let mut objs: Vec<_> = s
+            .bvh
+            .iter()
+            .map(|i| {
+                let m = match *i {
+                    Instance::Receiver(ref r) => Some(r.material.clone()),
+                    Instance::Emitter(ref e) => e.material().cloned(),
+                };
+                let name = m.and_then(|m| {
+                    s.materials
+                        .iter()
+                        .find(|x| Arc::ptr_eq(x.1, &m))
+                        .map(|x| x.0.clone())
+                });
+                let t = i.get_transform().transform(0.0);
+                (i.tag().to_owned(), name, t * Vector::new(1.0, 2.0, 3.0))
+            })
+            .collect();
+        objs.sort_by(|a, b| a.0.cmp(&b.0));
+        objs
+    };
+    let (objs, saved_objs) = (objects(&scene), objects(&saved));
+    assert_eq!(objs.len(), saved_objs.len());
+    for (a, b) in objs.iter().zip(saved_objs.iter()) {
+        assert_eq!((&a.0, &a.1), (&b.0, &b.1));
+        assert!((a.2 - b.2).length() < 1e-4, "{} moved", a.0);
+    }
+
+    // Saving again gives the same file
+    let json = scene.to_json(&rt, spp, &frame_info, &dir).unwrap();
+    let saved_json = saved
+        .to_json(&saved_rt, saved_spp, &saved_info, &dir)
+        .unwrap();
+    assert_eq!(json["materials"], saved_json["materials"]);
+    assert_eq!(json["film"], saved_json["film"]);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_save_unsupported() {
+    use crate::geometry::Mesh;
+    use crate::linalg::{Normal, Point};
+
+    let (mut scene, rt, spp, frame_info) = Scene::load_file("cornell.json").unwrap();
+    let mesh = Mesh::new(
+        Arc::new(vec![Point::broadcast(0.0); 3]),
+        Arc::new(vec![Normal::new(0.0, 0.0, 1.0); 3]),
+        Arc::new(vec![Point::broadcast(0.0); 3]),
+        vec![0, 1, 2],
+    );
+    let material = scene.materials.values().next().unwrap().clone();
+    let instances = vec![Instance::receiver(
+        Arc::new(BoundableGeometry::Mesh(mesh)),
+        material,
+        AnimatedTransform::unanimated(&Transform::identity()),
+        "generated".to_owned(),
+    )];
+    scene.bvh = crate::geometry::BVH::new(4, instances, 0.0, 0.0);
+    match scene.to_json(&rt, spp, &frame_info, Path::new(".")) {
+        Err(e @ SceneError::Unsupported { .. }) => assert_eq!(
+            e.to_string(),
+            "Unable to save the scene: the mesh of 'generated' was not loaded from an OBJ file"
+        ),
+        _ => panic!("Expected the mesh to be unsupported"),
+    }
+}
+
+#[test]
+fn test_round_trip_animation() {
+    use super::{edit_scene, test_scene};
+    use crate::linalg::Point;
+
+    let keyframes = serde_json::json!({
+        "control_points": [
+            { "transform": [{ "type": "translate", "translation": [0, 0, 0] }] },
+            { "transform": [{ "type": "rotate_y", "rotation": 90 }] }
+        ],
+        "knots": [0, 0, 1, 1],
+        "degree": 1
+    });
+    let mut light = test_scene()["objects"][1].clone();
+    light["keyframes"] = keyframes.clone();
+    let group = serde_json::json!({
+        "name": "group",
+        "type": "group",
+        "keyframes": keyframes,
+        "objects": [light]
+    });
+    let scene = edit_scene(test_scene(), "/objects/1", Some(group));
+    let scene = edit_scene(
+        scene,
+        "/objects/1/objects/0/emission",
+        Some(serde_json::json!([
+            { "time": 0, "color": [1, 1, 1] },
+            { "time": 1, "color": [0, 1, 0] }
+        ])),
+    );
+    let (scene, rt, spp, frame_info) = Scene::load_value(&scene, Path::new("test.json")).unwrap();
+    let json = scene
+        .to_json(&rt, spp, &frame_info, Path::new("."))
+        .unwrap();
+    let (saved, ..) = match Scene::load_value(&json, Path::new("test.json")) {
+        Ok(s) => s,
+        Err(e) => panic!("{}", e),
+    };
+    let light = |s: &Scene| match s.bvh.iter().find(|i| i.tag() == "light") {
+        Some(Instance::Emitter(e)) => (e.get_transform().clone(), e.emission.clone()),
+        _ => panic!("The light is missing"),
+    };
+    let (a, b) = (light(&scene), light(&saved));
+    assert_eq!(b.0.keyframes().len(), 2);
+    for &t in &[0.0, 0.25, 0.5, 1.0] {
+        let (pa, pb) = (
+            a.0.transform(t) * Point::new(0.0, 10.0, 1.0),
+            b.0.transform(t) * Point::new(0.0, 10.0, 1.0),
+        );
+        assert!((pa - pb).length() < 1e-4, "at {}: {:?} != {:?}", t, pa, pb);
+        assert_eq!(a.1.color(t), b.1.color(t));
+    }
+}
+
+#[test]
+fn test_save_filters() {
+    let dir = std::env::temp_dir();
+    for filter in &[
+        serde_json::json!({ "type": "box", "width": 0.5, "height": 1.5 }),
+        serde_json::json!({ "type": "triangle", "width": 2.0, "height": 2.0 }),
+        serde_json::json!({ "type": "lanczos", "width": 4.0, "height": 3.0, "tau": 3.0 }),
+        serde_json::json!({ "type": "blackman_harris", "width": 2.0, "height": 2.5 }),
+    ] {
+        let mut desc = super::test_scene();
+        desc["film"]["filter"] = filter.clone();
+        let (scene, rt, spp, frame_info) =
+            Scene::load_value(&desc, Path::new("test.json")).unwrap();
+        let json = scene.to_json(&rt, spp, &frame_info, &dir).unwrap();
+        assert_eq!(&json["film"]["filter"], filter);
+    }
+}
+
+#[test]
+fn test_save_spectral() {
+    let mut desc = super::test_scene();
+    desc["film"]["spectral"] = serde_json::json!(true);
+    desc["objects"][1]["emission"] = serde_json::json!({ "temperature": 2700, "strength": 50 });
+    desc["materials"][0] = serde_json::json!({
+        "name": "white",
+        "type": "glass",
+        "reflect": [1, 1, 1],
+        "transmit": [1, 1, 1],
+        "eta": 1.5,
+        "abbe": 40
+    });
+    let (scene, rt, spp, frame_info) = Scene::load_value(&desc, Path::new("test.json")).unwrap();
+    let json = scene
+        .to_json(&rt, spp, &frame_info, &std::env::temp_dir())
+        .unwrap();
+    assert_eq!(json["film"]["spectral"], serde_json::json!(true));
+    let emission = &json["objects"][1]["emission"];
+    assert_eq!(emission["temperature"].as_f64(), Some(2700.0));
+    assert_eq!(emission["strength"].as_f64(), Some(50.0));
+    assert_eq!(json["materials"][0]["abbe"], serde_json::json!(40.0));
+    let (_, rt, ..) = Scene::load_value(&json, Path::new("test.json")).unwrap();
+    assert!(rt.spectral());
+}
+
+#[test]
+fn test_save_color_spaces() {
+    let dir = std::env::temp_dir().join(format!("aperture_save_colorspace_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let image = dir.join("tex.png");
+    image::save_buffer(&image, &[255; 12], 2, 2, image::RGB(8)).unwrap();
+    let mut desc = super::test_scene();
+    desc["film"]["colorspace"] = serde_json::json!({ "working": "acescg", "output": "rec2020" });
+    desc["textures"] = serde_json::json!([
+        { "name": "color", "type": "image", "file": image.to_str().unwrap() },
+        { "name": "data", "type": "image", "file": image.to_str().unwrap(), "colorspace": "raw" },
+        { "name": "srgb", "type": "image", "file": image.to_str().unwrap(), "colorspace": "srgb" },
+        {
+            "name": "wide",
+            "type": "image",
+            "file": image.to_str().unwrap(),
+            "colorspace": "display_p3"
+        }
+    ]);
+    let (scene, rt, spp, frame_info) = Scene::load_value(&desc, Path::new("test.json")).unwrap();
+    let json = scene.to_json(&rt, spp, &frame_info, &dir).unwrap();
+    assert_eq!(json["film"]["colorspace"], desc["film"]["colorspace"]);
+    let textures = json["textures"].as_array().unwrap();
+    let tag = |name: &str| {
+        let t = textures.iter().find(|t| t["name"] == name).unwrap();
+        t.get("colorspace").cloned()
+    };
+    assert_eq!(tag("color"), None);
+    assert_eq!(tag("data"), Some(Value::from("raw")));
+    // Tagged sRGB textures decode their scalars, so the tag is kept
+    assert_eq!(tag("srgb"), Some(Value::from("srgb")));
+    assert_eq!(tag("wide"), Some(Value::from("display_p3")));
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,525 @@
+//! Provides a JSON Schema (draft 7) describing the scene file format, for editors
+//! to validate and autocomplete scene files with. The schema can be written out with
+//! `aperture --schema > aperture.schema.json` and referenced from a scene file with
+//! `"$schema": "aperture.schema.json"`.
+//!
+//! The schema describes the same structure as the types in `scene::desc`, which
+//! are what the scene is actually loaded from.
+
+use serde_json::{Map, Value};
+
+use crate::film::{Aov, ColorSpace};
+
+/// Get the JSON Schema for scene files
+pub fn json_schema() -> Value {
+    serde_json::json!({
+        "$schema": "http://json-schema.org/draft-07/schema#",
+        "title": "Aperture scene file",
+        "type": "object",
+        "properties": {
+            "$schema": { "type": "string" },
+            "include": { "$ref": "#/definitions/files" },
+            "film": { "$ref": "#/definitions/film" },
+            "camera": { "$ref": "#/definitions/camera" },
+            "cameras": { "type": "array", "items": { "$ref": "#/definitions/camera" } },
+            "integrator": { "$ref": "#/definitions/integrator" },
+            "sampler": { "$ref": "#/definitions/sampler" },
+            "textures": entries("texture"),
+            "materials": entries("material"),
+            "objects": entries("object")
+        },
+        "additionalProperties": false,
+        "definitions": {
+            "files": {
+                "oneOf": [
+                    { "type": "string" },
+                    { "type": "array", "items": { "type": "string" } }
+                ]
+            },
+            "include": object(&[("include", "#/definitions/files")], &[]),
+            "vector": {
+                "type": "array",
+                "items": { "type": "number" },
+                "minItems": 3,
+                "maxItems": 3
+            },
+            "color": {
+                "type": "array",
+                "items": { "type": "number" },
+                "minItems": 3,
+                "maxItems": 4
+            },
+            "color_param": {
+                "oneOf": [{ "type": "string" }, { "$ref": "#/definitions/color" }]
+            },
+            "scalar_param": {
+                "oneOf": [{ "type": "string" }, { "type": "number" }]
+            },
+            "film": object(
+                &[
+                    ("width", "integer"),
+                    ("height", "integer"),
+                    ("samples", "integer"),
+                    ("frames", "integer"),
+                    ("start_frame", "integer"),
+                    ("end_frame", "integer"),
+                    ("scene_time", "number"),
+                    ("filter", "#/definitions/filter")
+                ],
+                &[
+                    ("aovs", "#/definitions/aovs"),
+                    ("max_sample_luminance", "number"),
+                    ("tonemap", "#/definitions/tonemap"),
+                    ("denoise", "#/definitions/denoise"),
+                    ("crop_window", "#/definitions/crop_window"),
+                    ("pixel_bounds", "#/definitions/pixel_bounds"),
+                    ("spectral", "boolean"),
+                    ("colorspace", "#/definitions/film_colorspace")
+                ]
+            ),
+            "film_colorspace": object(
+                &[],
+                &[
+                    ("working", "#/definitions/colorspace"),
+                    ("output", "#/definitions/colorspace")
+                ]
+            ),
+            "colorspace": { "enum": ColorSpace::ALL.iter().map(ColorSpace::name).collect::<Vec<_>>() },
+            "texture_colorspace": {
+                "oneOf": [{ "$ref": "#/definitions/colorspace" }, { "enum": ["raw"] }]
+            },
+            "crop_window": {
+                "type": "array",
+                "items": { "type": "number", "minimum": 0, "maximum": 1 },
+                "minItems": 4,
+                "maxItems": 4
+            },
+            "pixel_bounds": {
+                "type": "array",
+                "items": { "type": "integer", "minimum": 0 },
+                "minItems": 4,
+                "maxItems": 4
+            },
+            "tonemap": object(
+                &[],
+                &[
+                    ("operator", "#/definitions/tonemap_operator"),
+                    ("exposure", "number"),
+                    ("white_balance", "#/definitions/vector")
+                ]
+            ),
+            "denoise": object(
+                &[],
+                &[
+                    ("radius", "integer"),
+                    ("color_sigma", "number"),
+                    ("albedo_sigma", "number"),
+                    ("normal_sigma", "number"),
+                    ("depth_sigma", "number")
+                ]
+            ),
+            "tonemap_operator": { "enum": ["clamp", "reinhard", "hable", "aces"] },
+            "aovs": { "type": "array", "items": { "enum": Aov::ALL.iter().map(Aov::name).collect::<Vec<_>>() } },
+            "filter": tagged(&[
+                (
+                    "mitchell_netravali",
+                    &[("width", "number"), ("height", "number"), ("b", "number"), ("c", "number")],
+                    &[]
+                ),
+                (
+                    "gaussian",
+                    &[("width", "number"), ("height", "number"), ("alpha", "number")],
+                    &[]
+                ),
+                ("box", &[("width", "number"), ("height", "number")], &[]),
+                ("triangle", &[("width", "number"), ("height", "number")], &[]),
+                (
+                    "lanczos",
+                    &[("width", "number"), ("height", "number"), ("tau", "number")],
+                    &[]
+                ),
+                ("blackman_harris", &[("width", "number"), ("height", "number")], &[])
+            ]),
+            "camera": object(
+                &[("fov", "#/definitions/fov")],
+                &[
+                    ("transform", "#/definitions/transforms"),
+                    ("keyframes", "#/definitions/keyframes"),
+                    ("position", "#/definitions/vector"),
+                    ("target", "#/definitions/vector"),
+                    ("up", "#/definitions/vector"),
+                    ("fov_knots", "#/definitions/numbers"),
+                    ("fov_spline_degree", "integer"),
+                    ("shutter_size", "number"),
+                    ("active_at", "integer")
+                ]
+            ),
+            "fov": {
+                "oneOf": [{ "type": "number" }, { "$ref": "#/definitions/numbers" }]
+            },
+            "numbers": { "type": "array", "items": { "type": "number" } },
+            "integrator": tagged(&[
+                ("pathtracer", &[("min_depth", "integer"), ("max_depth", "integer")], &[]),
+                ("whitted", &[("min_depth", "integer")], &[]),
+                ("normals_debug", &[], &[])
+            ]),
+            "sampler": tagged(&[
+                (
+                    "low_discrepancy",
+                    &[],
+                    &[("block_size", "#/definitions/block_size"), ("seed", "integer")]
+                ),
+                (
+                    "adaptive",
+                    &[("min_spp", "integer"), ("max_spp", "integer")],
+                    &[("block_size", "#/definitions/block_size"), ("seed", "integer")]
+                ),
+                (
+                    "uniform",
+                    &[],
+                    &[("block_size", "#/definitions/block_size"), ("seed", "integer")]
+                )
+            ]),
+            "block_size": {
+                "type": "array",
+                "items": { "type": "integer" },
+                "minItems": 2,
+                "maxItems": 2
+            },
+            "texture": tagged(&[
+                (
+                    "image",
+                    &[("name", "string"), ("file", "string")],
+                    &[("colorspace", "#/definitions/texture_colorspace")]
+                ),
+                (
+                    "animated_image",
+                    &[("name", "string"), ("keyframes", "#/definitions/image_keyframes")],
+                    &[("colorspace", "#/definitions/texture_colorspace")]
+                ),
+                (
+                    "movie",
+                    &[
+                        ("name", "string"),
+                        ("file_prefix", "string"),
+                        ("file_suffix", "string"),
+                        ("frames", "integer"),
+                        ("framerate", "integer")
+                    ],
+                    &[("colorspace", "#/definitions/texture_colorspace")]
+                )
+            ]),
+            "image_keyframes": {
+                "type": "array",
+                "items": object(&[("file", "string"), ("time", "number")], &[])
+            },
+            "material": tagged(&[
+                (
+                    "glass",
+                    &[
+                        ("name", "string"),
+                        ("reflect", "#/definitions/color_param"),
+                        ("transmit", "#/definitions/color_param"),
+                        ("eta", "#/definitions/scalar_param")
+                    ],
+                    &[("abbe", "number")]
+                ),
+                (
+                    "rough_glass",
+                    &[
+                        ("name", "string"),
+                        ("reflect", "#/definitions/color_param"),
+                        ("transmit", "#/definitions/color_param"),
+                        ("eta", "#/definitions/scalar_param"),
+                        ("roughness", "#/definitions/scalar_param")
+                    ],
+                    &[]
+                ),
+                (
+                    "matte",
+                    &[
+                        ("name", "string"),
+                        ("diffuse", "#/definitions/color_param"),
+                        ("roughness", "#/definitions/scalar_param")
+                    ],
+                    &[]
+                ),
+                ("merl", &[("name", "string"), ("file", "string")], &[]),
+                (
+                    "metal",
+                    &[
+                        ("name", "string"),
+                        ("refractive_index", "#/definitions/color_param"),
+                        ("absorption_coefficient", "#/definitions/color_param"),
+                        ("roughness", "#/definitions/scalar_param")
+                    ],
+                    &[]
+                ),
+                (
+                    "plastic",
+                    &[
+                        ("name", "string"),
+                        ("diffuse", "#/definitions/color_param"),
+                        ("gloss", "#/definitions/color_param"),
+                        ("roughness", "#/definitions/scalar_param")
+                    ],
+                    &[]
+                ),
+                (
+                    "specular_metal",
+                    &[
+                        ("name", "string"),
+                        ("refractive_index", "#/definitions/color_param"),
+                        ("absorption_coefficient", "#/definitions/color_param")
+                    ],
+                    &[]
+                )
+            ]),
+            "object": tagged(&[
+                (
+                    "emitter",
+                    &[
+                        ("name", "string"),
+                        ("emitter", "#/definitions/emitter"),
+                        ("emission", "#/definitions/emission")
+                    ],
+                    &[
+                        ("material", "string"),
+                        ("geometry", "#/definitions/geometry"),
+                        ("transform", "#/definitions/transforms"),
+                        ("keyframes", "#/definitions/keyframes")
+                    ]
+                ),
+                (
+                    "receiver",
+                    &[
+                        ("name", "string"),
+                        ("material", "string"),
+                        ("geometry", "#/definitions/geometry")
+                    ],
+                    &[
+                        ("transform", "#/definitions/transforms"),
+                        ("keyframes", "#/definitions/keyframes")
+                    ]
+                ),
+                (
+                    "group",
+                    &[("name", "string"), ("objects", "#/definitions/objects")],
+                    &[
+                        ("transform", "#/definitions/transforms"),
+                        ("keyframes", "#/definitions/keyframes")
+                    ]
+                )
+            ]),
+            "objects": entries("object"),
+            "emitter": { "enum": ["point", "area"] },
+            "emission": {
+                "oneOf": [
+                    { "$ref": "#/definitions/color" },
+                    {
+                        "type": "array",
+                        "items": object(
+                            &[("time", "number"), ("color", "#/definitions/color")],
+                            &[]
+                        )
+                    },
+                    object(&[("temperature", "number")], &[("strength", "number")])
+                ]
+            },
+            "geometry": tagged(&[
+                ("sphere", &[("radius", "number")], &[]),
+                ("disk", &[("radius", "number"), ("inner_radius", "number")], &[]),
+                ("plane", &[], &[]),
+                ("rectangle", &[("width", "number"), ("height", "number")], &[]),
+                ("mesh", &[("file", "string"), ("model", "string")], &[])
+            ]),
+            "transforms": { "type": "array", "items": { "$ref": "#/definitions/transform" } },
+            "transform": tagged(&[
+                ("translate", &[("translation", "#/definitions/vector")], &[]),
+                ("scale", &[("scaling", "#/definitions/scaling")], &[]),
+                ("rotate_x", &[("rotation", "number")], &[]),
+                ("rotate_y", &[("rotation", "number")], &[]),
+                ("rotate_z", &[("rotation", "number")], &[]),
+                ("rotate", &[("rotation", "number"), ("axis", "#/definitions/vector")], &[]),
+                ("matrix", &[("matrix", "#/definitions/matrix")], &[])
+            ]),
+            "scaling": {
+                "oneOf": [{ "type": "number" }, { "$ref": "#/definitions/vector" }]
+            },
+            "matrix": {
+                "type": "array",
+                "items": {
+                    "type": "array",
+                    "items": { "type": "number" },
+                    "minItems": 4,
+                    "maxItems": 4
+                },
+                "minItems": 4,
+                "maxItems": 4
+            },
+            "keyframes": object(
+                &[
+                    ("control_points", "#/definitions/control_points"),
+                    ("knots", "#/definitions/numbers")
+                ],
+                &[("degree", "integer")]
+            ),
+            "control_points": {
+                "type": "array",
+                "items": object(&[("transform", "#/definitions/transforms")], &[])
+            }
+        }
+    })
+}
+
+/// The schema for a property, which is either a JSON type or a reference to a definition
+fn property(ty: &str) -> Value {
+    if ty.starts_with('#') {
+        serde_json::json!({ "$ref": ty })
+    } else {
+        serde_json::json!({ "type": ty })
+    }
+}
+
+/// The schema for an object with the `required` and `optional` properties and no others
+fn object(required: &[(&str, &str)], optional: &[(&str, &str)]) -> Value {
+    let mut properties = Map::new();
+    for &(key, ty) in required.iter().chain(optional) {
+        properties.insert(key.to_owned(), property(ty));
+    }
+    let required: Vec<_> = required.iter().map(|&(key, _)| key).collect();
+    serde_json::json!({
+        "type": "object",
+        "properties": properties,
+        "required": required,
+        "additionalProperties": false
+    })
+}
+
+/// The schema for an object picked by its `"type"`, given the type names along
+/// with the required and optional properties of each
+#[allow(clippy::type_complexity)]
+fn tagged(variants: &[(&str, &[(&str, &str)], &[(&str, &str)])]) -> Value {
+    let variants: Vec<_> = variants
+        .iter()
+        .map(|&(tag, required, optional)| {
+            let mut schema = object(required, optional);
+            schema["properties"]["type"] = serde_json::json!({ "const": tag });
+            schema["required"]
+                .as_array_mut()
+                .unwrap()
+                .insert(0, Value::from("type"));
+            schema
+        })
+        .collect();
+    serde_json::json!({ "oneOf": variants })
+}
+
+/// The schema for an array of textures, materials or objects which can include others
+fn entries(entry: &str) -> Value {
+    serde_json::json!({
+        "type": "array",
+        "items": {
+            "oneOf": [
+                { "$ref": format!("#/definitions/{}", entry) },
+                { "$ref": "#/definitions/include" }
+            ]
+        }
+    })
+}
+
+/// Check `value` against `schema`, a part of the `root` schema. Only supports
+/// the keywords used by the scene file schema
+#[cfg(test)]
+fn validate(root: &Value, schema: &Value, value: &Value) -> bool {
+    if let Some(r) = schema.get("$ref").and_then(Value::as_str) {
+        return validate(root, root.pointer(&r[1..]).unwrap(), value);
+    }
+    if let Some(variants) = schema.get("oneOf").and_then(Value::as_array) {
+        return variants.iter().filter(|s| validate(root, s, value)).count() == 1;
+    }
+    if let Some(c) = schema.get("const") {
+        return c == value;
+    }
+    if let Some(e) = schema.get("enum").and_then(Value::as_array) {
+        return e.contains(value);
+    }
+    let valid_type = match schema.get("type").and_then(Value::as_str) {
+        Some("object") => value.is_object(),
+        Some("array") => value.is_array(),
+        Some("string") => value.is_string(),
+        Some("number") => value.is_number(),
+        Some("integer") => value.is_u64() || value.is_i64(),
+        Some("boolean") => value.is_boolean(),
+        _ => true,
+    };
+    if !valid_type {
+        return false;
+    }
+    if let Some(array) = value.as_array() {
+        let len = array.len() as u64;
+        let min = schema.get("minItems").and_then(Value::as_u64).unwrap_or(0);
+        let max = schema
+            .get("maxItems")
+            .and_then(Value::as_u64)
+            .unwrap_or(u64::MAX);
+        if len < min || len > max {
+            return false;
+        }
+        if let Some(items) = schema.get("items") {
+            return array.iter().all(|v| validate(root, items, v));
+        }
+    }
+    if let Some(object) = value.as_object() {
+        let properties = &schema["properties"];
+        if let Some(required) = schema.get("required").and_then(Value::as_array) {
+            if !required
+                .iter()
+                .all(|k| object.contains_key(k.as_str().unwrap()))
+            {
+                return false;
+            }
+        }
+        return object.iter().all(|(k, v)| match properties.get(k) {
+            Some(s) => validate(root, s, v),
+            None => schema["additionalProperties"] != Value::Bool(false),
+        });
+    }
+    true
+}
+
+#[test]
+fn test_schema_validates_scenes() {
+    let schema = json_schema();
+    let cornell: Value =
+        serde_json::from_str(&std::fs::read_to_string("cornell.json").unwrap()).unwrap();
+    assert!(validate(&schema, &schema, &cornell));
+    let scene = super::test_scene();
+    assert!(validate(&schema, &schema, &scene));
+
+    let invalid = [
+        ("/objects/0/materail", Some(serde_json::json!("white"))),
+        ("/objects/0/material", None),
+        ("/objects/0/type", Some(serde_json::json!("camera"))),
+        ("/film/filter/alpha", Some(serde_json::json!(2.0))),
+        (
+            "/film/colorspace",
+            Some(serde_json::json!({ "working": "raw" })),
+        ),
+        ("/materials/0/diffuse", Some(serde_json::json!([1, 1]))),
+        (
+            "/camera/transform/0/translation",
+            Some(serde_json::json!([1])),
+        ),
+    ];
+    for (pointer, value) in invalid.iter().cloned() {
+        let edited = super::edit_scene(scene.clone(), pointer, value);
+        assert!(
+            !validate(&schema, &schema, &edited),
+            "editing {} should be invalid",
+            pointer
+        );
+        // The schema should agree with the scene loader
+        assert!(super::Scene::load_value(&edited, std::path::Path::new("test.json")).is_err());
+    }
+}
@@ -28,6 +28,10 @@ impl AnimatedImage {
             .collect::<Vec<_>>();
         Textures::AnimatedImage(Self { frames })
     }
+    /// Get the images making up the animation along with the time each is shown at
+    pub fn frames(&self) -> &[(f32, Image)] {
+        &self.frames
+    }
 
     pub fn active_keyframes(&self, time: f32) -> (usize, Option<usize>) {
         match self
@@ -5,7 +5,7 @@ use crate::{
 
 /// A single valued, solid scalar texture
 pub struct ConstantScalar {
-    val: f32,
+    pub val: f32,
 }
 
 impl ConstantScalar {
@@ -26,7 +26,7 @@ impl Texture for ConstantScalar {
 
 /// A single valued, solid color texture
 pub struct ConstantColor {
-    val: Colorf,
+    pub val: Colorf,
 }
 
 impl ConstantColor {
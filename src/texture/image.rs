@@ -1,26 +1,93 @@
 use crate::{
-    film::Colorf,
+    film::{ColorSpace, Colorf},
     linalg::clamp,
     texture::{bilinear_interpolate, Texture, Textures},
 };
 use image::{self, GenericImage};
+use std::path::{Path, PathBuf};
 
 /// An `Image` texture is a `Texture` whose samples come
-/// from an image file.
+/// from an image file. Colors are decoded from the color space the
+/// image is in and converted to the working color space, see
+/// `film::colorspace`. Scalars are only decoded if `decode_scalars` is set,
+/// otherwise they're read as data.
 pub struct Image {
     img: image::DynamicImage,
+    /// The file the image was loaded from, if it was loaded from a file
+    pub file: Option<PathBuf>,
+    /// The color space the image is in, or None if it holds data which is
+    /// used as it is
+    pub color_space: Option<ColorSpace>,
+    /// If scalar lookups are decoded from the color space as well. Images which
+    /// aren't tagged with their color space are taken to be sRGB colors, but scalar
+    /// textures like roughness maps or masks are usually stored as data
+    pub decode_scalars: bool,
+    /// The working color space colors are converted to
+    working: ColorSpace,
+    /// The decoded value of each 8-bit value in the image
+    decoded: Vec<f32>,
 }
 
 impl Image {
-    pub fn new_texture(img: image::DynamicImage) -> Textures {
-        Textures::Image(Image { img })
+    /// Create the texture for the image in `color_space`, or None if the image is
+    /// data, whose colors are converted to the `working` color space
+    pub fn new_texture(
+        img: image::DynamicImage,
+        color_space: Option<ColorSpace>,
+        decode_scalars: bool,
+        working: ColorSpace,
+    ) -> Textures {
+        Textures::Image(Image::new(img, None, color_space, decode_scalars, working))
+    }
+    /// Load the image texture from the image file
+    pub fn load_texture(
+        file: &Path,
+        color_space: Option<ColorSpace>,
+        decode_scalars: bool,
+        working: ColorSpace,
+    ) -> image::ImageResult<Textures> {
+        let img = image::open(file)?;
+        Ok(Textures::Image(Image::new(
+            img,
+            Some(file.to_path_buf()),
+            color_space,
+            decode_scalars,
+            working,
+        )))
+    }
+    fn new(
+        img: image::DynamicImage,
+        file: Option<PathBuf>,
+        color_space: Option<ColorSpace>,
+        decode_scalars: bool,
+        working: ColorSpace,
+    ) -> Image {
+        let decoded = (0..256)
+            .map(|i| {
+                let x = i as f32 / 255.0;
+                color_space.map_or(x, |c| c.decode_f32(x))
+            })
+            .collect();
+        Image {
+            img,
+            file,
+            color_space,
+            decode_scalars,
+            working,
+            decoded,
+        }
     }
 
     fn get_float(&self, x: u32, y: u32) -> f32 {
         let dims = self.img.dimensions();
         let x = clamp(x, 0, dims.0 - 1);
         let y = clamp(y, 0, dims.1 - 1);
-        self.img.get_pixel(x, y).data[0] as f32 / 255.0
+        let v = self.img.get_pixel(x, y).data[0];
+        if self.decode_scalars {
+            self.decoded[v as usize]
+        } else {
+            v as f32 / 255.0
+        }
     }
 
     fn get_color(&self, x: u32, y: u32) -> Colorf {
@@ -28,10 +95,11 @@ impl Image {
         let x = clamp(x, 0, dims.0 - 1);
         let y = clamp(y, 0, dims.1 - 1);
         let px = self.img.get_pixel(x, y);
+        // Alpha is always linear
         Colorf::with_alpha(
-            px.data[0] as f32 / 255.0,
-            px.data[1] as f32 / 255.0,
-            px.data[2] as f32 / 255.0,
+            self.decoded[px.data[0] as usize],
+            self.decoded[px.data[1] as usize],
+            self.decoded[px.data[2] as usize],
             px.data[3] as f32 / 255.0,
         )
     }
@@ -48,6 +116,75 @@ impl Texture for Image {
     fn sample_color(&self, u: f32, v: f32, _: f32) -> Colorf {
         let x = u * self.img.dimensions().0 as f32;
         let y = v * self.img.dimensions().1 as f32;
-        bilinear_interpolate(x, y, |px, py| self.get_color(px, py))
+        let c = bilinear_interpolate(x, y, |px, py| self.get_color(px, py));
+        match self.color_space {
+            Some(space) => space.convert(&c, self.working),
+            None => c,
+        }
     }
 }
+
+#[test]
+fn test_color_spaces() {
+    let mut img = image::DynamicImage::new_rgba8(1, 1);
+    img.put_pixel(
+        0,
+        0,
+        image::Rgba {
+            data: [255, 128, 0, 255],
+        },
+    );
+    let near = |a: f32, b: f32| (a - b).abs() < 1e-3;
+    let srgb = Image::new(
+        img.clone(),
+        None,
+        Some(ColorSpace::Srgb),
+        true,
+        ColorSpace::LinearSrgb,
+    );
+    let c = srgb.sample_color(0.0, 0.0, 0.0);
+    assert!(near(c.r, 1.0) && near(c.g, 0.2158) && near(c.b, 0.0) && near(c.a, 1.0));
+    assert!(near(srgb.sample_f32(0.0, 0.0, 0.0), 1.0));
+    // Data is used as it is
+    let raw = Image::new(img.clone(), None, None, false, ColorSpace::Acescg);
+    let c = raw.sample_color(0.0, 0.0, 0.0);
+    assert!(near(c.r, 1.0) && near(c.g, 128.0 / 255.0) && near(c.b, 0.0));
+    // Colors are converted to the working space
+    let acescg = Image::new(
+        img.clone(),
+        None,
+        Some(ColorSpace::Srgb),
+        true,
+        ColorSpace::Acescg,
+    );
+    let c = acescg.sample_color(0.0, 0.0, 0.0);
+    let expected =
+        ColorSpace::LinearSrgb.convert(&Colorf::new(1.0, 0.2158, 0.0), ColorSpace::Acescg);
+    assert!(near(c.r, expected.r) && near(c.g, expected.g) && near(c.b, expected.b));
+    // Scalars are read as data unless they're decoded, while colors are still decoded
+    let mut gray = image::DynamicImage::new_luma8(1, 1);
+    gray.put_pixel(
+        0,
+        0,
+        image::Rgba {
+            data: [128, 128, 128, 255],
+        },
+    );
+    let untagged = Image::new(
+        gray.clone(),
+        None,
+        Some(ColorSpace::Srgb),
+        false,
+        ColorSpace::LinearSrgb,
+    );
+    assert!(near(untagged.sample_f32(0.0, 0.0, 0.0), 128.0 / 255.0));
+    assert!(near(untagged.sample_color(0.0, 0.0, 0.0).r, 0.2158));
+    let decoded = Image::new(
+        gray,
+        None,
+        Some(ColorSpace::Srgb),
+        true,
+        ColorSpace::LinearSrgb,
+    );
+    assert!(near(decoded.sample_f32(0.0, 0.0, 0.0), 0.2158));
+}